@@ -11,6 +11,8 @@ use crate::{
     },
     locked_outpoints,
     miniscript::descriptor::{Descriptor, DescriptorPublicKey},
+    reservations::{self, Reservation},
+    silent_payments::{self, SilentPaymentAddress},
     ChangeSet, WalletPersister,
 };
 
@@ -164,6 +166,30 @@ where
         outpoints: [(outpoint, true)].into(),
     };
 
+    let reservations_changeset = reservations::ChangeSet {
+        outpoints: [(
+            outpoint,
+            Some(Reservation {
+                draft_id: "draft".into(),
+                expiry_height: Some(910240),
+            }),
+        )]
+        .into(),
+    };
+
+    let secp = Secp256k1::new();
+    let tweak = |byte| bitcoin::secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap();
+    let sp_address = SilentPaymentAddress::new(
+        tweak(1).public_key(&secp),
+        tweak(2).public_key(&secp),
+        Network::Testnet,
+    );
+    let silent_payments_changeset = silent_payments::ChangeSet {
+        address: Some(sp_address),
+        outputs: [(outpoint, tweak(3))].into(),
+        sent: [(spk_at_index(&descriptor, 0), sp_address)].into(),
+    };
+
     let mut changeset = ChangeSet {
         descriptor: Some(descriptor.clone()),
         change_descriptor: Some(change_descriptor.clone()),
@@ -172,6 +198,8 @@ where
         tx_graph: tx_graph_changeset,
         indexer: keychain_txout_changeset,
         locked_outpoints: locked_outpoints_changeset,
+        reservations: reservations_changeset,
+        silent_payments: silent_payments_changeset,
     };
 
     // persist and load
@@ -223,6 +251,23 @@ where
         outpoints: [(outpoint, true)].into(),
     };
 
+    let reservations_changeset = reservations::ChangeSet {
+        outpoints: [(
+            outpoint,
+            Some(Reservation {
+                draft_id: "another draft".into(),
+                expiry_height: None,
+            }),
+        )]
+        .into(),
+    };
+
+    let silent_payments_changeset = silent_payments::ChangeSet {
+        address: None,
+        outputs: [(outpoint, tweak(4))].into(),
+        sent: [(spk_at_index(&descriptor, 1), sp_address)].into(),
+    };
+
     let changeset_new = ChangeSet {
         descriptor: None,
         change_descriptor: None,
@@ -231,6 +276,8 @@ where
         tx_graph: tx_graph_changeset,
         indexer: keychain_txout_changeset,
         locked_outpoints: locked_outpoints_changeset,
+        reservations: reservations_changeset,
+        silent_payments: silent_payments_changeset,
     };
 
     // persist, load and check if same as merged
@@ -17,6 +17,10 @@ use bitcoin::FeeRate;
 use bitcoin::Psbt;
 use bitcoin::TxOut;
 
+mod v2;
+
+pub use v2::{PsbtV2, PsbtV2Error, PsbtV2Input, PsbtV2Output, TxModifiable};
+
 // TODO upstream the functions here to `rust-bitcoin`?
 
 /// Trait to add functions to extract utxos and calculate fees.
@@ -0,0 +1,784 @@
+//! PSBT version 2, as defined by [BIP370].
+//!
+//! Unlike version 0, a PSBTv2 doesn't embed the unsigned transaction: the fields of the
+//! transaction are spread over the input and output maps, so inputs and outputs can be added after
+//! the PSBT is created. `rust-bitcoin` only handles version 0, so [`PsbtV2`] is converted to a
+//! [`Psbt`] to be signed and finalized, see [`Wallet::sign_v2`] and [`Wallet::finalize_psbt_v2`].
+//!
+//! [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+//! [`Wallet::sign_v2`]: crate::Wallet::sign_v2
+//! [`Wallet::finalize_psbt_v2`]: crate::Wallet::finalize_psbt_v2
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use bitcoin::base64::prelude::{Engine as _, BASE64_STANDARD};
+use bitcoin::bip32::{KeySource, Xpub};
+use bitcoin::consensus::encode::{deserialize, deserialize_partial, serialize, VarInt};
+use bitcoin::psbt::{self, raw, Psbt};
+use bitcoin::{
+    absolute, transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness,
+};
+
+const MAGIC: &[u8; 5] = b"psbt\xff";
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+const SIGHASH_NONE: u32 = 0x02;
+const SIGHASH_SINGLE: u32 = 0x03;
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+/// A key-value map as serialized in a PSBT, in order.
+type RawMap = Vec<(raw::Key, Vec<u8>)>;
+
+/// A partially signed transaction, version 2
+///
+/// For a usage example see [`TxBuilder::finish_v2`](crate::TxBuilder::finish_v2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtV2 {
+    /// Version of the transaction.
+    pub tx_version: transaction::Version,
+    /// Lock time of the transaction if none of its inputs requires one, zero if `None`.
+    pub fallback_lock_time: Option<absolute::LockTime>,
+    /// Whether inputs and outputs can still be added.
+    pub tx_modifiable: TxModifiable,
+    /// Extended public keys used by the inputs and outputs, with their origin.
+    pub xpub: BTreeMap<Xpub, KeySource>,
+    /// Global proprietary key-value pairs.
+    pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
+    /// Unknown global key-value pairs.
+    pub unknown: BTreeMap<raw::Key, Vec<u8>>,
+    /// The inputs of the transaction.
+    pub inputs: Vec<PsbtV2Input>,
+    /// The outputs of the transaction.
+    pub outputs: Vec<PsbtV2Output>,
+}
+
+/// An input of a [`PsbtV2`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtV2Input {
+    /// The output spent by the input.
+    pub previous_output: OutPoint,
+    /// Sequence number of the input.
+    pub sequence: Sequence,
+    /// Minimum time based lock time the input requires.
+    pub required_time_lock_time: Option<absolute::Time>,
+    /// Minimum height based lock time the input requires.
+    pub required_height_lock_time: Option<absolute::Height>,
+    /// The fields shared with version 0.
+    pub psbt_input: psbt::Input,
+}
+
+impl PsbtV2Input {
+    /// Create an input spending `previous_output`, with the final sequence number and no
+    /// required lock time.
+    pub fn new(previous_output: OutPoint, psbt_input: psbt::Input) -> Self {
+        Self {
+            previous_output,
+            sequence: Sequence::MAX,
+            required_time_lock_time: None,
+            required_height_lock_time: None,
+            psbt_input,
+        }
+    }
+}
+
+/// An output of a [`PsbtV2`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtV2Output {
+    /// Amount of the output.
+    pub amount: Amount,
+    /// Script of the output.
+    pub script_pubkey: ScriptBuf,
+    /// The fields shared with version 0.
+    pub psbt_output: psbt::Output,
+}
+
+impl PsbtV2Output {
+    /// Create an output from a [`TxOut`].
+    pub fn new(txout: TxOut, psbt_output: psbt::Output) -> Self {
+        Self {
+            amount: txout.value,
+            script_pubkey: txout.script_pubkey,
+            psbt_output,
+        }
+    }
+}
+
+/// Flags of a [`PsbtV2`] telling whether the transaction can still be modified
+///
+/// The flags are cleared as the transaction is signed, depending on the sighash types of the
+/// signatures: see [`PsbtV2::update_tx_modifiable`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxModifiable {
+    /// Inputs can be added.
+    pub inputs: bool,
+    /// Outputs can be added.
+    pub outputs: bool,
+    /// Some input is signed with `SIGHASH_SINGLE`, so the indexes of inputs and outputs must be
+    /// preserved.
+    pub sighash_single: bool,
+}
+
+impl TxModifiable {
+    fn to_u8(self) -> u8 {
+        u8::from(self.inputs) | u8::from(self.outputs) << 1 | u8::from(self.sighash_single) << 2
+    }
+
+    fn from_u8(flags: u8) -> Self {
+        Self {
+            inputs: flags & 1 != 0,
+            outputs: flags & 2 != 0,
+            sighash_single: flags & 4 != 0,
+        }
+    }
+}
+
+/// Errors related to [`PsbtV2`]
+#[derive(Debug)]
+pub enum PsbtV2Error {
+    /// The bytes aren't a valid PSBT encoding.
+    InvalidEncoding,
+    /// The base64 string isn't valid.
+    InvalidBase64,
+    /// The PSBT isn't version 2.
+    InvalidVersion(u32),
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A field has an invalid value, or isn't allowed in version 2.
+    InvalidField(&'static str),
+    /// A key appears twice in the same map.
+    DuplicateKey(raw::Key),
+    /// Error parsing the fields shared with version 0.
+    Psbt(psbt::Error),
+    /// The inputs of the PSBT can't be modified.
+    InputsNotModifiable,
+    /// The outputs of the PSBT can't be modified.
+    OutputsNotModifiable,
+    /// The inputs require both a time based and a height based lock time.
+    LockTimeConflict,
+    /// Adding the input changes the lock time of the transaction, which invalidates the existing
+    /// signatures.
+    LockTimeChange,
+}
+
+impl fmt::Display for PsbtV2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEncoding => write!(f, "Invalid PSBT encoding"),
+            Self::InvalidBase64 => write!(f, "Invalid base64 encoding"),
+            Self::InvalidVersion(version) => {
+                write!(f, "Invalid PSBT version {version}, expected 2")
+            }
+            Self::MissingField(field) => write!(f, "Missing PSBT field {field}"),
+            Self::InvalidField(field) => write!(f, "Invalid PSBT field {field}"),
+            Self::DuplicateKey(key) => write!(f, "Duplicate PSBT key {key}"),
+            Self::Psbt(err) => write!(f, "PSBT error: {err}"),
+            Self::InputsNotModifiable => write!(f, "The inputs of the PSBT can't be modified"),
+            Self::OutputsNotModifiable => write!(f, "The outputs of the PSBT can't be modified"),
+            Self::LockTimeConflict => write!(
+                f,
+                "The inputs require both a time based and a height based lock time"
+            ),
+            Self::LockTimeChange => write!(
+                f,
+                "Adding the input changes the lock time of the signed transaction"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PsbtV2Error {}
+
+impl From<psbt::Error> for PsbtV2Error {
+    fn from(err: psbt::Error) -> Self {
+        Self::Psbt(err)
+    }
+}
+
+impl PsbtV2 {
+    /// Convert a version 0 PSBT.
+    ///
+    /// The lock time of the transaction becomes the fallback lock time and no input or output
+    /// can be added until [`PsbtV2::tx_modifiable`] is set.
+    pub fn from_v0(psbt: Psbt) -> Self {
+        let tx = psbt.unsigned_tx;
+        Self {
+            tx_version: tx.version,
+            fallback_lock_time: Some(tx.lock_time),
+            tx_modifiable: TxModifiable::default(),
+            xpub: psbt.xpub,
+            proprietary: psbt.proprietary,
+            unknown: psbt.unknown,
+            inputs: tx
+                .input
+                .into_iter()
+                .zip(psbt.inputs)
+                .map(|(txin, psbt_input)| PsbtV2Input {
+                    sequence: txin.sequence,
+                    ..PsbtV2Input::new(txin.previous_output, psbt_input)
+                })
+                .collect(),
+            outputs: tx
+                .output
+                .into_iter()
+                .zip(psbt.outputs)
+                .map(|(txout, psbt_output)| PsbtV2Output::new(txout, psbt_output))
+                .collect(),
+        }
+    }
+
+    /// Convert to a version 0 PSBT, e.g. to sign it.
+    ///
+    /// Fails if the lock time of the transaction can't be determined, see [`PsbtV2::lock_time`].
+    pub fn to_v0(&self) -> Result<Psbt, PsbtV2Error> {
+        Ok(self.to_v0_with_lock_time(self.lock_time()?))
+    }
+
+    fn to_v0_with_lock_time(&self, lock_time: absolute::LockTime) -> Psbt {
+        Psbt {
+            unsigned_tx: self.unsigned_tx_with_lock_time(lock_time),
+            version: 0,
+            xpub: self.xpub.clone(),
+            proprietary: self.proprietary.clone(),
+            unknown: self.unknown.clone(),
+            inputs: self
+                .inputs
+                .iter()
+                .map(|input| input.psbt_input.clone())
+                .collect(),
+            outputs: self
+                .outputs
+                .iter()
+                .map(|output| output.psbt_output.clone())
+                .collect(),
+        }
+    }
+
+    /// Copy the fields of a version 0 PSBT of the same transaction, e.g. once it is signed.
+    pub(crate) fn update_from_v0(&mut self, psbt: Psbt) {
+        debug_assert_eq!(psbt.inputs.len(), self.inputs.len());
+        debug_assert_eq!(psbt.outputs.len(), self.outputs.len());
+        self.xpub = psbt.xpub;
+        self.proprietary = psbt.proprietary;
+        self.unknown = psbt.unknown;
+        for (input, psbt_input) in self.inputs.iter_mut().zip(psbt.inputs) {
+            input.psbt_input = psbt_input;
+        }
+        for (output, psbt_output) in self.outputs.iter_mut().zip(psbt.outputs) {
+            output.psbt_output = psbt_output;
+        }
+    }
+
+    /// Returns the unsigned transaction.
+    pub fn unsigned_tx(&self) -> Result<Transaction, PsbtV2Error> {
+        Ok(self.unsigned_tx_with_lock_time(self.lock_time()?))
+    }
+
+    fn unsigned_tx_with_lock_time(&self, lock_time: absolute::LockTime) -> Transaction {
+        Transaction {
+            version: self.tx_version,
+            lock_time,
+            input: self
+                .inputs
+                .iter()
+                .map(|input| TxIn {
+                    previous_output: input.previous_output,
+                    script_sig: ScriptBuf::new(),
+                    sequence: input.sequence,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: self
+                .outputs
+                .iter()
+                .map(|output| TxOut {
+                    value: output.amount,
+                    script_pubkey: output.script_pubkey.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the lock time of the transaction.
+    ///
+    /// If no input requires a lock time, it is the fallback lock time. Otherwise it is the
+    /// greatest height required if all the inputs requiring a lock time accept a height based one,
+    /// or else the greatest time required if they all accept a time based one. Fails with
+    /// [`PsbtV2Error::LockTimeConflict`] if neither is possible.
+    pub fn lock_time(&self) -> Result<absolute::LockTime, PsbtV2Error> {
+        let constrained = self.inputs.iter().filter(|input| {
+            input.required_height_lock_time.is_some() || input.required_time_lock_time.is_some()
+        });
+        if constrained.clone().next().is_none() {
+            return Ok(self.fallback_lock_time.unwrap_or(absolute::LockTime::ZERO));
+        }
+        let heights: Option<Vec<absolute::Height>> = constrained
+            .clone()
+            .map(|input| input.required_height_lock_time)
+            .collect();
+        if let Some(height) = heights.and_then(|heights| heights.into_iter().max()) {
+            return Ok(height.into());
+        }
+        let times: Option<Vec<absolute::Time>> = constrained
+            .map(|input| input.required_time_lock_time)
+            .collect();
+        match times.and_then(|times| times.into_iter().max()) {
+            Some(time) => Ok(time.into()),
+            None => Err(PsbtV2Error::LockTimeConflict),
+        }
+    }
+
+    /// Add an input to the transaction.
+    ///
+    /// Fails if inputs can't be added, or if the input changes the lock time of a transaction
+    /// that is already signed. The input is added last, so that the inputs signed with
+    /// `SIGHASH_SINGLE` keep the index of their output.
+    pub fn add_input(&mut self, input: PsbtV2Input) -> Result<(), PsbtV2Error> {
+        if !self.tx_modifiable.inputs {
+            return Err(PsbtV2Error::InputsNotModifiable);
+        }
+        let lock_time = self.lock_time()?;
+        self.inputs.push(input);
+        match self.lock_time() {
+            Ok(new_lock_time) if new_lock_time == lock_time || !self.has_signatures() => Ok(()),
+            Ok(_) => {
+                self.inputs.pop();
+                Err(PsbtV2Error::LockTimeChange)
+            }
+            Err(err) => {
+                self.inputs.pop();
+                Err(err)
+            }
+        }
+    }
+
+    /// Add an output to the transaction.
+    ///
+    /// Fails if outputs can't be added. The output is added last, so that the inputs signed with
+    /// `SIGHASH_SINGLE` keep the index of their output.
+    pub fn add_output(&mut self, output: PsbtV2Output) -> Result<(), PsbtV2Error> {
+        if !self.tx_modifiable.outputs {
+            return Err(PsbtV2Error::OutputsNotModifiable);
+        }
+        self.outputs.push(output);
+        Ok(())
+    }
+
+    fn has_signatures(&self) -> bool {
+        self.inputs.iter().any(|input| {
+            let input = &input.psbt_input;
+            !input.partial_sigs.is_empty()
+                || input.tap_key_sig.is_some()
+                || !input.tap_script_sigs.is_empty()
+                || input.final_script_sig.is_some()
+                || input.final_script_witness.is_some()
+        })
+    }
+
+    /// Update [`PsbtV2::tx_modifiable`] from the sighash types of the signatures of the inputs.
+    ///
+    /// Inputs can't be added once an input is signed without `SIGHASH_ANYONECANPAY`, and outputs
+    /// can't be added once an input is signed without `SIGHASH_NONE` or `SIGHASH_SINGLE`.
+    /// Signatures using `SIGHASH_SINGLE` set [`TxModifiable::sighash_single`].
+    pub fn update_tx_modifiable(&mut self) {
+        let sighash_types = self.inputs.iter().flat_map(|input| {
+            let input = &input.psbt_input;
+            let ecdsa = input
+                .partial_sigs
+                .values()
+                .map(|sig| sig.sighash_type.to_u32());
+            // `SIGHASH_DEFAULT` is `SIGHASH_ALL`.
+            let taproot = input
+                .tap_key_sig
+                .iter()
+                .chain(input.tap_script_sigs.values())
+                .map(|sig| sig.sighash_type as u32);
+            ecdsa.chain(taproot).collect::<Vec<_>>()
+        });
+        for sighash_type in sighash_types {
+            if sighash_type & SIGHASH_ANYONECANPAY == 0 {
+                self.tx_modifiable.inputs = false;
+            }
+            match sighash_type & !SIGHASH_ANYONECANPAY {
+                SIGHASH_NONE => {}
+                SIGHASH_SINGLE => self.tx_modifiable.sighash_single = true,
+                _ => self.tx_modifiable.outputs = false,
+            }
+        }
+    }
+
+    /// Serialize as raw bytes.
+    pub fn serialize(&self) -> Vec<u8> {
+        // The fields shared with version 0 are serialized by `rust-bitcoin`, the lock time of the
+        // embedded transaction doesn't matter since it is removed.
+        let v0 = self
+            .to_v0_with_lock_time(absolute::LockTime::ZERO)
+            .serialize();
+        let (mut global, mut input_maps, mut output_maps) =
+            split_v0(&v0, self.inputs.len(), self.outputs.len())
+                .expect("rust-bitcoin serializes valid PSBTs");
+
+        global.retain(|(key, _)| {
+            key.type_value != PSBT_GLOBAL_UNSIGNED_TX && key.type_value != PSBT_GLOBAL_VERSION
+        });
+        global.push((
+            key(PSBT_GLOBAL_TX_VERSION),
+            self.tx_version.0.to_le_bytes().to_vec(),
+        ));
+        if let Some(lock_time) = self.fallback_lock_time {
+            global.push((
+                key(PSBT_GLOBAL_FALLBACK_LOCKTIME),
+                lock_time.to_consensus_u32().to_le_bytes().to_vec(),
+            ));
+        }
+        global.push((
+            key(PSBT_GLOBAL_INPUT_COUNT),
+            serialize(&VarInt(self.inputs.len() as u64)),
+        ));
+        global.push((
+            key(PSBT_GLOBAL_OUTPUT_COUNT),
+            serialize(&VarInt(self.outputs.len() as u64)),
+        ));
+        if self.tx_modifiable != TxModifiable::default() {
+            global.push((
+                key(PSBT_GLOBAL_TX_MODIFIABLE),
+                vec![self.tx_modifiable.to_u8()],
+            ));
+        }
+        global.push((key(PSBT_GLOBAL_VERSION), 2u32.to_le_bytes().to_vec()));
+
+        for (map, input) in input_maps.iter_mut().zip(&self.inputs) {
+            map.push((
+                key(PSBT_IN_PREVIOUS_TXID),
+                serialize(&input.previous_output.txid),
+            ));
+            map.push((
+                key(PSBT_IN_OUTPUT_INDEX),
+                input.previous_output.vout.to_le_bytes().to_vec(),
+            ));
+            if input.sequence != Sequence::MAX {
+                map.push((
+                    key(PSBT_IN_SEQUENCE),
+                    input.sequence.0.to_le_bytes().to_vec(),
+                ));
+            }
+            if let Some(time) = input.required_time_lock_time {
+                map.push((
+                    key(PSBT_IN_REQUIRED_TIME_LOCKTIME),
+                    time.to_consensus_u32().to_le_bytes().to_vec(),
+                ));
+            }
+            if let Some(height) = input.required_height_lock_time {
+                map.push((
+                    key(PSBT_IN_REQUIRED_HEIGHT_LOCKTIME),
+                    height.to_consensus_u32().to_le_bytes().to_vec(),
+                ));
+            }
+        }
+        for (map, output) in output_maps.iter_mut().zip(&self.outputs) {
+            map.push((key(PSBT_OUT_AMOUNT), serialize(&output.amount)));
+            map.push((key(PSBT_OUT_SCRIPT), output.script_pubkey.to_bytes()));
+        }
+
+        join_maps(&global, &input_maps, &output_maps)
+    }
+
+    /// Deserialize from raw bytes.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, PsbtV2Error> {
+        let mut bytes = bytes
+            .strip_prefix(MAGIC)
+            .ok_or(PsbtV2Error::InvalidEncoding)?;
+
+        let mut global = read_unique_map(&mut bytes)?;
+        let version = take(&mut global, PSBT_GLOBAL_VERSION)
+            .map(|value| u32_field(value, "PSBT_GLOBAL_VERSION"))
+            .transpose()?
+            .unwrap_or(0);
+        if version != 2 {
+            return Err(PsbtV2Error::InvalidVersion(version));
+        }
+        if take(&mut global, PSBT_GLOBAL_UNSIGNED_TX).is_some() {
+            return Err(PsbtV2Error::InvalidField("PSBT_GLOBAL_UNSIGNED_TX"));
+        }
+        let tx_version = take_required(
+            &mut global,
+            PSBT_GLOBAL_TX_VERSION,
+            "PSBT_GLOBAL_TX_VERSION",
+        )
+        .and_then(|value| u32_field(value, "PSBT_GLOBAL_TX_VERSION"))?;
+        let fallback_lock_time = take(&mut global, PSBT_GLOBAL_FALLBACK_LOCKTIME)
+            .map(|value| u32_field(value, "PSBT_GLOBAL_FALLBACK_LOCKTIME"))
+            .transpose()?
+            .map(absolute::LockTime::from_consensus);
+        let input_count = take_required(
+            &mut global,
+            PSBT_GLOBAL_INPUT_COUNT,
+            "PSBT_GLOBAL_INPUT_COUNT",
+        )
+        .and_then(|value| count_field(value, "PSBT_GLOBAL_INPUT_COUNT"))?;
+        let output_count = take_required(
+            &mut global,
+            PSBT_GLOBAL_OUTPUT_COUNT,
+            "PSBT_GLOBAL_OUTPUT_COUNT",
+        )
+        .and_then(|value| count_field(value, "PSBT_GLOBAL_OUTPUT_COUNT"))?;
+        let tx_modifiable = match take(&mut global, PSBT_GLOBAL_TX_MODIFIABLE) {
+            Some(value) => match value[..] {
+                [flags] => TxModifiable::from_u8(flags),
+                _ => return Err(PsbtV2Error::InvalidField("PSBT_GLOBAL_TX_MODIFIABLE")),
+            },
+            None => TxModifiable::default(),
+        };
+
+        let mut inputs = Vec::new();
+        let mut input_maps = Vec::new();
+        for _ in 0..input_count {
+            let mut map = read_unique_map(&mut bytes)?;
+            let txid = take_required(&mut map, PSBT_IN_PREVIOUS_TXID, "PSBT_IN_PREVIOUS_TXID")
+                .and_then(|value| {
+                    deserialize::<Txid>(&value)
+                        .map_err(|_| PsbtV2Error::InvalidField("PSBT_IN_PREVIOUS_TXID"))
+                })?;
+            let vout = take_required(&mut map, PSBT_IN_OUTPUT_INDEX, "PSBT_IN_OUTPUT_INDEX")
+                .and_then(|value| u32_field(value, "PSBT_IN_OUTPUT_INDEX"))?;
+            let sequence = take(&mut map, PSBT_IN_SEQUENCE)
+                .map(|value| u32_field(value, "PSBT_IN_SEQUENCE"))
+                .transpose()?
+                .map_or(Sequence::MAX, Sequence);
+            let required_time_lock_time = take(&mut map, PSBT_IN_REQUIRED_TIME_LOCKTIME)
+                .map(|value| {
+                    absolute::Time::from_consensus(u32_field(
+                        value,
+                        "PSBT_IN_REQUIRED_TIME_LOCKTIME",
+                    )?)
+                    .map_err(|_| PsbtV2Error::InvalidField("PSBT_IN_REQUIRED_TIME_LOCKTIME"))
+                })
+                .transpose()?;
+            let required_height_lock_time = take(&mut map, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)
+                .map(|value| {
+                    absolute::Height::from_consensus(u32_field(
+                        value,
+                        "PSBT_IN_REQUIRED_HEIGHT_LOCKTIME",
+                    )?)
+                    .map_err(|_| PsbtV2Error::InvalidField("PSBT_IN_REQUIRED_HEIGHT_LOCKTIME"))
+                })
+                .transpose()?;
+            inputs.push(PsbtV2Input {
+                previous_output: OutPoint::new(txid, vout),
+                sequence,
+                required_time_lock_time,
+                required_height_lock_time,
+                psbt_input: psbt::Input::default(),
+            });
+            input_maps.push(map);
+        }
+
+        let mut outputs = Vec::new();
+        let mut output_maps = Vec::new();
+        for _ in 0..output_count {
+            let mut map = read_unique_map(&mut bytes)?;
+            let amount =
+                take_required(&mut map, PSBT_OUT_AMOUNT, "PSBT_OUT_AMOUNT").and_then(|value| {
+                    deserialize::<Amount>(&value)
+                        .map_err(|_| PsbtV2Error::InvalidField("PSBT_OUT_AMOUNT"))
+                })?;
+            let script_pubkey = take_required(&mut map, PSBT_OUT_SCRIPT, "PSBT_OUT_SCRIPT")?;
+            outputs.push(PsbtV2Output {
+                amount,
+                script_pubkey: ScriptBuf::from_bytes(script_pubkey),
+                psbt_output: psbt::Output::default(),
+            });
+            output_maps.push(map);
+        }
+        if !bytes.is_empty() {
+            return Err(PsbtV2Error::InvalidEncoding);
+        }
+
+        // Let `rust-bitcoin` parse the fields shared with version 0, from a version 0 PSBT of the
+        // same transaction.
+        let mut psbt = PsbtV2 {
+            tx_version: transaction::Version(tx_version as i32),
+            fallback_lock_time,
+            tx_modifiable,
+            xpub: BTreeMap::new(),
+            proprietary: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+            inputs,
+            outputs,
+        };
+        let unsigned_tx = psbt.unsigned_tx_with_lock_time(absolute::LockTime::ZERO);
+        global.insert(0, (key(PSBT_GLOBAL_UNSIGNED_TX), serialize(&unsigned_tx)));
+        let v0 = Psbt::deserialize(&join_maps(&global, &input_maps, &output_maps))?;
+        psbt.update_from_v0(v0);
+        Ok(psbt)
+    }
+}
+
+impl From<Psbt> for PsbtV2 {
+    fn from(psbt: Psbt) -> Self {
+        Self::from_v0(psbt)
+    }
+}
+
+impl fmt::Display for PsbtV2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", BASE64_STANDARD.encode(self.serialize()))
+    }
+}
+
+impl FromStr for PsbtV2 {
+    type Err = PsbtV2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = BASE64_STANDARD
+            .decode(s)
+            .map_err(|_| PsbtV2Error::InvalidBase64)?;
+        Self::deserialize(&bytes)
+    }
+}
+
+fn key(type_value: u8) -> raw::Key {
+    raw::Key {
+        type_value,
+        key: Vec::new(),
+    }
+}
+
+/// Remove the value of the key of `type_value` without key data from `map`.
+fn take(map: &mut RawMap, type_value: u8) -> Option<Vec<u8>> {
+    let pos = map
+        .iter()
+        .position(|(key, _)| key.type_value == type_value && key.key.is_empty())?;
+    Some(map.remove(pos).1)
+}
+
+fn take_required(
+    map: &mut RawMap,
+    type_value: u8,
+    name: &'static str,
+) -> Result<Vec<u8>, PsbtV2Error> {
+    take(map, type_value).ok_or(PsbtV2Error::MissingField(name))
+}
+
+fn u32_field(value: Vec<u8>, name: &'static str) -> Result<u32, PsbtV2Error> {
+    <[u8; 4]>::try_from(value)
+        .map(u32::from_le_bytes)
+        .map_err(|_| PsbtV2Error::InvalidField(name))
+}
+
+fn count_field(value: Vec<u8>, name: &'static str) -> Result<u64, PsbtV2Error> {
+    match deserialize::<VarInt>(&value) {
+        Ok(VarInt(count)) => Ok(count),
+        Err(_) => Err(PsbtV2Error::InvalidField(name)),
+    }
+}
+
+fn read_var_int(bytes: &mut &[u8]) -> Result<u64, PsbtV2Error> {
+    let (VarInt(n), len) = deserialize_partial(bytes).map_err(|_| PsbtV2Error::InvalidEncoding)?;
+    *bytes = &bytes[len..];
+    Ok(n)
+}
+
+fn read_bytes<'a>(bytes: &mut &'a [u8], len: u64) -> Result<&'a [u8], PsbtV2Error> {
+    let len = usize::try_from(len).map_err(|_| PsbtV2Error::InvalidEncoding)?;
+    if bytes.len() < len {
+        return Err(PsbtV2Error::InvalidEncoding);
+    }
+    let (read, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(read)
+}
+
+/// Read a key-value map up to its separator.
+fn read_map(bytes: &mut &[u8]) -> Result<RawMap, PsbtV2Error> {
+    let mut map = RawMap::new();
+    loop {
+        let key_len = read_var_int(bytes)?;
+        if key_len == 0 {
+            return Ok(map);
+        }
+        let key = match read_bytes(bytes, key_len)? {
+            // Key types are compact sizes, but like `rust-bitcoin` we read and write a single
+            // byte, whatever its value.
+            [type_value, key @ ..] => raw::Key {
+                type_value: *type_value,
+                key: key.to_vec(),
+            },
+            [] => return Err(PsbtV2Error::InvalidEncoding),
+        };
+        let value_len = read_var_int(bytes)?;
+        let value = read_bytes(bytes, value_len)?.to_vec();
+        map.push((key, value));
+    }
+}
+
+/// Read a key-value map up to its separator, rejecting duplicate keys.
+fn read_unique_map(bytes: &mut &[u8]) -> Result<RawMap, PsbtV2Error> {
+    let map = read_map(bytes)?;
+    for (i, (key, _)) in map.iter().enumerate() {
+        if map[..i].iter().any(|(k, _)| k == key) {
+            return Err(PsbtV2Error::DuplicateKey(key.clone()));
+        }
+    }
+    Ok(map)
+}
+
+fn write_map(buf: &mut Vec<u8>, map: &RawMap) {
+    for (key, value) in map {
+        buf.extend(serialize(&VarInt(key.key.len() as u64 + 1)));
+        buf.push(key.type_value);
+        buf.extend(&key.key);
+        buf.extend(serialize(&VarInt(value.len() as u64)));
+        buf.extend(value);
+    }
+    buf.push(0x00);
+}
+
+/// Split a serialized version 0 PSBT into its maps. The unknown keys of the PSBT may repeat the
+/// keys of known fields, they are kept as they are.
+fn split_v0(
+    bytes: &[u8],
+    input_count: usize,
+    output_count: usize,
+) -> Result<(RawMap, Vec<RawMap>, Vec<RawMap>), PsbtV2Error> {
+    let mut bytes = bytes
+        .strip_prefix(MAGIC)
+        .ok_or(PsbtV2Error::InvalidEncoding)?;
+    let global = read_map(&mut bytes)?;
+    let input_maps = (0..input_count)
+        .map(|_| read_map(&mut bytes))
+        .collect::<Result<_, _>>()?;
+    let output_maps = (0..output_count)
+        .map(|_| read_map(&mut bytes))
+        .collect::<Result<_, _>>()?;
+    Ok((global, input_maps, output_maps))
+}
+
+fn join_maps(global: &RawMap, input_maps: &[RawMap], output_maps: &[RawMap]) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    write_map(&mut buf, global);
+    for map in input_maps.iter().chain(output_maps) {
+        write_map(&mut buf, map);
+    }
+    buf
+}
@@ -0,0 +1,337 @@
+//! BIP21 payment URIs
+//!
+//! This module implements parsing and serialization of the `bitcoin:` URIs described in
+//! [BIP21](https://github.com/bitcoin/bips/blob/master/bip-0021.mediawiki).
+//!
+//! ## Examples
+//!
+//! ### Pay a URI
+//!
+//! ```
+//! # use bdk_wallet::bip21::PaymentUri;
+//! # use bdk_wallet::test_utils::*;
+//! # let (mut wallet, _) = get_funded_wallet_wpkh();
+//! let uri: PaymentUri =
+//!     "bitcoin:2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX?amount=0.0002&label=Lunch".parse()?;
+//! assert_eq!(uri.label.as_deref(), Some("Lunch"));
+//!
+//! let mut builder = wallet.build_tx();
+//! builder.add_payment_uri(&uri)?;
+//! let psbt = builder.finish()?;
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! ### Request a payment
+//!
+//! ```
+//! # use bdk_wallet::test_utils::*;
+//! # use bdk_wallet::KeychainKind;
+//! # use bitcoin::Amount;
+//! # let (mut wallet, _) = get_funded_wallet_wpkh();
+//! let address = wallet.reveal_next_address(KeychainKind::External);
+//! let uri = address.payment_uri(Some(Amount::from_sat(20_000)), Some("Lunch".into()));
+//! assert!(uri.to_string().ends_with("?amount=0.0002&label=Lunch"));
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use bitcoin::address::{self, NetworkUnchecked};
+use bitcoin::amount::{Denomination, ParseAmountError};
+use bitcoin::{Address, Amount, Network};
+
+const SCHEME: &str = "bitcoin:";
+const REQUIRED_PREFIX: &str = "req-";
+
+/// A BIP21 payment URI
+///
+/// For a usage example see [this module](crate::wallet::bip21)'s documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentUri {
+    /// Address to pay
+    pub address: Address<NetworkUnchecked>,
+    /// Amount to pay
+    pub amount: Option<Amount>,
+    /// Label for the address, e.g. the name of the recipient
+    pub label: Option<String>,
+    /// Message describing the payment
+    pub message: Option<String>,
+    /// Other parameters, percent-decoded and in the order they appear in the URI
+    ///
+    /// Parameters starting with `req-` are required to be understood and are rejected when
+    /// parsing a URI.
+    pub extras: Vec<(String, String)>,
+}
+
+impl PaymentUri {
+    /// Create a payment URI for `address` without any parameter.
+    pub fn new(address: Address) -> Self {
+        Self {
+            address: address.into_unchecked(),
+            amount: None,
+            label: None,
+            message: None,
+            extras: Vec::new(),
+        }
+    }
+
+    /// The BIP78 payjoin endpoint of the URI, from its `pj` parameter.
+    ///
+    /// See [`payjoin::Sender`](crate::wallet::payjoin::Sender).
+    pub fn payjoin_endpoint(&self) -> Option<&str> {
+        self.extras
+            .iter()
+            .find(|(key, _)| key == "pj")
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The address to pay, after checking that it is valid on `network`.
+    pub fn address_for(&self, network: Network) -> Result<Address, Bip21Error> {
+        self.address
+            .clone()
+            .require_network(network)
+            .map_err(|_| Bip21Error::NetworkMismatch(network))
+    }
+}
+
+impl FromStr for PaymentUri {
+    type Err = Bip21Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .get(..SCHEME.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+            .map(|_| &s[SCHEME.len()..])
+            .ok_or(Bip21Error::InvalidScheme)?;
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut uri = PaymentUri {
+            address: Address::from_str(address).map_err(Bip21Error::Address)?,
+            amount: None,
+            label: None,
+            message: None,
+            extras: Vec::new(),
+        };
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let key = percent_decode(key)?;
+            match key.as_str() {
+                "amount" if uri.amount.is_none() => {
+                    let amount = Amount::from_str_in(value, Denomination::Bitcoin)
+                        .map_err(Bip21Error::Amount)?;
+                    uri.amount = Some(amount);
+                }
+                "label" if uri.label.is_none() => uri.label = Some(percent_decode(value)?),
+                "message" if uri.message.is_none() => uri.message = Some(percent_decode(value)?),
+                "amount" | "label" | "message" => return Err(Bip21Error::DuplicateParameter(key)),
+                _ if key.starts_with(REQUIRED_PREFIX) => {
+                    return Err(Bip21Error::UnknownRequiredParameter(key))
+                }
+                _ => uri.extras.push((key, percent_decode(value)?)),
+            }
+        }
+
+        Ok(uri)
+    }
+}
+
+impl fmt::Display for PaymentUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{SCHEME}{}", self.address.assume_checked_ref())?;
+
+        let mut separator = '?';
+        let mut write_param = |f: &mut fmt::Formatter<'_>, key: &str, value: &str| -> fmt::Result {
+            write!(
+                f,
+                "{separator}{}={}",
+                percent_encode(key),
+                percent_encode(value)
+            )?;
+            separator = '&';
+            Ok(())
+        };
+        if let Some(amount) = self.amount {
+            write_param(f, "amount", &amount_to_string(amount))?;
+        }
+        if let Some(label) = &self.label {
+            write_param(f, "label", label)?;
+        }
+        if let Some(message) = &self.message {
+            write_param(f, "message", message)?;
+        }
+        for (key, value) in &self.extras {
+            write_param(f, key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Format `amount` in bitcoin, without trailing zeros.
+fn amount_to_string(amount: Amount) -> String {
+    let amount = amount.to_string_in(Denomination::Bitcoin);
+    match amount.split_once('.') {
+        Some((whole, fraction)) if fraction.trim_end_matches('0').is_empty() => whole.to_string(),
+        Some(_) => amount.trim_end_matches('0').to_string(),
+        None => amount,
+    }
+}
+
+/// Percent-encode everything but the RFC3986 unreserved characters.
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&alloc::format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(s: &str) -> Result<String, Bip21Error> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let high = iter.next().and_then(|b| (b as char).to_digit(16));
+        let low = iter.next().and_then(|b| (b as char).to_digit(16));
+        match (high, low) {
+            (Some(high), Some(low)) => bytes.push((high * 16 + low) as u8),
+            _ => return Err(Bip21Error::InvalidEncoding),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| Bip21Error::InvalidEncoding)
+}
+
+/// Errors returned when parsing or paying a [`PaymentUri`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bip21Error {
+    /// The URI doesn't start with `bitcoin:`
+    InvalidScheme,
+    /// The address of the URI is invalid
+    Address(address::ParseError),
+    /// The amount of the URI isn't a valid amount of bitcoin
+    Amount(ParseAmountError),
+    /// A parameter isn't correctly percent-encoded UTF-8
+    InvalidEncoding,
+    /// A parameter appears more than once
+    DuplicateParameter(String),
+    /// A `req-` parameter that we don't understand
+    UnknownRequiredParameter(String),
+    /// The address isn't valid on the network of the wallet
+    NetworkMismatch(Network),
+    /// The URI doesn't specify the amount to pay
+    MissingAmount,
+}
+
+impl fmt::Display for Bip21Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bip21Error::InvalidScheme => write!(f, "URI scheme is not `bitcoin:`"),
+            Bip21Error::Address(err) => write!(f, "Invalid address: {err}"),
+            Bip21Error::Amount(err) => write!(f, "Invalid amount: {err}"),
+            Bip21Error::InvalidEncoding => write!(f, "Invalid percent-encoding"),
+            Bip21Error::DuplicateParameter(key) => write!(f, "Duplicate parameter `{key}`"),
+            Bip21Error::UnknownRequiredParameter(key) => {
+                write!(f, "Unknown required parameter `{key}`")
+            }
+            Bip21Error::NetworkMismatch(network) => {
+                write!(f, "Address is not valid on network {network}")
+            }
+            Bip21Error::MissingAmount => write!(f, "URI doesn't specify an amount"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Bip21Error {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ADDRESS: &str = "2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX";
+
+    #[test]
+    fn test_parse_payment_uri() {
+        let uri: PaymentUri = alloc::format!(
+            "BITCOIN:{ADDRESS}?amount=20.3&label=Luke-Jr&message=Donation%20for%20project%20xyz&somethingyoudontunderstand=50%25"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(uri.address, Address::from_str(ADDRESS).unwrap());
+        assert_eq!(uri.amount, Some(Amount::from_sat(2_030_000_000)));
+        assert_eq!(uri.label.as_deref(), Some("Luke-Jr"));
+        assert_eq!(uri.message.as_deref(), Some("Donation for project xyz"));
+        assert_eq!(
+            uri.extras,
+            vec![("somethingyoudontunderstand".into(), "50%".into())]
+        );
+
+        let uri: PaymentUri = alloc::format!("bitcoin:{ADDRESS}").parse().unwrap();
+        assert_eq!(uri, PaymentUri::new(uri.address.clone().assume_checked()));
+    }
+
+    #[test]
+    fn test_parse_payment_uri_errors() {
+        let parse = |uri: &str| PaymentUri::from_str(uri).unwrap_err();
+
+        assert_eq!(parse(ADDRESS), Bip21Error::InvalidScheme);
+        assert!(matches!(
+            parse("bitcoin:notanaddress"),
+            Bip21Error::Address(_)
+        ));
+        assert!(matches!(
+            parse(&alloc::format!("bitcoin:{ADDRESS}?amount=1.5btc")),
+            Bip21Error::Amount(_)
+        ));
+        assert_eq!(
+            parse(&alloc::format!("bitcoin:{ADDRESS}?label=%E")),
+            Bip21Error::InvalidEncoding
+        );
+        assert_eq!(
+            parse(&alloc::format!("bitcoin:{ADDRESS}?label=%FF")),
+            Bip21Error::InvalidEncoding
+        );
+        assert_eq!(
+            parse(&alloc::format!("bitcoin:{ADDRESS}?amount=1&amount=2")),
+            Bip21Error::DuplicateParameter("amount".into())
+        );
+        assert_eq!(
+            parse(&alloc::format!(
+                "bitcoin:{ADDRESS}?req-somethingyoudontunderstand=50"
+            )),
+            Bip21Error::UnknownRequiredParameter("req-somethingyoudontunderstand".into())
+        );
+    }
+
+    #[test]
+    fn test_payment_uri_roundtrip() {
+        let mut uri = PaymentUri::new(Address::from_str(ADDRESS).unwrap().assume_checked());
+        assert_eq!(uri.to_string(), alloc::format!("bitcoin:{ADDRESS}"));
+
+        uri.amount = Some(Amount::from_sat(100_000_000));
+        uri.label = Some("Luke-Jr".into());
+        uri.message = Some("50% off & more".into());
+        uri.extras.push(("lightning".into(), "lnbc1".into()));
+        let s = uri.to_string();
+        assert_eq!(
+            s,
+            alloc::format!(
+                "bitcoin:{ADDRESS}?amount=1&label=Luke-Jr&message=50%25%20off%20%26%20more&lightning=lnbc1"
+            )
+        );
+        assert_eq!(PaymentUri::from_str(&s).unwrap(), uri);
+
+        uri.amount = Some(Amount::from_sat(1_230));
+        assert!(uri.to_string().contains("amount=0.0000123&"));
+    }
+}
@@ -4,7 +4,7 @@ use bdk_chain::{
 use miniscript::{Descriptor, DescriptorPublicKey};
 use serde::{Deserialize, Serialize};
 
-use crate::locked_outpoints;
+use crate::{locked_outpoints, reservations, silent_payments};
 
 type IndexedTxGraphChangeSet =
     indexed_tx_graph::ChangeSet<ConfirmationBlockTime, keychain_txout::ChangeSet>;
@@ -118,6 +118,10 @@ pub struct ChangeSet {
     pub indexer: keychain_txout::ChangeSet,
     /// Changes to locked outpoints.
     pub locked_outpoints: locked_outpoints::ChangeSet,
+    /// Changes to reserved outpoints.
+    pub reservations: reservations::ChangeSet,
+    /// Changes to the silent payment outputs.
+    pub silent_payments: silent_payments::ChangeSet,
 }
 
 impl Merge for ChangeSet {
@@ -149,6 +153,12 @@ impl Merge for ChangeSet {
         // merge locked outpoints
         self.locked_outpoints.merge(other.locked_outpoints);
 
+        // merge reserved outpoints
+        self.reservations.merge(other.reservations);
+
+        // merge silent payment outputs
+        self.silent_payments.merge(other.silent_payments);
+
         Merge::merge(&mut self.local_chain, other.local_chain);
         Merge::merge(&mut self.tx_graph, other.tx_graph);
         Merge::merge(&mut self.indexer, other.indexer);
@@ -162,6 +172,8 @@ impl Merge for ChangeSet {
             && self.tx_graph.is_empty()
             && self.indexer.is_empty()
             && self.locked_outpoints.is_empty()
+            && self.reservations.is_empty()
+            && self.silent_payments.is_empty()
     }
 }
 
@@ -173,6 +185,14 @@ impl ChangeSet {
     pub const WALLET_TABLE_NAME: &'static str = "bdk_wallet";
     /// Name of table to store wallet locked outpoints.
     pub const WALLET_OUTPOINT_LOCK_TABLE_NAME: &'static str = "bdk_wallet_locked_outpoints";
+    /// Name of table to store wallet reserved outpoints.
+    pub const WALLET_OUTPOINT_RESERVATION_TABLE_NAME: &'static str =
+        "bdk_wallet_reserved_outpoints";
+    /// Name of table to store wallet silent payment outputs.
+    pub const WALLET_SILENT_PAYMENT_TABLE_NAME: &'static str = "bdk_wallet_silent_payments";
+    /// Name of table to store the outputs the wallet sent to silent payment addresses.
+    pub const WALLET_SILENT_PAYMENT_SENT_TABLE_NAME: &'static str =
+        "bdk_wallet_silent_payments_sent";
 
     /// Get v0 sqlite [ChangeSet] schema
     pub fn schema_v0() -> alloc::string::String {
@@ -199,12 +219,60 @@ impl ChangeSet {
         )
     }
 
+    /// Get v2 sqlite [`ChangeSet`] schema. Schema v2 adds a table for reserved outpoints.
+    pub fn schema_v2() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                txid TEXT NOT NULL, \
+                vout INTEGER NOT NULL, \
+                draft_id TEXT NOT NULL, \
+                expiry_height INTEGER, \
+                PRIMARY KEY(txid, vout) \
+                ) STRICT;",
+            Self::WALLET_OUTPOINT_RESERVATION_TABLE_NAME,
+        )
+    }
+
+    /// Get v3 sqlite [`ChangeSet`] schema. Schema v3 adds the silent payment address of the wallet
+    /// and a table for the outputs paying it.
+    pub fn schema_v3() -> alloc::string::String {
+        format!(
+            "ALTER TABLE {} ADD COLUMN silent_payment_address TEXT; \
+            CREATE TABLE {} ( \
+                txid TEXT NOT NULL, \
+                vout INTEGER NOT NULL, \
+                tweak TEXT NOT NULL, \
+                PRIMARY KEY(txid, vout) \
+                ) STRICT;",
+            Self::WALLET_TABLE_NAME,
+            Self::WALLET_SILENT_PAYMENT_TABLE_NAME,
+        )
+    }
+
+    /// Get v4 sqlite [`ChangeSet`] schema. Schema v4 adds a table for the outputs sent to silent
+    /// payment addresses.
+    pub fn schema_v4() -> alloc::string::String {
+        format!(
+            "CREATE TABLE {} ( \
+                script_pubkey BLOB PRIMARY KEY NOT NULL, \
+                address TEXT NOT NULL \
+                ) STRICT;",
+            Self::WALLET_SILENT_PAYMENT_SENT_TABLE_NAME,
+        )
+    }
+
     /// Initialize sqlite tables for wallet tables.
     pub fn init_sqlite_tables(db_tx: &chain::rusqlite::Transaction) -> chain::rusqlite::Result<()> {
         crate::rusqlite_impl::migrate_schema(
             db_tx,
             Self::WALLET_SCHEMA_NAME,
-            &[&Self::schema_v0(), &Self::schema_v1()],
+            &[
+                &Self::schema_v0(),
+                &Self::schema_v1(),
+                &Self::schema_v2(),
+                &Self::schema_v3(),
+                &Self::schema_v4(),
+            ],
         )?;
 
         bdk_chain::local_chain::ChangeSet::init_sqlite_tables(db_tx)?;
@@ -223,7 +291,7 @@ impl ChangeSet {
         let mut changeset = Self::default();
 
         let mut wallet_statement = db_tx.prepare(&format!(
-            "SELECT descriptor, change_descriptor, network FROM {}",
+            "SELECT descriptor, change_descriptor, network, silent_payment_address FROM {}",
             Self::WALLET_TABLE_NAME,
         ))?;
         let row = wallet_statement
@@ -234,13 +302,17 @@ impl ChangeSet {
                         "change_descriptor",
                     )?,
                     row.get::<_, Option<Impl<bitcoin::Network>>>("network")?,
+                    row.get::<_, Option<alloc::string::String>>("silent_payment_address")?,
                 ))
             })
             .optional()?;
-        if let Some((desc, change_desc, network)) = row {
+        if let Some((desc, change_desc, network, sp_address)) = row {
             changeset.descriptor = desc.map(Impl::into_inner);
             changeset.change_descriptor = change_desc.map(Impl::into_inner);
             changeset.network = network.map(Impl::into_inner);
+            changeset.silent_payments.address = sp_address
+                .map(|address| address.parse().map_err(from_sql_error))
+                .transpose()?;
         }
 
         // Select locked outpoints.
@@ -261,6 +333,71 @@ impl ChangeSet {
             locked_outpoints.insert(outpoint, true);
         }
 
+        // Select reserved outpoints.
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT txid, vout, draft_id, expiry_height FROM {}",
+            Self::WALLET_OUTPOINT_RESERVATION_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<Txid>>("txid")?,
+                row.get::<_, u32>("vout")?,
+                row.get::<_, alloc::string::String>("draft_id")?,
+                row.get::<_, Option<u32>>("expiry_height")?,
+            ))
+        })?;
+        let reserved_outpoints = &mut changeset.reservations.outpoints;
+        for row in rows {
+            let (Impl(txid), vout, draft_id, expiry_height) = row?;
+            let outpoint = OutPoint::new(txid, vout);
+            let reservation = crate::reservations::Reservation {
+                draft_id,
+                expiry_height,
+            };
+            reserved_outpoints.insert(outpoint, Some(reservation));
+        }
+
+        // Select silent payment outputs.
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT txid, vout, tweak FROM {}",
+            Self::WALLET_SILENT_PAYMENT_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<Txid>>("txid")?,
+                row.get::<_, u32>("vout")?,
+                row.get::<_, alloc::string::String>("tweak")?,
+            ))
+        })?;
+        for row in rows {
+            let (Impl(txid), vout, tweak) = row?;
+            let tweak = tweak.parse().map_err(from_sql_error)?;
+            changeset
+                .silent_payments
+                .outputs
+                .insert(OutPoint::new(txid, vout), tweak);
+        }
+
+        // Select outputs sent to silent payment addresses.
+        let mut stmt = db_tx.prepare(&format!(
+            "SELECT script_pubkey, address FROM {}",
+            Self::WALLET_SILENT_PAYMENT_SENT_TABLE_NAME,
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Impl<bitcoin::ScriptBuf>>("script_pubkey")?,
+                row.get::<_, alloc::string::String>("address")?,
+            ))
+        })?;
+        for row in rows {
+            let (Impl(script_pubkey), address) = row?;
+            let address = address.parse().map_err(from_sql_error)?;
+            changeset
+                .silent_payments
+                .sent
+                .insert(script_pubkey, address);
+        }
+
         changeset.local_chain = local_chain::ChangeSet::from_sqlite(db_tx)?;
         changeset.tx_graph = tx_graph::ChangeSet::<_>::from_sqlite(db_tx)?;
         changeset.indexer = keychain_txout::ChangeSet::from_sqlite(db_tx)?;
@@ -309,6 +446,17 @@ impl ChangeSet {
             })?;
         }
 
+        let mut sp_address_statement = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(id, silent_payment_address) VALUES(:id, :silent_payment_address) ON CONFLICT(id) DO UPDATE SET silent_payment_address=:silent_payment_address",
+            Self::WALLET_TABLE_NAME,
+        ))?;
+        if let Some(address) = &self.silent_payments.address {
+            sp_address_statement.execute(named_params! {
+                ":id": 0,
+                ":silent_payment_address": alloc::string::ToString::to_string(address),
+            })?;
+        }
+
         // Insert or delete locked outpoints.
         let mut insert_stmt = db_tx.prepare_cached(&format!(
             "INSERT OR IGNORE INTO {}(txid, vout) VALUES(:txid, :vout)",
@@ -333,6 +481,61 @@ impl ChangeSet {
             }
         }
 
+        // Insert, update or delete reserved outpoints.
+        let mut upsert_stmt = db_tx.prepare_cached(&format!(
+            "INSERT INTO {}(txid, vout, draft_id, expiry_height) VALUES(:txid, :vout, :draft_id, :expiry_height) ON CONFLICT(txid, vout) DO UPDATE SET draft_id=:draft_id, expiry_height=:expiry_height",
+            Self::WALLET_OUTPOINT_RESERVATION_TABLE_NAME
+        ))?;
+        let mut delete_stmt = db_tx.prepare_cached(&format!(
+            "DELETE FROM {} WHERE txid=:txid AND vout=:vout",
+            Self::WALLET_OUTPOINT_RESERVATION_TABLE_NAME,
+        ))?;
+        for (&outpoint, reservation) in &self.reservations.outpoints {
+            let bitcoin::OutPoint { txid, vout } = outpoint;
+            match reservation {
+                Some(reservation) => {
+                    upsert_stmt.execute(named_params! {
+                        ":txid": Impl(txid),
+                        ":vout": vout,
+                        ":draft_id": reservation.draft_id,
+                        ":expiry_height": reservation.expiry_height,
+                    })?;
+                }
+                None => {
+                    delete_stmt.execute(named_params! {
+                        ":txid": Impl(txid),
+                        ":vout": vout,
+                    })?;
+                }
+            }
+        }
+
+        // Insert silent payment outputs.
+        let mut insert_stmt = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(txid, vout, tweak) VALUES(:txid, :vout, :tweak)",
+            Self::WALLET_SILENT_PAYMENT_TABLE_NAME
+        ))?;
+        for (&outpoint, tweak) in &self.silent_payments.outputs {
+            let bitcoin::OutPoint { txid, vout } = outpoint;
+            insert_stmt.execute(named_params! {
+                ":txid": Impl(txid),
+                ":vout": vout,
+                ":tweak": alloc::string::ToString::to_string(&tweak.display_secret()),
+            })?;
+        }
+
+        // Insert outputs sent to silent payment addresses.
+        let mut insert_stmt = db_tx.prepare_cached(&format!(
+            "INSERT OR IGNORE INTO {}(script_pubkey, address) VALUES(:script_pubkey, :address)",
+            Self::WALLET_SILENT_PAYMENT_SENT_TABLE_NAME
+        ))?;
+        for (script_pubkey, address) in &self.silent_payments.sent {
+            insert_stmt.execute(named_params! {
+                ":script_pubkey": Impl(script_pubkey.clone()),
+                ":address": alloc::string::ToString::to_string(address),
+            })?;
+        }
+
         self.local_chain.persist_to_sqlite(db_tx)?;
         self.tx_graph.persist_to_sqlite(db_tx)?;
         self.indexer.persist_to_sqlite(db_tx)?;
@@ -385,3 +588,31 @@ impl From<locked_outpoints::ChangeSet> for ChangeSet {
         }
     }
 }
+
+impl From<reservations::ChangeSet> for ChangeSet {
+    fn from(reservations: reservations::ChangeSet) -> Self {
+        Self {
+            reservations,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<silent_payments::ChangeSet> for ChangeSet {
+    fn from(silent_payments: silent_payments::ChangeSet) -> Self {
+        Self {
+            silent_payments,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+fn from_sql_error<E: core::fmt::Display>(err: E) -> chain::rusqlite::Error {
+    use alloc::string::ToString;
+    chain::rusqlite::Error::FromSqlConversionFailure(
+        0,
+        chain::rusqlite::types::Type::Text,
+        err.to_string().into(),
+    )
+}
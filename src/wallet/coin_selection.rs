@@ -16,7 +16,10 @@
 //!
 //! You can specify a custom coin selection algorithm through the [`coin_selection`] method on
 //! [`TxBuilder`]. [`DefaultCoinSelectionAlgorithm`] aliases the coin selection algorithm that will
-//! be used if it is not explicitly set.
+//! be used if it is not explicitly set. [`WasteMetricCoinSelection`] runs several of the
+//! algorithms of this module and picks the least wasteful selection.
+//! The algorithms of this module can explain their selection with a [`SelectionDiagnostics`]
+//! report, see [`CoinSelectionContext::diagnostics`].
 //!
 //! [`TxBuilder`]: super::tx_builder::TxBuilder
 //! [`coin_selection`]: super::tx_builder::TxBuilder::coin_selection
@@ -68,6 +71,7 @@
 //!             return Err(coin_selection::InsufficientFunds {
 //!                 needed: amount_needed_with_fees,
 //!                 available: selected_amount,
+//!                 diagnostics: None,
 //!             });
 //!         }
 //!
@@ -79,6 +83,9 @@
 //!             selected: all_utxos_selected,
 //!             fee_amount: additional_fees,
 //!             excess,
+//!             algorithm: None,
+//!             waste: None,
+//!             diagnostics: None,
 //!         })
 //!     }
 //! }
@@ -106,16 +113,18 @@ use crate::Utxo;
 use crate::WeightedUtxo;
 use bitcoin::{Amount, FeeRate, SignedAmount};
 
+use alloc::boxed::Box;
 use alloc::vec::Vec;
 use bitcoin::consensus::encode::serialize;
-use bitcoin::TxIn;
+use bitcoin::{OutPoint, TxIn};
 use bitcoin::{Script, Weight};
 
 use core::convert::TryInto;
 use core::fmt::{self, Formatter};
 use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
 
-use super::utils::shuffle_slice;
+use super::utils::{shuffle_slice, ScriptType};
 /// Default coin selection algorithm used by [`TxBuilder`](super::tx_builder::TxBuilder) if not
 /// overridden
 pub type DefaultCoinSelectionAlgorithm = BranchAndBoundCoinSelection<SingleRandomDraw>;
@@ -129,6 +138,8 @@ pub struct InsufficientFunds {
     pub needed: Amount,
     /// Amount available for spending
     pub available: Amount,
+    /// Why the selection failed, if requested with [`CoinSelectionContext::diagnostics`]
+    pub diagnostics: Option<Box<SelectionDiagnostics>>,
 }
 
 impl fmt::Display for InsufficientFunds {
@@ -174,6 +185,12 @@ pub struct CoinSelectionResult {
     pub fee_amount: Amount,
     /// Remaining amount after deducing fees and outgoing outputs
     pub excess: Excess,
+    /// The algorithm which made the selection, set by [`WasteMetricCoinSelection`]
+    pub algorithm: Option<SelectionAlgorithm>,
+    /// The waste metric of the selection, set by [`WasteMetricCoinSelection`]
+    pub waste: Option<SignedAmount>,
+    /// How the selection was made, if requested with [`CoinSelectionContext::diagnostics`]
+    pub diagnostics: Option<Box<SelectionDiagnostics>>,
 }
 
 impl CoinSelectionResult {
@@ -194,6 +211,294 @@ impl CoinSelectionResult {
     }
 }
 
+/// Report of how a coin selection was made, or why it failed
+///
+/// Coin selection algorithms only fill in this report if asked to with
+/// [`CoinSelectionContext::diagnostics`], see also
+/// [`TxBuilder::diagnostics`](super::tx_builder::TxBuilder::diagnostics). It is displayed as a
+/// human readable report.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectionDiagnostics {
+    /// The UTXOs coin selection could choose from
+    pub candidates: Vec<SelectionCandidate>,
+    /// The UTXOs which were not considered, and why
+    pub excluded: Vec<(OutPoint, ExclusionReason)>,
+    /// The candidates whose relative timelock (`older()`) isn't met at the spending height
+    ///
+    /// They can still be selected, but the transaction can't be mined until they mature.
+    pub timelock_not_met: Vec<OutPoint>,
+    /// The runs of the coin selection algorithms, in order
+    ///
+    /// When an algorithm fails, its [`outcome`](SelectionAttempt::outcome) is the reason why the
+    /// next algorithm was tried.
+    pub attempts: Vec<SelectionAttempt>,
+}
+
+/// A UTXO coin selection could choose from, see [`SelectionDiagnostics`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionCandidate {
+    /// The outpoint of the UTXO
+    pub outpoint: OutPoint,
+    /// The value of the UTXO
+    pub value: Amount,
+    /// The value of the UTXO minus the fee for spending it
+    pub effective_value: SignedAmount,
+    /// Whether the UTXO must be spent
+    pub required: bool,
+}
+
+/// Why a UTXO was not considered by coin selection, see [`SelectionDiagnostics`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// Only the manually selected UTXOs can be spent
+    NotManuallySelected,
+    /// The UTXO is locked
+    Locked,
+    /// The UTXO is reserved by another transaction
+    Reserved,
+    /// The UTXO is the output of a coinbase transaction which is not mature yet
+    ImmatureCoinbase,
+    /// The UTXO is unconfirmed and the transaction replaces another one, which only allows new
+    /// confirmed inputs
+    Unconfirmed,
+    /// The UTXO has fewer confirmations than required
+    BelowMinConfirmations,
+    /// The UTXO was marked as unspendable
+    Unspendable,
+    /// The UTXO is not allowed by the change spend policy
+    ChangePolicy,
+    /// Spending the UTXO would break the TRUC (BIP431) topology rules
+    TrucTopology,
+    /// The UTXO can't be spent by a transaction paying silent payment addresses
+    SilentPaymentIneligible,
+    /// Spending the UTXO costs at least its value at the fee rate
+    NegativeEffectiveValue,
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotManuallySelected => write!(f, "not manually selected"),
+            Self::Locked => write!(f, "locked"),
+            Self::Reserved => write!(f, "reserved"),
+            Self::ImmatureCoinbase => write!(f, "immature coinbase"),
+            Self::Unconfirmed => write!(f, "unconfirmed"),
+            Self::BelowMinConfirmations => write!(f, "not enough confirmations"),
+            Self::Unspendable => write!(f, "marked unspendable"),
+            Self::ChangePolicy => write!(f, "change spend policy"),
+            Self::TrucTopology => write!(f, "TRUC topology"),
+            Self::SilentPaymentIneligible => write!(f, "not eligible for silent payments"),
+            Self::NegativeEffectiveValue => write!(f, "negative effective value"),
+        }
+    }
+}
+
+/// A run of a coin selection algorithm, see [`SelectionDiagnostics`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionAttempt {
+    /// The algorithm run
+    pub algorithm: SelectionAlgorithm,
+    /// How the run ended
+    pub outcome: SelectionOutcome,
+    /// The waste metric of the selection, set by [`WasteMetricCoinSelection`]
+    pub waste: Option<SignedAmount>,
+}
+
+/// How a run of a coin selection algorithm ended, see [`SelectionAttempt`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionOutcome {
+    /// UTXOs were selected
+    Selected {
+        /// The number of UTXOs selected
+        inputs: usize,
+        /// The total value of the UTXOs selected
+        amount: Amount,
+    },
+    /// [`BranchAndBoundCoinSelection`] found no selection avoiding change
+    NoExactMatch,
+    /// [`BranchAndBoundCoinSelection`] gave up after too many tries
+    TotalTriesExceeded,
+    /// [`CoinGrinderCoinSelection`] found no selection paying for a change output
+    NoSolution,
+    /// The UTXOs are not enough to pay the target and the fee
+    InsufficientFunds {
+        /// Amount needed
+        needed: Amount,
+        /// Amount available
+        available: Amount,
+    },
+}
+
+impl fmt::Display for SelectionOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Selected { inputs, amount } => {
+                write!(f, "selected {inputs} UTXOs worth {amount}")
+            }
+            Self::NoExactMatch => write!(f, "no selection without change"),
+            Self::TotalTriesExceeded => write!(f, "too many tries"),
+            Self::NoSolution => write!(f, "no selection with change"),
+            Self::InsufficientFunds { needed, available } => {
+                write!(
+                    f,
+                    "insufficient funds: {available} available of {needed} needed"
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for SelectionDiagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Candidates:")?;
+        for candidate in &self.candidates {
+            write!(
+                f,
+                "  {} value {} effective value {}",
+                candidate.outpoint, candidate.value, candidate.effective_value
+            )?;
+            if candidate.required {
+                write!(f, " (required)")?;
+            }
+            if self.timelock_not_met.contains(&candidate.outpoint) {
+                write!(f, " (timelock not met)")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "Excluded:")?;
+        for (outpoint, reason) in &self.excluded {
+            writeln!(f, "  {outpoint}: {reason}")?;
+        }
+        writeln!(f, "Attempts:")?;
+        for attempt in &self.attempts {
+            write!(f, "  {}: {}", attempt.algorithm, attempt.outcome)?;
+            if let Some(waste) = attempt.waste {
+                write!(f, ", waste {waste}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl SelectionDiagnostics {
+    // Start the diagnostics of a selection among `required_utxos` and `optional_utxos`, if the
+    // context asks for them.
+    fn start(
+        context: &CoinSelectionContext,
+        required_utxos: &[WeightedUtxo],
+        optional_utxos: &[WeightedUtxo],
+        fee_rate: FeeRate,
+    ) -> Option<Self> {
+        if !context.diagnostics {
+            return None;
+        }
+        let candidate = |required: bool, weighted_utxo: &WeightedUtxo| {
+            let group = OutputGroup::new(weighted_utxo.clone(), fee_rate);
+            SelectionCandidate {
+                outpoint: weighted_utxo.utxo.outpoint(),
+                value: weighted_utxo.utxo.txout().value,
+                effective_value: group.effective_value,
+                required,
+            }
+        };
+        let candidates = required_utxos
+            .iter()
+            .map(|u| candidate(true, u))
+            .chain(optional_utxos.iter().map(|u| candidate(false, u)))
+            .collect();
+        Some(Self {
+            candidates,
+            ..Default::default()
+        })
+    }
+
+    // Exclude the optional candidates with a negative effective value, as the search algorithms
+    // do.
+    fn exclude_negative_effective_value(&mut self) {
+        let excluded = self
+            .candidates
+            .iter()
+            .filter(|c| !c.required && !c.effective_value.is_positive())
+            .map(|c| (c.outpoint, ExclusionReason::NegativeEffectiveValue))
+            .collect::<Vec<_>>();
+        self.excluded.extend(excluded);
+    }
+
+    // Append the diagnostics of a later run.
+    fn append(&mut self, other: SelectionDiagnostics) {
+        if self.candidates.is_empty() {
+            self.candidates = other.candidates;
+        }
+        for excluded in other.excluded {
+            if !self.excluded.contains(&excluded) {
+                self.excluded.push(excluded);
+            }
+        }
+        for outpoint in other.timelock_not_met {
+            if !self.timelock_not_met.contains(&outpoint) {
+                self.timelock_not_met.push(outpoint);
+            }
+        }
+        self.attempts.extend(other.attempts);
+    }
+}
+
+impl SelectionOutcome {
+    fn of(result: &Result<CoinSelectionResult, InsufficientFunds>) -> Self {
+        match result {
+            Ok(result) => Self::Selected {
+                inputs: result.selected.len(),
+                amount: result.selected_amount(),
+            },
+            Err(err) => Self::InsufficientFunds {
+                needed: err.needed,
+                available: err.available,
+            },
+        }
+    }
+}
+
+fn diagnostics_of(
+    result: &mut Result<CoinSelectionResult, InsufficientFunds>,
+) -> &mut Option<Box<SelectionDiagnostics>> {
+    match result {
+        Ok(result) => &mut result.diagnostics,
+        Err(err) => &mut err.diagnostics,
+    }
+}
+
+// Put `diagnostics` before the ones `result` already carries.
+pub(crate) fn with_diagnostics(
+    mut result: Result<CoinSelectionResult, InsufficientFunds>,
+    diagnostics: Option<SelectionDiagnostics>,
+) -> Result<CoinSelectionResult, InsufficientFunds> {
+    if let Some(mut diagnostics) = diagnostics {
+        let slot = diagnostics_of(&mut result);
+        if let Some(later) = slot.take() {
+            diagnostics.append(*later);
+        }
+        *slot = Some(Box::new(diagnostics));
+    }
+    result
+}
+
+// Record the run of `algorithm` which ended with `result`.
+fn record_attempt(
+    mut diagnostics: Option<SelectionDiagnostics>,
+    algorithm: SelectionAlgorithm,
+    result: Result<CoinSelectionResult, InsufficientFunds>,
+) -> Result<CoinSelectionResult, InsufficientFunds> {
+    if let Some(diagnostics) = &mut diagnostics {
+        diagnostics.attempts.push(SelectionAttempt {
+            algorithm,
+            outcome: SelectionOutcome::of(&result),
+            waste: None,
+        });
+    }
+    with_diagnostics(result, diagnostics)
+}
+
 /// Trait for generalized coin selection algorithms
 ///
 /// This trait can be implemented to make the [`Wallet`](super::Wallet) use a customized coin
@@ -222,6 +527,75 @@ pub trait CoinSelectionAlgorithm: core::fmt::Debug {
         drain_script: &Script,
         rand: &mut R,
     ) -> Result<CoinSelectionResult, InsufficientFunds>;
+
+    /// Perform the coin selection taking the long-term fee expectations and the change
+    /// requirements of `context` into account
+    ///
+    /// The algorithms of this module use `context` to decide when change is worth creating.
+    /// The default implementation ignores it and calls [`coin_select`](Self::coin_select).
+    #[allow(clippy::too_many_arguments)]
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let _ = context;
+        self.coin_select(
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+}
+
+impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for &Cs {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        (**self).coin_select(
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        (**self).coin_select_with_context(
+            context,
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
 }
 
 /// Simple and dumb coin selection
@@ -235,12 +609,35 @@ impl CoinSelectionAlgorithm for LargestFirstCoinSelection {
     fn coin_select<R: RngCore>(
         &self,
         required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
         mut optional_utxos: Vec<WeightedUtxo>,
         fee_rate: FeeRate,
         target_amount: Amount,
         drain_script: &Script,
         _: &mut R,
     ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let diagnostics =
+            SelectionDiagnostics::start(context, &required_utxos, &optional_utxos, fee_rate);
         // We put the "required UTXOs" first and make sure the optional UTXOs are sorted,
         // initially smallest to largest, before being reversed with `.rev()`.
         let utxos = {
@@ -251,7 +648,11 @@ impl CoinSelectionAlgorithm for LargestFirstCoinSelection {
                 .chain(optional_utxos.into_iter().rev().map(|utxo| (false, utxo)))
         };
 
-        select_sorted_utxos(utxos, fee_rate, target_amount, drain_script)
+        record_attempt(
+            diagnostics,
+            SelectionAlgorithm::LargestFirst,
+            select_sorted_utxos(context, utxos, fee_rate, target_amount, drain_script),
+        )
     }
 }
 
@@ -267,12 +668,35 @@ impl CoinSelectionAlgorithm for OldestFirstCoinSelection {
     fn coin_select<R: RngCore>(
         &self,
         required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
         mut optional_utxos: Vec<WeightedUtxo>,
         fee_rate: FeeRate,
         target_amount: Amount,
         drain_script: &Script,
         _: &mut R,
     ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let diagnostics =
+            SelectionDiagnostics::start(context, &required_utxos, &optional_utxos, fee_rate);
         // We put the "required UTXOs" first and make sure the optional UTXOs are sorted from
         // oldest to newest according to blocktime
         // For UTXOs that doesn't exist in DB (Utxo::Foreign), they will have lowest priority to be
@@ -289,7 +713,140 @@ impl CoinSelectionAlgorithm for OldestFirstCoinSelection {
                 .chain(optional_utxos.into_iter().map(|utxo| (false, utxo)))
         };
 
-        select_sorted_utxos(utxos, fee_rate, target_amount, drain_script)
+        record_attempt(
+            diagnostics,
+            SelectionAlgorithm::OldestFirst,
+            select_sorted_utxos(context, utxos, fee_rate, target_amount, drain_script),
+        )
+    }
+}
+
+/// Long-term fee expectations and change requirements used by the coin selection algorithms
+///
+/// A change output costs the fee for creating it now and the fee for spending it later. Change
+/// is only created if the remaining amount pays for the change output and leaves at least
+/// [`min_change`](Self::min_change), the dust limit of the drain script and the cost of spending
+/// it later at [`discard_fee_rate`](Self::discard_fee_rate). Otherwise the remaining amount is
+/// dropped to fees.
+///
+/// The default context reproduces [`decide_change`]: change is created as soon as it is above the
+/// dust limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinSelectionContext {
+    /// Fee rate expected in the long term, at which the wallet will spend its outputs
+    pub long_term_fee_rate: FeeRate,
+    /// Weight of an input spending a change output
+    pub change_spend_weight: Weight,
+    /// Fee rate at which spending a change output later must be affordable for it to be created
+    pub discard_fee_rate: FeeRate,
+    /// Minimum value of a change output
+    pub min_change: Amount,
+    /// Whether to report how the selection was made, see [`SelectionDiagnostics`]
+    #[serde(default)]
+    pub diagnostics: bool,
+}
+
+impl Default for CoinSelectionContext {
+    fn default() -> Self {
+        Self {
+            // Bitcoin Core's default `-consolidatefeerate`
+            long_term_fee_rate: FeeRate::from_sat_per_vb_u32(10),
+            // P2WPKH input: outpoint, sequence and script_sig len (41 bytes) + witness
+            change_spend_weight: Weight::from_wu(41 * 4 + 1 + 1 + 72 + 1 + 33),
+            discard_fee_rate: FeeRate::ZERO,
+            min_change: Amount::ZERO,
+            diagnostics: false,
+        }
+    }
+}
+
+impl CoinSelectionContext {
+    /// Fee for creating a change output paying to `drain_script` at `fee_rate`
+    pub fn change_fee(&self, fee_rate: FeeRate, drain_script: &Script) -> Amount {
+        // drain_output_len = size(len(script_pubkey)) + len(script_pubkey) + size(output_value)
+        let drain_output_len = serialize(drain_script).len() + 8usize;
+        fee_rate * Weight::from_vb(drain_output_len as u64).expect("overflow occurred")
+    }
+
+    /// Minimum value of a change output paying to `drain_script`
+    pub fn min_change_value(&self, drain_script: &Script) -> Amount {
+        drain_script
+            .minimal_non_dust()
+            .max(self.min_change)
+            .max(self.discard_fee_rate * self.change_spend_weight)
+    }
+
+    /// Cost of a change output paying to `drain_script`: the fee for creating it at `fee_rate`
+    /// and for spending it later at the discard fee rate
+    pub fn cost_of_change(&self, fee_rate: FeeRate, drain_script: &Script) -> Amount {
+        self.change_fee(fee_rate, drain_script) + self.discard_fee_rate * self.change_spend_weight
+    }
+
+    /// Decide if change can be created
+    ///
+    /// - `remaining_amount`: the amount in which the selected coins exceed the target amount
+    /// - `fee_rate`: required fee rate for the current selection
+    /// - `drain_script`: script to consider change creation
+    pub fn decide_change(
+        &self,
+        remaining_amount: Amount,
+        fee_rate: FeeRate,
+        drain_script: &Script,
+    ) -> Excess {
+        let change_fee = self.change_fee(fee_rate, drain_script);
+        let drain_val = remaining_amount.checked_sub(change_fee).unwrap_or_default();
+        let dust_threshold = self.min_change_value(drain_script);
+
+        if drain_val.is_dust(drain_script) || drain_val < dust_threshold {
+            Excess::NoChange {
+                dust_threshold,
+                change_fee,
+                remaining_amount,
+            }
+        } else {
+            Excess::Change {
+                amount: drain_val,
+                fee: change_fee,
+            }
+        }
+    }
+
+    /// Calculate the waste metric of a selection `result` made from `utxos` at `fee_rate`
+    ///
+    /// This is the fee paid for the selected inputs at `fee_rate` minus the fee they would cost at
+    /// the long-term fee rate, plus either the fee for creating the change output and spending it
+    /// later at the long-term fee rate, or the excess dropped to fees when there is no change.
+    pub fn waste(
+        &self,
+        result: &CoinSelectionResult,
+        utxos: &[WeightedUtxo],
+        fee_rate: FeeRate,
+    ) -> SignedAmount {
+        let inputs_weight = result
+            .selected
+            .iter()
+            .filter_map(|utxo| {
+                utxos
+                    .iter()
+                    .find(|wutxo| wutxo.utxo.outpoint() == utxo.outpoint())
+            })
+            .map(|wutxo| {
+                TxIn::default()
+                    .segwit_weight()
+                    .checked_add(wutxo.satisfaction_weight)
+                    .expect("`Weight` addition should not cause an integer overflow")
+            })
+            .fold(Weight::ZERO, |acc, weight| acc + weight);
+        let to_signed = |amount: Amount| amount.to_signed().expect("signed amount");
+        let inputs_waste = to_signed(fee_rate * inputs_weight)
+            - to_signed(self.long_term_fee_rate * inputs_weight);
+        let change_waste = match result.excess {
+            Excess::Change { fee, .. } => fee + self.long_term_fee_rate * self.change_spend_weight,
+            Excess::NoChange {
+                remaining_amount, ..
+            } => remaining_amount,
+        };
+        inputs_waste + to_signed(change_waste)
     }
 }
 
@@ -298,29 +855,14 @@ impl CoinSelectionAlgorithm for OldestFirstCoinSelection {
 /// - `remaining_amount`: the amount in which the selected coins exceed the target amount
 /// - `fee_rate`: required fee rate for the current selection
 /// - `drain_script`: script to consider change creation
+///
+/// This is [`CoinSelectionContext::decide_change`] with the default context.
 pub fn decide_change(remaining_amount: Amount, fee_rate: FeeRate, drain_script: &Script) -> Excess {
-    // drain_output_len = size(len(script_pubkey)) + len(script_pubkey) + size(output_value)
-    let drain_output_len = serialize(drain_script).len() + 8usize;
-    let change_fee =
-        fee_rate * Weight::from_vb(drain_output_len as u64).expect("overflow occurred");
-    let drain_val = remaining_amount.checked_sub(change_fee).unwrap_or_default();
-
-    if drain_val.is_dust(drain_script) {
-        let dust_threshold = drain_script.minimal_non_dust();
-        Excess::NoChange {
-            dust_threshold,
-            change_fee,
-            remaining_amount,
-        }
-    } else {
-        Excess::Change {
-            amount: drain_val,
-            fee: change_fee,
-        }
-    }
+    CoinSelectionContext::default().decide_change(remaining_amount, fee_rate, drain_script)
 }
 
 fn select_sorted_utxos(
+    context: &CoinSelectionContext,
     utxos: impl Iterator<Item = (bool, WeightedUtxo)>,
     fee_rate: FeeRate,
     target_amount: Amount,
@@ -352,17 +894,21 @@ fn select_sorted_utxos(
         return Err(InsufficientFunds {
             needed: amount_needed_with_fees,
             available: selected_amount,
+            diagnostics: None,
         });
     }
 
     let remaining_amount = selected_amount - amount_needed_with_fees;
 
-    let excess = decide_change(remaining_amount, fee_rate, drain_script);
+    let excess = context.decide_change(remaining_amount, fee_rate, drain_script);
 
     Ok(CoinSelectionResult {
         selected,
         fee_amount,
         excess,
+        algorithm: None,
+        waste: None,
+        diagnostics: None,
     })
 }
 
@@ -377,6 +923,14 @@ struct OutputGroup {
 }
 
 impl OutputGroup {
+    // Weight of the input spending the UTXO.
+    fn weight(&self) -> Weight {
+        TxIn::default()
+            .segwit_weight()
+            .checked_add(self.weighted_utxo.satisfaction_weight)
+            .expect("`Weight` addition should not cause an integer overflow")
+    }
+
     fn new(weighted_utxo: WeightedUtxo, fee_rate: FeeRate) -> Self {
         let fee = fee_rate
             * TxIn::default()
@@ -401,9 +955,12 @@ impl OutputGroup {
 /// Branch and bound coin selection
 ///
 /// Code adapted from Bitcoin Core's implementation and from Mark Erhardt Master's Thesis: <http://murch.one/wp-content/uploads/2016/11/erhardt2016coinselection.pdf>
+///
+/// A selection avoids change if its excess is below the cost of a change output paying to the
+/// drain script, see [`CoinSelectionContext::cost_of_change`]. Among those, the one with the
+/// least waste at the [`long_term_fee_rate`](CoinSelectionContext::long_term_fee_rate) is chosen.
 #[derive(Debug, Clone)]
 pub struct BranchAndBoundCoinSelection<Cs = SingleRandomDraw> {
-    size_of_change: u64,
     fallback_algorithm: Cs,
 }
 
@@ -421,8 +978,6 @@ enum BnbError {
 impl<Cs: Default> Default for BranchAndBoundCoinSelection<Cs> {
     fn default() -> Self {
         Self {
-            // P2WPKH cost of change -> value (8 bytes) + script len (1 bytes) + script (22 bytes)
-            size_of_change: 8 + 1 + 22,
             fallback_algorithm: Cs::default(),
         }
     }
@@ -430,11 +985,21 @@ impl<Cs: Default> Default for BranchAndBoundCoinSelection<Cs> {
 
 impl<Cs> BranchAndBoundCoinSelection<Cs> {
     /// Create new instance with a target `size_of_change` and `fallback_algorithm`.
-    pub fn new(size_of_change: u64, fallback_algorithm: Cs) -> Self {
-        Self {
-            size_of_change,
-            fallback_algorithm,
-        }
+    ///
+    /// `size_of_change` is ignored, the cost of change is derived from the drain script and the
+    /// [`CoinSelectionContext`].
+    #[deprecated(
+        since = "3.0.0",
+        note = "the size of change is derived from the drain script, use `with_fallback` instead"
+    )]
+    pub fn new(_size_of_change: u64, fallback_algorithm: Cs) -> Self {
+        Self::with_fallback(fallback_algorithm)
+    }
+
+    /// Create new instance with a `fallback_algorithm`, used when no selection without change is
+    /// found.
+    pub fn with_fallback(fallback_algorithm: Cs) -> Self {
+        Self { fallback_algorithm }
     }
 }
 
@@ -449,6 +1014,27 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
         target_amount: Amount,
         drain_script: &Script,
         rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
     ) -> Result<CoinSelectionResult, InsufficientFunds> {
         // Mapping every (UTXO, usize) to an output group
         let required_ogs: Vec<OutputGroup> = required_utxos
@@ -464,6 +1050,12 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
             .filter(|u| u.effective_value.is_positive())
             .collect();
 
+        let mut diagnostics =
+            SelectionDiagnostics::start(context, &required_utxos, &optional_utxos, fee_rate);
+        if let Some(diagnostics) = &mut diagnostics {
+            diagnostics.exclude_negative_effective_value();
+        }
+
         let curr_value = required_ogs
             .iter()
             .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
@@ -472,8 +1064,9 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
             .iter()
             .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
 
-        let cost_of_change = (Weight::from_vb(self.size_of_change).expect("overflow occurred")
-            * fee_rate)
+        // Creating the change now and spending it later at the discard fee rate.
+        let cost_of_change = context
+            .cost_of_change(fee_rate, drain_script)
             .to_signed()
             .expect("signed amount");
 
@@ -504,10 +1097,15 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
                 );
 
                 // Add to the target the fee cost of the UTXOs
-                return Err(InsufficientFunds {
-                    needed: target_amount + utxo_fees,
-                    available: utxo_value,
-                });
+                return record_attempt(
+                    diagnostics,
+                    SelectionAlgorithm::BranchAndBound,
+                    Err(InsufficientFunds {
+                        needed: target_amount + utxo_fees,
+                        available: utxo_value,
+                        diagnostics: None,
+                    }),
+                );
             }
         }
 
@@ -523,9 +1121,13 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
                 .to_unsigned()
                 .expect("remaining amount can't be negative");
 
-            let excess = decide_change(remaining_amount, fee_rate, drain_script);
+            let excess = context.decide_change(remaining_amount, fee_rate, drain_script);
 
-            return Ok(calculate_cs_result(vec![], required_ogs, excess));
+            return record_attempt(
+                diagnostics,
+                SelectionAlgorithm::BranchAndBound,
+                Ok(calculate_cs_result(vec![], required_ogs, excess)),
+            );
         }
 
         match self.bnb(
@@ -537,16 +1139,33 @@ impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for BranchAndBoundCoinSe
             cost_of_change,
             drain_script,
             fee_rate,
+            context,
         ) {
-            Ok(r) => Ok(r),
-            Err(_) => self.fallback_algorithm.coin_select(
-                required_utxos,
-                optional_utxos,
-                fee_rate,
-                target_amount,
-                drain_script,
-                rand,
-            ),
+            Ok(r) => record_attempt(diagnostics, SelectionAlgorithm::BranchAndBound, Ok(r)),
+            Err(err) => {
+                if let Some(diagnostics) = &mut diagnostics {
+                    diagnostics.attempts.push(SelectionAttempt {
+                        algorithm: SelectionAlgorithm::BranchAndBound,
+                        outcome: match err {
+                            BnbError::NoExactMatch => SelectionOutcome::NoExactMatch,
+                            BnbError::TotalTriesExceeded => SelectionOutcome::TotalTriesExceeded,
+                        },
+                        waste: None,
+                    });
+                }
+                with_diagnostics(
+                    self.fallback_algorithm.coin_select_with_context(
+                        context,
+                        required_utxos,
+                        optional_utxos,
+                        fee_rate,
+                        target_amount,
+                        drain_script,
+                        rand,
+                    ),
+                    diagnostics,
+                )
+            }
         }
     }
 }
@@ -565,6 +1184,7 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
         cost_of_change: SignedAmount,
         drain_script: &Script,
         fee_rate: FeeRate,
+        context: &CoinSelectionContext,
     ) -> Result<CoinSelectionResult, BnbError> {
         // current_selection[i] will contain true if we are using optional_utxos[i],
         // false otherwise. Note that current_selection.len() could be less than
@@ -580,6 +1200,18 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
         let mut best_selection = Vec::new();
         let mut best_selection_value = None;
 
+        // The waste of the optional UTXOs currently selected, and of the best selection. Inputs
+        // are cheaper to spend now when the fee rate is below the long-term one.
+        let input_waste = |utxo: &OutputGroup| {
+            utxo.fee.to_signed().expect("signed amount")
+                - (context.long_term_fee_rate * utxo.weight())
+                    .to_signed()
+                    .expect("signed amount")
+        };
+        let is_fee_rate_high = fee_rate > context.long_term_fee_rate;
+        let mut curr_waste = SignedAmount::ZERO;
+        let mut best_waste = SignedAmount::MAX;
+
         // Depth First search loop for choosing the UTXOs
         for _ in 0..BNB_TOTAL_TRIES {
             // Conditions for starting a backtrack
@@ -591,16 +1223,21 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
                 || curr_value > target_amount + cost_of_change
             {
                 backtrack = true;
+            } else if is_fee_rate_high && curr_waste > best_waste {
+                // Adding inputs only adds waste when the fee rate is high.
+                backtrack = true;
             } else if curr_value >= target_amount {
                 // Selected value is within range, there's no point in going forward. Start
                 // backtracking
                 backtrack = true;
 
-                // If we found a solution better than the previous one, or if there wasn't previous
-                // solution, update the best solution
-                if best_selection_value.is_none() || curr_value < best_selection_value.unwrap() {
+                // If we found a solution wasting less than the previous one, or if there wasn't
+                // previous solution, update the best solution. The excess goes to fees.
+                let waste = curr_waste + (curr_value - target_amount);
+                if best_selection_value.is_none() || waste < best_waste {
                     best_selection.clone_from(&current_selection);
                     best_selection_value = Some(curr_value);
+                    best_waste = waste;
                 }
 
                 // If we found a perfect match, break here
@@ -635,6 +1272,7 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
 
                 let utxo = &optional_utxos[current_selection.len() - 1];
                 curr_value -= utxo.effective_value;
+                curr_waste -= input_waste(utxo);
             } else {
                 // Moving forwards, continuing down this branch
                 let utxo = &optional_utxos[current_selection.len()];
@@ -645,6 +1283,7 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
                 // Inclusion branch first (Largest First Exploration)
                 current_selection.push(true);
                 curr_value += utxo.effective_value;
+                curr_waste += input_waste(utxo);
             }
         }
 
@@ -669,7 +1308,7 @@ impl<Cs> BranchAndBoundCoinSelection<Cs> {
             .to_unsigned()
             .expect("valid unsigned");
 
-        let excess = decide_change(remaining_amount, fee_rate, drain_script);
+        let excess = context.decide_change(remaining_amount, fee_rate, drain_script);
 
         Ok(calculate_cs_result(selected_utxos, required_utxos, excess))
     }
@@ -683,12 +1322,35 @@ impl CoinSelectionAlgorithm for SingleRandomDraw {
     fn coin_select<R: RngCore>(
         &self,
         required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
         mut optional_utxos: Vec<WeightedUtxo>,
         fee_rate: FeeRate,
         target_amount: Amount,
         drain_script: &Script,
         rand: &mut R,
     ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let diagnostics =
+            SelectionDiagnostics::start(context, &required_utxos, &optional_utxos, fee_rate);
         // We put the required UTXOs first and then the randomize optional UTXOs to take as needed
         let utxos = {
             shuffle_slice(&mut optional_utxos, rand);
@@ -700,702 +1362,2025 @@ impl CoinSelectionAlgorithm for SingleRandomDraw {
         };
 
         // select required UTXOs and then random optional UTXOs.
-        select_sorted_utxos(utxos, fee_rate, target_amount, drain_script)
+        record_attempt(
+            diagnostics,
+            SelectionAlgorithm::SingleRandomDraw,
+            select_sorted_utxos(context, utxos, fee_rate, target_amount, drain_script),
+        )
     }
 }
 
-fn calculate_cs_result(
-    mut selected_utxos: Vec<OutputGroup>,
-    mut required_utxos: Vec<OutputGroup>,
-    excess: Excess,
-) -> CoinSelectionResult {
-    selected_utxos.append(&mut required_utxos);
-    let fee_amount = selected_utxos.iter().map(|u| u.fee).sum();
-    let selected = selected_utxos
-        .into_iter()
-        .map(|u| u.weighted_utxo.utxo)
-        .collect::<Vec<_>>();
+/// CoinGrinder coin selection, adapted from Bitcoin Core's implementation
+///
+/// At high fee rates the weight of the inputs matters more than avoiding change. This algorithm
+/// does a depth-first search over the available UTXOs for the selection with the lowest total
+/// input weight which still pays the target, the fee and a change output above the dust limit of
+/// `drain_script`. The search is bounded to a fixed number of iterations, after which the best
+/// selection found so far is returned.
+///
+/// If no selection can produce change, [`LargestFirstCoinSelection`] is used instead.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CoinGrinderCoinSelection;
 
-    CoinSelectionResult {
-        selected,
-        fee_amount,
-        excess,
+const COIN_GRINDER_TOTAL_TRIES: usize = 100_000;
+
+impl CoinSelectionAlgorithm for CoinGrinderCoinSelection {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
     }
-}
 
-#[cfg(test)]
-mod test {
-    use assert_matches::assert_matches;
-    use bitcoin::hashes::Hash;
-    use bitcoin::{psbt, OutPoint, Sequence};
-    use chain::{ChainPosition, ConfirmationBlockTime};
-    use core::str::FromStr;
-    use rand::rngs::StdRng;
-    use std::boxed::Box;
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let required_ogs: Vec<OutputGroup> = required_utxos
+            .iter()
+            .map(|u| OutputGroup::new(u.clone(), fee_rate))
+            .collect();
+        let mut optional_ogs: Vec<OutputGroup> = optional_utxos
+            .iter()
+            .map(|u| OutputGroup::new(u.clone(), fee_rate))
+            .filter(|u| u.effective_value.is_positive())
+            .collect();
+        let mut diagnostics =
+            SelectionDiagnostics::start(context, &required_utxos, &optional_utxos, fee_rate);
+        if let Some(diagnostics) = &mut diagnostics {
+            diagnostics.exclude_negative_effective_value();
+        }
+        // Largest effective value first, the lightest first among equal values.
+        optional_ogs.sort_unstable_by(|a, b| {
+            b.effective_value
+                .cmp(&a.effective_value)
+                .then(a.weight().cmp(&b.weight()))
+        });
 
-    use bitcoin::{Amount, BlockHash, ScriptBuf, TxIn, TxOut};
+        // The selection must also pay for a change output worth creating.
+        let change_target =
+            context.change_fee(fee_rate, drain_script) + context.min_change_value(drain_script);
+        let target = (target_amount + change_target)
+            .to_signed()
+            .expect("signed amount");
 
-    use super::*;
-    use crate::types::*;
+        let mut curr_value = required_ogs
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+        let mut curr_available_value = optional_ogs
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+        let mut curr_weight = Weight::ZERO;
 
-    use rand::prelude::SliceRandom;
-    use rand::{thread_rng, Rng, RngCore, SeedableRng};
+        let mut current_selection: Vec<bool> = Vec::with_capacity(optional_ogs.len());
+        let mut best_selection = Vec::new();
+        let mut best_weight = None;
+        let mut best_value = SignedAmount::ZERO;
+
+        if curr_value < target {
+            for _ in 0..COIN_GRINDER_TOTAL_TRIES {
+                let mut backtrack = false;
+                if curr_value + curr_available_value < target
+                    || best_weight.is_some_and(|best| curr_weight > best)
+                {
+                    // The target is out of reach or the selection is already too heavy.
+                    backtrack = true;
+                } else if curr_value >= target {
+                    backtrack = true;
+                    let is_better = match best_weight {
+                        None => true,
+                        Some(best) => {
+                            curr_weight < best || (curr_weight == best && curr_value < best_value)
+                        }
+                    };
+                    if is_better {
+                        best_selection.clone_from(&current_selection);
+                        best_weight = Some(curr_weight);
+                        best_value = curr_value;
+                    }
+                }
 
-    // signature len (1WU) + signature and sighash (72WU)
-    // + pubkey len (1WU) + pubkey (33WU)
-    const P2WPKH_SATISFACTION_SIZE: usize = 1 + 72 + 1 + 33;
+                if backtrack {
+                    while let Some(false) = current_selection.last() {
+                        current_selection.pop();
+                        curr_available_value +=
+                            optional_ogs[current_selection.len()].effective_value;
+                    }
 
-    const FEE_AMOUNT: Amount = Amount::from_sat(50);
+                    if let Some(c) = current_selection.last_mut() {
+                        // Output was included on previous iterations, try excluding now.
+                        *c = false;
+                    } else {
+                        // Every branch was searched.
+                        break;
+                    }
 
-    fn unconfirmed_utxo(value: Amount, index: u32, last_seen: u64) -> WeightedUtxo {
-        utxo(
-            value,
-            index,
-            ChainPosition::Unconfirmed {
-                first_seen: Some(last_seen),
-                last_seen: Some(last_seen),
-            },
+                    let utxo = &optional_ogs[current_selection.len() - 1];
+                    curr_value -= utxo.effective_value;
+                    curr_weight -= utxo.weight();
+                } else {
+                    let index = current_selection.len();
+                    let utxo = &optional_ogs[index];
+                    curr_available_value -= utxo.effective_value;
+
+                    // Including a UTXO equivalent to the one we just excluded leads to the same
+                    // selections, skip it as well.
+                    let is_clone = index > 0
+                        && !current_selection[index - 1]
+                        && optional_ogs[index - 1].effective_value == utxo.effective_value
+                        && optional_ogs[index - 1].weight() == utxo.weight();
+                    if is_clone {
+                        current_selection.push(false);
+                    } else {
+                        current_selection.push(true);
+                        curr_value += utxo.effective_value;
+                        curr_weight += utxo.weight();
+                    }
+                }
+            }
+
+            if best_weight.is_none() {
+                if let Some(diagnostics) = &mut diagnostics {
+                    diagnostics.attempts.push(SelectionAttempt {
+                        algorithm: SelectionAlgorithm::CoinGrinder,
+                        outcome: SelectionOutcome::NoSolution,
+                        waste: None,
+                    });
+                }
+                return with_diagnostics(
+                    LargestFirstCoinSelection.coin_select_with_context(
+                        context,
+                        required_utxos,
+                        optional_utxos,
+                        fee_rate,
+                        target_amount,
+                        drain_script,
+                        rand,
+                    ),
+                    diagnostics,
+                );
+            }
+            curr_value = best_value;
+        }
+
+        let selected_utxos = optional_ogs
+            .into_iter()
+            .zip(best_selection)
+            .filter_map(|(optional, is_in_best)| if is_in_best { Some(optional) } else { None })
+            .collect::<Vec<OutputGroup>>();
+        let remaining_amount = (curr_value - target_amount.to_signed().expect("signed amount"))
+            .to_unsigned()
+            .expect("remaining amount can't be negative");
+        let excess = context.decide_change(remaining_amount, fee_rate, drain_script);
+
+        record_attempt(
+            diagnostics,
+            SelectionAlgorithm::CoinGrinder,
+            Ok(calculate_cs_result(selected_utxos, required_ogs, excess)),
         )
     }
+}
 
-    fn confirmed_utxo(
-        value: Amount,
-        index: u32,
-        confirmation_height: u32,
-        confirmation_time: u64,
-    ) -> WeightedUtxo {
-        utxo(
-            value,
-            index,
-            ChainPosition::Confirmed {
-                anchor: ConfirmationBlockTime {
-                    block_id: chain::BlockId {
-                        height: confirmation_height,
-                        hash: bitcoin::BlockHash::all_zeros(),
-                    },
-                    confirmation_time,
-                },
-                transitively: None,
-            },
-        )
+/// Coin selection algorithms of this module, as compared by [`WasteMetricCoinSelection`] and
+/// reported by [`SelectionDiagnostics`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionAlgorithm {
+    /// [`BranchAndBoundCoinSelection`], without fallback
+    BranchAndBound,
+    /// [`CoinGrinderCoinSelection`], only tried at high fee rates
+    CoinGrinder,
+    /// [`LargestFirstCoinSelection`]
+    LargestFirst,
+    /// [`OldestFirstCoinSelection`]
+    OldestFirst,
+    /// [`SingleRandomDraw`]
+    SingleRandomDraw,
+}
+
+impl fmt::Display for SelectionAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BranchAndBound => write!(f, "branch and bound"),
+            Self::CoinGrinder => write!(f, "coin grinder"),
+            Self::LargestFirst => write!(f, "largest first"),
+            Self::OldestFirst => write!(f, "oldest first"),
+            Self::SingleRandomDraw => write!(f, "single random draw"),
+        }
     }
+}
 
-    fn foreign_utxo(value: Amount, index: u32) -> WeightedUtxo {
-        assert!(index < 10);
-        let outpoint = OutPoint::from_str(&format!(
-            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
-        ))
-        .unwrap();
-        WeightedUtxo {
-            utxo: Utxo::Foreign {
-                outpoint,
-                sequence: Sequence(0xFFFFFFFD),
-                psbt_input: Box::new(psbt::Input {
-                    witness_utxo: Some(TxOut {
-                        value,
-                        script_pubkey: ScriptBuf::new(),
-                    }),
-                    non_witness_utxo: None,
-                    ..Default::default()
-                }),
-            },
-            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+impl SelectionAlgorithm {
+    /// All the algorithms, in the order they are tried
+    pub const ALL: [SelectionAlgorithm; 5] = [
+        Self::BranchAndBound,
+        Self::CoinGrinder,
+        Self::LargestFirst,
+        Self::OldestFirst,
+        Self::SingleRandomDraw,
+    ];
+
+    #[allow(clippy::too_many_arguments)]
+    fn coin_select<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        match self {
+            Self::BranchAndBound => BranchAndBoundCoinSelection::<NoFallback>::default()
+                .coin_select_with_context(
+                    context,
+                    required_utxos,
+                    optional_utxos,
+                    fee_rate,
+                    target_amount,
+                    drain_script,
+                    rand,
+                ),
+            Self::CoinGrinder => CoinGrinderCoinSelection.coin_select_with_context(
+                context,
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ),
+            Self::LargestFirst => LargestFirstCoinSelection.coin_select_with_context(
+                context,
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ),
+            Self::OldestFirst => OldestFirstCoinSelection.coin_select_with_context(
+                context,
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ),
+            Self::SingleRandomDraw => SingleRandomDraw.coin_select_with_context(
+                context,
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ),
         }
     }
+}
 
-    fn utxo(
-        value: Amount,
-        index: u32,
-        chain_position: ChainPosition<ConfirmationBlockTime>,
-    ) -> WeightedUtxo {
-        assert!(index < 10);
-        let outpoint = OutPoint::from_str(&format!(
-            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
-        ))
-        .unwrap();
-        WeightedUtxo {
-            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
-            utxo: Utxo::Local(LocalOutput {
-                outpoint,
-                txout: TxOut {
-                    value,
-                    script_pubkey: ScriptBuf::new(),
-                },
-                keychain: KeychainKind::External,
-                is_spent: false,
-                derivation_index: 42,
-                chain_position,
-            }),
+// Fallback of the branch and bound run by `WasteMetricCoinSelection`, which tries the other
+// algorithms itself.
+#[derive(Debug, Default)]
+struct NoFallback;
+
+impl CoinSelectionAlgorithm for NoFallback {
+    fn coin_select<R: RngCore>(
+        &self,
+        _: Vec<WeightedUtxo>,
+        _: Vec<WeightedUtxo>,
+        _: FeeRate,
+        target_amount: Amount,
+        _: &Script,
+        _: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        Err(InsufficientFunds {
+            needed: target_amount,
+            available: Amount::ZERO,
+            diagnostics: None,
+        })
+    }
+}
+
+/// Run several coin selection algorithms and pick the least wasteful selection, as Bitcoin Core
+/// does.
+///
+/// The waste metric of a selection, see [`CoinSelectionContext::waste`], is the difference
+/// between the fee paid for its inputs at the current fee rate and the fee they would cost at the
+/// long-term fee rate, plus the cost of the change. When the current fee rate is high, selections
+/// with fewer inputs are preferred, and when it is low the wallet consolidates its UTXOs.
+///
+/// The algorithms of [`SelectionAlgorithm::ALL`] are compared, and the first one with the lowest
+/// waste wins. [`CoinSelectionResult::algorithm`] and [`CoinSelectionResult::waste`] report the
+/// winner. [`CoinGrinderCoinSelection`] is only tried when the fee rate is higher than the
+/// long-term fee rate times the [`coin_grinder_threshold`](Self::coin_grinder_threshold), 3 by
+/// default.
+#[derive(Debug, Clone)]
+pub struct WasteMetricCoinSelection {
+    algorithms: Vec<SelectionAlgorithm>,
+    coin_grinder_threshold: u64,
+}
+
+impl Default for WasteMetricCoinSelection {
+    fn default() -> Self {
+        Self {
+            algorithms: SelectionAlgorithm::ALL.to_vec(),
+            coin_grinder_threshold: 3,
         }
     }
+}
 
-    fn get_test_utxos() -> Vec<WeightedUtxo> {
-        vec![
-            unconfirmed_utxo(Amount::from_sat(100_000), 0, 0),
-            unconfirmed_utxo(FEE_AMOUNT - Amount::from_sat(40), 1, 0),
-            unconfirmed_utxo(Amount::from_sat(200_000), 2, 0),
-        ]
+impl WasteMetricCoinSelection {
+    /// Only compare the given `algorithms`.
+    pub fn algorithms(mut self, algorithms: impl IntoIterator<Item = SelectionAlgorithm>) -> Self {
+        self.algorithms = algorithms.into_iter().collect();
+        self
     }
 
-    fn get_oldest_first_test_utxos() -> Vec<WeightedUtxo> {
-        // ensure utxos are from different tx
-        let utxo1 = confirmed_utxo(Amount::from_sat(120_000), 1, 1, 1231006505);
-        let utxo2 = confirmed_utxo(Amount::from_sat(80_000), 2, 2, 1231006505);
-        let utxo3 = confirmed_utxo(Amount::from_sat(300_000), 3, 3, 1231006505);
-        vec![utxo1, utxo2, utxo3]
+    /// Try [`CoinGrinderCoinSelection`] when the fee rate is higher than the long-term fee rate
+    /// times `multiplier`.
+    pub fn coin_grinder_threshold(mut self, multiplier: u64) -> Self {
+        self.coin_grinder_threshold = multiplier;
+        self
     }
+}
 
-    fn generate_random_utxos(rng: &mut StdRng, utxos_number: usize) -> Vec<WeightedUtxo> {
-        let mut res = Vec::new();
-        for i in 0..utxos_number {
-            res.push(WeightedUtxo {
-                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
-                utxo: Utxo::Local(LocalOutput {
-                    outpoint: OutPoint::from_str(&format!(
-                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
-                    ))
-                    .unwrap(),
-                    txout: TxOut {
-                        value: Amount::from_sat(rng.gen_range(0..200000000)),
-                        script_pubkey: ScriptBuf::new(),
-                    },
-                    keychain: KeychainKind::External,
-                    is_spent: false,
-                    derivation_index: rng.next_u32(),
-                    chain_position: if rng.gen_bool(0.5) {
-                        ChainPosition::Confirmed {
-                            anchor: ConfirmationBlockTime {
-                                block_id: chain::BlockId {
-                                    height: rng.next_u32(),
-                                    hash: BlockHash::all_zeros(),
-                                },
-                                confirmation_time: rng.next_u64(),
-                            },
-                            transitively: None,
-                        }
-                    } else {
-                        ChainPosition::Unconfirmed {
-                            first_seen: Some(1),
-                            last_seen: Some(1),
-                        }
-                    },
-                }),
-            });
-        }
-        res
+impl CoinSelectionAlgorithm for WasteMetricCoinSelection {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
     }
 
-    fn generate_same_value_utxos(utxos_value: Amount, utxos_number: usize) -> Vec<WeightedUtxo> {
-        (0..utxos_number)
-            .map(|i| WeightedUtxo {
-                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
-                utxo: Utxo::Local(LocalOutput {
-                    outpoint: OutPoint::from_str(&format!(
-                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
-                    ))
-                    .unwrap(),
-                    txout: TxOut {
-                        value: utxos_value,
-                        script_pubkey: ScriptBuf::new(),
-                    },
-                    keychain: KeychainKind::External,
-                    is_spent: false,
-                    derivation_index: 42,
-                    chain_position: ChainPosition::Unconfirmed {
-                        first_seen: Some(1),
-                        last_seen: Some(1),
-                    },
-                }),
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let utxos = required_utxos
+            .iter()
+            .chain(&optional_utxos)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let high_fee_rate = context
+            .long_term_fee_rate
+            .checked_mul(self.coin_grinder_threshold)
+            .is_some_and(|threshold| fee_rate > threshold);
+
+        let mut diagnostics =
+            SelectionDiagnostics::start(context, &required_utxos, &optional_utxos, fee_rate);
+        let mut best: Option<CoinSelectionResult> = None;
+        let mut error = None;
+        for algorithm in &self.algorithms {
+            if *algorithm == SelectionAlgorithm::CoinGrinder && !high_fee_rate {
+                continue;
+            }
+            let mut outcome = algorithm.coin_select(
+                context,
+                required_utxos.clone(),
+                optional_utxos.clone(),
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            );
+            let run = diagnostics_of(&mut outcome).take();
+            let mut result = match outcome {
+                Ok(result) => result,
+                Err(err) => {
+                    if let (Some(diagnostics), Some(run)) = (&mut diagnostics, run) {
+                        diagnostics.append(*run);
+                    }
+                    // The branch and bound error isn't meaningful.
+                    if *algorithm != SelectionAlgorithm::BranchAndBound || error.is_none() {
+                        error = Some(err);
+                    }
+                    continue;
+                }
+            };
+            let waste = context.waste(&result, &utxos, fee_rate);
+            if let (Some(diagnostics), Some(mut run)) = (&mut diagnostics, run) {
+                if let Some(attempt) = run.attempts.last_mut() {
+                    attempt.waste = Some(waste);
+                }
+                diagnostics.append(*run);
+            }
+            if best
+                .as_ref()
+                .and_then(|best| best.waste)
+                .is_some_and(|best| best <= waste)
+            {
+                continue;
+            }
+            result.algorithm = Some(*algorithm);
+            result.waste = Some(waste);
+            best = Some(result);
+        }
+
+        let result = best.ok_or_else(|| {
+            error.unwrap_or(InsufficientFunds {
+                needed: target_amount,
+                available: Amount::ZERO,
+                diagnostics: None,
             })
-            .collect()
+        });
+        with_diagnostics(result, diagnostics)
+    }
+}
+
+/// Avoid partial spends of UTXOs sent to the same script pubkey, like Bitcoin Core's
+/// `avoid_partial_spends`
+///
+/// When an address received several UTXOs, spending only some of them links the others to the
+/// transaction anyway. This wraps another [`CoinSelectionAlgorithm`] so that it selects whole
+/// groups of UTXOs sharing a script pubkey: whenever the selection contains a UTXO of a group, the
+/// rest of the group is made required and the selection is made again.
+///
+/// Spending whole groups can cost more fee than needed. The grouped selection is only used if the
+/// fee it pays, including any excess dropped to fees, is at most `max_extra_fee` higher than the
+/// one of the selection made without grouping.
+#[derive(Debug, Clone)]
+pub struct AvoidPartialSpends<Cs = DefaultCoinSelectionAlgorithm> {
+    coin_selection: Cs,
+    max_extra_fee: Amount,
+}
+
+impl<Cs> AvoidPartialSpends<Cs> {
+    /// Create new instance wrapping `coin_selection`, which may pay up to `max_extra_fee` to avoid
+    /// partial spends.
+    pub fn new(coin_selection: Cs, max_extra_fee: Amount) -> Self {
+        Self {
+            coin_selection,
+            max_extra_fee,
+        }
+    }
+}
+
+// Fee paid by a selection, including the excess dropped to fees.
+fn total_fee(result: &CoinSelectionResult) -> Amount {
+    result.fee_amount
+        + match result.excess {
+            Excess::Change { fee, .. } => fee,
+            Excess::NoChange {
+                remaining_amount, ..
+            } => remaining_amount,
+        }
+}
+
+impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for AvoidPartialSpends<Cs> {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let ungrouped = self.coin_selection.coin_select_with_context(
+            context,
+            required_utxos.clone(),
+            optional_utxos.clone(),
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )?;
+
+        // Make the rest of the groups of the selected UTXOs required until the selection only
+        // contains whole groups. Each round adds at least one UTXO, so this terminates.
+        let mut required = required_utxos;
+        let mut optional = optional_utxos;
+        let mut grouped = None;
+        loop {
+            let selection = match &grouped {
+                None => &ungrouped,
+                Some(result) => result,
+            };
+            let selected_spks = selection
+                .selected
+                .iter()
+                .map(|utxo| utxo.txout().script_pubkey.clone())
+                .collect::<Vec<_>>();
+            let (missing, rest): (Vec<_>, Vec<_>) = optional
+                .into_iter()
+                .partition(|wutxo| selected_spks.contains(&wutxo.utxo.txout().script_pubkey));
+            optional = rest;
+            if missing.is_empty() {
+                break;
+            }
+            required.extend(missing);
+            match self.coin_selection.coin_select_with_context(
+                context,
+                required.clone(),
+                optional.clone(),
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ) {
+                Ok(result) => grouped = Some(result),
+                Err(_) => return Ok(ungrouped),
+            }
+        }
+
+        match grouped {
+            Some(grouped) if total_fee(&grouped) <= total_fee(&ungrouped) + self.max_extra_fee => {
+                Ok(grouped)
+            }
+            _ => Ok(ungrouped),
+        }
+    }
+}
+
+/// Prefer spending inputs of a single script type
+///
+/// Spending inputs of different script types in one transaction, for instance P2WPKH and P2TR
+/// outputs, tells that they belong to the same wallet. This wraps another
+/// [`CoinSelectionAlgorithm`] and runs it once per script type of the available UTXOs. The
+/// cheapest of the selections made of a single script type is used, and only if no script type
+/// can pay for the transaction on its own are UTXOs of different types mixed.
+///
+/// When required UTXOs have a single script type, only the optional UTXOs of that type are
+/// considered. When they already mix types the selection is left to the wrapped algorithm.
+#[derive(Debug, Clone)]
+pub struct AvoidMixedScriptTypes<Cs = DefaultCoinSelectionAlgorithm> {
+    coin_selection: Cs,
+}
+
+impl<Cs> AvoidMixedScriptTypes<Cs> {
+    /// Create new instance wrapping `coin_selection`.
+    pub fn new(coin_selection: Cs) -> Self {
+        Self { coin_selection }
+    }
+}
+
+impl<Cs: CoinSelectionAlgorithm> CoinSelectionAlgorithm for AvoidMixedScriptTypes<Cs> {
+    fn coin_select<R: RngCore>(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        self.coin_select_with_context(
+            &CoinSelectionContext::default(),
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rand,
+        )
+    }
+
+    fn coin_select_with_context<R: RngCore>(
+        &self,
+        context: &CoinSelectionContext,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: Amount,
+        drain_script: &Script,
+        rand: &mut R,
+    ) -> Result<CoinSelectionResult, InsufficientFunds> {
+        let script_type = |wutxo: &WeightedUtxo| ScriptType::of(&wutxo.utxo.txout().script_pubkey);
+        let script_types = |utxos: &[WeightedUtxo]| {
+            let mut types = Vec::new();
+            for ty in utxos.iter().map(script_type) {
+                if !types.contains(&ty) {
+                    types.push(ty);
+                }
+            }
+            types
+        };
+        let candidate_types = match script_types(&required_utxos).as_slice() {
+            [] => script_types(&optional_utxos),
+            [ty] => vec![*ty],
+            _ => Vec::new(),
+        };
+
+        let mut best: Option<CoinSelectionResult> = None;
+        for ty in candidate_types {
+            let optional = optional_utxos
+                .iter()
+                .filter(|wutxo| script_type(wutxo) == ty)
+                .cloned()
+                .collect();
+            if let Ok(result) = self.coin_selection.coin_select_with_context(
+                context,
+                required_utxos.clone(),
+                optional,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ) {
+                if best
+                    .as_ref()
+                    .is_none_or(|best| total_fee(&result) < total_fee(best))
+                {
+                    best = Some(result);
+                }
+            }
+        }
+
+        match best {
+            Some(result) => Ok(result),
+            None => self.coin_selection.coin_select_with_context(
+                context,
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rand,
+            ),
+        }
+    }
+}
+
+fn calculate_cs_result(
+    mut selected_utxos: Vec<OutputGroup>,
+    mut required_utxos: Vec<OutputGroup>,
+    excess: Excess,
+) -> CoinSelectionResult {
+    selected_utxos.append(&mut required_utxos);
+    let fee_amount = selected_utxos.iter().map(|u| u.fee).sum();
+    let selected = selected_utxos
+        .into_iter()
+        .map(|u| u.weighted_utxo.utxo)
+        .collect::<Vec<_>>();
+
+    CoinSelectionResult {
+        selected,
+        fee_amount,
+        excess,
+        algorithm: None,
+        waste: None,
+        diagnostics: None,
     }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{psbt, OutPoint, Sequence};
+    use chain::{ChainPosition, ConfirmationBlockTime};
+    use core::str::FromStr;
+    use rand::rngs::StdRng;
+    use std::boxed::Box;
+
+    use bitcoin::{Amount, BlockHash, ScriptBuf, TxIn, TxOut};
+
+    use super::*;
+    use crate::types::*;
+
+    use rand::prelude::SliceRandom;
+    use rand::{thread_rng, Rng, RngCore, SeedableRng};
+
+    // signature len (1WU) + signature and sighash (72WU)
+    // + pubkey len (1WU) + pubkey (33WU)
+    const P2WPKH_SATISFACTION_SIZE: usize = 1 + 72 + 1 + 33;
+
+    const FEE_AMOUNT: Amount = Amount::from_sat(50);
+
+    fn unconfirmed_utxo(value: Amount, index: u32, last_seen: u64) -> WeightedUtxo {
+        utxo(
+            value,
+            index,
+            ChainPosition::Unconfirmed {
+                first_seen: Some(last_seen),
+                last_seen: Some(last_seen),
+            },
+        )
+    }
+
+    fn confirmed_utxo(
+        value: Amount,
+        index: u32,
+        confirmation_height: u32,
+        confirmation_time: u64,
+    ) -> WeightedUtxo {
+        utxo(
+            value,
+            index,
+            ChainPosition::Confirmed {
+                anchor: ConfirmationBlockTime {
+                    block_id: chain::BlockId {
+                        height: confirmation_height,
+                        hash: bitcoin::BlockHash::all_zeros(),
+                    },
+                    confirmation_time,
+                },
+                transitively: None,
+            },
+        )
+    }
+
+    fn foreign_utxo(value: Amount, index: u32) -> WeightedUtxo {
+        assert!(index < 10);
+        let outpoint = OutPoint::from_str(&format!(
+            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
+        ))
+        .unwrap();
+        WeightedUtxo {
+            utxo: Utxo::Foreign {
+                outpoint,
+                sequence: Sequence(0xFFFFFFFD),
+                psbt_input: Box::new(psbt::Input {
+                    witness_utxo: Some(TxOut {
+                        value,
+                        script_pubkey: ScriptBuf::new(),
+                    }),
+                    non_witness_utxo: None,
+                    ..Default::default()
+                }),
+            },
+            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+        }
+    }
+
+    fn utxo(
+        value: Amount,
+        index: u32,
+        chain_position: ChainPosition<ConfirmationBlockTime>,
+    ) -> WeightedUtxo {
+        assert!(index < 10);
+        let outpoint = OutPoint::from_str(&format!(
+            "000000000000000000000000000000000000000000000000000000000000000{index}:0"
+        ))
+        .unwrap();
+        WeightedUtxo {
+            satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+            utxo: Utxo::Local(LocalOutput {
+                outpoint,
+                txout: TxOut {
+                    value,
+                    script_pubkey: ScriptBuf::new(),
+                },
+                keychain: KeychainKind::External,
+                is_spent: false,
+                derivation_index: 42,
+                chain_position,
+            }),
+        }
+    }
+
+    fn get_test_utxos() -> Vec<WeightedUtxo> {
+        vec![
+            unconfirmed_utxo(Amount::from_sat(100_000), 0, 0),
+            unconfirmed_utxo(FEE_AMOUNT - Amount::from_sat(40), 1, 0),
+            unconfirmed_utxo(Amount::from_sat(200_000), 2, 0),
+        ]
+    }
+
+    fn get_oldest_first_test_utxos() -> Vec<WeightedUtxo> {
+        // ensure utxos are from different tx
+        let utxo1 = confirmed_utxo(Amount::from_sat(120_000), 1, 1, 1231006505);
+        let utxo2 = confirmed_utxo(Amount::from_sat(80_000), 2, 2, 1231006505);
+        let utxo3 = confirmed_utxo(Amount::from_sat(300_000), 3, 3, 1231006505);
+        vec![utxo1, utxo2, utxo3]
+    }
+
+    fn generate_random_utxos(rng: &mut StdRng, utxos_number: usize) -> Vec<WeightedUtxo> {
+        let mut res = Vec::new();
+        for i in 0..utxos_number {
+            res.push(WeightedUtxo {
+                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+                utxo: Utxo::Local(LocalOutput {
+                    outpoint: OutPoint::from_str(&format!(
+                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
+                    ))
+                    .unwrap(),
+                    txout: TxOut {
+                        value: Amount::from_sat(rng.gen_range(0..200000000)),
+                        script_pubkey: ScriptBuf::new(),
+                    },
+                    keychain: KeychainKind::External,
+                    is_spent: false,
+                    derivation_index: rng.next_u32(),
+                    chain_position: if rng.gen_bool(0.5) {
+                        ChainPosition::Confirmed {
+                            anchor: ConfirmationBlockTime {
+                                block_id: chain::BlockId {
+                                    height: rng.next_u32(),
+                                    hash: BlockHash::all_zeros(),
+                                },
+                                confirmation_time: rng.next_u64(),
+                            },
+                            transitively: None,
+                        }
+                    } else {
+                        ChainPosition::Unconfirmed {
+                            first_seen: Some(1),
+                            last_seen: Some(1),
+                        }
+                    },
+                }),
+            });
+        }
+        res
+    }
+
+    fn generate_same_value_utxos(utxos_value: Amount, utxos_number: usize) -> Vec<WeightedUtxo> {
+        (0..utxos_number)
+            .map(|i| WeightedUtxo {
+                satisfaction_weight: Weight::from_wu_usize(P2WPKH_SATISFACTION_SIZE),
+                utxo: Utxo::Local(LocalOutput {
+                    outpoint: OutPoint::from_str(&format!(
+                        "ebd9813ecebc57ff8f30797de7c205e3c7498ca950ea4341ee51a685ff2fa30a:{i}"
+                    ))
+                    .unwrap(),
+                    txout: TxOut {
+                        value: utxos_value,
+                        script_pubkey: ScriptBuf::new(),
+                    },
+                    keychain: KeychainKind::External,
+                    is_spent: false,
+                    derivation_index: 42,
+                    chain_position: ChainPosition::Unconfirmed {
+                        first_seen: Some(1),
+                        last_seen: Some(1),
+                    },
+                }),
+            })
+            .collect()
+    }
+
+    fn sum_random_utxos(mut rng: &mut StdRng, utxos: &mut [WeightedUtxo]) -> Amount {
+        let utxos_picked_len = rng.gen_range(2..utxos.len() / 2);
+        utxos.shuffle(&mut rng);
+        utxos[..utxos_picked_len]
+            .iter()
+            .map(|u| u.utxo.txout().value)
+            .sum()
+    }
+
+    fn calc_target_amount(utxos: &[WeightedUtxo], fee_rate: FeeRate) -> Amount {
+        utxos
+            .iter()
+            .cloned()
+            .map(|utxo| OutputGroup::new(utxo, fee_rate).effective_value)
+            .sum::<SignedAmount>()
+            .to_unsigned()
+            .expect("unsigned amount")
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_success() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_use_all() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_use_only_necessary() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(68));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_insufficient_funds() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(1),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_largest_first_coin_selection_insufficient_funds_high_fees() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+
+        let result = LargestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(1000),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_success() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(180_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_use_all() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                utxos,
+                vec![],
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_uses_all_optional_with_foreign_utxo_locals_sorted_first() {
+        let utxos = get_oldest_first_test_utxos();
+        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
+        all_utxos.extend_from_slice(&utxos);
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(619_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                all_utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 4);
+        assert_eq!(result.selected_amount(), Amount::from_sat(620_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(272));
+        assert!(matches!(result.selected[3], Utxo::Foreign { .. }));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_uses_only_all_optional_local_utxos_not_a_single_foreign() {
+        let utxos = get_oldest_first_test_utxos();
+        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
+        all_utxos.extend_from_slice(&utxos);
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(499_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                all_utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        assert!(result
+            .selected
+            .iter()
+            .all(|utxo| matches!(utxo, Utxo::Local(..))));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_use_only_necessary() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(120_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(68));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_insufficient_funds() {
+        let utxos = get_oldest_first_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(600_000) + FEE_AMOUNT;
+
+        let result = OldestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(1),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_oldest_first_coin_selection_insufficient_funds_high_fees() {
+        let utxos = get_oldest_first_test_utxos();
+
+        let target_amount =
+            utxos.iter().map(|wu| wu.utxo.txout().value).sum::<Amount>() - Amount::from_sat(50);
+        let drain_script = ScriptBuf::default();
+
+        let result = OldestFirstCoinSelection.coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(1000),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_success() {
+        // In this case bnb won't find a suitable match and single random draw will
+        // select three outputs
+        let utxos = generate_same_value_utxos(Amount::from_sat(100_000), 20);
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_required_are_enough() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                utxos.clone(),
+                utxos,
+                FeeRate::from_sat_per_vb_unchecked(1),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
+        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_optional_are_enough() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let fee_rate = FeeRate::BROADCAST_MIN;
+        // first and third utxo's effective value
+        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
+    }
+
+    #[test]
+    fn test_single_random_draw_function_success() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let mut utxos = generate_random_utxos(&mut rng, 300);
+        let target_amount = sum_random_utxos(&mut rng, &mut utxos) + FEE_AMOUNT;
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
+        let drain_script = ScriptBuf::default();
+
+        let result = SingleRandomDraw.coin_select(
+            vec![],
+            utxos,
+            fee_rate,
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+
+        assert!(
+            matches!(result, Ok(CoinSelectionResult {selected, fee_amount, ..})
+                if selected.iter().map(|u| u.txout().value).sum::<Amount>() > target_amount
+                && fee_amount == Amount::from_sat(selected.len() as u64 * 68)
+            )
+        );
+    }
+
+    #[test]
+    fn test_single_random_draw_function_error() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+        // 100_000, 10, 200_000
+        let utxos = get_test_utxos();
+        let target_amount = Amount::from_sat(300_000) + FEE_AMOUNT;
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
+        let drain_script = ScriptBuf::default();
+
+        let result = SingleRandomDraw.coin_select(
+            vec![],
+            utxos,
+            fee_rate,
+            target_amount,
+            &drain_script,
+            &mut rng,
+        );
+
+        assert!(
+            matches!(result, Err(InsufficientFunds {needed, available, ..})
+                if needed == Amount::from_sat(300_254) && available == Amount::from_sat(300_010))
+        );
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_required_not_enough() {
+        let utxos = get_test_utxos();
+
+        let required = vec![utxos[0].clone()];
+        let mut optional = utxos[1..].to_vec();
+        optional.push(utxo(
+            Amount::from_sat(500_000),
+            3,
+            ChainPosition::<ConfirmationBlockTime>::Unconfirmed {
+                first_seen: Some(1),
+                last_seen: Some(1),
+            },
+        ));
 
-    fn sum_random_utxos(mut rng: &mut StdRng, utxos: &mut [WeightedUtxo]) -> Amount {
-        let utxos_picked_len = rng.gen_range(2..utxos.len() / 2);
-        utxos.shuffle(&mut rng);
-        utxos[..utxos_picked_len]
+        // Defensive assertions, for sanity and in case someone changes the test utxos vector.
+        let amount = required
             .iter()
             .map(|u| u.utxo.txout().value)
-            .sum()
+            .sum::<Amount>();
+        assert_eq!(amount, Amount::from_sat(100_000));
+        let amount = optional
+            .iter()
+            .map(|u| u.utxo.txout().value)
+            .sum::<Amount>();
+        assert!(amount > Amount::from_sat(150_000));
+        let drain_script = ScriptBuf::default();
+
+        let fee_rate = FeeRate::BROADCAST_MIN;
+        // first and third utxo's effective value
+        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select(
+                required,
+                optional,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
     }
 
-    fn calc_target_amount(utxos: &[WeightedUtxo], fee_rate: FeeRate) -> Amount {
-        utxos
-            .iter()
-            .cloned()
-            .map(|utxo| OutputGroup::new(utxo, fee_rate).effective_value)
-            .sum::<SignedAmount>()
-            .to_unsigned()
-            .expect("unsigned amount")
+    #[test]
+    fn test_bnb_coin_selection_insufficient_funds() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(1),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
     }
 
     #[test]
-    fn test_largest_first_coin_selection_success() {
+    fn test_bnb_coin_selection_insufficient_funds_high_fees() {
         let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
         let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(1000),
+            target_amount,
+            &drain_script,
+            &mut thread_rng(),
+        );
+        assert!(matches!(result, Err(InsufficientFunds { .. })));
+    }
+
+    #[test]
+    fn test_bnb_coin_selection_check_fee_rate() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let fee_rate = FeeRate::BROADCAST_MIN;
+        // first utxo's effective value
+        let target_amount = calc_target_amount(&utxos[0..1], fee_rate);
+
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
             .coin_select(
-                utxos,
                 vec![],
-                FeeRate::from_sat_per_vb_unchecked(1),
+                utxos,
+                fee_rate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
+        let input_weight =
+            TxIn::default().segwit_weight().to_wu() + P2WPKH_SATISFACTION_SIZE as u64;
+        // the final fee rate should be exactly the same as the fee rate given
+        let result_feerate = result.fee_amount / Weight::from_wu(input_weight);
+        assert_eq!(result_feerate, fee_rate);
     }
 
     #[test]
-    fn test_largest_first_coin_selection_use_all() {
-        let utxos = get_test_utxos();
+    fn test_bnb_coin_selection_exact_match() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+        for _i in 0..200 {
+            let mut optional_utxos = generate_random_utxos(&mut rng, 16);
+            let target_amount = sum_random_utxos(&mut rng, &mut optional_utxos);
+            let drain_script = ScriptBuf::default();
+            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+                .coin_select(
+                    vec![],
+                    optional_utxos,
+                    FeeRate::ZERO,
+                    target_amount,
+                    &drain_script,
+                    &mut thread_rng(),
+                )
+                .unwrap();
+            assert_eq!(result.selected_amount(), target_amount);
+        }
+    }
+
+    #[test]
+    fn test_bnb_function_no_exact_match() {
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
+        let utxos: Vec<OutputGroup> = get_test_utxos()
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
+
+        let curr_available_value = utxos
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+
+        let size_of_change = 31;
+        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
+            .to_signed()
+            .unwrap();
+
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
+        let result = BranchAndBoundCoinSelection::with_fallback(SingleRandomDraw).bnb(
+            vec![],
+            utxos,
+            SignedAmount::ZERO,
+            curr_available_value,
+            target_amount,
+            cost_of_change,
+            &drain_script,
+            fee_rate,
+            &CoinSelectionContext::default(),
+        );
+        assert!(matches!(result, Err(BnbError::NoExactMatch)));
+    }
 
-        let result = LargestFirstCoinSelection
-            .coin_select(
-                utxos,
+    #[test]
+    fn test_bnb_function_tries_exceeded() {
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
+        let utxos: Vec<OutputGroup> = generate_same_value_utxos(Amount::from_sat(100_000), 100_000)
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
+
+        let curr_available_value = utxos
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+
+        let size_of_change = 31;
+        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
+            .to_signed()
+            .unwrap();
+        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
+
+        let drain_script = ScriptBuf::default();
+
+        let result = BranchAndBoundCoinSelection::with_fallback(SingleRandomDraw).bnb(
+            vec![],
+            utxos,
+            SignedAmount::ZERO,
+            curr_available_value,
+            target_amount,
+            cost_of_change,
+            &drain_script,
+            fee_rate,
+            &CoinSelectionContext::default(),
+        );
+        assert!(matches!(result, Err(BnbError::TotalTriesExceeded)));
+    }
+
+    // The match won't be exact but still in the range
+    #[test]
+    fn test_bnb_function_almost_exact_match_with_fees() {
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
+        let size_of_change = 31;
+        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
+            .to_signed()
+            .unwrap();
+
+        let utxos: Vec<_> = generate_same_value_utxos(Amount::from_sat(50_000), 10)
+            .into_iter()
+            .map(|u| OutputGroup::new(u, fee_rate))
+            .collect();
+
+        let curr_value = SignedAmount::ZERO;
+
+        let curr_available_value = utxos
+            .iter()
+            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+
+        // 2*(value of 1 utxo)  - 2*(1 utxo fees with 1.0sat/vbyte fee rate) -
+        // cost_of_change + 5.
+        let target_amount = 2 * 50_000 - 2 * 67 - cost_of_change.to_sat() + 5;
+        let target_amount = SignedAmount::from_sat(target_amount);
+
+        let drain_script = ScriptBuf::default();
+
+        let result = BranchAndBoundCoinSelection::with_fallback(SingleRandomDraw)
+            .bnb(
                 vec![],
-                FeeRate::from_sat_per_vb_unchecked(1),
+                utxos,
+                curr_value,
+                curr_available_value,
                 target_amount,
+                cost_of_change,
                 &drain_script,
-                &mut thread_rng(),
+                fee_rate,
+                &CoinSelectionContext::default(),
             )
             .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(136));
+    }
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
+    // TODO: bnb() function should be optimized, and this test should be done with more utxos
+    #[test]
+    fn test_bnb_function_exact_match_more_utxos() {
+        let seed = [0; 32];
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let fee_rate = FeeRate::ZERO;
+
+        for _ in 0..200 {
+            let optional_utxos: Vec<_> = generate_random_utxos(&mut rng, 40)
+                .into_iter()
+                .map(|u| OutputGroup::new(u, fee_rate))
+                .collect();
+
+            let curr_value = SignedAmount::ZERO;
+
+            let curr_available_value = optional_utxos
+                .iter()
+                .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+
+            let target_amount =
+                optional_utxos[3].effective_value + optional_utxos[23].effective_value;
+
+            let drain_script = ScriptBuf::default();
+
+            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+                .bnb(
+                    vec![],
+                    optional_utxos,
+                    curr_value,
+                    curr_available_value,
+                    target_amount,
+                    SignedAmount::ZERO,
+                    &drain_script,
+                    fee_rate,
+                    &CoinSelectionContext::default(),
+                )
+                .unwrap();
+            assert_eq!(
+                result.selected_amount(),
+                target_amount.to_unsigned().unwrap()
+            );
+        }
     }
 
     #[test]
-    fn test_largest_first_coin_selection_use_only_necessary() {
+    fn test_bnb_exclude_negative_effective_value() {
         let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection
-            .coin_select(
-                vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
+        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            vec![],
+            utxos,
+            FeeRate::from_sat_per_vb_unchecked(10),
+            Amount::from_sat(500_000),
+            &drain_script,
+            &mut thread_rng(),
+        );
 
-        assert_eq!(result.selected.len(), 1);
-        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(68));
+        assert_matches!(
+            selection,
+            Err(InsufficientFunds {
+                available,
+                ..
+            }) if available.to_sat() == 300_000
+        );
     }
 
     #[test]
-    fn test_largest_first_coin_selection_insufficient_funds() {
+    fn test_bnb_include_negative_effective_value_when_required() {
         let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection.coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_unchecked(1),
-            target_amount,
+        let (required, optional) = utxos.into_iter().partition(
+            |u| matches!(u, WeightedUtxo { utxo, .. } if utxo.txout().value.to_sat() < 1000),
+        );
+
+        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+            required,
+            optional,
+            FeeRate::from_sat_per_vb_unchecked(10),
+            Amount::from_sat(500_000),
             &drain_script,
             &mut thread_rng(),
         );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+
+        assert_matches!(
+            selection,
+            Err(InsufficientFunds {
+                available,
+                ..
+            }) if available.to_sat() == 300_010
+        );
     }
 
     #[test]
-    fn test_largest_first_coin_selection_insufficient_funds_high_fees() {
+    fn test_bnb_sum_of_effective_value_negative() {
         let utxos = get_test_utxos();
         let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
 
-        let result = LargestFirstCoinSelection.coin_select(
-            vec![],
+        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
             utxos,
-            FeeRate::from_sat_per_vb_unchecked(1000),
-            target_amount,
+            vec![],
+            FeeRate::from_sat_per_vb_unchecked(10_000),
+            Amount::from_sat(500_000),
             &drain_script,
             &mut thread_rng(),
         );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
+
+        assert_matches!(
+            selection,
+            Err(InsufficientFunds {
+                available,
+                ..
+            }) if available.to_sat() == 300_010
+        );
     }
 
     #[test]
-    fn test_oldest_first_coin_selection_success() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(180_000) + FEE_AMOUNT;
-
-        let result = OldestFirstCoinSelection
+    fn test_bnb_fallback_algorithm() {
+        // utxo value
+        // 120k + 80k + 300k
+        let optional_utxos = get_oldest_first_test_utxos();
+        let feerate = FeeRate::BROADCAST_MIN;
+        let target_amount = Amount::from_sat(190_000);
+        let drain_script = ScriptBuf::new();
+        // bnb won't find exact match and should select oldest first
+        let bnb_with_oldest_first =
+            BranchAndBoundCoinSelection::with_fallback(OldestFirstCoinSelection);
+        let res = bnb_with_oldest_first
             .coin_select(
                 vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
+                optional_utxos,
+                feerate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
-
-        assert_eq!(result.selected.len(), 2);
-        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
+        assert_eq!(res.selected_amount(), Amount::from_sat(200_000));
     }
 
     #[test]
-    fn test_oldest_first_coin_selection_use_all() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+    fn test_bnb_cost_of_change_from_drain_script() {
+        let fee_rate = FeeRate::from_sat_per_vb_u32(1);
+        let utxos = vec![unconfirmed_utxo(Amount::from_sat(50_000), 0, 0)];
+        let effective_value = OutputGroup::new(utxos[0].clone(), fee_rate).effective_value;
+        // Dropping 40 sats to fees is cheaper than a P2WSH change output, not a P2WPKH one.
+        let target_amount = (effective_value - SignedAmount::from_sat(40))
+            .to_unsigned()
+            .unwrap();
+        let bnb = BranchAndBoundCoinSelection::<NoFallback>::default();
 
-        let result = OldestFirstCoinSelection
+        let p2wpkh = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        assert!(bnb
             .coin_select(
-                utxos,
                 vec![],
-                FeeRate::from_sat_per_vb_unchecked(1),
+                utxos.clone(),
+                fee_rate,
                 target_amount,
-                &drain_script,
+                &p2wpkh,
                 &mut thread_rng(),
             )
-            .unwrap();
+            .is_err());
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        let p2wsh = ScriptBuf::new_p2wsh(&bitcoin::WScriptHash::all_zeros());
+        let result = bnb
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                target_amount,
+                &p2wsh,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 1);
+        assert_matches!(result.excess, Excess::NoChange { .. });
     }
 
     #[test]
-    fn test_oldest_first_coin_selection_uses_all_optional_with_foreign_utxo_locals_sorted_first() {
-        let utxos = get_oldest_first_test_utxos();
-        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
-        all_utxos.extend_from_slice(&utxos);
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(619_000) + FEE_AMOUNT;
+    fn test_bnb_prefers_less_waste() {
+        // Above the long-term fee rate, an input costs more than a bit of excess.
+        let fee_rate = FeeRate::from_sat_per_vb_u32(20);
+        let utxos = vec![
+            unconfirmed_utxo(Amount::from_sat(30_000), 0, 0),
+            unconfirmed_utxo(Amount::from_sat(20_000), 1, 0),
+            unconfirmed_utxo(Amount::from_sat(49_000), 2, 0),
+        ];
+        let effective_value =
+            |i: usize| OutputGroup::new(utxos[i].clone(), fee_rate).effective_value;
+        let target_amount = (effective_value(0) + effective_value(1) - SignedAmount::from_sat(100))
+            .to_unsigned()
+            .unwrap();
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
 
-        let result = OldestFirstCoinSelection
+        let result = BranchAndBoundCoinSelection::<NoFallback>::default()
             .coin_select(
                 vec![],
-                all_utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
+                utxos.clone(),
+                fee_rate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
+        assert_eq!(
+            result
+                .selected
+                .iter()
+                .map(|utxo| utxo.outpoint())
+                .collect::<Vec<_>>(),
+            vec![utxos[2].utxo.outpoint()]
+        );
+    }
 
-        assert_eq!(result.selected.len(), 4);
-        assert_eq!(result.selected_amount(), Amount::from_sat(620_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(272));
-        assert!(matches!(result.selected[3], Utxo::Foreign { .. }));
+    #[test]
+    fn test_deterministic_coin_selection_picks_same_utxos() {
+        enum CoinSelectionAlgo {
+            BranchAndBound,
+            OldestFirst,
+            LargestFirst,
+        }
+
+        struct TestCase<'a> {
+            name: &'a str,
+            coin_selection_algo: CoinSelectionAlgo,
+            exp_vouts: &'a [u32],
+        }
+
+        let test_cases = [
+            TestCase {
+                name: "branch and bound",
+                coin_selection_algo: CoinSelectionAlgo::BranchAndBound,
+                // note: we expect these to be sorted largest first, which indicates
+                // BnB succeeded with no fallback
+                exp_vouts: &[29, 28, 27],
+            },
+            TestCase {
+                name: "oldest first",
+                coin_selection_algo: CoinSelectionAlgo::OldestFirst,
+                exp_vouts: &[0, 1, 2],
+            },
+            TestCase {
+                name: "largest first",
+                coin_selection_algo: CoinSelectionAlgo::LargestFirst,
+                exp_vouts: &[29, 28, 27],
+            },
+        ];
+
+        let optional = generate_same_value_utxos(Amount::from_sat(100_000), 30);
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
+        let target_amount = calc_target_amount(&optional[0..3], fee_rate);
+        assert_eq!(target_amount, Amount::from_sat(299_796));
+        let drain_script = ScriptBuf::default();
+
+        for tc in test_cases {
+            let optional = optional.clone();
+
+            let result = match tc.coin_selection_algo {
+                CoinSelectionAlgo::BranchAndBound => {
+                    BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
+                        vec![],
+                        optional,
+                        fee_rate,
+                        target_amount,
+                        &drain_script,
+                        &mut thread_rng(),
+                    )
+                }
+                CoinSelectionAlgo::OldestFirst => OldestFirstCoinSelection.coin_select(
+                    vec![],
+                    optional,
+                    fee_rate,
+                    target_amount,
+                    &drain_script,
+                    &mut thread_rng(),
+                ),
+                CoinSelectionAlgo::LargestFirst => LargestFirstCoinSelection.coin_select(
+                    vec![],
+                    optional,
+                    fee_rate,
+                    target_amount,
+                    &drain_script,
+                    &mut thread_rng(),
+                ),
+            };
+
+            assert!(result.is_ok(), "coin_select failed {}", tc.name);
+            let result = result.unwrap();
+            assert!(matches!(result.excess, Excess::NoChange { .. },));
+            assert_eq!(
+                result.selected.len(),
+                3,
+                "wrong selected len for {}",
+                tc.name
+            );
+            assert_eq!(
+                result.selected_amount(),
+                Amount::from_sat(300_000),
+                "wrong selected amount for {}",
+                tc.name
+            );
+            assert_eq!(
+                result.fee_amount,
+                Amount::from_sat(204),
+                "wrong fee amount for {}",
+                tc.name
+            );
+            let vouts = result
+                .selected
+                .iter()
+                .map(|utxo| utxo.outpoint().vout)
+                .collect::<Vec<u32>>();
+            assert_eq!(vouts, tc.exp_vouts, "wrong selected vouts for {}", tc.name);
+        }
     }
 
-    #[test]
-    fn test_oldest_first_coin_selection_uses_only_all_optional_local_utxos_not_a_single_foreign() {
-        let utxos = get_oldest_first_test_utxos();
-        let mut all_utxos = vec![foreign_utxo(Amount::from_sat(120_000), 1)];
-        all_utxos.extend_from_slice(&utxos);
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(499_000) + FEE_AMOUNT;
-
-        let result = OldestFirstCoinSelection
-            .coin_select(
-                vec![],
-                all_utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
-                target_amount,
-                &drain_script,
-                &mut thread_rng(),
-            )
-            .unwrap();
-
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(500_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
-        assert!(result
-            .selected
-            .iter()
-            .all(|utxo| matches!(utxo, Utxo::Local(..))));
+    fn get_waste_test_utxos() -> Vec<WeightedUtxo> {
+        vec![
+            confirmed_utxo(Amount::from_sat(20_000), 0, 1, 1231006505),
+            confirmed_utxo(Amount::from_sat(20_000), 1, 2, 1231006506),
+            confirmed_utxo(Amount::from_sat(20_000), 2, 3, 1231006507),
+            confirmed_utxo(Amount::from_sat(200_000), 3, 100, 1231006600),
+        ]
     }
 
     #[test]
-    fn test_oldest_first_coin_selection_use_only_necessary() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+    fn test_waste_metric_coin_selection_consolidates_at_low_fee_rate() {
+        let utxos = get_waste_test_utxos();
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let selection = WasteMetricCoinSelection::default();
 
-        let result = OldestFirstCoinSelection
+        let result = selection
             .coin_select(
                 vec![],
-                utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
-                target_amount,
-                &drain_script,
+                utxos.clone(),
+                fee_rate,
+                Amount::from_sat(50_000),
+                &ScriptBuf::new(),
                 &mut thread_rng(),
             )
             .unwrap();
 
-        assert_eq!(result.selected.len(), 1);
-        assert_eq!(result.selected_amount(), Amount::from_sat(120_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(68));
-    }
-
-    #[test]
-    fn test_oldest_first_coin_selection_insufficient_funds() {
-        let utxos = get_oldest_first_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(600_000) + FEE_AMOUNT;
-
-        let result = OldestFirstCoinSelection.coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_unchecked(1),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
-    }
-
-    #[test]
-    fn test_oldest_first_coin_selection_insufficient_funds_high_fees() {
-        let utxos = get_oldest_first_test_utxos();
-
-        let target_amount =
-            utxos.iter().map(|wu| wu.utxo.txout().value).sum::<Amount>() - Amount::from_sat(50);
-        let drain_script = ScriptBuf::default();
-
-        let result = OldestFirstCoinSelection.coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_unchecked(1000),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
+        // Each input saves 68 vb * 9 sat/vb, the change costs 9 sat now and 680 sat later.
+        assert_eq!(result.algorithm, Some(SelectionAlgorithm::OldestFirst));
+        assert_eq!(result.selected.len(), 3);
+        assert_eq!(result.waste, Some(SignedAmount::from_sat(-1836 + 9 + 680)));
+        assert_eq!(
+            CoinSelectionContext::default().waste(&result, &utxos, fee_rate),
+            result.waste.unwrap()
         );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
     }
 
     #[test]
-    fn test_bnb_coin_selection_success() {
-        // In this case bnb won't find a suitable match and single random draw will
-        // select three outputs
-        let utxos = generate_same_value_utxos(Amount::from_sat(100_000), 20);
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+    fn test_waste_metric_coin_selection_fewer_inputs_at_high_fee_rate() {
+        let utxos = get_waste_test_utxos();
+        let fee_rate = FeeRate::from_sat_per_vb(50).unwrap();
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        let result = WasteMetricCoinSelection::default()
             .coin_select(
                 vec![],
                 utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
-                target_amount,
-                &drain_script,
+                fee_rate,
+                Amount::from_sat(50_000),
+                &ScriptBuf::new(),
                 &mut thread_rng(),
             )
             .unwrap();
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
+        // Coin grinder runs above 30 sat/vb and finds the same single input as largest first.
+        assert_eq!(result.algorithm, Some(SelectionAlgorithm::CoinGrinder));
+        assert_eq!(result.selected_amount(), Amount::from_sat(200_000));
+        assert_eq!(result.waste, Some(SignedAmount::from_sat(2720 + 450 + 680)));
     }
 
     #[test]
-    fn test_bnb_coin_selection_required_are_enough() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(20_000) + FEE_AMOUNT;
+    fn test_waste_metric_coin_selection_prefers_changeless() {
+        let utxos = get_waste_test_utxos();
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        // A single small UTXO pays exactly the target and its own fee.
+        let target_amount = Amount::from_sat(20_000 - 68 * 20);
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        let result = WasteMetricCoinSelection::default()
             .coin_select(
+                vec![],
                 utxos.clone(),
-                utxos,
-                FeeRate::from_sat_per_vb_unchecked(1),
+                fee_rate,
                 target_amount,
-                &drain_script,
+                &ScriptBuf::new(),
                 &mut thread_rng(),
             )
             .unwrap();
 
-        assert_eq!(result.selected.len(), 3);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_010));
-        assert_eq!(result.fee_amount, Amount::from_sat(204));
-    }
-
-    #[test]
-    fn test_bnb_coin_selection_optional_are_enough() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let fee_rate = FeeRate::BROADCAST_MIN;
-        // first and third utxo's effective value
-        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
+        assert_eq!(result.algorithm, Some(SelectionAlgorithm::BranchAndBound));
+        assert!(matches!(result.excess, Excess::NoChange { .. }));
+        assert_eq!(result.waste, Some(SignedAmount::from_sat(68 * 10)));
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        // Without branch and bound the same coin is selected by oldest first.
+        let result = WasteMetricCoinSelection::default()
+            .algorithms([
+                SelectionAlgorithm::LargestFirst,
+                SelectionAlgorithm::OldestFirst,
+            ])
             .coin_select(
                 vec![],
                 utxos,
                 fee_rate,
                 target_amount,
-                &drain_script,
+                &ScriptBuf::new(),
                 &mut thread_rng(),
             )
             .unwrap();
-
-        assert_eq!(result.selected.len(), 2);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
+        assert_eq!(result.algorithm, Some(SelectionAlgorithm::OldestFirst));
+        assert_eq!(result.waste, Some(SignedAmount::from_sat(68 * 10)));
     }
 
     #[test]
-    fn test_single_random_draw_function_success() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
-        let mut utxos = generate_random_utxos(&mut rng, 300);
-        let target_amount = sum_random_utxos(&mut rng, &mut utxos) + FEE_AMOUNT;
-        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
-        let drain_script = ScriptBuf::default();
-
-        let result = SingleRandomDraw.coin_select(
+    fn test_waste_metric_coin_selection_insufficient_funds() {
+        let result = WasteMetricCoinSelection::default().coin_select(
             vec![],
-            utxos,
-            fee_rate,
-            target_amount,
-            &drain_script,
+            get_waste_test_utxos(),
+            FeeRate::from_sat_per_vb(1).unwrap(),
+            Amount::from_sat(500_000),
+            &ScriptBuf::new(),
             &mut thread_rng(),
         );
-
-        assert!(
-            matches!(result, Ok(CoinSelectionResult {selected, fee_amount, ..})
-                if selected.iter().map(|u| u.txout().value).sum::<Amount>() > target_amount
-                && fee_amount == Amount::from_sat(selected.len() as u64 * 68)
-            )
+        assert_matches!(
+            result,
+            Err(InsufficientFunds { available, .. }) if available == Amount::from_sat(260_000)
         );
     }
 
-    #[test]
-    fn test_single_random_draw_function_error() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
-
-        // 100_000, 10, 200_000
-        let utxos = get_test_utxos();
-        let target_amount = Amount::from_sat(300_000) + FEE_AMOUNT;
-        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
-        let drain_script = ScriptBuf::default();
-
-        let result = SingleRandomDraw.coin_select(
-            vec![],
-            utxos,
-            fee_rate,
-            target_amount,
-            &drain_script,
-            &mut rng,
-        );
-
-        assert!(matches!(result, Err(InsufficientFunds {needed, available})
-                if needed == Amount::from_sat(300_254) && available == Amount::from_sat(300_010)));
+    fn get_coin_grinder_test_utxos() -> Vec<WeightedUtxo> {
+        // The oldest UTXO is large enough on its own, but much heavier to spend.
+        let mut heavy = confirmed_utxo(Amount::from_sat(60_000), 0, 1, 1231006505);
+        heavy.satisfaction_weight = Weight::from_wu(1000);
+        vec![
+            heavy,
+            confirmed_utxo(Amount::from_sat(30_000), 1, 2, 1231006506),
+            confirmed_utxo(Amount::from_sat(30_000), 2, 3, 1231006507),
+        ]
     }
 
     #[test]
-    fn test_bnb_coin_selection_required_not_enough() {
-        let utxos = get_test_utxos();
-
-        let required = vec![utxos[0].clone()];
-        let mut optional = utxos[1..].to_vec();
-        optional.push(utxo(
-            Amount::from_sat(500_000),
-            3,
-            ChainPosition::<ConfirmationBlockTime>::Unconfirmed {
-                first_seen: Some(1),
-                last_seen: Some(1),
-            },
-        ));
-
-        // Defensive assertions, for sanity and in case someone changes the test utxos vector.
-        let amount = required
-            .iter()
-            .map(|u| u.utxo.txout().value)
-            .sum::<Amount>();
-        assert_eq!(amount, Amount::from_sat(100_000));
-        let amount = optional
-            .iter()
-            .map(|u| u.utxo.txout().value)
-            .sum::<Amount>();
-        assert!(amount > Amount::from_sat(150_000));
-        let drain_script = ScriptBuf::default();
-
-        let fee_rate = FeeRate::BROADCAST_MIN;
-        // first and third utxo's effective value
-        let target_amount = calc_target_amount(&[utxos[0].clone(), utxos[2].clone()], fee_rate);
+    fn test_coin_grinder_minimizes_input_weight() {
+        let utxos = get_coin_grinder_test_utxos();
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        let target_amount = Amount::from_sat(50_000);
 
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        let result = CoinGrinderCoinSelection
             .coin_select(
-                required,
-                optional,
+                vec![],
+                utxos.clone(),
                 fee_rate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
+        assert_eq!(result.selected.len(), 2);
+        assert_eq!(result.selected_amount(), Amount::from_sat(60_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(2 * 68 * 20));
+        assert_matches!(result.excess, Excess::Change { .. });
 
-        assert_eq!(result.selected.len(), 2);
-        assert_eq!(result.selected_amount(), Amount::from_sat(300_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
-    }
-
-    #[test]
-    fn test_bnb_coin_selection_insufficient_funds() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(500_000) + FEE_AMOUNT;
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_unchecked(1),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
-
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
-    }
-
-    #[test]
-    fn test_bnb_coin_selection_insufficient_funds_high_fees() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_unchecked(1000),
-            target_amount,
-            &drain_script,
-            &mut thread_rng(),
-        );
-        assert!(matches!(result, Err(InsufficientFunds { .. })));
-    }
-
-    #[test]
-    fn test_bnb_coin_selection_check_fee_rate() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-        let fee_rate = FeeRate::BROADCAST_MIN;
-        // first utxo's effective value
-        let target_amount = calc_target_amount(&utxos[0..1], fee_rate);
-
-        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+        // Largest first spends the heavy UTXO.
+        let result = LargestFirstCoinSelection
             .coin_select(
                 vec![],
                 utxos,
@@ -1405,386 +3390,552 @@ mod test {
                 &mut thread_rng(),
             )
             .unwrap();
-
-        assert_eq!(result.selected.len(), 1);
-        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
-        let input_weight =
-            TxIn::default().segwit_weight().to_wu() + P2WPKH_SATISFACTION_SIZE as u64;
-        // the final fee rate should be exactly the same as the fee rate given
-        let result_feerate = result.fee_amount / Weight::from_wu(input_weight);
-        assert_eq!(result_feerate, fee_rate);
+        assert_eq!(result.selected_amount(), Amount::from_sat(60_000));
     }
 
     #[test]
-    fn test_bnb_coin_selection_exact_match() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
+    fn test_coin_grinder_required_are_enough() {
+        let utxos = get_coin_grinder_test_utxos();
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
 
-        for _i in 0..200 {
-            let mut optional_utxos = generate_random_utxos(&mut rng, 16);
-            let target_amount = sum_random_utxos(&mut rng, &mut optional_utxos);
-            let drain_script = ScriptBuf::default();
-            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
-                .coin_select(
-                    vec![],
-                    optional_utxos,
-                    FeeRate::ZERO,
-                    target_amount,
-                    &drain_script,
-                    &mut thread_rng(),
-                )
-                .unwrap();
-            assert_eq!(result.selected_amount(), target_amount);
-        }
+        let result = CoinGrinderCoinSelection
+            .coin_select(
+                utxos[..1].to_vec(),
+                utxos[1..].to_vec(),
+                FeeRate::from_sat_per_vb(20).unwrap(),
+                Amount::from_sat(20_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 1);
+        assert_eq!(result.selected_amount(), Amount::from_sat(60_000));
     }
 
     #[test]
-    fn test_bnb_function_no_exact_match() {
-        let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
-        let utxos: Vec<OutputGroup> = get_test_utxos()
-            .into_iter()
-            .map(|u| OutputGroup::new(u, fee_rate))
-            .collect();
-
-        let curr_available_value = utxos
-            .iter()
-            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
-
-        let size_of_change = 31;
-        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
-            .to_signed()
+    fn test_coin_grinder_without_change() {
+        let utxos = get_coin_grinder_test_utxos();
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+
+        // Spending everything can't pay for change, fall back to a changeless selection.
+        let available = Amount::from_sat(120_000 - 1165_u64.div_ceil(4) - 2 * 68);
+        let result = CoinGrinderCoinSelection
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                fee_rate,
+                available,
+                &drain_script,
+                &mut thread_rng(),
+            )
             .unwrap();
+        assert_eq!(result.selected.len(), 3);
+        assert_matches!(result.excess, Excess::NoChange { .. });
 
-        let drain_script = ScriptBuf::default();
-        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
-        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw).bnb(
+        let result = CoinGrinderCoinSelection.coin_select(
             vec![],
             utxos,
-            SignedAmount::ZERO,
-            curr_available_value,
-            target_amount,
-            cost_of_change,
-            &drain_script,
             fee_rate,
+            available + Amount::ONE_SAT,
+            &drain_script,
+            &mut thread_rng(),
         );
-        assert!(matches!(result, Err(BnbError::NoExactMatch)));
+        assert_matches!(result, Err(InsufficientFunds { .. }));
     }
 
     #[test]
-    fn test_bnb_function_tries_exceeded() {
-        let fee_rate = FeeRate::from_sat_per_vb_unchecked(10);
-        let utxos: Vec<OutputGroup> = generate_same_value_utxos(Amount::from_sat(100_000), 100_000)
-            .into_iter()
-            .map(|u| OutputGroup::new(u, fee_rate))
-            .collect();
-
-        let curr_available_value = utxos
-            .iter()
-            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
-
-        let size_of_change = 31;
-        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
-            .to_signed()
+    fn test_coin_grinder_search_is_bounded() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let utxos = generate_random_utxos(&mut rng, 300);
+        let fee_rate = FeeRate::from_sat_per_vb(10).unwrap();
+        let target_amount = sum_random_utxos(&mut rng, &mut utxos.clone());
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+
+        let result = CoinGrinderCoinSelection
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut rng,
+            )
             .unwrap();
-        let target_amount = SignedAmount::from_sat(20_000) + FEE_AMOUNT.to_signed().unwrap();
-
-        let drain_script = ScriptBuf::default();
-
-        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw).bnb(
-            vec![],
-            utxos,
-            SignedAmount::ZERO,
-            curr_available_value,
-            target_amount,
-            cost_of_change,
-            &drain_script,
-            fee_rate,
-        );
-        assert!(matches!(result, Err(BnbError::TotalTriesExceeded)));
+        assert!(result.selected_amount() > target_amount + result.fee_amount);
     }
 
-    // The match won't be exact but still in the range
     #[test]
-    fn test_bnb_function_almost_exact_match_with_fees() {
-        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
-        let size_of_change = 31;
-        let cost_of_change = (Weight::from_vb_unchecked(size_of_change) * fee_rate)
-            .to_signed()
+    fn test_waste_metric_coin_selection_coin_grinder_threshold() {
+        let utxos = get_coin_grinder_test_utxos();
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let target_amount = Amount::from_sat(50_000);
+
+        let result = WasteMetricCoinSelection::default()
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                FeeRate::from_sat_per_vb(50).unwrap(),
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
             .unwrap();
+        assert_eq!(result.algorithm, Some(SelectionAlgorithm::CoinGrinder));
+        assert_eq!(result.selected.len(), 2);
 
-        let utxos: Vec<_> = generate_same_value_utxos(Amount::from_sat(50_000), 10)
-            .into_iter()
-            .map(|u| OutputGroup::new(u, fee_rate))
-            .collect();
+        // 20 sat/vb is below three times the long-term fee rate.
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        let result = WasteMetricCoinSelection::default()
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_ne!(result.algorithm, Some(SelectionAlgorithm::CoinGrinder));
 
-        let curr_value = SignedAmount::ZERO;
+        let result = WasteMetricCoinSelection::default()
+            .coin_grinder_threshold(1)
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.algorithm, Some(SelectionAlgorithm::CoinGrinder));
+    }
 
-        let curr_available_value = utxos
-            .iter()
-            .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+    fn utxo_to_script(mut weighted_utxo: WeightedUtxo, script_pubkey: ScriptBuf) -> WeightedUtxo {
+        if let Utxo::Local(local) = &mut weighted_utxo.utxo {
+            local.txout.script_pubkey = script_pubkey;
+        }
+        weighted_utxo
+    }
 
-        // 2*(value of 1 utxo)  - 2*(1 utxo fees with 1.0sat/vbyte fee rate) -
-        // cost_of_change + 5.
-        let target_amount = 2 * 50_000 - 2 * 67 - cost_of_change.to_sat() + 5;
-        let target_amount = SignedAmount::from_sat(target_amount);
+    #[test]
+    fn test_avoid_partial_spends() {
+        let reused = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let utxos = vec![
+            utxo_to_script(
+                confirmed_utxo(Amount::from_sat(100_000), 0, 1, 0),
+                reused.clone(),
+            ),
+            utxo_to_script(confirmed_utxo(Amount::from_sat(10_000), 1, 2, 0), reused),
+            confirmed_utxo(Amount::from_sat(50_000), 2, 3, 0),
+        ];
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let target_amount = Amount::from_sat(60_000);
+        let drain_script = ScriptBuf::new();
 
-        let drain_script = ScriptBuf::default();
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
 
-        let result = BranchAndBoundCoinSelection::new(size_of_change, SingleRandomDraw)
-            .bnb(
+        // Spending the second UTXO of the address costs 68 sat more.
+        let result = AvoidPartialSpends::new(LargestFirstCoinSelection, Amount::from_sat(68))
+            .coin_select(
                 vec![],
-                utxos,
-                curr_value,
-                curr_available_value,
+                utxos.clone(),
+                fee_rate,
                 target_amount,
-                cost_of_change,
                 &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(110_000));
+        assert_eq!(result.fee_amount, Amount::from_sat(2 * 68));
+
+        let result = AvoidPartialSpends::new(LargestFirstCoinSelection, Amount::from_sat(67))
+            .coin_select(
+                vec![],
+                utxos,
                 fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
             )
             .unwrap();
         assert_eq!(result.selected_amount(), Amount::from_sat(100_000));
-        assert_eq!(result.fee_amount, Amount::from_sat(136));
     }
 
-    // TODO: bnb() function should be optimized, and this test should be done with more utxos
     #[test]
-    fn test_bnb_function_exact_match_more_utxos() {
-        let seed = [0; 32];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
-        let fee_rate = FeeRate::ZERO;
-
-        for _ in 0..200 {
-            let optional_utxos: Vec<_> = generate_random_utxos(&mut rng, 40)
-                .into_iter()
-                .map(|u| OutputGroup::new(u, fee_rate))
-                .collect();
+    fn test_avoid_partial_spends_required_utxos() {
+        let reused = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let required = vec![utxo_to_script(
+            confirmed_utxo(Amount::from_sat(10_000), 0, 1, 0),
+            reused.clone(),
+        )];
+        let optional = vec![
+            utxo_to_script(confirmed_utxo(Amount::from_sat(20_000), 1, 2, 0), reused),
+            confirmed_utxo(Amount::from_sat(50_000), 2, 3, 0),
+        ];
 
-            let curr_value = SignedAmount::ZERO;
+        let result = AvoidPartialSpends::new(OldestFirstCoinSelection, Amount::MAX_MONEY)
+            .coin_select(
+                required,
+                optional,
+                FeeRate::from_sat_per_vb(1).unwrap(),
+                Amount::from_sat(5_000),
+                &ScriptBuf::new(),
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(30_000));
+    }
 
-            let curr_available_value = optional_utxos
+    #[test]
+    fn test_avoid_mixed_script_types() {
+        let p2tr =
+            ScriptBuf::new_p2tr_tweaked(bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(
+                bitcoin::XOnlyPublicKey::from_str(
+                    "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115",
+                )
+                .unwrap(),
+            ));
+        let p2wpkh = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let utxos = vec![
+            utxo_to_script(
+                confirmed_utxo(Amount::from_sat(30_000), 0, 1, 0),
+                p2wpkh.clone(),
+            ),
+            utxo_to_script(
+                confirmed_utxo(Amount::from_sat(35_000), 1, 2, 0),
+                p2tr.clone(),
+            ),
+            utxo_to_script(confirmed_utxo(Amount::from_sat(40_000), 2, 3, 0), p2wpkh),
+        ];
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let drain_script = ScriptBuf::new();
+        let selected_types = |result: &CoinSelectionResult| {
+            result
+                .selected
                 .iter()
-                .fold(SignedAmount::ZERO, |acc, x| acc + x.effective_value);
+                .map(|utxo| ScriptType::of(&utxo.txout().script_pubkey))
+                .collect::<Vec<_>>()
+        };
 
-            let target_amount =
-                optional_utxos[3].effective_value + optional_utxos[23].effective_value;
+        // Largest first mixes the two largest UTXOs.
+        let result = LargestFirstCoinSelection
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                fee_rate,
+                Amount::from_sat(60_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(75_000));
 
-            let drain_script = ScriptBuf::default();
+        let result = AvoidMixedScriptTypes::new(LargestFirstCoinSelection)
+            .coin_select(
+                vec![],
+                utxos.clone(),
+                fee_rate,
+                Amount::from_sat(60_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected_amount(), Amount::from_sat(70_000));
+        assert_eq!(
+            selected_types(&result),
+            [ScriptType::P2wpkh, ScriptType::P2wpkh]
+        );
 
-            let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
-                .bnb(
-                    vec![],
-                    optional_utxos,
-                    curr_value,
-                    curr_available_value,
-                    target_amount,
-                    SignedAmount::ZERO,
-                    &drain_script,
-                    fee_rate,
-                )
-                .unwrap();
-            assert_eq!(
-                result.selected_amount(),
-                target_amount.to_unsigned().unwrap()
-            );
-        }
+        // The type of the required UTXOs is kept.
+        let result = AvoidMixedScriptTypes::new(LargestFirstCoinSelection)
+            .coin_select(
+                utxos[1..2].to_vec(),
+                [&utxos[..1], &utxos[2..]].concat(),
+                fee_rate,
+                Amount::from_sat(20_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(selected_types(&result), [ScriptType::P2tr]);
+
+        // No single type is enough, mix them.
+        let result = AvoidMixedScriptTypes::new(LargestFirstCoinSelection)
+            .coin_select(
+                vec![],
+                utxos,
+                fee_rate,
+                Amount::from_sat(90_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(result.selected.len(), 3);
     }
 
     #[test]
-    fn test_bnb_exclude_negative_effective_value() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
+    fn test_coin_selection_context_decide_change() {
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let remaining_amount = Amount::from_sat(5_031);
 
-        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            vec![],
-            utxos,
-            FeeRate::from_sat_per_vb_unchecked(10),
-            Amount::from_sat(500_000),
-            &drain_script,
-            &mut thread_rng(),
+        // The default context behaves like `decide_change`.
+        let context = CoinSelectionContext::default();
+        assert_matches!(
+            decide_change(remaining_amount, fee_rate, &drain_script),
+            Excess::Change { amount, .. } if amount == Amount::from_sat(5_000)
         );
-
         assert_matches!(
-            selection,
-            Err(InsufficientFunds {
-                available,
-                ..
-            }) if available.to_sat() == 300_000
+            context.decide_change(remaining_amount, fee_rate, &drain_script),
+            Excess::Change { amount, fee } if amount == Amount::from_sat(5_000) && fee == Amount::from_sat(31)
         );
-    }
-
-    #[test]
-    fn test_bnb_include_negative_effective_value_when_required() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
 
-        let (required, optional) = utxos.into_iter().partition(
-            |u| matches!(u, WeightedUtxo { utxo, .. } if utxo.txout().value.to_sat() < 1000),
+        // Change below the minimum change value is dropped.
+        let context = CoinSelectionContext {
+            min_change: Amount::from_sat(10_000),
+            ..Default::default()
+        };
+        assert_matches!(
+            context.decide_change(remaining_amount, fee_rate, &drain_script),
+            Excess::NoChange { dust_threshold, .. } if dust_threshold == Amount::from_sat(10_000)
         );
 
-        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            required,
-            optional,
-            FeeRate::from_sat_per_vb_unchecked(10),
-            Amount::from_sat(500_000),
-            &drain_script,
-            &mut thread_rng(),
+        // So is change which would cost more than its value to spend at the discard fee rate.
+        let context = CoinSelectionContext {
+            discard_fee_rate: FeeRate::from_sat_per_vb(100).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            context.min_change_value(&drain_script),
+            Amount::from_sat(6_800)
+        );
+        assert_eq!(
+            context.cost_of_change(fee_rate, &drain_script),
+            Amount::from_sat(31 + 6_800)
         );
-
         assert_matches!(
-            selection,
-            Err(InsufficientFunds {
-                available,
-                ..
-            }) if available.to_sat() == 300_010
+            context.decide_change(remaining_amount, fee_rate, &drain_script),
+            Excess::NoChange { .. }
         );
     }
 
     #[test]
-    fn test_bnb_sum_of_effective_value_negative() {
-        let utxos = get_test_utxos();
-        let drain_script = ScriptBuf::default();
-
-        let selection = BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-            utxos,
-            vec![],
-            FeeRate::from_sat_per_vb_unchecked(10_000),
-            Amount::from_sat(500_000),
-            &drain_script,
-            &mut thread_rng(),
-        );
+    fn test_coin_select_with_context_min_change() {
+        let utxos = get_waste_test_utxos();
+        let drain_script = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let target_amount = Amount::from_sat(195_000);
+        let context = CoinSelectionContext {
+            min_change: Amount::from_sat(10_000),
+            ..Default::default()
+        };
 
-        assert_matches!(
-            selection,
-            Err(InsufficientFunds {
-                available,
-                ..
-            }) if available.to_sat() == 300_010
-        );
+        let algorithms: [&dyn Fn(&CoinSelectionContext) -> CoinSelectionResult; 3] = [
+            &|context| {
+                LargestFirstCoinSelection
+                    .coin_select_with_context(
+                        context,
+                        vec![],
+                        utxos.clone(),
+                        fee_rate,
+                        target_amount,
+                        &drain_script,
+                        &mut thread_rng(),
+                    )
+                    .unwrap()
+            },
+            &|context| {
+                CoinGrinderCoinSelection
+                    .coin_select_with_context(
+                        context,
+                        vec![],
+                        utxos.clone(),
+                        fee_rate,
+                        target_amount,
+                        &drain_script,
+                        &mut thread_rng(),
+                    )
+                    .unwrap()
+            },
+            &|context| {
+                WasteMetricCoinSelection::default()
+                    .coin_select_with_context(
+                        context,
+                        vec![],
+                        utxos.clone(),
+                        fee_rate,
+                        target_amount,
+                        &drain_script,
+                        &mut thread_rng(),
+                    )
+                    .unwrap()
+            },
+        ];
+        for select in algorithms {
+            // The 200_000 sat UTXO leaves less than 5_000 sat of change.
+            let result = select(&CoinSelectionContext::default());
+            assert_matches!(result.excess, Excess::Change { .. });
+            let result = select(&context);
+            if result.selected.len() == 1 {
+                assert_matches!(result.excess, Excess::NoChange { .. });
+            } else {
+                assert_matches!(result.excess, Excess::Change { amount, .. } if amount >= context.min_change);
+            }
+        }
     }
 
     #[test]
-    fn test_bnb_fallback_algorithm() {
-        // utxo value
-        // 120k + 80k + 300k
-        let optional_utxos = get_oldest_first_test_utxos();
-        let feerate = FeeRate::BROADCAST_MIN;
-        let target_amount = Amount::from_sat(190_000);
-        let drain_script = ScriptBuf::new();
-        // bnb won't find exact match and should select oldest first
-        let bnb_with_oldest_first =
-            BranchAndBoundCoinSelection::new(8 + 1 + 22, OldestFirstCoinSelection);
-        let res = bnb_with_oldest_first
+    fn test_selection_diagnostics() {
+        let utxos = get_test_utxos();
+        let drain_script = ScriptBuf::default();
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let target_amount = Amount::from_sat(250_000) + FEE_AMOUNT;
+        let context = CoinSelectionContext {
+            diagnostics: true,
+            ..Default::default()
+        };
+
+        // No diagnostics unless asked for.
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
             .coin_select(
                 vec![],
-                optional_utxos,
-                feerate,
+                utxos.clone(),
+                fee_rate,
                 target_amount,
                 &drain_script,
                 &mut thread_rng(),
             )
             .unwrap();
-        assert_eq!(res.selected_amount(), Amount::from_sat(200_000));
-    }
-
-    #[test]
-    fn test_deterministic_coin_selection_picks_same_utxos() {
-        enum CoinSelectionAlgo {
-            BranchAndBound,
-            OldestFirst,
-            LargestFirst,
-        }
-
-        struct TestCase<'a> {
-            name: &'a str,
-            coin_selection_algo: CoinSelectionAlgo,
-            exp_vouts: &'a [u32],
-        }
+        assert!(result.diagnostics.is_none());
 
-        let test_cases = [
-            TestCase {
-                name: "branch and bound",
-                coin_selection_algo: CoinSelectionAlgo::BranchAndBound,
-                // note: we expect these to be sorted largest first, which indicates
-                // BnB succeeded with no fallback
-                exp_vouts: &[29, 28, 27],
-            },
-            TestCase {
-                name: "oldest first",
-                coin_selection_algo: CoinSelectionAlgo::OldestFirst,
-                exp_vouts: &[0, 1, 2],
-            },
-            TestCase {
-                name: "largest first",
-                coin_selection_algo: CoinSelectionAlgo::LargestFirst,
-                exp_vouts: &[29, 28, 27],
-            },
-        ];
+        // Branch and bound finds no exact match and falls back to single random draw.
+        let result = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select_with_context(
+                &context,
+                vec![],
+                utxos.clone(),
+                fee_rate,
+                target_amount,
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap();
+        let diagnostics = result.diagnostics.as_ref().expect("diagnostics");
+        assert_eq!(diagnostics.candidates.len(), 3);
+        assert!(diagnostics.candidates.iter().all(|c| !c.required));
+        assert_eq!(
+            diagnostics.excluded,
+            vec![(
+                utxos[1].utxo.outpoint(),
+                ExclusionReason::NegativeEffectiveValue
+            )]
+        );
+        let outcomes = diagnostics
+            .attempts
+            .iter()
+            .map(|attempt| (attempt.algorithm, attempt.outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                (
+                    SelectionAlgorithm::BranchAndBound,
+                    SelectionOutcome::NoExactMatch
+                ),
+                (
+                    SelectionAlgorithm::SingleRandomDraw,
+                    SelectionOutcome::Selected {
+                        inputs: result.selected.len(),
+                        amount: result.selected_amount(),
+                    }
+                ),
+            ]
+        );
 
-        let optional = generate_same_value_utxos(Amount::from_sat(100_000), 30);
-        let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
-        let target_amount = calc_target_amount(&optional[0..3], fee_rate);
-        assert_eq!(target_amount, Amount::from_sat(299_796));
-        let drain_script = ScriptBuf::default();
+        // The error carries the diagnostics as well.
+        let err = BranchAndBoundCoinSelection::<SingleRandomDraw>::default()
+            .coin_select_with_context(
+                &context,
+                vec![],
+                utxos,
+                fee_rate,
+                Amount::from_sat(1_000_000),
+                &drain_script,
+                &mut thread_rng(),
+            )
+            .unwrap_err();
+        let diagnostics = err.diagnostics.expect("diagnostics");
+        assert_eq!(diagnostics.attempts.len(), 1);
+        assert_matches!(
+            diagnostics.attempts[0].outcome,
+            SelectionOutcome::InsufficientFunds { needed, available }
+                if needed == err.needed && available == err.available
+        );
+    }
 
-        for tc in test_cases {
-            let optional = optional.clone();
+    #[test]
+    fn test_waste_metric_coin_selection_diagnostics() {
+        let utxos = get_waste_test_utxos();
+        let context = CoinSelectionContext {
+            diagnostics: true,
+            ..Default::default()
+        };
 
-            let result = match tc.coin_selection_algo {
-                CoinSelectionAlgo::BranchAndBound => {
-                    BranchAndBoundCoinSelection::<SingleRandomDraw>::default().coin_select(
-                        vec![],
-                        optional,
-                        fee_rate,
-                        target_amount,
-                        &drain_script,
-                        &mut thread_rng(),
-                    )
-                }
-                CoinSelectionAlgo::OldestFirst => OldestFirstCoinSelection.coin_select(
-                    vec![],
-                    optional,
-                    fee_rate,
-                    target_amount,
-                    &drain_script,
-                    &mut thread_rng(),
-                ),
-                CoinSelectionAlgo::LargestFirst => LargestFirstCoinSelection.coin_select(
-                    vec![],
-                    optional,
-                    fee_rate,
-                    target_amount,
-                    &drain_script,
-                    &mut thread_rng(),
-                ),
-            };
+        let result = WasteMetricCoinSelection::default()
+            .coin_select_with_context(
+                &context,
+                vec![],
+                utxos,
+                FeeRate::from_sat_per_vb(1).unwrap(),
+                Amount::from_sat(50_000),
+                &ScriptBuf::new(),
+                &mut thread_rng(),
+            )
+            .unwrap();
+        let diagnostics = result.diagnostics.as_ref().expect("diagnostics");
+        assert_eq!(diagnostics.candidates.len(), 4);
 
-            assert!(result.is_ok(), "coin_select failed {}", tc.name);
-            let result = result.unwrap();
-            assert!(matches!(result.excess, Excess::NoChange { .. },));
-            assert_eq!(
-                result.selected.len(),
-                3,
-                "wrong selected len for {}",
-                tc.name
-            );
-            assert_eq!(
-                result.selected_amount(),
-                Amount::from_sat(300_000),
-                "wrong selected amount for {}",
-                tc.name
-            );
-            assert_eq!(
-                result.fee_amount,
-                Amount::from_sat(204),
-                "wrong fee amount for {}",
-                tc.name
-            );
-            let vouts = result
-                .selected
-                .iter()
-                .map(|utxo| utxo.outpoint().vout)
-                .collect::<Vec<u32>>();
-            assert_eq!(vouts, tc.exp_vouts, "wrong selected vouts for {}", tc.name);
+        // Coin grinder only runs at high fee rates.
+        let algorithms = diagnostics
+            .attempts
+            .iter()
+            .map(|attempt| attempt.algorithm)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            algorithms,
+            vec![
+                SelectionAlgorithm::BranchAndBound,
+                SelectionAlgorithm::LargestFirst,
+                SelectionAlgorithm::OldestFirst,
+                SelectionAlgorithm::SingleRandomDraw,
+            ]
+        );
+        for attempt in &diagnostics.attempts {
+            match attempt.outcome {
+                SelectionOutcome::Selected { .. } => assert!(attempt.waste.is_some()),
+                _ => assert!(attempt.waste.is_none()),
+            }
         }
+        let winner = diagnostics
+            .attempts
+            .iter()
+            .find(|attempt| Some(attempt.algorithm) == result.algorithm)
+            .unwrap();
+        assert_eq!(winner.waste, result.waste);
+
+        let report = format!("{diagnostics}");
+        assert!(report.contains("oldest first: selected 3 UTXOs"));
     }
 }
@@ -0,0 +1,330 @@
+//! Multi-party PSBT contributions
+//!
+//! When several parties fund a single transaction, each of them adds its own inputs and outputs
+//! to a shared PSBT and pays for the part of the fee caused by them. [`Wallet::contribute_to_psbt`]
+//! does that for the wallet: it selects coins covering our [`Contribution`], adds a change output
+//! if needed and fills in the PSBT metadata of our inputs and outputs so that the wallet is able
+//! to sign them later.
+//!
+//! Once every party has signed its own copy of the PSBT, [`combine_psbts`] merges the signatures
+//! back into a single PSBT which can be finalized.
+//!
+//! ## Example
+//!
+//! ```
+//! # use bdk_wallet::contribution::{combine_psbts, Contribution};
+//! # use bdk_wallet::test_utils::*;
+//! # use bdk_wallet::{KeychainKind, SignOptions};
+//! # use bitcoin::{absolute, transaction, Amount, FeeRate, Psbt, Transaction, TxOut};
+//! let (mut alice, _) = get_funded_wallet_wpkh();
+//! let (mut bob, _) = get_funded_wallet_single(get_test_tr_single_sig());
+//! let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+//! let payee = alice.peek_address(KeychainKind::External, 42).script_pubkey();
+//!
+//! // Each party pays half of a 60_000 sat output.
+//! let tx = Transaction {
+//!     version: transaction::Version::TWO,
+//!     lock_time: absolute::LockTime::ZERO,
+//!     input: vec![],
+//!     output: vec![TxOut { value: Amount::from_sat(60_000), script_pubkey: payee }],
+//! };
+//! let mut psbt = Psbt::from_unsigned_tx(tx)?;
+//! let contribution = Contribution::new(vec![], fee_rate).amount(Amount::from_sat(30_000));
+//! alice.contribute_to_psbt(&mut psbt, contribution.clone())?;
+//! bob.contribute_to_psbt(&mut psbt, contribution)?;
+//! # let options = SignOptions { try_finalize: false, ..Default::default() };
+//!
+//! let mut alice_psbt = psbt.clone();
+//! alice.sign(&mut alice_psbt, options.clone())?;
+//! let mut bob_psbt = psbt;
+//! bob.sign(&mut bob_psbt, options)?;
+//!
+//! let mut psbt = combine_psbts([alice_psbt, bob_psbt])?;
+//! // Each party finalizes its own inputs.
+//! alice.finalize_psbt(&mut psbt, SignOptions::default())?;
+//! assert!(bob.finalize_psbt(&mut psbt, SignOptions::default())?);
+//! # Ok::<_, anyhow::Error>(())
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use bitcoin::psbt::{self, Psbt};
+use bitcoin::{Amount, FeeRate, OutPoint, Sequence, TxIn, TxOut, Weight};
+use rand_core::RngCore;
+
+use crate::types::{Utxo, WeightedUtxo};
+use crate::wallet::coin_selection::{
+    CoinSelectionAlgorithm, DefaultCoinSelectionAlgorithm, Excess,
+};
+use crate::wallet::error::CreateTxError;
+use crate::wallet::reservations::Reservation;
+use crate::wallet::tx_builder::TxParams;
+use crate::wallet::Wallet;
+use crate::KeychainKind;
+
+/// The part of a shared transaction paid by the wallet.
+///
+/// See [`Wallet::contribute_to_psbt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    /// Outputs added to the transaction and paid by the wallet.
+    pub outputs: Vec<TxOut>,
+    /// Our share of the outputs which are already in the transaction.
+    pub amount: Amount,
+    /// Fee rate paid for the weight of our inputs and outputs.
+    pub fee_rate: FeeRate,
+    /// Reservation of the selected coins, see [`Contribution::reserve_utxos`].
+    pub reservation: Option<Reservation>,
+}
+
+impl Contribution {
+    /// Create a contribution adding and paying for `outputs` at `fee_rate`.
+    pub fn new(outputs: Vec<TxOut>, fee_rate: FeeRate) -> Self {
+        Self {
+            outputs,
+            amount: Amount::ZERO,
+            fee_rate,
+            reservation: None,
+        }
+    }
+
+    /// Also pay `amount` of the outputs already in the transaction.
+    pub fn amount(mut self, amount: Amount) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    /// Reserve the selected coins under `draft_id`, like
+    /// [`TxBuilder::reserve_utxos`](crate::TxBuilder::reserve_utxos) does.
+    ///
+    /// Without a reservation, nothing keeps another contribution or transaction of the wallet
+    /// from selecting the same coins before the shared transaction is broadcast.
+    pub fn reserve_utxos(
+        mut self,
+        draft_id: impl Into<String>,
+        expiry_height: Option<u32>,
+    ) -> Self {
+        self.reservation = Some(Reservation {
+            draft_id: draft_id.into(),
+            expiry_height,
+        });
+        self
+    }
+}
+
+/// Error returned by [`combine_psbts`].
+#[derive(Debug)]
+pub enum CombineError {
+    /// No PSBT was given.
+    NoPsbt,
+    /// The PSBTs can't be combined, usually because they don't share the same unsigned
+    /// transaction.
+    Psbt(psbt::Error),
+}
+
+impl fmt::Display for CombineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoPsbt => write!(f, "No PSBT to combine"),
+            Self::Psbt(err) => write!(f, "Cannot combine PSBTs: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CombineError {}
+
+/// Merge several PSBTs of the same transaction into one, as the BIP174 combiner role does.
+///
+/// The signatures and metadata of every input and output are merged into the first PSBT.
+pub fn combine_psbts(psbts: impl IntoIterator<Item = Psbt>) -> Result<Psbt, CombineError> {
+    let mut psbts = psbts.into_iter();
+    let mut combined = psbts.next().ok_or(CombineError::NoPsbt)?;
+    for psbt in psbts {
+        combined.combine(psbt).map_err(CombineError::Psbt)?;
+    }
+    Ok(combined)
+}
+
+impl Wallet {
+    /// Add our [`Contribution`] to a shared `psbt`.
+    ///
+    /// See [`Wallet::contribute_to_psbt_with_aux_rand`].
+    #[cfg(feature = "std")]
+    pub fn contribute_to_psbt(
+        &mut self,
+        psbt: &mut Psbt,
+        contribution: Contribution,
+    ) -> Result<(), CreateTxError> {
+        self.contribute_to_psbt_with_aux_rand(
+            psbt,
+            contribution,
+            &mut bitcoin::key::rand::thread_rng(),
+        )
+    }
+
+    /// Add our [`Contribution`] to a shared `psbt`.
+    ///
+    /// The outputs of `contribution` are added to the transaction and coins of the wallet are
+    /// selected to pay for them, for `contribution.amount` of the outputs already in the
+    /// transaction and for the fee of the weight we add at `contribution.fee_rate`. The weight of
+    /// the inputs and outputs of other parties is not taken into account, each party pays for its
+    /// own. If the selected coins exceed the amount needed a change output paying to the wallet
+    /// is added.
+    ///
+    /// Our inputs and outputs are inserted at random positions, so that they can't be told apart
+    /// from the ones of the other parties by their order. If an input of the transaction is
+    /// signed with or asks for `SIGHASH_SINGLE` they are appended instead, to keep it paired with
+    /// its output.
+    ///
+    /// Our inputs use the sequence of the first input of the transaction, or
+    /// [`Sequence::ENABLE_RBF_NO_LOCKTIME`] if there is none yet, and their PSBT metadata is
+    /// filled in so that the wallet can sign them. UTXOs already spent by the transaction are
+    /// never selected, neither are the ones reserved by a draft other than the one of
+    /// [`Contribution::reserve_utxos`]. The selected coins are only reserved if asked to, see
+    /// [`Contribution::reserve_utxos`].
+    ///
+    /// **WARNING**: A change address may be revealed, you must persist the staged changes to
+    /// avoid reusing it.
+    ///
+    /// Uses a provided random number generator (rng).
+    pub fn contribute_to_psbt_with_aux_rand(
+        &mut self,
+        psbt: &mut Psbt,
+        contribution: Contribution,
+        rng: &mut impl RngCore,
+    ) -> Result<(), CreateTxError> {
+        let Contribution {
+            outputs,
+            amount,
+            fee_rate,
+            reservation,
+        } = contribution;
+
+        for (index, txout) in outputs.iter().enumerate() {
+            if txout.value < txout.script_pubkey.minimal_non_dust()
+                && !txout.script_pubkey.is_op_return()
+            {
+                return Err(CreateTxError::OutputBelowDustLimit(
+                    psbt.unsigned_tx.output.len() + index,
+                ));
+            }
+        }
+
+        let spent = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<OutPoint>>();
+        let current_height = self.latest_checkpoint().height();
+        let params = TxParams {
+            reservation: reservation.clone(),
+            ..Default::default()
+        };
+        let candidates = self
+            .filter_utxos(&params, current_height)
+            .into_iter()
+            .filter(|wutxo| !spent.contains(&wutxo.utxo.outpoint()))
+            .collect::<Vec<WeightedUtxo>>();
+
+        let outputs_weight = outputs
+            .iter()
+            .map(|txout| Weight::from_vb_unchecked(txout.size() as u64))
+            .sum::<Weight>();
+        let target = amount + outputs.iter().map(|txout| txout.value).sum::<Amount>();
+        let base_fee = fee_rate * outputs_weight;
+
+        // The change address is only revealed if a change output is added.
+        let (change_keychain, change_index, drain_script) =
+            self.peek_change_spk(KeychainKind::Internal);
+        let result = DefaultCoinSelectionAlgorithm::default().coin_select(
+            Vec::new(),
+            candidates,
+            fee_rate,
+            target + base_fee,
+            &drain_script,
+            rng,
+        )?;
+        if result.selected.is_empty() {
+            return Err(CreateTxError::NoUtxosSelected);
+        }
+
+        let sequence = psbt
+            .unsigned_tx
+            .input
+            .first()
+            .map(|txin| txin.sequence)
+            .unwrap_or(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let keep_order = psbt.inputs.iter().any(uses_sighash_single);
+        let mut position = |len: usize| {
+            if keep_order {
+                len
+            } else {
+                (rng.next_u32() as usize) % (len + 1)
+            }
+        };
+
+        let selected = result
+            .selected
+            .iter()
+            .map(|utxo| utxo.outpoint())
+            .filter(|outpoint| self.indexed_graph.index.txout(*outpoint).is_some())
+            .collect::<Vec<_>>();
+        for utxo in result.selected {
+            let txin = TxIn {
+                previous_output: utxo.outpoint(),
+                sequence,
+                ..Default::default()
+            };
+            let input = match utxo {
+                Utxo::Local(local) => self.get_psbt_input(local, None, false)?,
+                Utxo::Foreign { psbt_input, .. } => *psbt_input,
+            };
+            let index = position(psbt.inputs.len());
+            psbt.unsigned_tx.input.insert(index, txin);
+            psbt.inputs.insert(index, input);
+        }
+
+        let mut outputs = outputs;
+        if let Excess::Change { amount, .. } = result.excess {
+            outputs.push(TxOut {
+                value: amount,
+                script_pubkey: drain_script,
+            });
+            self.use_change_spk(change_keychain, change_index);
+        }
+        for txout in outputs {
+            let index = position(psbt.outputs.len());
+            psbt.unsigned_tx.output.insert(index, txout);
+            psbt.outputs.insert(index, psbt::Output::default());
+        }
+
+        if let Some(reservation) = reservation {
+            self.reserve_outpoints(selected, reservation);
+        }
+
+        self.update_psbt_with_descriptor(psbt)?;
+        Ok(())
+    }
+}
+
+/// Whether `input` is signed with `SIGHASH_SINGLE` or asks for it, committing to the output at
+/// its index.
+fn uses_sighash_single(input: &psbt::Input) -> bool {
+    let is_single = |sighash_type: u32| sighash_type & 0x1f == 0x03;
+    input
+        .sighash_type
+        .is_some_and(|sighash_type| is_single(sighash_type.to_u32()))
+        || input
+            .partial_sigs
+            .values()
+            .any(|sig| is_single(sig.sighash_type.to_u32()))
+        || input
+            .tap_key_sig
+            .iter()
+            .chain(input.tap_script_sigs.values())
+            .any(|sig| is_single(sig.sighash_type as u32))
+}
@@ -14,9 +14,10 @@
 use crate::descriptor::policy::PolicyError;
 use crate::descriptor::DescriptorError;
 use crate::wallet::coin_selection;
+use crate::wallet::silent_payments::SilentPaymentError;
 use crate::{descriptor, KeychainKind};
 use alloc::string::String;
-use bitcoin::{absolute, psbt, Amount, OutPoint, Sequence, Txid};
+use bitcoin::{absolute, psbt, Amount, OutPoint, Sequence, Txid, Weight};
 use core::fmt;
 
 /// Errors returned by miniscript when updating inconsistent PSBTs
@@ -104,6 +105,16 @@ pub enum CreateTxError {
     MissingNonWitnessUtxo(OutPoint),
     /// Miniscript PSBT error
     MiniscriptPsbt(MiniscriptPsbtError),
+    /// The transaction would break the BIP431 TRUC topology rules
+    Truc(TrucError),
+    /// An index passed to [`TxBuilder::subtract_fee_from`] doesn't match any recipient
+    ///
+    /// [`TxBuilder::subtract_fee_from`]: crate::wallet::tx_builder::TxBuilder::subtract_fee_from
+    InvalidSubtractFeeIndex(usize),
+    /// The transaction wouldn't be relayed by nodes running the default standardness policy
+    NonStandard(NonStandardError),
+    /// A silent payment output couldn't be derived
+    SilentPayment(SilentPaymentError),
 }
 
 impl fmt::Display for CreateTxError {
@@ -170,6 +181,16 @@ impl fmt::Display for CreateTxError {
             CreateTxError::MiniscriptPsbt(err) => {
                 write!(f, "Miniscript PSBT error: {err}")
             }
+            CreateTxError::Truc(err) => err.fmt(f),
+            CreateTxError::InvalidSubtractFeeIndex(index) => {
+                write!(f, "Cannot subtract fee from unknown recipient: {index}")
+            }
+            CreateTxError::NonStandard(err) => {
+                write!(f, "Non-standard transaction: {err}")
+            }
+            CreateTxError::SilentPayment(err) => {
+                write!(f, "Silent payment error: {err}")
+            }
         }
     }
 }
@@ -204,9 +225,203 @@ impl From<coin_selection::InsufficientFunds> for CreateTxError {
     }
 }
 
+impl From<TrucError> for CreateTxError {
+    fn from(err: TrucError) -> Self {
+        CreateTxError::Truc(err)
+    }
+}
+
+impl From<NonStandardError> for CreateTxError {
+    fn from(err: NonStandardError) -> Self {
+        CreateTxError::NonStandard(err)
+    }
+}
+
+impl From<SilentPaymentError> for CreateTxError {
+    fn from(err: SilentPaymentError) -> Self {
+        CreateTxError::SilentPayment(err)
+    }
+}
+
 #[cfg(feature = "std")]
 impl std::error::Error for CreateTxError {}
 
+/// Violations of the BIP431 TRUC (Topologically Restricted Until Confirmation) rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrucError {
+    /// A TRUC transaction can't spend the outputs of an unconfirmed non-TRUC transaction
+    UnconfirmedNonTrucParent(Txid),
+    /// A non-TRUC transaction can't spend the outputs of an unconfirmed TRUC transaction
+    UnconfirmedTrucParent(Txid),
+    /// An unconfirmed TRUC transaction can't have more than one unconfirmed ancestor
+    AncestorLimit(Txid),
+    /// An unconfirmed TRUC transaction can't have more than one unconfirmed descendant
+    DescendantLimit(Txid),
+    /// The transaction is larger than TRUC transactions are allowed to be
+    TooLarge {
+        /// Estimated virtual size of the transaction
+        vsize: u64,
+        /// Maximum virtual size allowed
+        max: u64,
+    },
+}
+
+impl fmt::Display for TrucError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrucError::UnconfirmedNonTrucParent(txid) => {
+                write!(
+                    f,
+                    "TRUC transaction spends unconfirmed non-TRUC parent {txid}"
+                )
+            }
+            TrucError::UnconfirmedTrucParent(txid) => {
+                write!(
+                    f,
+                    "Non-TRUC transaction spends unconfirmed TRUC parent {txid}"
+                )
+            }
+            TrucError::AncestorLimit(txid) => {
+                write!(f, "TRUC transaction exceeds the ancestor limit with {txid}")
+            }
+            TrucError::DescendantLimit(txid) => {
+                write!(f, "Unconfirmed TRUC parent {txid} already has a descendant")
+            }
+            TrucError::TooLarge { vsize, max } => {
+                write!(
+                    f,
+                    "TRUC transaction of {vsize} vB exceeds the limit of {max} vB"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TrucError {}
+
+/// Violations of the default relay policy of Bitcoin Core
+///
+/// Input and output indexes refer to the transaction returned in the PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NonStandardError {
+    /// The transaction is heavier than the maximum standard weight
+    TxWeight {
+        /// Estimated weight of the signed transaction
+        weight: Weight,
+        /// Maximum standard weight
+        max: Weight,
+    },
+    /// The transaction without its witness data is smaller than the minimum standard size
+    TooSmall {
+        /// Size of the transaction without its witness data, in bytes
+        size: usize,
+        /// Minimum standard size, in bytes
+        min: usize,
+    },
+    /// The `OP_RETURN` output scripts are larger than allowed by the
+    /// [`DataCarrierPolicy`](crate::wallet::tx_builder::DataCarrierPolicy)
+    DataCarrierSize {
+        /// Index of the output exceeding the limit
+        index: usize,
+        /// Size of the `OP_RETURN` output scripts up to this one, in bytes
+        size: usize,
+        /// Maximum size, in bytes
+        max: usize,
+    },
+    /// The transaction has more than one `OP_RETURN` output, which the
+    /// [`DataCarrierPolicy`](crate::wallet::tx_builder::DataCarrierPolicy) doesn't allow
+    MultipleDataCarriers,
+    /// An output is a bare multisig script
+    BareMultisig(usize),
+    /// The signature operations of the transaction cost more than the standard limit
+    SigopsCost {
+        /// Signature operations cost of the transaction
+        cost: usize,
+        /// Maximum standard cost
+        max: usize,
+    },
+    /// The redeem script of a P2SH input has too many signature operations
+    P2shSigops {
+        /// Index of the input
+        input: usize,
+        /// Signature operations in the redeem script
+        sigops: usize,
+        /// Maximum standard number of signature operations
+        max: usize,
+    },
+    /// The redeem script of a P2SH input is larger than a script element can be
+    RedeemScriptSize {
+        /// Index of the input
+        input: usize,
+        /// Size of the redeem script, in bytes
+        size: usize,
+        /// Maximum standard size, in bytes
+        max: usize,
+    },
+    /// The witness script of a P2WSH input is larger than the standard limit
+    WitnessScriptSize {
+        /// Index of the input
+        input: usize,
+        /// Size of the witness script, in bytes
+        size: usize,
+        /// Maximum standard size, in bytes
+        max: usize,
+    },
+}
+
+impl fmt::Display for NonStandardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NonStandardError::TxWeight { weight, max } => {
+                write!(f, "weight of {weight} exceeds the limit of {max}")
+            }
+            NonStandardError::TooSmall { size, min } => {
+                write!(
+                    f,
+                    "non-witness size of {size} bytes is below the minimum of {min} bytes"
+                )
+            }
+            NonStandardError::DataCarrierSize { index, size, max } => {
+                write!(
+                    f,
+                    "OP_RETURN outputs up to {index} of {size} bytes exceed the limit of {max} bytes"
+                )
+            }
+            NonStandardError::MultipleDataCarriers => {
+                write!(f, "more than one OP_RETURN output")
+            }
+            NonStandardError::BareMultisig(index) => {
+                write!(f, "output {index} is a bare multisig")
+            }
+            NonStandardError::SigopsCost { cost, max } => {
+                write!(f, "sigops cost of {cost} exceeds the limit of {max}")
+            }
+            NonStandardError::P2shSigops { input, sigops, max } => {
+                write!(
+                    f,
+                    "redeem script of input {input} has {sigops} sigops, more than the limit of {max}"
+                )
+            }
+            NonStandardError::RedeemScriptSize { input, size, max } => {
+                write!(
+                    f,
+                    "redeem script of input {input} of {size} bytes exceeds the limit of {max} bytes"
+                )
+            }
+            NonStandardError::WitnessScriptSize { input, size, max } => {
+                write!(
+                    f,
+                    "witness script of input {input} of {size} bytes exceeds the limit of {max} bytes"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NonStandardError {}
+
 #[derive(Debug)]
 /// Error returned from [`Wallet::build_fee_bump`]
 ///
@@ -224,6 +439,14 @@ pub enum BuildFeeBumpError {
     FeeRateUnavailable,
     /// Input references an invalid output index in the previous transaction
     InvalidOutputIndex(OutPoint),
+    /// Trying to cancel a tx that doesn't spend any output owned by the wallet
+    NoLocalInputs(Txid),
+    /// No transaction to replace was given
+    NoTransactions,
+    /// The same transaction was given more than once
+    DuplicateTransaction(Txid),
+    /// Trying to replace TRUC and non-TRUC transactions at once
+    MixedTrucVersions,
 }
 
 impl fmt::Display for BuildFeeBumpError {
@@ -250,9 +473,60 @@ impl fmt::Display for BuildFeeBumpError {
             Self::InvalidOutputIndex(op) => {
                 write!(f, "A txin referenced an invalid output: {op}")
             }
+            Self::NoLocalInputs(txid) => {
+                write!(
+                    f,
+                    "Transaction doesn't spend any wallet output with txid: {txid}"
+                )
+            }
+            Self::NoTransactions => write!(f, "No transaction to replace"),
+            Self::DuplicateTransaction(txid) => {
+                write!(f, "Transaction to replace given twice with txid: {txid}")
+            }
+            Self::MixedTrucVersions => {
+                write!(f, "Can't replace TRUC and non-TRUC transactions together")
+            }
         }
     }
 }
 
 #[cfg(feature = "std")]
 impl std::error::Error for BuildFeeBumpError {}
+
+#[derive(Debug)]
+/// Error returned from [`Wallet::build_cpfp`]
+///
+/// [`Wallet::build_cpfp`]: super::Wallet::build_cpfp
+pub enum BuildCpfpError {
+    /// Thrown when a tx is not found in the internal database
+    TransactionNotFound(Txid),
+    /// Happens when trying to pay for a transaction that is already confirmed
+    TransactionConfirmed(Txid),
+    /// The parent transaction has no unspent output owned by the wallet nor unspent anchor
+    NoSpendableOutputs(Txid),
+    /// The fee of an unconfirmed ancestor can't be calculated
+    FeeRateUnavailable,
+}
+
+impl fmt::Display for BuildCpfpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransactionNotFound(txid) => {
+                write!(
+                    f,
+                    "Transaction not found in the internal database with txid: {txid}"
+                )
+            }
+            Self::TransactionConfirmed(txid) => {
+                write!(f, "Transaction already confirmed with txid: {txid}")
+            }
+            Self::NoSpendableOutputs(txid) => {
+                write!(f, "Transaction has no spendable outputs with txid: {txid}")
+            }
+            Self::FeeRateUnavailable => write!(f, "Fee rate unavailable"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildCpfpError {}
@@ -37,11 +37,11 @@ use bitcoin::{
     absolute,
     consensus::encode::serialize,
     constants::genesis_block,
-    psbt,
-    secp256k1::Secp256k1,
+    psbt, relative,
+    secp256k1::{self, Secp256k1},
     sighash::{EcdsaSighashType, TapSighashType},
     transaction, Address, Amount, Block, BlockHash, FeeRate, Network, NetworkKind, OutPoint, Psbt,
-    ScriptBuf, Sequence, SignedAmount, Transaction, TxOut, Txid, Weight, Witness,
+    Script, ScriptBuf, Sequence, SignedAmount, Transaction, TxIn, TxOut, Txid, Weight, Witness,
 };
 use miniscript::{
     descriptor::KeyMap,
@@ -49,31 +49,47 @@ use miniscript::{
 };
 use rand_core::RngCore;
 
+pub mod bip21;
 mod changeset;
 pub mod coin_selection;
+pub mod contribution;
 pub mod error;
 pub mod export;
 pub mod locked_outpoints;
 mod params;
+pub mod payjoin;
 mod persisted;
+pub mod reservations;
 pub mod signer;
+pub mod silent_payments;
 pub mod tx_builder;
 pub(crate) mod utils;
 
-use crate::collections::{BTreeMap, HashMap, HashSet};
+use crate::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use crate::descriptor::{
-    check_wallet_descriptor, error::Error as DescriptorError, policy::BuildSatisfaction,
+    check_wallet_descriptor,
+    error::Error as DescriptorError,
+    policy::{BuildSatisfaction, Condition},
     DerivedDescriptor, DescriptorMeta, ExtendedDescriptor, ExtractPolicy, IntoWalletDescriptor,
     Policy, XKeyUtils,
 };
-use crate::psbt::PsbtUtils;
+use crate::psbt::{PsbtUtils, PsbtV2};
 use crate::types::*;
 use crate::wallet::{
-    coin_selection::{DefaultCoinSelectionAlgorithm, Excess, InsufficientFunds},
-    error::{BuildFeeBumpError, CreateTxError, MiniscriptPsbtError},
+    bip21::PaymentUri,
+    coin_selection::{
+        with_diagnostics, AvoidMixedScriptTypes, AvoidPartialSpends, CoinSelectionAlgorithm,
+        CoinSelectionContext, CoinSelectionResult, DefaultCoinSelectionAlgorithm, Excess,
+        ExclusionReason, InsufficientFunds, SelectionDiagnostics,
+    },
+    error::{BuildCpfpError, BuildFeeBumpError, CreateTxError, MiniscriptPsbtError, TrucError},
     signer::{SignOptions, SignerError, SignerOrdering, SignersContainer, TransactionSigner},
+    silent_payments::{derive_output_scripts, SilentPaymentAddress, SilentPaymentError},
     tx_builder::{FeePolicy, TxBuilder, TxParams},
-    utils::{check_nsequence_rbf, After, Older, SecpCtx},
+    utils::{
+        check_nsequence_rbf, check_standardness, discourage_fee_sniping, is_p2a, After, Older,
+        ScriptType, SecpCtx,
+    },
 };
 
 // re-exports
@@ -83,6 +99,7 @@ pub use params::*;
 pub use persisted::*;
 pub use utils::IsDust;
 pub use utils::TxDetails;
+pub use utils::TxPreview;
 
 /// A Bitcoin wallet
 ///
@@ -111,6 +128,8 @@ pub struct Wallet {
     network: Network,
     secp: SecpCtx,
     locked_outpoints: HashSet<OutPoint>,
+    reserved_outpoints: HashMap<OutPoint, reservations::Reservation>,
+    silent_payments: silent_payments::ReceiverState,
 }
 
 /// An update to [`Wallet`].
@@ -169,6 +188,17 @@ impl Deref for AddressInfo {
     }
 }
 
+impl AddressInfo {
+    /// A BIP21 payment URI requesting `amount` to this address, labeled with `label`.
+    pub fn payment_uri(&self, amount: Option<Amount>, label: Option<String>) -> PaymentUri {
+        PaymentUri {
+            amount,
+            label,
+            ..PaymentUri::new(self.address.clone())
+        }
+    }
+}
+
 impl fmt::Display for AddressInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.address)
@@ -233,6 +263,13 @@ pub enum LoadMismatch {
         /// The expected descriptor.
         expected: Option<Box<ExtendedDescriptor>>,
     },
+    /// Silent payment address does not match.
+    SilentPaymentAddress {
+        /// The address that is loaded.
+        loaded: Box<SilentPaymentAddress>,
+        /// The expected address.
+        expected: Box<SilentPaymentAddress>,
+    },
 }
 
 impl fmt::Display for LoadMismatch {
@@ -264,6 +301,12 @@ impl fmt::Display for LoadMismatch {
                         .map_or("None".to_string(), |d| d.to_string())
                 )
             }
+            LoadMismatch::SilentPaymentAddress { loaded, expected } => {
+                write!(
+                    f,
+                    "Silent payment address mismatch: loaded {loaded}, expected {expected}"
+                )
+            }
         }
     }
 }
@@ -477,12 +520,27 @@ impl Wallet {
         };
 
         let locked_outpoints = HashSet::new();
+        let reserved_outpoints = HashMap::new();
+
+        let mut silent_payments = silent_payments::ReceiverState::default();
+        if let Some((scan_key, spend_key)) = params.silent_payments {
+            silent_payments.scan_key = Some(scan_key);
+            silent_payments.address = Some(SilentPaymentAddress::new(
+                scan_key.public_key(&secp),
+                spend_key,
+                network,
+            ));
+        }
 
         let mut stage = ChangeSet {
             descriptor: Some(descriptor.clone()),
             change_descriptor: change_descriptor.clone(),
             local_chain: chain_changeset,
             network: Some(network),
+            silent_payments: silent_payments::ChangeSet {
+                address: silent_payments.address,
+                ..Default::default()
+            },
             ..Default::default()
         };
 
@@ -505,6 +563,8 @@ impl Wallet {
             stage,
             secp,
             locked_outpoints,
+            reserved_outpoints,
+            silent_payments,
         })
     }
 
@@ -690,8 +750,42 @@ impl Wallet {
             .map(|(op, _)| op)
             .collect();
 
+        // Apply reserved outpoints
+        let reserved_outpoints = changeset
+            .reservations
+            .outpoints
+            .into_iter()
+            .filter_map(|(op, reservation)| Some((op, reservation?)))
+            .collect();
+
         let mut stage = ChangeSet::default();
 
+        // Restore the silent payment outputs, enabling receiving if it wasn't yet.
+        let mut silent_payments = silent_payments::ReceiverState {
+            address: changeset.silent_payments.address,
+            outputs: changeset.silent_payments.outputs,
+            sent: changeset.silent_payments.sent,
+            ..Default::default()
+        };
+        if let Some((scan_key, spend_key)) = params.silent_payments {
+            let expected =
+                SilentPaymentAddress::new(scan_key.public_key(&secp), spend_key, network);
+            match silent_payments.address {
+                Some(loaded) if loaded != expected => {
+                    return Err(LoadError::Mismatch(LoadMismatch::SilentPaymentAddress {
+                        loaded: Box::new(loaded),
+                        expected: Box::new(expected),
+                    }));
+                }
+                Some(_) => {}
+                None => {
+                    silent_payments.address = Some(expected);
+                    stage.silent_payments.address = Some(expected);
+                }
+            }
+            silent_payments.scan_key = Some(scan_key);
+        }
+
         let indexed_graph = make_indexed_graph(
             &mut stage,
             changeset.tx_graph,
@@ -712,6 +806,8 @@ impl Wallet {
             network,
             secp,
             locked_outpoints,
+            reserved_outpoints,
+            silent_payments,
         }))
     }
 
@@ -920,6 +1016,50 @@ impl Wallet {
             .map(|((k, i), full_txo)| new_local_utxo(k, i, full_txo))
     }
 
+    /// Return the list of unspent pay-to-anchor (P2A) outputs of the wallet's transactions
+    ///
+    /// Anchors are not part of any keychain, but anyone can spend them without a signature. They
+    /// are returned as foreign UTXOs ready to be spent, either with [`TxBuilder::add_utxo`] or
+    /// through [`Wallet::build_cpfp`].
+    pub fn list_unspent_anchors(&self) -> impl Iterator<Item = WeightedUtxo> {
+        let txs: HashMap<Txid, Arc<Transaction>> = self
+            .transactions()
+            .map(|wtx| (wtx.tx_node.txid, wtx.tx_node.tx))
+            .collect();
+        let anchors = txs.iter().flat_map(|(txid, tx)| {
+            tx.output
+                .iter()
+                .enumerate()
+                .filter(|(_, txout)| is_p2a(&txout.script_pubkey))
+                .map(|(vout, _)| ((), OutPoint::new(*txid, vout as u32)))
+        });
+        let unspent: Vec<WeightedUtxo> = self
+            .indexed_graph
+            .graph()
+            .filter_chain_unspents(
+                &self.chain,
+                self.chain.tip().block_id(),
+                CanonicalizationParams::default(),
+                anchors,
+            )
+            .map(|(_, full_txo)| WeightedUtxo {
+                // Spending an anchor only takes an empty witness.
+                satisfaction_weight: Weight::ZERO,
+                utxo: Utxo::Foreign {
+                    outpoint: full_txo.outpoint,
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    psbt_input: Box::new(psbt::Input {
+                        non_witness_utxo: Some(txs[&full_txo.outpoint.txid].as_ref().clone()),
+                        witness_utxo: Some(full_txo.txout),
+                        final_script_witness: Some(Witness::new()),
+                        ..Default::default()
+                    }),
+                },
+            })
+            .collect();
+        unspent.into_iter()
+    }
+
     /// Get the [`TxDetails`] of a wallet transaction.
     ///
     /// If the transaction with txid [`Txid`] cannot be found in the wallet's transactions, `None`
@@ -1102,6 +1242,206 @@ impl Wallet {
         self.calculate_fee(tx).map(|fee| fee / tx.weight())
     }
 
+    /// Calculate the [`FeeRate`] of the package formed by `tx` and all of its unconfirmed
+    /// ancestors.
+    ///
+    /// This is the feerate miners see when deciding whether to include `tx` together with the
+    /// unconfirmed transactions it depends on, e.g. after paying for a stuck transaction with
+    /// [`build_cpfp`]. Note `tx` does not have to be in the graph for this to work, but it must be
+    /// signed for its weight to be accurate.
+    ///
+    /// [`build_cpfp`]: Self::build_cpfp
+    pub fn calculate_package_fee_rate(
+        &self,
+        tx: &Transaction,
+    ) -> Result<FeeRate, CalculateFeeError> {
+        let txid = tx.compute_txid();
+        let ancestors =
+            self.unconfirmed_ancestors(tx.input.iter().map(|txin| txin.previous_output.txid));
+
+        let mut fee = self.calculate_fee(tx)?;
+        let mut weight = tx.weight();
+        for ancestor in ancestors.iter().filter(|a| a.compute_txid() != txid) {
+            fee += self.calculate_fee(ancestor)?;
+            weight += ancestor.weight();
+        }
+        Ok(fee / weight)
+    }
+
+    /// Returns the canonical, unconfirmed transactions among `txids` together with all of their
+    /// unconfirmed ancestors.
+    fn unconfirmed_ancestors(
+        &self,
+        txids: impl IntoIterator<Item = Txid>,
+    ) -> Vec<Arc<Transaction>> {
+        let unconfirmed = self.unconfirmed_txs();
+
+        let mut visited = HashSet::<Txid>::new();
+        let mut ancestors = Vec::new();
+        let mut stack: Vec<Txid> = txids.into_iter().collect();
+        while let Some(txid) = stack.pop() {
+            if let Some(tx) = unconfirmed.get(&txid) {
+                if visited.insert(txid) {
+                    stack.extend(tx.input.iter().map(|txin| txin.previous_output.txid));
+                    ancestors.push(tx.clone());
+                }
+            }
+        }
+        ancestors
+    }
+
+    /// Returns the total fee and weight of the unconfirmed ancestors of `txids` which a child
+    /// must pay for to reach `fee_rate`.
+    ///
+    /// As Bitcoin Core's MiniMiner does, ancestors which together with their own ancestors already
+    /// pay at least `fee_rate` are considered mined on their own, starting with the ones paying
+    /// the most. The ancestors left pay less than `fee_rate` and a child spending them must make
+    /// up for the difference, its *bump fee*. Ancestors whose fee is unknown are ignored.
+    fn unconfirmed_ancestor_package(
+        &self,
+        txids: impl IntoIterator<Item = Txid>,
+        fee_rate: FeeRate,
+    ) -> tx_builder::PackageFee {
+        let mut pending: HashMap<Txid, (Arc<Transaction>, Amount)> = self
+            .unconfirmed_ancestors(txids)
+            .into_iter()
+            .filter_map(|tx| {
+                let fee = self.calculate_fee(&tx).ok()?;
+                Some((tx.compute_txid(), (tx, fee)))
+            })
+            .collect();
+
+        // The ancestor set of `txid` among the pending transactions.
+        let ancestor_set = |pending: &HashMap<Txid, (Arc<Transaction>, Amount)>, txid: Txid| {
+            let mut set = HashSet::<Txid>::new();
+            let mut stack = vec![txid];
+            while let Some(txid) = stack.pop() {
+                if let Some((tx, _)) = pending.get(&txid) {
+                    if set.insert(txid) {
+                        stack.extend(tx.input.iter().map(|txin| txin.previous_output.txid));
+                    }
+                }
+            }
+            set
+        };
+        let package_fee = |pending: &HashMap<Txid, (Arc<Transaction>, Amount)>, txids: &[Txid]| {
+            txids.iter().fold(
+                tx_builder::PackageFee {
+                    absolute: Amount::ZERO,
+                    weight: Weight::ZERO,
+                },
+                |mut package, txid| {
+                    let (tx, fee) = &pending[txid];
+                    package.absolute += *fee;
+                    package.weight += tx.weight();
+                    package
+                },
+            )
+        };
+
+        loop {
+            let best = pending
+                .keys()
+                .map(|&txid| {
+                    let set = ancestor_set(&pending, txid).into_iter().collect::<Vec<_>>();
+                    let package = package_fee(&pending, &set);
+                    (package.absolute / package.weight, set)
+                })
+                .max_by_key(|(rate, _)| *rate);
+            match best {
+                Some((rate, set)) if rate >= fee_rate => {
+                    for txid in set {
+                        pending.remove(&txid);
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let txids = pending.keys().copied().collect::<Vec<_>>();
+        package_fee(&pending, &txids)
+    }
+
+    /// Returns the canonical, unconfirmed transactions of the wallet.
+    fn unconfirmed_txs(&self) -> HashMap<Txid, Arc<Transaction>> {
+        self.transactions()
+            .filter(|wtx| !wtx.chain_position.is_confirmed())
+            .map(|wtx| (wtx.tx_node.txid, wtx.tx_node.tx))
+            .collect()
+    }
+
+    /// Enforces the BIP431 TRUC topology rules on the UTXOs spent by a transaction of `version`.
+    ///
+    /// Returns an error if a `required` UTXO can't be spent and drops the `optional` UTXOs that
+    /// can't be spent. For TRUC transactions, returns the unconfirmed parent, if any.
+    fn check_truc_topology(
+        &self,
+        version: transaction::Version,
+        required: &[WeightedUtxo],
+        optional: &mut Vec<WeightedUtxo>,
+    ) -> Result<Option<Txid>, TrucError> {
+        let unconfirmed = self.unconfirmed_txs();
+        let is_truc = version == tx_builder::TRUC_VERSION;
+        // Transactions spending the same outputs as ours are replaced, so they don't count as
+        // descendants.
+        let required_outpoints: HashSet<OutPoint> =
+            required.iter().map(|u| u.utxo.outpoint()).collect();
+
+        let check_parent = |txid: Txid, parent: &Transaction| {
+            match (is_truc, parent.version == tx_builder::TRUC_VERSION) {
+                (true, false) => return Err(TrucError::UnconfirmedNonTrucParent(txid)),
+                (false, true) => return Err(TrucError::UnconfirmedTrucParent(txid)),
+                (false, false) => return Ok(()),
+                (true, true) => {}
+            }
+            if let Some(ancestor) = parent
+                .input
+                .iter()
+                .map(|txin| txin.previous_output.txid)
+                .find(|txid| unconfirmed.contains_key(txid))
+            {
+                return Err(TrucError::AncestorLimit(ancestor));
+            }
+            let has_descendant = unconfirmed.values().any(|tx| {
+                tx.input
+                    .iter()
+                    .any(|txin| txin.previous_output.txid == txid)
+                    && !tx
+                        .input
+                        .iter()
+                        .any(|txin| required_outpoints.contains(&txin.previous_output))
+            });
+            if has_descendant {
+                return Err(TrucError::DescendantLimit(txid));
+            }
+            Ok(())
+        };
+
+        let mut truc_parent = None;
+        for utxo in required {
+            let txid = utxo.utxo.outpoint().txid;
+            if let Some(parent) = unconfirmed.get(&txid) {
+                check_parent(txid, parent)?;
+                match truc_parent {
+                    Some(other) if other != txid => return Err(TrucError::AncestorLimit(txid)),
+                    _ if is_truc => truc_parent = Some(txid),
+                    _ => {}
+                }
+            }
+        }
+
+        // Only spend unconfirmed outputs of the parent we already have, so that coin selection
+        // can't add a second unconfirmed ancestor.
+        optional.retain(|utxo| {
+            let txid = utxo.utxo.outpoint().txid;
+            unconfirmed.get(&txid).is_none_or(|parent| {
+                check_parent(txid, parent).is_ok() && (!is_truc || truc_parent == Some(txid))
+            })
+        });
+
+        Ok(truc_parent)
+    }
+
     /// Compute the `tx`'s sent and received [`Amount`]s.
     ///
     /// This method returns a tuple `(sent, received)`. Sent is the sum of the txin amounts
@@ -1250,8 +1590,18 @@ impl Wallet {
             &self.chain,
             self.chain.tip().block_id(),
             CanonicalizationParams::default(),
-            self.indexed_graph.index.outpoints().iter().cloned(),
-            |&(k, _), _| k == KeychainKind::Internal,
+            self.indexed_graph
+                .index
+                .outpoints()
+                .iter()
+                .map(|&((keychain, index), outpoint)| ((Some(keychain), index), outpoint))
+                .chain(
+                    self.silent_payments
+                        .outputs
+                        .keys()
+                        .map(|&outpoint| ((None, 0), outpoint)),
+                ),
+            |&(k, _), _| k == Some(KeychainKind::Internal),
         )
     }
 
@@ -1356,12 +1706,245 @@ impl Wallet {
         }
     }
 
+    /// Start building a transaction from a set of previously prepared [`TxParams`].
+    ///
+    /// This is useful when the parameters of a transaction are assembled somewhere else, for
+    /// instance in a different process, and only later turned into a PSBT by the wallet. The
+    /// returned [`TxBuilder`] can be further customized before calling [`TxBuilder::finish`].
+    ///
+    /// Any manually selected UTXOs contained in `params` are used as they are, it is up to the
+    /// caller to make sure they are still unspent.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+    /// let mut params = TxParams::new();
+    /// params.add_recipient(to_address.script_pubkey(), Amount::from_sat(50_000));
+    ///
+    /// let psbt = wallet.build_tx_from_params(params).finish()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`TxBuilder`]: crate::TxBuilder
+    pub fn build_tx_from_params(
+        &mut self,
+        params: TxParams,
+    ) -> TxBuilder<'_, DefaultCoinSelectionAlgorithm> {
+        TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        }
+    }
+
+    /// Preview the transaction that would be created from `params`, without changing the wallet.
+    ///
+    /// This runs the same coin selection and weight estimation as [`TxBuilder::finish`] with the
+    /// default coin selection algorithm, but doesn't reveal or mark as used the change address,
+    /// doesn't reserve UTXOs and doesn't stage any change. It is meant to quote fees, for instance
+    /// while a user is typing an amount.
+    ///
+    /// Uses the thread-local random number generator (rng), so the selected inputs may differ
+    /// from the ones selected by a later call to [`TxBuilder::finish`].
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let wallet = doctest_wallet!();
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+    /// let mut params = TxParams::new();
+    /// params
+    ///     .add_recipient(to_address.script_pubkey(), Amount::from_sat(50_000))
+    ///     .fee_rate(FeeRate::from_sat_per_vb(5).unwrap());
+    ///
+    /// let preview = wallet.preview_tx(params)?;
+    /// println!("fee: {}, vsize: {}", preview.fee, preview.vsize);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn preview_tx(&self, params: TxParams) -> Result<TxPreview, CreateTxError> {
+        self.preview_tx_with_aux_rand(params, &mut bitcoin::key::rand::thread_rng())
+    }
+
+    /// Preview the transaction that would be created from `params`, without changing the wallet.
+    ///
+    /// Uses a provided random number generator (rng). See [`Wallet::preview_tx`].
+    pub fn preview_tx_with_aux_rand(
+        &self,
+        params: TxParams,
+        rng: &mut impl RngCore,
+    ) -> Result<TxPreview, CreateTxError> {
+        let draft = self.draft_tx(DefaultCoinSelectionAlgorithm::default(), params, rng)?;
+        let tx = &draft.psbt.unsigned_tx;
+
+        let input_amount: Amount = draft.selected.iter().map(|u| u.txout().value).sum();
+        let output_amount: Amount = tx.output.iter().map(|txout| txout.value).sum();
+        let fee = input_amount - output_amount;
+        Ok(TxPreview {
+            fee,
+            fee_rate: fee / draft.estimated_weight,
+            bump_fee: draft.bump_fee,
+            package_fee_rate: (fee + draft.ancestors.absolute)
+                / (draft.estimated_weight + draft.ancestors.weight),
+            selected: draft.selected,
+            change: draft.change,
+            vsize: draft.estimated_weight.to_vbytes_ceil(),
+            diagnostics: draft.diagnostics,
+        })
+    }
+
+    /// Compute the maximum amount that can be sent to `script_pubkey` at `fee_rate`.
+    ///
+    /// This is exactly the amount the drain output of a transaction built with
+    /// [`TxBuilder::drain_wallet`] and [`TxBuilder::drain_to`] would receive. The other `options`
+    /// are taken into account as they would be by the [`TxBuilder`]: the change spend policy,
+    /// the unspendable outpoints (including the ones excluded with
+    /// [`TxBuilder::exclude_below_confirmations`]), the policy paths, and so on. Locked and
+    /// reserved outpoints are never spent. Any recipient in `options` is paid first.
+    ///
+    /// Returns [`CreateTxError::CoinSelection`] if the wallet can't afford a non-dust output, or
+    /// zero if it can only afford the recipients in `options`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+    /// let fee_rate = FeeRate::from_sat_per_vb(5).unwrap();
+    /// let mut options = TxParams::new();
+    /// options.do_not_spend_change();
+    /// let max = wallet.max_sendable(to_address.script_pubkey(), fee_rate, options.clone())?;
+    ///
+    /// let mut builder = wallet.build_tx_from_params(options);
+    /// builder
+    ///     .drain_wallet()
+    ///     .drain_to(to_address.script_pubkey())
+    ///     .fee_rate(fee_rate);
+    /// let psbt = builder.finish()?;
+    /// assert!(psbt.unsigned_tx.output.iter().any(|txout| txout.value == max));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn max_sendable(
+        &self,
+        script_pubkey: ScriptBuf,
+        fee_rate: FeeRate,
+        mut options: TxParams,
+    ) -> Result<Amount, CreateTxError> {
+        options
+            .drain_wallet()
+            .drain_to(script_pubkey)
+            .fee_rate(fee_rate);
+        let preview = self.preview_tx(options)?;
+        // Without a drain output, whatever is left after paying the recipients goes to the fee.
+        Ok(preview.change.unwrap_or(Amount::ZERO))
+    }
+
     pub(crate) fn create_tx<Cs: coin_selection::CoinSelectionAlgorithm>(
         &mut self,
         coin_selection: Cs,
         params: TxParams,
         rng: &mut impl RngCore,
     ) -> Result<Psbt, CreateTxError> {
+        let reservation = params.reservation.clone();
+        let DraftTx {
+            psbt,
+            change_index,
+            silent_payments_sent,
+            ..
+        } = self.draft_tx(coin_selection, params, rng)?;
+
+        // Recording changes to the change keychain.
+        if let Some((keychain, index)) = change_index {
+            self.use_change_spk(keychain, index);
+        }
+
+        // Remember the silent payment outputs, to derive them again if the tx is replaced.
+        if !silent_payments_sent.is_empty() {
+            self.silent_payments
+                .sent
+                .extend(silent_payments_sent.clone());
+            self.stage.merge(
+                silent_payments::ChangeSet {
+                    sent: silent_payments_sent,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        // Reserve the spent wallet outputs for this draft.
+        if let Some(reservation) = reservation {
+            let outpoints = psbt
+                .unsigned_tx
+                .input
+                .iter()
+                .map(|txin| txin.previous_output)
+                .filter(|op| self.indexed_graph.index.txout(*op).is_some())
+                .collect::<Vec<_>>();
+            self.reserve_outpoints(outpoints, reservation);
+        }
+
+        Ok(psbt)
+    }
+
+    /// The script pubkey to send change to in `keychain`, without revealing it.
+    ///
+    /// This is the first unused script pubkey of the internal keychain. Change sent to the
+    /// external keychain always gets a new address, the unused ones may have been given out.
+    fn peek_change_spk(&self, keychain: KeychainKind) -> (KeychainKind, u32, ScriptBuf) {
+        let change_keychain = self.map_keychain(keychain);
+        let unused = if change_keychain == self.map_keychain(KeychainKind::Internal) {
+            self.indexed_graph
+                .index
+                .unused_keychain_spks(change_keychain)
+                .next()
+        } else {
+            None
+        };
+        let (index, spk) = unused.unwrap_or_else(|| {
+            let (next_index, _) = self
+                .indexed_graph
+                .index
+                .next_index(change_keychain)
+                .expect("keychain must exist");
+            let spk = self
+                .peek_address(change_keychain, next_index)
+                .script_pubkey();
+            (next_index, spk)
+        });
+        (change_keychain, index, spk)
+    }
+
+    /// Reveal the change script pubkey at `index` of `keychain`, if it isn't yet, and mark it
+    /// used.
+    fn use_change_spk(&mut self, keychain: KeychainKind, index: u32) {
+        if let Some((_, index_changeset)) =
+            self.indexed_graph.index.reveal_to_target(keychain, index)
+        {
+            self.stage.merge(index_changeset.into());
+            self.mark_used(keychain, index);
+        }
+    }
+
+    /// Build the transaction described by `params` without changing the wallet.
+    fn draft_tx<Cs: coin_selection::CoinSelectionAlgorithm>(
+        &self,
+        coin_selection: Cs,
+        params: TxParams,
+        rng: &mut impl RngCore,
+    ) -> Result<DraftTx, CreateTxError> {
         let keychains: BTreeMap<_, _> = self.indexed_graph.index.keychains().collect();
         let external_descriptor = keychains.get(&KeychainKind::External).expect("must exist");
         let internal_descriptor = keychains.get(&KeychainKind::Internal);
@@ -1526,6 +2109,14 @@ impl Wallet {
             }
         };
 
+        // When paying for unconfirmed ancestors, the child must also make up for the difference
+        // between what the ancestors paid and what they would pay at the target feerate.
+        if let Some(package_fee) = params.package_fee {
+            fee_amount += (fee_rate * package_fee.weight)
+                .checked_sub(package_fee.absolute)
+                .unwrap_or_default();
+        }
+
         let mut tx = Transaction {
             version,
             lock_time,
@@ -1541,7 +2132,13 @@ impl Wallet {
         let recipients = params.recipients.iter().map(|(r, v)| (r, *v));
 
         for (index, (script_pubkey, value)) in recipients.enumerate() {
-            if !params.allow_dust && value.is_dust(script_pubkey) && !script_pubkey.is_op_return() {
+            // Zero-value (ephemeral) anchors are allowed in TRUC transactions.
+            let is_ephemeral_anchor = version == tx_builder::TRUC_VERSION && is_p2a(script_pubkey);
+            if !params.allow_dust
+                && value.is_dust(script_pubkey)
+                && !script_pubkey.is_op_return()
+                && !is_ephemeral_anchor
+            {
                 return Err(CreateTxError::OutputBelowDustLimit(index));
             }
 
@@ -1555,12 +2152,66 @@ impl Wallet {
             outgoing += value;
         }
 
+        // Silent payment outputs can only be derived once the inputs are known, pay the spend key
+        // until then.
+        let silent_payments_start = tx.output.len();
+        for (address, value) in &params.silent_payment_recipients {
+            if !address.is_valid_for_network(self.network()) {
+                return Err(SilentPaymentError::NetworkMismatch(self.network()).into());
+            }
+            let script_pubkey = address.placeholder_script_pubkey();
+            if !params.allow_dust && value.is_dust(&script_pubkey) {
+                return Err(CreateTxError::OutputBelowDustLimit(tx.output.len()));
+            }
+            tx.output.push(TxOut {
+                script_pubkey,
+                value: *value,
+            });
+            outgoing += *value;
+        }
+
         fee_amount += fee_rate * tx.weight();
 
+        let subtract_fee_from: BTreeSet<usize> = params.subtract_fee_from.iter().copied().collect();
+        if let Some(&index) = subtract_fee_from.iter().find(|&&i| i >= tx.output.len()) {
+            return Err(CreateTxError::InvalidSubtractFeeIndex(index));
+        }
+
+        let truc_parent;
+        let mut excluded;
         let (required_utxos, optional_utxos) = {
             // NOTE: manual selection overrides unspendable
             let mut required: Vec<WeightedUtxo> = params.utxos.clone();
-            let optional = self.filter_utxos(&params, current_height.to_consensus_u32());
+            let mut optional;
+            (optional, excluded) = self.classify_utxos(&params, current_height.to_consensus_u32());
+            if !params.silent_payment_recipients.is_empty() {
+                optional.retain(|u| {
+                    let eligible = match &u.utxo {
+                        Utxo::Local(local) => self.silent_payment_input_key(local).is_some(),
+                        Utxo::Foreign { .. } => false,
+                    };
+                    if !eligible && params.coin_selection_context.diagnostics {
+                        excluded
+                            .push((u.utxo.outpoint(), ExclusionReason::SilentPaymentIneligible));
+                    }
+                    eligible
+                });
+            }
+            if params.coin_selection_context.diagnostics {
+                let candidates = optional
+                    .iter()
+                    .map(|u| u.utxo.outpoint())
+                    .collect::<Vec<_>>();
+                truc_parent = self.check_truc_topology(version, &required, &mut optional)?;
+                excluded.extend(
+                    candidates
+                        .into_iter()
+                        .filter(|op| !optional.iter().any(|u| u.utxo.outpoint() == *op))
+                        .map(|op| (op, ExclusionReason::TrucTopology)),
+                );
+            } else {
+                truc_parent = self.check_truc_topology(version, &required, &mut optional)?;
+            }
 
             // If `drain_wallet` is true, all UTxOs are required.
             if params.drain_wallet {
@@ -1571,44 +2222,133 @@ impl Wallet {
             }
         };
 
+        // BIP125 requires the replacement to pay at least the absolute fee of the replaced
+        // transactions plus the incremental relay fee for its own size. All the original inputs
+        // are required, so their weight is a lower bound of the weight of the replacement.
+        if let (Some(previous_fee), FeePolicy::FeeRate(_)) =
+            (params.bumping_fee, params.fee_policy.unwrap_or_default())
+        {
+            let min_weight = tx.weight()
+                + required_utxos
+                    .iter()
+                    .map(|u| TxIn::default().segwit_weight() + u.satisfaction_weight)
+                    .sum();
+            let required_fee = previous_fee.absolute + FeeRate::BROADCAST_MIN * min_weight;
+            fee_amount += required_fee
+                .checked_sub(fee_rate * min_weight)
+                .unwrap_or_default();
+        }
+
         // Get drain script.
         let mut drain_index = Option::<(KeychainKind, u32)>::None;
         let drain_script = match params.drain_to {
             Some(ref drain_recipient) => drain_recipient.clone(),
             None => {
-                let change_keychain = self.map_keychain(KeychainKind::Internal);
-                let (index, spk) = self
-                    .indexed_graph
-                    .index
-                    .unused_keychain_spks(change_keychain)
-                    .next()
-                    .unwrap_or_else(|| {
-                        let (next_index, _) = self
-                            .indexed_graph
-                            .index
-                            .next_index(change_keychain)
-                            .expect("keychain must exist");
-                        let spk = self
-                            .peek_address(change_keychain, next_index)
-                            .script_pubkey();
-                        (next_index, spk)
-                    });
+                let change_keychain = if params.avoid_mixed_script_types {
+                    self.change_keychain_for(&params.recipients)
+                } else {
+                    KeychainKind::Internal
+                };
+                let (change_keychain, index, spk) = self.peek_change_spk(change_keychain);
                 drain_index = Some((change_keychain, index));
                 spk
             }
         };
 
-        let coin_selection = coin_selection
-            .coin_select(
-                required_utxos,
-                optional_utxos,
-                fee_rate,
-                outgoing + fee_amount,
-                &drain_script,
-                rng,
-            )
+        // Keep track of the input weights to estimate the weight of the signed transaction.
+        let satisfaction_weights: HashMap<OutPoint, Weight> = required_utxos
+            .iter()
+            .chain(&optional_utxos)
+            .map(|u| (u.utxo.outpoint(), u.satisfaction_weight))
+            .collect();
+
+        // When the recipients pay for the fee, coins are only selected for the amounts sent.
+        let selection_fee_rate = if subtract_fee_from.is_empty() {
+            fee_rate
+        } else {
+            FeeRate::ZERO
+        };
+
+        // Unconfirmed inputs must also make up for their ancestors paying less than `fee_rate`,
+        // unless we are already paying for them (CPFP). The bump fee depends on the selected
+        // inputs, so select again with the bump fee added to the target until it is covered.
+        let mut bump_fee = Amount::ZERO;
+        let (coin_selection, ancestors) = loop {
+            let selection_target = if subtract_fee_from.is_empty() {
+                outgoing + fee_amount + bump_fee
+            } else {
+                outgoing
+            };
+            let coin_selection = if params.avoid_mixed_script_types {
+                select_coins(
+                    AvoidMixedScriptTypes::new(&coin_selection),
+                    &params.coin_selection_context,
+                    params.avoid_partial_spends,
+                    required_utxos.clone(),
+                    optional_utxos.clone(),
+                    selection_fee_rate,
+                    selection_target,
+                    &drain_script,
+                    rng,
+                )
+            } else {
+                select_coins(
+                    &coin_selection,
+                    &params.coin_selection_context,
+                    params.avoid_partial_spends,
+                    required_utxos.clone(),
+                    optional_utxos.clone(),
+                    selection_fee_rate,
+                    selection_target,
+                    &drain_script,
+                    rng,
+                )
+            };
+            let coin_selection = if params.coin_selection_context.diagnostics {
+                let diagnostics = SelectionDiagnostics {
+                    excluded: excluded.clone(),
+                    timelock_not_met: self.timelock_not_met(
+                        &params,
+                        current_height.to_consensus_u32(),
+                        required_utxos.iter().chain(&optional_utxos),
+                    ),
+                    ..Default::default()
+                };
+                with_diagnostics(coin_selection, Some(diagnostics))
+            } else {
+                coin_selection
+            }
             .map_err(CreateTxError::CoinSelection)?;
 
+            let ancestors = match params.package_fee {
+                Some(package_fee) => package_fee,
+                None => self.unconfirmed_ancestor_package(
+                    coin_selection.selected.iter().map(|u| u.outpoint().txid),
+                    fee_rate,
+                ),
+            };
+            let required_bump_fee = match params.package_fee {
+                Some(_) => Amount::ZERO,
+                None => (fee_rate * ancestors.weight)
+                    .checked_sub(ancestors.absolute)
+                    .unwrap_or_default(),
+            };
+            if !subtract_fee_from.is_empty() {
+                bump_fee = required_bump_fee;
+            }
+            if required_bump_fee <= bump_fee {
+                break (coin_selection, ancestors);
+            }
+            bump_fee = required_bump_fee;
+        };
+        // When building a CPFP the fee for the ancestors is already part of `fee_amount`.
+        let ancestors_bump_fee = match params.package_fee {
+            Some(package_fee) => (fee_rate * package_fee.weight)
+                .checked_sub(package_fee.absolute)
+                .unwrap_or_default(),
+            None => bump_fee,
+        };
+
         let excess = &coin_selection.excess;
         tx.input = coin_selection
             .selected
@@ -1621,15 +2361,54 @@ impl Wallet {
             })
             .collect();
 
+        // Discourage fee sniping following BIP326, unless a timelock was explicitly requested
+        // or is required by the descriptors.
+        if let (true, None, None, absolute::LockTime::Blocks(height)) = (
+            params.anti_fee_sniping,
+            params.locktime,
+            requirements.timelock,
+            current_height,
+        ) {
+            // A relative timelock can only be used when every input is a confirmed taproot
+            // output of ours whose depth fits in a nSequence.
+            let input_depths = (params.sequence.is_none()
+                && requirements.csv.is_none()
+                && version >= transaction::Version::TWO)
+                .then(|| {
+                    coin_selection
+                        .selected
+                        .iter()
+                        .map(|utxo| match utxo {
+                            Utxo::Local(local) if local.txout.script_pubkey.is_p2tr() => {
+                                let conf_height =
+                                    local.chain_position.confirmation_height_upper_bound()?;
+                                let depth = height.to_consensus_u32().checked_sub(conf_height)?;
+                                u16::try_from(depth + 1).ok()
+                            }
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<u16>>>()
+                })
+                .flatten();
+            discourage_fee_sniping(&mut tx, height, input_depths.as_deref(), rng);
+        }
+
         if tx.output.is_empty() {
             // Uh oh, our transaction has no outputs.
             // We allow this when:
             // - We have a drain_to address and the utxos we must spend (this happens,
             // for example, when we RBF).
             // - We have a drain_to address and drain_wallet set.
+            // - We are paying for unconfirmed ancestors (CPFP), or replacing transactions
+            // without paying anyone (cancelling), in which case the funds go back to our change
+            // address.
             // Otherwise, we don't know who we should send the funds to, and how much
             // we should send!
-            if params.drain_to.is_some() && (params.drain_wallet || !params.utxos.is_empty()) {
+            if (params.drain_to.is_some()
+                || params.package_fee.is_some()
+                || params.bumping_fee.is_some())
+                && (params.drain_wallet || !params.utxos.is_empty())
+            {
                 if let Excess::NoChange {
                     dust_threshold,
                     remaining_amount,
@@ -1641,6 +2420,7 @@ impl Wallet {
                         available: remaining_amount
                             .checked_sub(*change_fee)
                             .unwrap_or_default(),
+                        diagnostics: coin_selection.diagnostics.clone(),
                     }));
                 }
             } else {
@@ -1662,22 +2442,121 @@ impl Wallet {
             tx.output.push(drain_output);
         }
 
-        // Sort inputs/outputs according to the chosen algorithm.
-        params.ordering.sort_tx_with_aux_rand(&mut tx, rng);
-
-        let psbt = self.complete_transaction(tx, coin_selection.selected, params)?;
+        if !subtract_fee_from.is_empty() {
+            // Same fee as coin selection would have accounted for.
+            let input_weight: Weight = tx
+                .input
+                .iter()
+                .map(|txin| {
+                    TxIn::default().segwit_weight() + satisfaction_weights[&txin.previous_output]
+                })
+                .sum();
+            let drain_weight = match excess {
+                Excess::Change { .. } => tx.output.last().expect("drain output").weight(),
+                Excess::NoChange { .. } => Weight::ZERO,
+            };
+            let fee = fee_amount + bump_fee + fee_rate * (input_weight + drain_weight);
+
+            let available: Amount = subtract_fee_from.iter().map(|&i| tx.output[i].value).sum();
+            if available < fee {
+                return Err(CreateTxError::CoinSelection(InsufficientFunds {
+                    needed: fee,
+                    available,
+                    diagnostics: coin_selection.diagnostics.clone(),
+                }));
+            }
+            let count = subtract_fee_from.len() as u64;
+            let share = fee / count;
+            let remainder = fee - share * count;
+            for (n, &index) in subtract_fee_from.iter().enumerate() {
+                let deducted = if n == 0 { share + remainder } else { share };
+                let txout = &mut tx.output[index];
+                txout.value = txout
+                    .value
+                    .checked_sub(deducted)
+                    .ok_or(CreateTxError::OutputBelowDustLimit(index))?;
+                if !params.allow_dust && txout.value.is_dust(&txout.script_pubkey) {
+                    return Err(CreateTxError::OutputBelowDustLimit(index));
+                }
+            }
+        }
 
-        // Recording changes to the change keychain.
-        if let (Excess::Change { .. }, Some((keychain, index))) = (excess, drain_index) {
-            if let Some((_, index_changeset)) =
-                self.indexed_graph.index.reveal_to_target(keychain, index)
+        let mut silent_payments_sent = BTreeMap::new();
+        if !params.silent_payment_recipients.is_empty() {
+            let input_keys = coin_selection
+                .selected
+                .iter()
+                .map(|utxo| {
+                    match utxo {
+                        Utxo::Local(local) => self.silent_payment_input_key(local),
+                        Utxo::Foreign { .. } => None,
+                    }
+                    .ok_or(SilentPaymentError::IneligibleInput(utxo.outpoint()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let recipients: Vec<_> = params
+                .silent_payment_recipients
+                .iter()
+                .map(|(address, _)| *address)
+                .collect();
+            let scripts = derive_output_scripts(&self.secp, &input_keys, &recipients)?;
+            for ((txout, script_pubkey), address) in tx.output[silent_payments_start..]
+                .iter_mut()
+                .zip(scripts)
+                .zip(recipients)
             {
-                self.stage.merge(index_changeset.into());
-                self.mark_used(keychain, index);
+                txout.script_pubkey = script_pubkey.clone();
+                silent_payments_sent.insert(script_pubkey, address);
             }
         }
 
-        Ok(psbt)
+        // Account for the segwit marker and flag as well as the satisfaction of every input.
+        let estimated_weight = tx.weight()
+            + Weight::from_wu(2)
+            + tx.input
+                .iter()
+                .map(|txin| satisfaction_weights[&txin.previous_output])
+                .sum::<Weight>();
+
+        if version == tx_builder::TRUC_VERSION {
+            let max = if truc_parent.is_some() {
+                tx_builder::TRUC_CHILD_MAX_VSIZE
+            } else {
+                tx_builder::TRUC_MAX_VSIZE
+            };
+            let vsize = estimated_weight.to_vbytes_ceil();
+            if vsize > max {
+                return Err(TrucError::TooLarge { vsize, max }.into());
+            }
+        }
+
+        // Sort inputs/outputs according to the chosen algorithm.
+        params.ordering.sort_tx_with_aux_rand(&mut tx, rng);
+
+        let (change, change_index) = match excess {
+            Excess::Change { amount, .. } => (Some(*amount), drain_index),
+            Excess::NoChange { .. } => (None, None),
+        };
+        let selected = coin_selection.selected.clone();
+        let diagnostics = coin_selection.diagnostics.clone();
+        let allow_non_standard = params.allow_non_standard;
+        let data_carrier_policy = params.data_carrier_policy;
+        let psbt = self.complete_transaction(tx, coin_selection.selected, params)?;
+        if !allow_non_standard {
+            check_standardness(&psbt, estimated_weight, &data_carrier_policy)?;
+        }
+
+        Ok(DraftTx {
+            psbt,
+            selected,
+            change,
+            change_index,
+            estimated_weight,
+            ancestors,
+            bump_fee: ancestors_bump_fee,
+            diagnostics,
+            silent_payments_sent,
+        })
     }
 
     /// Bump the fee of a transaction previously created with this wallet.
@@ -1720,10 +2599,37 @@ impl Wallet {
     /// // broadcast fee_bumped_tx to replace original
     /// # Ok::<(), anyhow::Error>(())
     /// ```
-    // TODO: support for merging multiple transactions while bumping the fees
     pub fn build_fee_bump(
         &mut self,
         txid: Txid,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildFeeBumpError> {
+        self.build_fee_bump_many(&[txid])
+    }
+
+    /// Replace several transactions previously created with this wallet with a single one paying
+    /// a higher fee.
+    ///
+    /// This works like [`build_fee_bump`], but the returned [`TxBuilder`] is pre-populated with
+    /// the inputs and recipient outputs of *all* the transactions in `txids`, so that they can be
+    /// batched together. The change outputs of the original transactions are removed, a single new
+    /// change output is added if needed when finishing the builder.
+    ///
+    /// Following BIP125, the replacement pays at least the sum of the absolute fees of the
+    /// replaced transactions plus the incremental relay fee for its own size, and its feerate
+    /// must be higher than the feerate of every replaced transaction.
+    ///
+    /// The outputs of a replaced transaction that are spent by another one of them are removed
+    /// as well, whoever they pay. The outputs paying silent payment addresses are derived again
+    /// from the inputs of the replacement.
+    ///
+    /// Returns an error if `txids` is empty or lists a transaction twice, if any of the
+    /// transactions is already confirmed or doesn't explicitly signal *replace by fee* (RBF), or
+    /// if TRUC and non-TRUC transactions are mixed.
+    ///
+    /// [`build_fee_bump`]: Self::build_fee_bump
+    pub fn build_fee_bump_many(
+        &mut self,
+        txids: &[Txid],
     ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildFeeBumpError> {
         let tx_graph = self.indexed_graph.graph();
         let txout_index = &self.indexed_graph.index;
@@ -1733,53 +2639,96 @@ impl Wallet {
             .map(|canon_tx| (canon_tx.tx_node.txid, canon_tx.chain_position))
             .collect();
 
-        let mut tx = tx_graph
-            .get_tx(txid)
-            .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
-            .as_ref()
-            .clone();
+        if txids.is_empty() {
+            return Err(BuildFeeBumpError::NoTransactions);
+        }
+        let mut visited = HashSet::<Txid>::new();
+        if let Some(&txid) = txids.iter().find(|&&txid| !visited.insert(txid)) {
+            return Err(BuildFeeBumpError::DuplicateTransaction(txid));
+        }
 
-        if chain_positions
-            .get(&txid)
-            .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
-            .is_confirmed()
-        {
-            return Err(BuildFeeBumpError::TransactionConfirmed(txid));
+        let mut txs = Vec::with_capacity(txids.len());
+        for &txid in txids {
+            let tx = tx_graph
+                .get_tx(txid)
+                .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
+                .as_ref()
+                .clone();
+
+            if chain_positions
+                .get(&txid)
+                .ok_or(BuildFeeBumpError::TransactionNotFound(txid))?
+                .is_confirmed()
+            {
+                return Err(BuildFeeBumpError::TransactionConfirmed(txid));
+            }
+
+            // TRUC transactions are always replaceable.
+            if tx.version != tx_builder::TRUC_VERSION
+                && !tx
+                    .input
+                    .iter()
+                    .any(|txin| txin.sequence.to_consensus_u32() <= 0xFFFFFFFD)
+            {
+                return Err(BuildFeeBumpError::IrreplaceableTransaction(
+                    tx.compute_txid(),
+                ));
+            }
+
+            txs.push(tx);
         }
 
-        if !tx
-            .input
-            .iter()
-            .any(|txin| txin.sequence.to_consensus_u32() <= 0xFFFFFFFD)
-        {
-            return Err(BuildFeeBumpError::IrreplaceableTransaction(
-                tx.compute_txid(),
-            ));
+        // A TRUC transaction can't be merged with a non-TRUC one, the replacement couldn't follow
+        // the topology rules of one without breaking the expectations of the other.
+        let is_truc = |tx: &Transaction| tx.version == tx_builder::TRUC_VERSION;
+        if txs.iter().any(is_truc) && !txs.iter().all(is_truc) {
+            return Err(BuildFeeBumpError::MixedTrucVersions);
         }
 
-        let fee = self
-            .calculate_fee(&tx)
-            .map_err(|_| BuildFeeBumpError::FeeRateUnavailable)?;
-        let fee_rate = fee / tx.weight();
+        // The outputs of a replaced tx spent by another one go away with it.
+        let spent_by_replaced: HashSet<OutPoint> = txs
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+            .filter(|outpoint| visited.contains(&outpoint.txid))
+            .collect();
 
-        // Remove the inputs from the tx and process them.
-        let utxos: Vec<WeightedUtxo> = tx
-            .input
-            .drain(..)
-            .map(|txin| -> Result<_, BuildFeeBumpError> {
+        let mut version = None;
+        let mut previous_fee = tx_builder::PreviousFee {
+            absolute: Amount::ZERO,
+            rate: FeeRate::ZERO,
+        };
+        let mut utxos = Vec::<WeightedUtxo>::new();
+        let mut recipients = Vec::<(ScriptBuf, Amount)>::new();
+        let mut silent_payment_recipients = Vec::<(SilentPaymentAddress, Amount)>::new();
+
+        for mut tx in txs {
+            let txid = tx.compute_txid();
+            let fee = self
+                .calculate_fee(&tx)
+                .map_err(|_| BuildFeeBumpError::FeeRateUnavailable)?;
+            previous_fee.absolute += fee;
+            previous_fee.rate = previous_fee.rate.max(fee / tx.weight());
+            version = version.max(Some(tx.version));
+
+            // Remove the inputs from the tx and process them. Inputs spending the outputs of
+            // another replaced tx are dropped, since that tx goes away as well.
+            for txin in tx.input.drain(..) {
                 let outpoint = txin.previous_output;
+                if visited.contains(&outpoint.txid) {
+                    continue;
+                }
                 let prev_txout = tx_graph
                     .get_txout(outpoint)
                     .cloned()
                     .ok_or(BuildFeeBumpError::UnknownUtxo(outpoint))?;
-                match txout_index.index_of_spk(prev_txout.script_pubkey.clone()) {
+                let utxo = match txout_index.index_of_spk(prev_txout.script_pubkey.clone()) {
                     Some(&(keychain, derivation_index)) => {
                         let txout = prev_txout;
                         let chain_position = chain_positions
                             .get(&outpoint.txid)
                             .cloned()
                             .ok_or(BuildFeeBumpError::TransactionNotFound(outpoint.txid))?;
-                        Ok(WeightedUtxo {
+                        WeightedUtxo {
                             satisfaction_weight: self
                                 .public_descriptor(keychain)
                                 .max_weight_to_satisfy()
@@ -1792,9 +2741,9 @@ impl Wallet {
                                 derivation_index,
                                 chain_position,
                             }),
-                        })
+                        }
                     }
-                    None => Ok(WeightedUtxo {
+                    None => WeightedUtxo {
                         satisfaction_weight: Weight::from_wu_usize(
                             serialize(&txin.script_sig).len() * 4 + serialize(&txin.witness).len(),
                         ),
@@ -1812,40 +2761,226 @@ impl Wallet {
                                 ..Default::default()
                             }),
                         },
-                    }),
-                }
-            })
-            .collect::<Result<_, _>>()?;
+                    },
+                };
+                utxos.push(utxo);
+            }
 
-        if tx.output.len() > 1 {
             let mut change_index = None;
-            for (index, txout) in tx.output.iter().enumerate() {
-                let change_keychain = self.map_keychain(KeychainKind::Internal);
-                match txout_index.index_of_spk(txout.script_pubkey.clone()) {
-                    Some((keychain, _)) if *keychain == change_keychain => {
-                        change_index = Some(index)
+            if tx.output.len() > 1 {
+                for (index, txout) in tx.output.iter().enumerate() {
+                    let change_keychain = self.map_keychain(KeychainKind::Internal);
+                    match txout_index.index_of_spk(txout.script_pubkey.clone()) {
+                        Some((keychain, _)) if *keychain == change_keychain => {
+                            change_index = Some(index)
+                        }
+                        _ => {}
                     }
-                    _ => {}
                 }
             }
 
-            if let Some(change_index) = change_index {
-                tx.output.remove(change_index);
+            // Silent payment outputs are derived from the inputs, they are derived again for
+            // the inputs of the replacement.
+            for (index, txout) in tx.output.into_iter().enumerate() {
+                if Some(index) == change_index
+                    || spent_by_replaced.contains(&OutPoint::new(txid, index as u32))
+                {
+                    continue;
+                }
+                match self.silent_payments.sent.get(&txout.script_pubkey) {
+                    Some(&address) => silent_payment_recipients.push((address, txout.value)),
+                    None => recipients.push((txout.script_pubkey, txout.value)),
+                }
             }
         }
 
         let params = TxParams {
-            version: Some(tx.version),
-            recipients: tx
-                .output
-                .into_iter()
-                .map(|txout| (txout.script_pubkey, txout.value))
-                .collect(),
+            version,
+            recipients,
+            silent_payment_recipients,
+            utxos,
+            bumping_fee: Some(previous_fee),
+            ..Default::default()
+        };
+
+        Ok(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
+
+    /// Cancel a transaction previously created with this wallet by double-spending it back to
+    /// the wallet.
+    ///
+    /// The returned [`TxBuilder`] spends every input of the original transaction that is owned by
+    /// the wallet and sends everything, minus the fee, to an unused internal address. The fee
+    /// rules of [`build_fee_bump`] apply, so that the cancellation can replace the original
+    /// transaction in the mempool. The fee rate defaults to the lowest one accepted for the
+    /// replacement, i.e. the fee rate of the original transaction plus 1 sat/vb.
+    ///
+    /// Unlike [`cancel_tx`], which only frees the change addresses of the transaction locally, the
+    /// resulting transaction must be signed and broadcast to take effect.
+    ///
+    /// Returns an error if the transaction is already confirmed, doesn't explicitly signal
+    /// *replace by fee* (RBF) or doesn't spend any output owned by the wallet.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let txid: Txid = todo!();
+    /// let mut psbt = wallet.build_cancel_tx(txid)?.finish()?;
+    /// wallet.sign(&mut psbt, SignOptions::default())?;
+    /// let cancel_tx = psbt.extract_tx()?;
+    /// // broadcast cancel_tx to replace the original
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`build_fee_bump`]: Self::build_fee_bump
+    /// [`cancel_tx`]: Self::cancel_tx
+    pub fn build_cancel_tx(
+        &mut self,
+        txid: Txid,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildFeeBumpError> {
+        let TxBuilder { mut params, .. } = self.build_fee_bump(txid)?;
+
+        params
+            .utxos
+            .retain(|wutxo| matches!(wutxo.utxo, Utxo::Local(_)));
+        if params.utxos.is_empty() {
+            return Err(BuildFeeBumpError::NoLocalInputs(txid));
+        }
+
+        let previous_fee = params
+            .bumping_fee
+            .expect("fee bump must set the previous fee");
+        params.fee_policy = Some(FeePolicy::FeeRate(FeeRate::from_sat_per_kwu(
+            previous_fee.rate.to_sat_per_kwu() + FeeRate::BROADCAST_MIN.to_sat_per_kwu(),
+        )));
+
+        // Without recipients nor `drain_to`, everything goes to a change address when finishing.
+        params.recipients.clear();
+
+        Ok(TxBuilder {
+            wallet: self,
+            params,
+            coin_selection: DefaultCoinSelectionAlgorithm::default(),
+        })
+    }
+
+    /// Pay for one or more unconfirmed transactions by spending their outputs (child pays for
+    /// parent).
+    ///
+    /// This is useful when a transaction can't be replaced, for instance because it was sent to us
+    /// by someone else. The returned [`TxBuilder`] spends every unspent output of `parent_txids`
+    /// owned by the wallet, as well as their pay-to-anchor (P2A) outputs (see
+    /// [`list_unspent_anchors`]), and sends the funds back to a change address. The fee of the
+    /// child is sized so that the package formed by the child and all of its unconfirmed
+    /// ancestors reaches `target_package_feerate`. More wallet UTXOs are added by coin selection
+    /// if the parents' outputs aren't enough to pay for the package.
+    ///
+    /// The package feerate the child will reach is the [`TxPreview::package_fee_rate`] of
+    /// [`preview_tx`] called with the [`TxBuilder::params`] of the returned builder. Once the child
+    /// is signed, it can also be checked with [`calculate_package_fee_rate`].
+    ///
+    /// Returns an error if any of the parents is unknown, already confirmed or has neither an
+    /// unspent output owned by the wallet nor an unspent anchor.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let mut wallet = doctest_wallet!();
+    /// # let parent_txid: Txid = todo!();
+    /// let params = {
+    ///     let builder = wallet.build_cpfp(&[parent_txid], FeeRate::from_sat_per_vb(10).unwrap())?;
+    ///     builder.params().clone()
+    /// };
+    /// let package_feerate = wallet.preview_tx(params.clone())?.package_fee_rate;
+    /// let mut psbt = wallet.build_tx_from_params(params).finish()?;
+    /// wallet.sign(&mut psbt, SignOptions::default())?;
+    /// let child = psbt.extract_tx()?;
+    /// assert!(wallet.calculate_package_fee_rate(&child)? >= package_feerate);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`calculate_package_fee_rate`]: Self::calculate_package_fee_rate
+    /// [`preview_tx`]: Self::preview_tx
+    /// [`list_unspent_anchors`]: Self::list_unspent_anchors
+    pub fn build_cpfp(
+        &mut self,
+        parent_txids: &[Txid],
+        target_package_feerate: FeeRate,
+    ) -> Result<TxBuilder<'_, DefaultCoinSelectionAlgorithm>, BuildCpfpError> {
+        let chain_tip = self.chain.tip().block_id();
+        let chain_positions: HashMap<Txid, ChainPosition<_>> = self
+            .indexed_graph
+            .graph()
+            .list_canonical_txs(&self.chain, chain_tip, CanonicalizationParams::default())
+            .map(|canon_tx| (canon_tx.tx_node.txid, canon_tx.chain_position))
+            .collect();
+
+        for &txid in parent_txids {
+            if chain_positions
+                .get(&txid)
+                .ok_or(BuildCpfpError::TransactionNotFound(txid))?
+                .is_confirmed()
+            {
+                return Err(BuildCpfpError::TransactionConfirmed(txid));
+            }
+        }
+
+        let mut utxos: Vec<WeightedUtxo> = self
+            .list_unspent()
+            .filter(|output| parent_txids.contains(&output.outpoint.txid))
+            .filter(|output| !self.is_outpoint_locked(output.outpoint))
+            .map(|output| WeightedUtxo {
+                satisfaction_weight: self
+                    .public_descriptor(output.keychain)
+                    .max_weight_to_satisfy()
+                    .expect("descriptor should be satisfiable"),
+                utxo: Utxo::Local(output),
+            })
+            .collect();
+        // The parents may also be bumped through their anchors.
+        utxos.extend(
+            self.list_unspent_anchors()
+                .filter(|anchor| parent_txids.contains(&anchor.utxo.outpoint().txid)),
+        );
+        if let Some(&txid) = parent_txids
+            .iter()
+            .find(|txid| !utxos.iter().any(|u| u.utxo.outpoint().txid == **txid))
+        {
+            return Err(BuildCpfpError::NoSpendableOutputs(txid));
+        }
+
+        let mut package_fee = tx_builder::PackageFee {
+            absolute: Amount::ZERO,
+            weight: Weight::ZERO,
+        };
+        for ancestor in self.unconfirmed_ancestors(parent_txids.iter().copied()) {
+            package_fee.absolute += self
+                .calculate_fee(&ancestor)
+                .map_err(|_| BuildCpfpError::FeeRateUnavailable)?;
+            package_fee.weight += ancestor.weight();
+        }
+
+        // Children of TRUC transactions must be TRUC transactions themselves.
+        let version = parent_txids
+            .iter()
+            .filter_map(|txid| self.indexed_graph.graph().get_tx(*txid))
+            .any(|tx| tx.version == tx_builder::TRUC_VERSION)
+            .then_some(tx_builder::TRUC_VERSION);
+
+        let params = TxParams {
             utxos,
-            bumping_fee: Some(tx_builder::PreviousFee {
-                absolute: fee,
-                rate: fee_rate,
-            }),
+            version,
+            fee_policy: Some(FeePolicy::FeeRate(target_package_feerate)),
+            package_fee: Some(package_fee),
             ..Default::default()
         };
 
@@ -1947,6 +3082,18 @@ impl Wallet {
         )
     }
 
+    /// The timelocks required to spend the outputs of `keychain` following `policy_path`.
+    pub(crate) fn spending_condition(
+        &self,
+        keychain: KeychainKind,
+        policy_path: Option<&BTreeMap<String, Vec<usize>>>,
+    ) -> Result<Condition, CreateTxError> {
+        let Some(policy) = self.policies(keychain)? else {
+            return Ok(Condition::default());
+        };
+        Ok(policy.get_condition(policy_path.unwrap_or(&BTreeMap::new()))?)
+    }
+
     /// Returns the descriptor used to create addresses for a particular `keychain`.
     ///
     /// It's the "public" version of the wallet's descriptor, meaning a new descriptor that has
@@ -1959,6 +3106,48 @@ impl Wallet {
             .expect("keychain must exist")
     }
 
+    /// Sign a PSBT version 2 with all the wallet's signers, like [`Wallet::sign`].
+    ///
+    /// The [`PsbtV2::tx_modifiable`] flags are updated from the sighash types of the new
+    /// signatures before the PSBT is finalized, so that inputs or outputs can't be added anymore
+    /// if that would invalidate them.
+    pub fn sign_v2(
+        &self,
+        psbt: &mut PsbtV2,
+        sign_options: SignOptions,
+    ) -> Result<bool, SignerError> {
+        let mut v0 = psbt.to_v0().map_err(SignerError::PsbtV2)?;
+        self.sign(
+            &mut v0,
+            SignOptions {
+                try_finalize: false,
+                ..sign_options.clone()
+            },
+        )?;
+        psbt.update_from_v0(v0);
+        psbt.update_tx_modifiable();
+
+        if sign_options.try_finalize {
+            self.finalize_psbt_v2(psbt, sign_options)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Finalize a PSBT version 2, like [`Wallet::finalize_psbt`].
+    ///
+    /// Returns `true` if the PSBT could be finalized, and `false` otherwise.
+    pub fn finalize_psbt_v2(
+        &self,
+        psbt: &mut PsbtV2,
+        sign_options: SignOptions,
+    ) -> Result<bool, SignerError> {
+        let mut v0 = psbt.to_v0().map_err(SignerError::PsbtV2)?;
+        let finalized = self.finalize_psbt(&mut v0, sign_options)?;
+        psbt.update_from_v0(v0);
+        Ok(finalized)
+    }
+
     /// Finalize a PSBT, i.e., for each input determine if sufficient data is available to pass
     /// validation and construct the respective `scriptSig` or `scriptWitness`. Please refer to
     /// [BIP174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki#Input_Finalizer),
@@ -2062,7 +3251,17 @@ impl Wallet {
                         Err(_) => finished = false,
                     }
                 }
-                None => finished = false,
+                // Silent payment outputs aren't derived from a descriptor.
+                None => {
+                    let length = psbt.inputs.len();
+                    let psbt_input = psbt
+                        .inputs
+                        .get_mut(n)
+                        .ok_or(IndexOutOfBoundsError::new(n, length))?;
+                    if !silent_payments::finalize_input(psbt_input) {
+                        finished = false;
+                    }
+                }
             }
         }
 
@@ -2100,8 +3299,11 @@ impl Wallet {
 
     /// Informs the wallet that you no longer intend to broadcast a tx that was built from it.
     ///
-    /// This frees up the change address used when creating the tx for use in future transactions.
-    // TODO: Make this free up reserved utxos when that's implemented
+    /// This frees up the change address used when creating the tx for use in future transactions,
+    /// as well as any reservation of the outputs it spends (see [`TxBuilder::reserve_utxos`]).
+    /// To cancel a tx that was already broadcast, see [`build_cancel_tx`].
+    ///
+    /// [`build_cancel_tx`]: Self::build_cancel_tx
     pub fn cancel_tx(&mut self, tx: &Transaction) {
         let txout_index = &mut self.indexed_graph.index;
         for txout in &tx.output {
@@ -2111,6 +3313,22 @@ impl Wallet {
                 txout_index.unmark_used(*keychain, *index);
             }
         }
+
+        let changeset = reservations::ChangeSet {
+            outpoints: tx
+                .input
+                .iter()
+                .filter(|txin| {
+                    self.reserved_outpoints
+                        .remove(&txin.previous_output)
+                        .is_some()
+                })
+                .map(|txin| (txin.previous_output, None))
+                .collect(),
+        };
+        if !changeset.is_empty() {
+            self.stage.merge(changeset.into());
+        }
     }
 
     fn get_descriptor_for_txout(&self, txout: &TxOut) -> Option<DerivedDescriptor> {
@@ -2125,59 +3343,160 @@ impl Wallet {
     /// Given the options returns the list of utxos that must be used to form the
     /// transaction and any further that may be used if needed.
     fn filter_utxos(&self, params: &TxParams, current_height: u32) -> Vec<WeightedUtxo> {
-        if params.manually_selected_only {
-            vec![]
-        // Only process optional UTxOs if manually_selected_only is false.
-        } else {
-            let manually_selected_outpoints = params
-                .utxos
-                .iter()
-                .map(|wutxo| wutxo.utxo.outpoint())
-                .collect::<HashSet<OutPoint>>();
-            self.indexed_graph
-                .graph()
-                // Get all unspent UTxOs from wallet.
-                // NOTE: the UTxOs returned by the following method already belong to wallet as the
-                // call chain uses get_tx_node infallibly.
-                .filter_chain_unspents(
-                    &self.chain,
-                    self.chain.tip().block_id(),
-                    CanonicalizationParams::default(),
-                    self.indexed_graph.index.outpoints().iter().cloned(),
-                )
-                // Filter out locked outpoints.
-                .filter(|(_, txo)| !self.is_outpoint_locked(txo.outpoint))
-                // Only create LocalOutput if UTxO is mature.
-                .filter_map(move |((k, i), full_txo)| {
-                    full_txo
-                        .is_mature(current_height)
-                        .then(|| new_local_utxo(k, i, full_txo))
-                })
-                // only process UTXOs not selected manually, they will be considered later in the
-                // chain
-                // NOTE: this avoid UTXOs in both required and optional list
-                .filter(|may_spend| !manually_selected_outpoints.contains(&may_spend.outpoint))
-                // only add to optional UTxOs those which satisfy the change policy if we reuse
-                // change
-                .filter(|local_output| {
-                    self.keychains().count() == 1
-                        || params.change_policy.is_satisfied_by(local_output)
-                })
-                // Only add to optional UTxOs those marked as spendable.
-                .filter(|local_output| !params.unspendable.contains(&local_output.outpoint))
-                // If bumping fees only add to optional UTxOs those confirmed.
-                .filter(|local_output| {
-                    params.bumping_fee.is_none() || local_output.chain_position.is_confirmed()
-                })
-                .map(|utxo| WeightedUtxo {
-                    satisfaction_weight: self
-                        .public_descriptor(utxo.keychain)
-                        .max_weight_to_satisfy()
-                        .unwrap(),
-                    utxo: Utxo::Local(utxo),
+        self.classify_utxos(params, current_height).0
+    }
+
+    /// The outpoints of `utxos` whose relative timelock (`older()`) isn't met at the spending
+    /// height. They can still be spent, but the transaction can't be mined before they mature.
+    fn timelock_not_met<'u>(
+        &self,
+        params: &TxParams,
+        current_height: u32,
+        utxos: impl IntoIterator<Item = &'u WeightedUtxo>,
+    ) -> Vec<OutPoint> {
+        // Relative timelock in blocks of the spending path of each keychain. If the policy path
+        // is missing, `create_tx` fails on its own.
+        let csv_blocks = |keychain: KeychainKind| {
+            let path = match keychain {
+                KeychainKind::External => params.external_policy_path.as_ref(),
+                KeychainKind::Internal => params.internal_policy_path.as_ref(),
+            };
+            match self
+                .spending_condition(keychain, path)
+                .ok()?
+                .csv?
+                .to_relative_lock_time()?
+            {
+                relative::LockTime::Blocks(height) => Some(u32::from(height.value())),
+                relative::LockTime::Time(_) => None,
+            }
+        };
+        let csv_blocks = [KeychainKind::External, KeychainKind::Internal]
+            .into_iter()
+            .map(|keychain| (keychain, csv_blocks(keychain)))
+            .collect::<BTreeMap<_, _>>();
+
+        utxos
+            .into_iter()
+            .filter_map(|wutxo| match &wutxo.utxo {
+                Utxo::Local(local) => Some(local),
+                Utxo::Foreign { .. } => None,
+            })
+            .filter(|local| {
+                csv_blocks[&local.keychain].is_some_and(|blocks| {
+                    // The transaction can be mined in the block after `current_height`.
+                    let age = local
+                        .chain_position
+                        .confirmation_height_upper_bound()
+                        .map_or(0, |h| current_height.saturating_add(1).saturating_sub(h));
+                    age < blocks
                 })
-                .collect()
+            })
+            .map(|local| local.outpoint)
+            .collect()
+    }
+
+    /// Split the unspent outputs of the wallet between the ones coin selection may choose from
+    /// and the ones it must not, with the reason why. The manually selected UTXOs are in neither.
+    fn classify_utxos(
+        &self,
+        params: &TxParams,
+        current_height: u32,
+    ) -> (Vec<WeightedUtxo>, Vec<(OutPoint, ExclusionReason)>) {
+        // The excluded UTXOs are only needed to explain the selection.
+        let diagnostics = params.coin_selection_context.diagnostics;
+        if params.manually_selected_only && !diagnostics {
+            return (vec![], vec![]);
         }
+        let manually_selected_outpoints = params
+            .utxos
+            .iter()
+            .map(|wutxo| wutxo.utxo.outpoint())
+            .collect::<HashSet<OutPoint>>();
+        let tip_height = self.latest_checkpoint().height();
+
+        let mut excluded = Vec::new();
+        let mut available: Vec<WeightedUtxo> = self
+            .indexed_graph
+            .graph()
+            // Get all unspent UTxOs from wallet.
+            // NOTE: the UTxOs returned by the following method already belong to wallet as the
+            // call chain uses get_tx_node infallibly.
+            .filter_chain_unspents(
+                &self.chain,
+                self.chain.tip().block_id(),
+                CanonicalizationParams::default(),
+                self.indexed_graph.index.outpoints().iter().cloned(),
+            )
+            // Only process UTXOs not selected manually, they will be considered later in the
+            // chain.
+            // NOTE: this avoid UTXOs in both required and optional list
+            .filter(|(_, full_txo)| !manually_selected_outpoints.contains(&full_txo.outpoint))
+            .filter_map(|((keychain, index), full_txo)| {
+                let outpoint = full_txo.outpoint;
+                let reason = if params.manually_selected_only {
+                    Some(ExclusionReason::NotManuallySelected)
+                } else if self.is_outpoint_locked(outpoint) {
+                    Some(ExclusionReason::Locked)
+                } else if self.is_reserved_by_other_draft(outpoint, params) {
+                    Some(ExclusionReason::Reserved)
+                } else if !full_txo.is_mature(current_height) {
+                    Some(ExclusionReason::ImmatureCoinbase)
+                } else {
+                    None
+                };
+                let local_output = new_local_utxo(keychain, index, full_txo);
+                let confirmations = local_output
+                    .chain_position
+                    .confirmation_height_upper_bound()
+                    .map_or(0, |h| tip_height.saturating_add(1).saturating_sub(h));
+                let reason = reason.or_else(|| {
+                    // Only add to optional UTxOs those which satisfy the change policy if we
+                    // reuse change.
+                    if self.keychains().count() != 1
+                        && !params.change_policy.is_satisfied_by(&local_output)
+                    {
+                        Some(ExclusionReason::ChangePolicy)
+                    } else if params.unspendable.contains(&outpoint) {
+                        // Tell apart the UTXOs excluded for their number of confirmations.
+                        match params.min_confirmations {
+                            Some(min) if confirmations < min => {
+                                Some(ExclusionReason::BelowMinConfirmations)
+                            }
+                            _ => Some(ExclusionReason::Unspendable),
+                        }
+                    } else if params.bumping_fee.is_some()
+                        && !local_output.chain_position.is_confirmed()
+                    {
+                        // If bumping fees only add to optional UTxOs those confirmed.
+                        Some(ExclusionReason::Unconfirmed)
+                    } else {
+                        None
+                    }
+                });
+                match reason {
+                    Some(reason) => {
+                        if diagnostics {
+                            excluded.push((outpoint, reason));
+                        }
+                        None
+                    }
+                    None => Some(WeightedUtxo {
+                        satisfaction_weight: self
+                            .public_descriptor(local_output.keychain)
+                            .max_weight_to_satisfy()
+                            .unwrap(),
+                        utxo: Utxo::Local(local_output),
+                    }),
+                }
+            })
+            .collect();
+
+        // Only process optional UTxOs if manually_selected_only is false.
+        if !params.manually_selected_only {
+            available.extend(self.silent_payment_utxos(params, current_height));
+        }
+        (available, excluded)
     }
 
     fn complete_transaction(
@@ -2461,6 +3780,93 @@ impl Wallet {
         }
     }
 
+    /// List the reserved outpoints together with their [`Reservation`].
+    ///
+    /// This includes expired reservations that haven't been released yet.
+    ///
+    /// [`Reservation`]: reservations::Reservation
+    pub fn list_reserved_outpoints(
+        &self,
+    ) -> impl Iterator<Item = (OutPoint, &reservations::Reservation)> + '_ {
+        self.reserved_outpoints
+            .iter()
+            .map(|(op, reservation)| (*op, reservation))
+    }
+
+    /// Whether the `outpoint` is reserved by a draft and the reservation hasn't expired at the
+    /// current chain tip. See [`TxBuilder::reserve_utxos`] for more.
+    pub fn is_outpoint_reserved(&self, outpoint: OutPoint) -> bool {
+        let tip_height = self.chain.tip().height();
+        self.reserved_outpoints
+            .get(&outpoint)
+            .is_some_and(|reservation| !reservation.is_expired(tip_height))
+    }
+
+    /// Whether the `outpoint` is reserved by a draft other than the one `params` are reserving
+    /// their inputs for, as of the current chain tip.
+    fn is_reserved_by_other_draft(&self, outpoint: OutPoint, params: &TxParams) -> bool {
+        let tip_height = self.chain.tip().height();
+        self.reserved_outpoints
+            .get(&outpoint)
+            .is_some_and(|reservation| {
+                !reservation.is_expired(tip_height)
+                    && params
+                        .reservation
+                        .as_ref()
+                        .is_none_or(|own| own.draft_id != reservation.draft_id)
+            })
+    }
+
+    /// Reserve the wallet outputs identified by `outpoints` for the draft of `reservation`.
+    ///
+    /// Reserved outputs are not selected as inputs to fund a transaction until the reservation is
+    /// released or expires. Outpoints already reserved by another draft are moved to this one.
+    /// Transactions built with [`TxBuilder::reserve_utxos`] reserve their inputs automatically.
+    ///
+    /// **You must persist the staged change for the reservation to be persistent**. To release
+    /// the outpoints, see [`Wallet::release_reservation`].
+    pub fn reserve_outpoints(
+        &mut self,
+        outpoints: impl IntoIterator<Item = OutPoint>,
+        reservation: reservations::Reservation,
+    ) {
+        let mut changeset = reservations::ChangeSet::default();
+        for outpoint in outpoints {
+            if self.reserved_outpoints.get(&outpoint) != Some(&reservation) {
+                self.reserved_outpoints
+                    .insert(outpoint, reservation.clone());
+                changeset
+                    .outpoints
+                    .insert(outpoint, Some(reservation.clone()));
+            }
+        }
+        if !changeset.is_empty() {
+            self.stage.merge(changeset.into());
+        }
+    }
+
+    /// Release all the outpoints reserved by the draft identified by `draft_id`.
+    ///
+    /// **You must persist the staged change for the release to be persistent**.
+    pub fn release_reservation(&mut self, draft_id: &str) {
+        let released: Vec<OutPoint> = self
+            .reserved_outpoints
+            .iter()
+            .filter(|(_, reservation)| reservation.draft_id == draft_id)
+            .map(|(op, _)| *op)
+            .collect();
+        let changeset = reservations::ChangeSet {
+            outpoints: released
+                .into_iter()
+                .filter(|op| self.reserved_outpoints.remove(op).is_some())
+                .map(|op| (op, None))
+                .collect(),
+        };
+        if !changeset.is_empty() {
+            self.stage.merge(changeset.into());
+        }
+    }
+
     /// Introduces a `block` of `height` to the wallet, and tries to connect it to the
     /// `prev_blockhash` of the block's header.
     ///
@@ -2469,6 +3875,24 @@ impl Wallet {
     ///
     /// [`apply_block_connected_to`]: Self::apply_block_connected_to
     pub fn apply_block(&mut self, block: &Block, height: u32) -> Result<(), CannotConnectError> {
+        self.apply_block_with_tweaks(block, height, &BTreeMap::new())
+    }
+
+    /// Introduces a `block` of `height` to the wallet like [`apply_block`], using `tweaks` to
+    /// find the outputs paying the silent payment address of the wallet.
+    ///
+    /// `tweaks` maps the transactions of the block to their tweak data, as served by silent
+    /// payment indexes (see [`silent_payments::input_tweak_data`]). The tweak data of the other
+    /// transactions is computed from the outputs they spend, which is only possible if the wallet
+    /// knows about them.
+    ///
+    /// [`apply_block`]: Self::apply_block
+    pub fn apply_block_with_tweaks(
+        &mut self,
+        block: &Block,
+        height: u32,
+        tweaks: &BTreeMap<Txid, secp256k1::PublicKey>,
+    ) -> Result<(), CannotConnectError> {
         let connected_to = match height.checked_sub(1) {
             Some(prev_height) => BlockId {
                 height: prev_height,
@@ -2479,7 +3903,7 @@ impl Wallet {
                 hash: block.block_hash(),
             },
         };
-        self.apply_block_connected_to(block, height, connected_to)
+        self.apply_block_inner(block, height, connected_to, tweaks)
             .map_err(|err| match err {
                 ApplyHeaderError::InconsistentBlocks => {
                     unreachable!("connected_to is derived from the block so must be consistent")
@@ -2503,6 +3927,16 @@ impl Wallet {
         block: &Block,
         height: u32,
         connected_to: BlockId,
+    ) -> Result<(), ApplyHeaderError> {
+        self.apply_block_inner(block, height, connected_to, &BTreeMap::new())
+    }
+
+    fn apply_block_inner(
+        &mut self,
+        block: &Block,
+        height: u32,
+        connected_to: BlockId,
+        tweaks: &BTreeMap<Txid, secp256k1::PublicKey>,
     ) -> Result<(), ApplyHeaderError> {
         let mut changeset = ChangeSet::default();
         changeset.merge(
@@ -2515,13 +3949,15 @@ impl Wallet {
                 .apply_block_relevant(block, height)
                 .into(),
         );
+        changeset.merge(self.scan_block(block, height, tweaks));
         self.stage.merge(changeset);
         Ok(())
     }
 
     /// Apply relevant unconfirmed transactions to the wallet.
     ///
-    /// Transactions that are not relevant are filtered out.
+    /// Transactions that are not relevant are filtered out. Spending a silent payment output found
+    /// by the wallet makes a transaction relevant, even if it pays nothing back to the wallet.
     ///
     /// This method takes in an iterator of `(tx, last_seen)` where `last_seen` is the timestamp of
     /// when the transaction was last seen in the mempool. This is used for conflict resolution
@@ -2535,9 +3971,24 @@ impl Wallet {
         &mut self,
         unconfirmed_txs: impl IntoIterator<Item = (T, u64)>,
     ) {
-        let indexed_graph_changeset = self
+        let (silent_payment_spends, unconfirmed_txs): (Vec<_>, Vec<_>) = unconfirmed_txs
+            .into_iter()
+            .map(|(tx, last_seen)| (tx.into(), last_seen))
+            .partition(|(tx, _): &(Arc<Transaction>, u64)| {
+                tx.input.iter().any(|txin| {
+                    self.silent_payments
+                        .outputs
+                        .contains_key(&txin.previous_output)
+                })
+            });
+        let mut indexed_graph_changeset = self
             .indexed_graph
             .batch_insert_relevant_unconfirmed(unconfirmed_txs);
+        for (tx, last_seen) in silent_payment_spends {
+            let txid = tx.compute_txid();
+            indexed_graph_changeset.merge(self.indexed_graph.insert_tx(tx));
+            indexed_graph_changeset.merge(self.indexed_graph.insert_seen_at(txid, last_seen));
+        }
         self.stage.merge(indexed_graph_changeset.into());
     }
 
@@ -2612,6 +4063,27 @@ impl Wallet {
             keychain
         }
     }
+
+    /// The keychain to send change to so that it has the same script type as the `recipients`.
+    ///
+    /// The internal keychain is preferred, the external one is only used when its descriptor
+    /// matches the type of the recipients and the internal one doesn't.
+    fn change_keychain_for(&self, recipients: &[(ScriptBuf, Amount)]) -> KeychainKind {
+        let internal = self.map_keychain(KeychainKind::Internal);
+        let mut recipient_types = recipients.iter().map(|(spk, _)| ScriptType::of(spk));
+        let Some(recipient_type) = recipient_types.next() else {
+            return internal;
+        };
+        if recipient_types.any(|ty| ty != recipient_type) {
+            return internal;
+        }
+        [internal, KeychainKind::External]
+            .into_iter()
+            .find(|&keychain| {
+                ScriptType::of_descriptor(self.public_descriptor(keychain)) == recipient_type
+            })
+            .unwrap_or(internal)
+    }
 }
 
 /// Methods to construct sync/full-scan requests for spk-based chain sources.
@@ -2724,6 +4196,27 @@ where
     Ok(wallet_name)
 }
 
+/// A transaction built by the wallet, before any change to the wallet is recorded.
+struct DraftTx {
+    psbt: Psbt,
+    /// The UTXOs spent by the transaction.
+    selected: Vec<Utxo>,
+    /// The value of the change (or drain) output, if any.
+    change: Option<Amount>,
+    /// The keychain and derivation index of the change output, if it's ours.
+    change_index: Option<(KeychainKind, u32)>,
+    /// The estimated weight of the transaction once signed.
+    estimated_weight: Weight,
+    /// The unconfirmed ancestors paid for by the transaction.
+    ancestors: tx_builder::PackageFee,
+    /// The part of the fee paid for the unconfirmed ancestors.
+    bump_fee: Amount,
+    /// How the coins were selected, if requested.
+    diagnostics: Option<Box<SelectionDiagnostics>>,
+    /// The silent payment addresses paid, by their derived script pubkey.
+    silent_payments_sent: BTreeMap<ScriptBuf, SilentPaymentAddress>,
+}
+
 fn new_local_utxo(
     keychain: KeychainKind,
     derivation_index: u32,
@@ -2739,6 +4232,43 @@ fn new_local_utxo(
     }
 }
 
+/// Run `coin_selection`, spending whole groups of UTXOs of the same script pubkey if
+/// `avoid_partial_spends` is set.
+#[allow(clippy::too_many_arguments)]
+fn select_coins<Cs: CoinSelectionAlgorithm>(
+    coin_selection: Cs,
+    context: &CoinSelectionContext,
+    avoid_partial_spends: Option<Amount>,
+    required_utxos: Vec<WeightedUtxo>,
+    optional_utxos: Vec<WeightedUtxo>,
+    fee_rate: FeeRate,
+    target_amount: Amount,
+    drain_script: &Script,
+    rng: &mut impl RngCore,
+) -> Result<CoinSelectionResult, InsufficientFunds> {
+    match avoid_partial_spends {
+        Some(max_extra_fee) => AvoidPartialSpends::new(coin_selection, max_extra_fee)
+            .coin_select_with_context(
+                context,
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+                rng,
+            ),
+        None => coin_selection.coin_select_with_context(
+            context,
+            required_utxos,
+            optional_utxos,
+            fee_rate,
+            target_amount,
+            drain_script,
+            rng,
+        ),
+    }
+}
+
 fn make_indexed_graph(
     stage: &mut ChangeSet,
     tx_graph_changeset: chain::tx_graph::ChangeSet<ConfirmationBlockTime>,
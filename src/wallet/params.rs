@@ -1,6 +1,7 @@
 use alloc::boxed::Box;
 
 use bdk_chain::keychain_txout::DEFAULT_LOOKAHEAD;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
 use bitcoin::{BlockHash, Network, NetworkKind};
 use miniscript::descriptor::KeyMap;
 
@@ -67,6 +68,7 @@ pub struct CreateParams {
     pub(crate) genesis_hash: Option<BlockHash>,
     pub(crate) lookahead: u32,
     pub(crate) use_spk_cache: bool,
+    pub(crate) silent_payments: Option<(SecretKey, PublicKey)>,
 }
 
 impl CreateParams {
@@ -90,6 +92,7 @@ impl CreateParams {
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
             use_spk_cache: false,
+            silent_payments: None,
         }
     }
 
@@ -112,6 +115,7 @@ impl CreateParams {
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
             use_spk_cache: false,
+            silent_payments: None,
         }
     }
 
@@ -137,6 +141,7 @@ impl CreateParams {
             genesis_hash: None,
             lookahead: DEFAULT_LOOKAHEAD,
             use_spk_cache: false,
+            silent_payments: None,
         }
     }
 
@@ -182,6 +187,20 @@ impl CreateParams {
         self
     }
 
+    /// Receive silent payments to the address with the given secret `scan_key` and public
+    /// `spend_key`.
+    ///
+    /// Blocks applied to the wallet are scanned for outputs paying the address, see
+    /// [`Wallet::apply_block_with_tweaks`]. The scan key is not persisted and must be provided
+    /// again with [`LoadParams::silent_payments`] to keep scanning after loading the wallet. To
+    /// spend the outputs found, add a
+    /// [`SilentPaymentSigner`](crate::silent_payments::SilentPaymentSigner) holding the secret
+    /// spend key.
+    pub fn silent_payments(mut self, scan_key: SecretKey, spend_key: PublicKey) -> Self {
+        self.silent_payments = Some((scan_key, spend_key));
+        self
+    }
+
     /// Create [`PersistedWallet`] with the given [`WalletPersister`].
     pub fn create_wallet<P>(
         self,
@@ -222,6 +241,7 @@ pub struct LoadParams {
     pub(crate) check_change_descriptor: Option<Option<DescriptorToExtract>>,
     pub(crate) extract_keys: bool,
     pub(crate) use_spk_cache: bool,
+    pub(crate) silent_payments: Option<(SecretKey, PublicKey)>,
 }
 
 impl LoadParams {
@@ -239,6 +259,7 @@ impl LoadParams {
             check_change_descriptor: None,
             extract_keys: false,
             use_spk_cache: false,
+            silent_payments: None,
         }
     }
 
@@ -309,6 +330,16 @@ impl LoadParams {
         self
     }
 
+    /// Scan for silent payments to the address with the given secret `scan_key` and public
+    /// `spend_key`.
+    ///
+    /// If the loaded wallet already has a silent payment address it must match, otherwise
+    /// receiving to the address is enabled from now on. See [`CreateParams::silent_payments`].
+    pub fn silent_payments(mut self, scan_key: SecretKey, spend_key: PublicKey) -> Self {
+        self.silent_payments = Some((scan_key, spend_key));
+        self
+    }
+
     /// Load [`PersistedWallet`] with the given [`WalletPersister`].
     pub fn load_wallet<P>(
         self,
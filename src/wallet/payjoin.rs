@@ -0,0 +1,744 @@
+//! BIP78 payjoin
+//!
+//! This module implements both sides of a [BIP78](https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki)
+//! payjoin. The sender signs an *original* transaction paying the receiver and posts it to the
+//! receiver's endpoint. The receiver adds some of its own inputs to it and sends back a
+//! *proposal*, which the sender checks and signs again before broadcasting it.
+//!
+//! The HTTP requests are left to an implementation of [`Transport`].
+//!
+//! ## Example
+//!
+//! ```
+//! # use bdk_wallet::payjoin::{Params, Receiver, Sender, Transport};
+//! # use bdk_wallet::test_utils::*;
+//! # use bdk_wallet::{KeychainKind, SignOptions, Wallet};
+//! # use bitcoin::Amount;
+//! // A receiver running in the same process, a real one would be an HTTP client.
+//! struct InProcess<'a>(&'a Wallet);
+//!
+//! impl Transport for InProcess<'_> {
+//!     type Error = bdk_wallet::payjoin::PayjoinError;
+//!
+//!     fn post(&mut self, url: &str, body: &str) -> Result<String, Self::Error> {
+//!         let (_, query) = url.split_once('?').unwrap_or((url, ""));
+//!         let proposal = Receiver::from_request(query, body)?.propose(self.0, SignOptions::default())?;
+//!         Ok(proposal.to_string())
+//!     }
+//! }
+//!
+//! let (mut sender, _) = get_funded_wallet_wpkh();
+//! let (mut receiver, _) = get_funded_wallet_single(get_test_wpkh());
+//! let address = receiver.reveal_next_address(KeychainKind::External);
+//!
+//! let mut builder = sender.build_tx();
+//! builder.add_recipient(address.script_pubkey(), Amount::from_sat(10_000));
+//! let mut original = builder.finish()?;
+//! sender.sign(&mut original, SignOptions::default())?;
+//!
+//! let payjoin = Sender::new(original, "https://example.com/pj", Params::default())?;
+//! let mut proposal = payjoin.send(&sender, &mut InProcess(&receiver))?;
+//! assert!(sender.sign(&mut proposal, SignOptions::default())?);
+//! let tx = proposal.extract_tx()?;
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use bitcoin::psbt::{self, PsbtParseError};
+use bitcoin::{Amount, FeeRate, OutPoint, Psbt, ScriptBuf, TxIn, TxOut, Weight};
+use rand_core::RngCore;
+
+use crate::collections::HashMap;
+use crate::psbt::PsbtUtils;
+use crate::types::{Utxo, WeightedUtxo};
+use crate::wallet::coin_selection::{CoinSelectionAlgorithm, DefaultCoinSelectionAlgorithm};
+use crate::wallet::signer::{SignOptions, SignerError};
+use crate::wallet::tx_builder::TxParams;
+use crate::wallet::utils::ScriptType;
+use crate::wallet::Wallet;
+use crate::KeychainKind;
+
+/// The parameters a sender passes to the receiver along with the original PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Params {
+    /// Index of the output the receiver may take its fee contribution from, usually our change
+    pub additional_fee_output_index: Option<usize>,
+    /// Maximum amount the sender agrees to pay for the fee of the receiver's inputs
+    pub max_additional_fee_contribution: Amount,
+    /// Minimum fee rate of the proposal
+    ///
+    /// Regardless of this value, the proposal must pay at least the fee of the original
+    /// transaction plus the sender's contribution.
+    pub min_fee_rate: FeeRate,
+    /// Whether the receiver is forbidden from changing the script or lowering the amount of the
+    /// outputs paying it
+    pub disable_output_substitution: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            additional_fee_output_index: None,
+            max_additional_fee_contribution: Amount::ZERO,
+            min_fee_rate: FeeRate::ZERO,
+            disable_output_substitution: false,
+        }
+    }
+}
+
+impl Params {
+    /// Encode the parameters as the query string of a payjoin request.
+    pub fn to_query(&self) -> String {
+        let mut query = String::from("v=1");
+        if let Some(index) = self.additional_fee_output_index {
+            query += &alloc::format!(
+                "&additionalfeeoutputindex={index}&maxadditionalfeecontribution={}",
+                self.max_additional_fee_contribution.to_sat()
+            );
+        }
+        if self.min_fee_rate > FeeRate::ZERO {
+            let sat_per_vb = self.min_fee_rate.to_sat_per_kwu() as f64 / 250.0;
+            query += &alloc::format!("&minfeerate={sat_per_vb}");
+        }
+        if self.disable_output_substitution {
+            query += "&disableoutputsubstitution=true";
+        }
+        query
+    }
+
+    /// Decode the parameters from the query string of a payjoin request.
+    ///
+    /// Unknown parameters are ignored.
+    pub fn from_query(query: &str) -> Result<Self, PayjoinError> {
+        let mut params = Params::default();
+        let mut version = None;
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let invalid = || PayjoinError::InvalidParam(key.to_string());
+            match key {
+                "v" => version = Some(value),
+                "additionalfeeoutputindex" => {
+                    params.additional_fee_output_index = Some(value.parse().map_err(|_| invalid())?)
+                }
+                "maxadditionalfeecontribution" => {
+                    params.max_additional_fee_contribution =
+                        Amount::from_sat(value.parse().map_err(|_| invalid())?)
+                }
+                "minfeerate" => {
+                    let sat_per_vb = value.parse::<f64>().map_err(|_| invalid())?;
+                    if !sat_per_vb.is_finite() || sat_per_vb < 0.0 {
+                        return Err(invalid());
+                    }
+                    params.min_fee_rate = FeeRate::from_sat_per_kwu((sat_per_vb * 250.0) as u64);
+                }
+                "disableoutputsubstitution" => {
+                    params.disable_output_substitution = value.parse().map_err(|_| invalid())?
+                }
+                _ => {}
+            }
+        }
+        match version {
+            Some("1") => Ok(params),
+            _ => Err(PayjoinError::InvalidParam("v".to_string())),
+        }
+    }
+}
+
+/// The way payjoin requests are delivered to the receiver, usually an HTTP client.
+pub trait Transport {
+    /// Error returned when the request fails
+    type Error: fmt::Display;
+
+    /// POST `body` to `url` and return the body of the response.
+    fn post(&mut self, url: &str, body: &str) -> Result<String, Self::Error>;
+}
+
+/// The sending side of a payjoin
+///
+/// For a usage example see [this module](crate::wallet::payjoin)'s documentation.
+#[derive(Debug, Clone)]
+pub struct Sender {
+    original: Psbt,
+    endpoint: String,
+    params: Params,
+}
+
+impl Sender {
+    /// Start a payjoin to `endpoint` with the signed `original` PSBT.
+    ///
+    /// The original transaction is a valid payment on its own, the receiver may broadcast it
+    /// instead of the payjoin.
+    pub fn new(
+        original: Psbt,
+        endpoint: impl Into<String>,
+        params: Params,
+    ) -> Result<Self, PayjoinError> {
+        if !is_finalized(&original) {
+            return Err(PayjoinError::NotFinalized);
+        }
+        if let Some(index) = params.additional_fee_output_index {
+            if index >= original.unsigned_tx.output.len() {
+                return Err(PayjoinError::InvalidParam(
+                    "additionalfeeoutputindex".to_string(),
+                ));
+            }
+        }
+        Ok(Self {
+            original,
+            endpoint: endpoint.into(),
+            params,
+        })
+    }
+
+    /// The URL and the body of the request to send to the receiver.
+    pub fn request(&self) -> (String, String) {
+        let separator = if self.endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let url = alloc::format!("{}{separator}{}", self.endpoint, self.params.to_query());
+        (url, self.original.to_string())
+    }
+
+    /// Send the request with `transport` and check the proposal of the receiver.
+    ///
+    /// See [`Sender::process_proposal`].
+    pub fn send<T: Transport>(
+        &self,
+        wallet: &Wallet,
+        transport: &mut T,
+    ) -> Result<Psbt, PayjoinError> {
+        let (url, body) = self.request();
+        let response = transport
+            .post(&url, &body)
+            .map_err(|err| PayjoinError::Transport(err.to_string()))?;
+        let proposal = Psbt::from_str(&response).map_err(PayjoinError::Psbt)?;
+        self.process_proposal(wallet, proposal)
+    }
+
+    /// Check the proposal of the receiver against the original PSBT.
+    ///
+    /// The proposal must spend all of our original inputs with the same sequence and only add
+    /// signed inputs of the same type, it must keep paying our outputs and respect the fee
+    /// contribution and fee rate limits of the [`Params`]. Our fee contribution must be paid
+    /// entirely as fee, on top of the fee of the original transaction. On success, the returned PSBT is
+    /// ready to be signed with [`Wallet::sign`].
+    pub fn process_proposal(
+        &self,
+        wallet: &Wallet,
+        mut proposal: Psbt,
+    ) -> Result<Psbt, PayjoinError> {
+        let original_tx = &self.original.unsigned_tx;
+        let tx = &proposal.unsigned_tx;
+        if tx.version != original_tx.version || tx.lock_time != original_tx.lock_time {
+            return Err(PayjoinError::TxModified);
+        }
+
+        let original_inputs = original_tx
+            .input
+            .iter()
+            .zip(&self.original.inputs)
+            .enumerate()
+            .map(|(index, (txin, input))| (txin.previous_output, (index, txin, input)))
+            .collect::<HashMap<_, _>>();
+        let sequence = original_tx.input[0].sequence;
+        let original_input_type = input_type(&self.original)?;
+
+        // Build the transaction as it will be once signed to know its weight.
+        let mut signed_tx = tx.clone();
+        let mut input_value = Amount::ZERO;
+        let mut receiver_weight = Weight::ZERO;
+        for (index, input) in proposal.inputs.iter().enumerate() {
+            let txin = &mut signed_tx.input[index];
+            let outpoint = txin.previous_output;
+            match original_inputs.get(&outpoint) {
+                Some(&(original_index, original_txin, original_input)) => {
+                    if txin.sequence != original_txin.sequence
+                        || input.final_script_sig.is_some()
+                        || input.final_script_witness.is_some()
+                        || !input.partial_sigs.is_empty()
+                    {
+                        return Err(PayjoinError::InvalidInput(outpoint));
+                    }
+                    input_value += self
+                        .original
+                        .get_utxo_for(original_index)
+                        .ok_or(PayjoinError::MissingUtxo(outpoint))?
+                        .value;
+                    txin.script_sig = original_input.final_script_sig.clone().unwrap_or_default();
+                    txin.witness = original_input
+                        .final_script_witness
+                        .clone()
+                        .unwrap_or_default();
+                }
+                None => {
+                    if txin.sequence != sequence || !is_input_finalized(input) {
+                        return Err(PayjoinError::InvalidInput(outpoint));
+                    }
+                    let utxo = proposal
+                        .get_utxo_for(index)
+                        .ok_or(PayjoinError::MissingUtxo(outpoint))?;
+                    if original_input_type
+                        .is_some_and(|input_type| input_type != ScriptType::of(&utxo.script_pubkey))
+                    {
+                        return Err(PayjoinError::InputTypeMismatch(outpoint));
+                    }
+                    input_value += utxo.value;
+                    txin.script_sig = input.final_script_sig.clone().unwrap_or_default();
+                    txin.witness = input.final_script_witness.clone().unwrap_or_default();
+                    // The receiver doesn't know the size of its signatures when it computes its
+                    // fee, allow for them to be a byte shorter than it expected.
+                    receiver_weight += txin.segwit_weight() + Weight::from_vb_unchecked(1);
+                }
+            }
+        }
+        if let Some(missing) = original_inputs.keys().find(|outpoint| {
+            !tx.input
+                .iter()
+                .any(|txin| txin.previous_output == **outpoint)
+        }) {
+            return Err(PayjoinError::MissingInput(*missing));
+        }
+
+        // Our outputs may only decrease by the fee contribution, the payee's outputs may only
+        // change if output substitution is allowed. Each output of the proposal can stand for a
+        // single original output, so that dropping one of two identical outputs is noticed.
+        let mut unmatched = tx.output.iter().collect::<Vec<&TxOut>>();
+        for (index, original_txout) in original_tx.output.iter().enumerate() {
+            let script_pubkey = &original_txout.script_pubkey;
+            let is_ours = wallet.is_mine(script_pubkey.clone());
+            if !is_ours && !self.params.disable_output_substitution {
+                continue;
+            }
+            let min_value = if self.params.additional_fee_output_index == Some(index) {
+                original_txout
+                    .value
+                    .checked_sub(self.params.max_additional_fee_contribution)
+                    .unwrap_or(Amount::ZERO)
+            } else {
+                original_txout.value
+            };
+            let position = unmatched
+                .iter()
+                .position(|txout| &txout.script_pubkey == script_pubkey && txout.value >= min_value)
+                .ok_or_else(|| PayjoinError::InvalidOutput(script_pubkey.clone()))?;
+            unmatched.swap_remove(position);
+        }
+
+        let ours = |txout: &&TxOut| wallet.is_mine(txout.script_pubkey.clone());
+        let original_change = original_tx.output.iter().filter(ours).map(|o| o.value);
+        let change = tx.output.iter().filter(ours).map(|o| o.value);
+        let contribution = original_change
+            .sum::<Amount>()
+            .checked_sub(change.sum())
+            .unwrap_or(Amount::ZERO);
+        let original_fee_rate = original_fee_rate(&self.original)?;
+        let max = self
+            .params
+            .max_additional_fee_contribution
+            .min(original_fee_rate * receiver_weight);
+        if contribution > max {
+            return Err(PayjoinError::FeeContributionTooHigh { contribution, max });
+        }
+
+        // Whatever we contribute must go to the fee, not to the receiver.
+        let original_fee = self.original.fee_amount().ok_or(PayjoinError::MissingUtxo(
+            original_tx.input[0].previous_output,
+        ))?;
+        let fee = input_value
+            .checked_sub(tx.output.iter().map(|txout| txout.value).sum())
+            .ok_or(PayjoinError::TxModified)?;
+        if fee < original_fee + contribution {
+            return Err(PayjoinError::FeeContributionNotPaid {
+                contribution,
+                additional_fee: fee.checked_sub(original_fee).unwrap_or(Amount::ZERO),
+            });
+        }
+        let fee_rate = fee / signed_tx.weight();
+        if fee_rate < self.params.min_fee_rate {
+            return Err(PayjoinError::FeeRateTooLow {
+                fee_rate,
+                min: self.params.min_fee_rate,
+            });
+        }
+
+        // Restore the data the receiver stripped from our inputs so that we can sign them again.
+        for (txin, input) in proposal.unsigned_tx.input.iter().zip(&mut proposal.inputs) {
+            if let Some((_, _, original_input)) = original_inputs.get(&txin.previous_output) {
+                *input = psbt::Input {
+                    non_witness_utxo: original_input.non_witness_utxo.clone(),
+                    witness_utxo: original_input.witness_utxo.clone(),
+                    ..Default::default()
+                };
+            }
+        }
+
+        Ok(proposal)
+    }
+}
+
+/// The receiving side of a payjoin
+///
+/// For a usage example see [this module](crate::wallet::payjoin)'s documentation.
+#[derive(Debug, Clone)]
+pub struct Receiver {
+    original: Psbt,
+    params: Params,
+}
+
+impl Receiver {
+    /// Decode a payjoin request from the `query` string of its URL and its `body`.
+    pub fn from_request(query: &str, body: &str) -> Result<Self, PayjoinError> {
+        Ok(Self {
+            original: Psbt::from_str(body.trim()).map_err(PayjoinError::Psbt)?,
+            params: Params::from_query(query)?,
+        })
+    }
+
+    /// The original PSBT of the sender.
+    ///
+    /// If the payjoin fails the receiver may broadcast the original transaction instead.
+    pub fn original(&self) -> &Psbt {
+        &self.original
+    }
+
+    /// The parameters of the request.
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Check that the original PSBT is a signed payment to `wallet` that it can contribute to.
+    pub fn check_original(&self, wallet: &Wallet) -> Result<(), PayjoinError> {
+        let psbt = &self.original;
+        if !is_finalized(psbt) {
+            return Err(PayjoinError::NotFinalized);
+        }
+        for (index, txin) in psbt.unsigned_tx.input.iter().enumerate() {
+            let utxo = psbt
+                .get_utxo_for(index)
+                .ok_or(PayjoinError::MissingUtxo(txin.previous_output))?;
+            if wallet.is_mine(utxo.script_pubkey) {
+                return Err(PayjoinError::OriginalSpendsOurOutput(txin.previous_output));
+            }
+        }
+        if input_type(psbt)?.is_none() {
+            return Err(PayjoinError::MixedInputTypes);
+        }
+        if let Some(index) = self.params.additional_fee_output_index {
+            match psbt.unsigned_tx.output.get(index) {
+                Some(txout) if !wallet.is_mine(txout.script_pubkey.clone()) => {}
+                _ => {
+                    return Err(PayjoinError::InvalidParam(
+                        "additionalfeeoutputindex".to_string(),
+                    ))
+                }
+            }
+        }
+        if !psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .any(|txout| wallet.is_mine(txout.script_pubkey.clone()))
+        {
+            return Err(PayjoinError::OriginalDoesNotPayUs);
+        }
+        let fee_rate = original_fee_rate(psbt)?;
+        if fee_rate < self.params.min_fee_rate {
+            return Err(PayjoinError::FeeRateTooLow {
+                fee_rate,
+                min: self.params.min_fee_rate,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the original PSBT and propose a payjoin adding inputs of `wallet` to it.
+    ///
+    /// See [`Receiver::propose_with_aux_rand`].
+    #[cfg(feature = "std")]
+    pub fn propose(self, wallet: &Wallet, sign_options: SignOptions) -> Result<Psbt, PayjoinError> {
+        self.propose_with_aux_rand(wallet, sign_options, &mut bitcoin::key::rand::thread_rng())
+    }
+
+    /// Check the original PSBT and propose a payjoin adding inputs of `wallet` to it.
+    ///
+    /// Coin selection picks wallet UTXOs of the same type as the sender's inputs, which are
+    /// inserted at random positions. Their value goes to the first output paying `wallet`. The
+    /// fee for the new inputs, at the original fee rate, is taken from the output designated by
+    /// the sender up to its maximum contribution, and from our output otherwise. Our inputs are
+    /// signed with `sign_options`, and the sender's inputs are stripped of their signatures.
+    ///
+    /// Uses a provided random number generator (rng).
+    pub fn propose_with_aux_rand(
+        self,
+        wallet: &Wallet,
+        sign_options: SignOptions,
+        rng: &mut impl RngCore,
+    ) -> Result<Psbt, PayjoinError> {
+        self.check_original(wallet)?;
+        let Receiver { original, params } = self;
+        let fee_rate = original_fee_rate(&original)?;
+        let sequence = original.unsigned_tx.input[0].sequence;
+        let sender_input_type = input_type(&original)?;
+
+        let current_height = wallet.latest_checkpoint().height();
+        let candidates = wallet
+            .filter_utxos(&TxParams::default(), current_height)
+            .into_iter()
+            .filter(|wutxo| {
+                Some(ScriptType::of(&wutxo.utxo.txout().script_pubkey)) == sender_input_type
+            })
+            .collect::<Vec<WeightedUtxo>>();
+        let drain_script = wallet
+            .peek_address(KeychainKind::Internal, 0)
+            .script_pubkey();
+        let selected = DefaultCoinSelectionAlgorithm::default()
+            .coin_select(
+                Vec::new(),
+                candidates.clone(),
+                fee_rate,
+                Amount::ONE_SAT,
+                &drain_script,
+                rng,
+            )
+            .map_err(|_| PayjoinError::NoMatchingUtxo)?
+            .selected;
+
+        let mut psbt = original.clone();
+        let mut added_value = Amount::ZERO;
+        let mut added_weight = Weight::ZERO;
+        for utxo in selected {
+            let Utxo::Local(local) = utxo else {
+                continue;
+            };
+            let satisfaction_weight = candidates
+                .iter()
+                .find(|wutxo| wutxo.utxo.outpoint() == local.outpoint)
+                .map(|wutxo| wutxo.satisfaction_weight)
+                .unwrap_or(Weight::ZERO);
+            let txin = TxIn {
+                previous_output: local.outpoint,
+                sequence,
+                ..Default::default()
+            };
+            added_value += local.txout.value;
+            added_weight += txin.segwit_weight() + satisfaction_weight;
+            let input = wallet
+                .get_psbt_input(local, None, false)
+                .map_err(|_| PayjoinError::NoMatchingUtxo)?;
+            let position = (rng.next_u32() as usize) % (psbt.inputs.len() + 1);
+            psbt.unsigned_tx.input.insert(position, txin);
+            psbt.inputs.insert(position, input);
+        }
+
+        // Share the fee of our inputs with the sender as agreed.
+        let added_fee = fee_rate * added_weight;
+        let mut contribution = Amount::ZERO;
+        if let Some(index) = params.additional_fee_output_index {
+            let txout = &mut psbt.unsigned_tx.output[index];
+            let spendable = txout
+                .value
+                .checked_sub(txout.script_pubkey.minimal_non_dust())
+                .unwrap_or(Amount::ZERO);
+            contribution = params
+                .max_additional_fee_contribution
+                .min(added_fee)
+                .min(spendable);
+            txout.value -= contribution;
+        }
+        let ours = psbt
+            .unsigned_tx
+            .output
+            .iter_mut()
+            .find(|txout| wallet.is_mine(txout.script_pubkey.clone()))
+            .expect("checked by check_original");
+        ours.value = (ours.value + added_value + contribution)
+            .checked_sub(added_fee)
+            .ok_or(PayjoinError::NoMatchingUtxo)?;
+
+        psbt.xpub.clear();
+        if !wallet
+            .sign(&mut psbt, sign_options)
+            .map_err(PayjoinError::Signer)?
+        {
+            return Err(PayjoinError::NotFinalized);
+        }
+
+        // The sender signs its inputs again, don't send back its signatures nor any metadata.
+        let original_outpoints = original
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<OutPoint>>();
+        for (txin, input) in psbt.unsigned_tx.input.iter().zip(&mut psbt.inputs) {
+            if original_outpoints.contains(&txin.previous_output) {
+                *input = psbt::Input::default();
+            }
+        }
+        for output in &mut psbt.outputs {
+            *output = psbt::Output::default();
+        }
+
+        Ok(psbt)
+    }
+}
+
+/// The type shared by all the inputs of `psbt`, `None` if they have different types.
+fn input_type(psbt: &Psbt) -> Result<Option<ScriptType>, PayjoinError> {
+    let mut types = Vec::new();
+    for (index, txin) in psbt.unsigned_tx.input.iter().enumerate() {
+        let utxo = psbt
+            .get_utxo_for(index)
+            .ok_or(PayjoinError::MissingUtxo(txin.previous_output))?;
+        types.push(ScriptType::of(&utxo.script_pubkey));
+    }
+    types.dedup();
+    Ok(match types.as_slice() {
+        [input_type] => Some(*input_type),
+        _ => None,
+    })
+}
+
+fn original_fee_rate(psbt: &Psbt) -> Result<FeeRate, PayjoinError> {
+    let fee = psbt.fee_amount().ok_or(PayjoinError::MissingUtxo(
+        psbt.unsigned_tx.input[0].previous_output,
+    ))?;
+    Ok(fee / psbt.clone().extract_tx_unchecked_fee_rate().weight())
+}
+
+fn is_input_finalized(input: &psbt::Input) -> bool {
+    input.final_script_sig.is_some() || input.final_script_witness.is_some()
+}
+
+fn is_finalized(psbt: &Psbt) -> bool {
+    !psbt.inputs.is_empty() && psbt.inputs.iter().all(is_input_finalized)
+}
+
+/// Errors returned by the sender and the receiver of a payjoin
+#[derive(Debug)]
+pub enum PayjoinError {
+    /// A PSBT couldn't be decoded
+    Psbt(PsbtParseError),
+    /// The request couldn't be delivered
+    Transport(String),
+    /// A parameter of the request is invalid or unsupported
+    InvalidParam(String),
+    /// A PSBT that should be fully signed isn't
+    NotFinalized,
+    /// The previous output spent by an input is unknown
+    MissingUtxo(OutPoint),
+    /// The original PSBT spends an output of the receiver
+    OriginalSpendsOurOutput(OutPoint),
+    /// The original PSBT doesn't pay the receiver
+    OriginalDoesNotPayUs,
+    /// The inputs of the original PSBT aren't all of the same type
+    MixedInputTypes,
+    /// The receiver has no UTXO of the same type as the sender's inputs to contribute
+    NoMatchingUtxo,
+    /// The transaction pays a lower fee rate than the minimum requested
+    FeeRateTooLow {
+        /// Fee rate of the transaction
+        fee_rate: FeeRate,
+        /// Minimum fee rate
+        min: FeeRate,
+    },
+    /// The proposal changes the version, the lock time or the fee of the original transaction
+    TxModified,
+    /// The proposal doesn't spend one of the sender's inputs
+    MissingInput(OutPoint),
+    /// An input of the proposal was tampered with, or one of the receiver's isn't signed
+    InvalidInput(OutPoint),
+    /// An input of the receiver is of a different type than the sender's
+    InputTypeMismatch(OutPoint),
+    /// The proposal removes or lowers an output it isn't allowed to
+    InvalidOutput(ScriptBuf),
+    /// The proposal makes the sender pay more fees than it agreed to
+    FeeContributionTooHigh {
+        /// Additional fee paid by the sender
+        contribution: Amount,
+        /// Maximum additional fee
+        max: Amount,
+    },
+    /// The proposal doesn't pay at least the original fee plus the sender's contribution
+    FeeContributionNotPaid {
+        /// Additional fee paid by the sender
+        contribution: Amount,
+        /// Fee of the proposal above the original fee
+        additional_fee: Amount,
+    },
+    /// The receiver couldn't sign its inputs
+    Signer(SignerError),
+}
+
+impl fmt::Display for PayjoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayjoinError::Psbt(err) => write!(f, "Invalid PSBT: {err}"),
+            PayjoinError::Transport(err) => write!(f, "Transport error: {err}"),
+            PayjoinError::InvalidParam(key) => write!(f, "Invalid parameter `{key}`"),
+            PayjoinError::NotFinalized => write!(f, "PSBT is not fully signed"),
+            PayjoinError::MissingUtxo(outpoint) => {
+                write!(f, "Missing previous output of input {outpoint}")
+            }
+            PayjoinError::OriginalSpendsOurOutput(outpoint) => {
+                write!(f, "Original transaction spends our output {outpoint}")
+            }
+            PayjoinError::OriginalDoesNotPayUs => {
+                write!(f, "Original transaction doesn't pay us")
+            }
+            PayjoinError::MixedInputTypes => {
+                write!(f, "Original transaction has inputs of different types")
+            }
+            PayjoinError::NoMatchingUtxo => {
+                write!(f, "No UTXO of the same type as the sender's inputs")
+            }
+            PayjoinError::FeeRateTooLow { fee_rate, min } => {
+                write!(
+                    f,
+                    "Fee rate of {fee_rate:#} is below the minimum of {min:#}"
+                )
+            }
+            PayjoinError::TxModified => write!(f, "Proposal modifies the original transaction"),
+            PayjoinError::MissingInput(outpoint) => {
+                write!(f, "Proposal doesn't spend our input {outpoint}")
+            }
+            PayjoinError::InvalidInput(outpoint) => {
+                write!(f, "Invalid proposal input {outpoint}")
+            }
+            PayjoinError::InputTypeMismatch(outpoint) => {
+                write!(
+                    f,
+                    "Proposal input {outpoint} doesn't match the type of ours"
+                )
+            }
+            PayjoinError::InvalidOutput(script_pubkey) => {
+                write!(f, "Proposal removes or lowers output {script_pubkey}")
+            }
+            PayjoinError::FeeContributionTooHigh { contribution, max } => {
+                write!(
+                    f,
+                    "Fee contribution of {contribution} exceeds the maximum of {max}"
+                )
+            }
+            PayjoinError::FeeContributionNotPaid {
+                contribution,
+                additional_fee,
+            } => {
+                write!(
+                    f,
+                    "Fee contribution of {contribution} exceeds the additional fee of {additional_fee}"
+                )
+            }
+            PayjoinError::Signer(err) => write!(f, "Signer error: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PayjoinError {}
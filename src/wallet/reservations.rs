@@ -0,0 +1,47 @@
+//! Module containing the reserved outpoints change set.
+
+use alloc::string::String;
+
+use bdk_chain::Merge;
+use bitcoin::OutPoint;
+use serde::{Deserialize, Serialize};
+
+use crate::collections::BTreeMap;
+
+/// A reservation of a wallet output by a transaction draft.
+///
+/// Reserved outputs are skipped by coin selection until the reservation is released or expires.
+/// See [`TxBuilder::reserve_utxos`](crate::TxBuilder::reserve_utxos).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Reservation {
+    /// Identifier of the draft holding the reservation.
+    pub draft_id: String,
+    /// Block height at which the reservation expires, if any.
+    pub expiry_height: Option<u32>,
+}
+
+impl Reservation {
+    /// Whether the reservation is expired at the given block `height`.
+    pub fn is_expired(&self, height: u32) -> bool {
+        self.expiry_height.is_some_and(|expiry| height >= expiry)
+    }
+}
+
+/// Represents changes to reserved outpoints.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// The reservation of an outpoint, `None` if the reservation was released.
+    pub outpoints: BTreeMap<OutPoint, Option<Reservation>>,
+}
+
+impl Merge for ChangeSet {
+    fn merge(&mut self, other: Self) {
+        // Extend self with other. Any entries in `self` that share the same
+        // outpoint are overwritten.
+        self.outpoints.extend(other.outpoints);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.outpoints.is_empty()
+    }
+}
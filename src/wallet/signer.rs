@@ -105,7 +105,7 @@ use miniscript::{SigType, ToPublicKey};
 
 use super::utils::SecpCtx;
 use crate::descriptor::{DescriptorMeta, XKeyUtils};
-use crate::psbt::PsbtUtils;
+use crate::psbt::{PsbtUtils, PsbtV2Error};
 use crate::types::IndexOutOfBoundsError;
 use crate::wallet::error::MiniscriptPsbtError;
 
@@ -168,6 +168,8 @@ pub enum SignerError {
     Psbt(psbt::SignError),
     /// Miniscript PSBT error
     MiniscriptPsbt(MiniscriptPsbtError),
+    /// PSBT version 2 error
+    PsbtV2(PsbtV2Error),
     /// To be used only by external libraries implementing [`InputSigner`] or
     /// [`TransactionSigner`], so that they can return their own custom errors, without having to
     /// modify [`SignerError`] in BDK.
@@ -191,6 +193,7 @@ impl fmt::Display for SignerError {
             Self::SighashTaproot(err) => write!(f, "Error while computing the hash to sign a Taproot input: {err}"),
             Self::Psbt(err) => write!(f, "Error computing the sighash: {err}"),
             Self::MiniscriptPsbt(err) => write!(f, "Miniscript PSBT error: {err}"),
+            Self::PsbtV2(err) => write!(f, "PSBT version 2 error: {err}"),
             Self::External(err) => write!(f, "{err}"),
         }
     }
@@ -842,7 +845,7 @@ impl Default for SignOptions {
 }
 
 /// Computes the taproot sighash.
-fn compute_tap_sighash(
+pub(crate) fn compute_tap_sighash(
     psbt: &Psbt,
     input_index: usize,
     extra: Option<taproot::TapLeafHash>,
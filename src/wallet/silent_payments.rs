@@ -0,0 +1,1167 @@
+//! BIP352 silent payments
+//!
+//! This module implements sending to the static `sp1...` addresses described in
+//! [BIP352](https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki). The taproot output
+//! paid to a silent payment address is derived from the private keys of the inputs of the
+//! transaction, so it is only known once coin selection is done: [`TxBuilder`] adds a placeholder
+//! output while selecting coins and replaces it with the derived output before returning the PSBT.
+//!
+//! Only inputs spending P2PKH, P2WPKH, P2SH-P2WPKH and P2TR outputs of the wallet can be used, and
+//! the wallet must hold their private keys.
+//!
+//! A wallet can also receive silent payments, next to the outputs of its descriptors, when created
+//! with [`CreateParams::silent_payments`]. The blocks applied to the wallet are scanned for outputs
+//! paying its address, which needs the tweak data of their transactions: it is either computed from
+//! the spent outputs known to the wallet or provided with [`Wallet::apply_block_with_tweaks`]. The
+//! outputs found are spent with a [`SilentPaymentSigner`]. Labels are not supported.
+//!
+//! ## Example
+//!
+//! ```
+//! # use bdk_wallet::silent_payments::SilentPaymentAddress;
+//! # use bdk_wallet::test_utils::*;
+//! # use bitcoin::Amount;
+//! # let (mut wallet, _) = get_funded_wallet_wpkh();
+//! let address: SilentPaymentAddress =
+//!     "sprt1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xcrdz399".parse()?;
+//!
+//! let mut builder = wallet.build_tx();
+//! builder.add_silent_payment_recipient(address, Amount::from_sat(10_000))?;
+//! let psbt = builder.finish()?;
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`TxBuilder`]: crate::wallet::tx_builder::TxBuilder
+//! [`CreateParams::silent_payments`]: crate::CreateParams::silent_payments
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use bdk_chain::{BlockId, CanonicalizationParams, ChainPosition, ConfirmationBlockTime, Merge};
+use bitcoin::bech32::primitives::decode::{CheckedHrpstring, CheckedHrpstringError};
+use bitcoin::bech32::{Bech32m, ByteIterExt, Fe32, Fe32IterExt, Hrp};
+use bitcoin::bip32::ChildNumber;
+use bitcoin::hashes::{hash160, sha256, Hash, HashEngine};
+use bitcoin::key::{Keypair, Parity, TapTweak};
+use bitcoin::psbt::{self, raw, Psbt};
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::{
+    self, Message, PublicKey, Scalar, Secp256k1, SecretKey, Verification, XOnlyPublicKey,
+};
+use bitcoin::{
+    taproot, Block, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Weight, Witness,
+};
+use miniscript::descriptor::{
+    DescriptorPublicKey, DescriptorSecretKey, ShInner, SinglePubKey, Wildcard,
+};
+use miniscript::Descriptor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::collections::HashMap;
+use crate::psbt::PsbtUtils;
+use crate::types::IndexOutOfBoundsError;
+use crate::types::{KeychainKind, LocalOutput, Utxo, WeightedUtxo};
+use crate::wallet::signer::{
+    compute_tap_sighash, InputSigner, SignOptions, SignerCommon, SignerError, SignerId,
+};
+use crate::wallet::tx_builder::{ChangeSpendPolicy, TxParams};
+use crate::wallet::utils::SecpCtx;
+use crate::wallet::Wallet;
+
+const HRP_MAINNET: Hrp = Hrp::parse_unchecked("sp");
+const HRP_TESTNET: Hrp = Hrp::parse_unchecked("tsp");
+const HRP_REGTEST: Hrp = Hrp::parse_unchecked("sprt");
+
+/// Length of the payload of a version 0 address, i.e. the scan and spend keys.
+const PAYLOAD_LEN: usize = 66;
+
+const INPUTS_TAG: &str = "BIP0352/Inputs";
+const SHARED_SECRET_TAG: &str = "BIP0352/SharedSecret";
+
+/// A silent payment address
+///
+/// For a usage example see [this module](crate::wallet::silent_payments)'s documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SilentPaymentAddress {
+    /// Public key the receiver scans the chain with
+    pub scan: PublicKey,
+    /// Public key the receiver spends the outputs with
+    pub spend: PublicKey,
+    /// Network of the address
+    ///
+    /// All the test networks share the same encoding and parse as [`Network::Testnet`].
+    pub network: Network,
+}
+
+impl SilentPaymentAddress {
+    /// Create a version 0 address from the scan and spend public keys of the receiver.
+    pub fn new(scan: PublicKey, spend: PublicKey, network: Network) -> Self {
+        Self {
+            scan,
+            spend,
+            network,
+        }
+    }
+
+    /// A P2TR output paying the spend key, of the same size as the outputs derived from it.
+    pub(crate) fn placeholder_script_pubkey(&self) -> ScriptBuf {
+        let (key, _) = self.spend.x_only_public_key();
+        ScriptBuf::new_p2tr_tweaked(key.dangerous_assume_tweaked())
+    }
+
+    /// Whether the address can be used on `network`.
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        hrp(self.network) == hrp(network)
+    }
+}
+
+fn hrp(network: Network) -> Hrp {
+    match network {
+        Network::Bitcoin => HRP_MAINNET,
+        Network::Regtest => HRP_REGTEST,
+        _ => HRP_TESTNET,
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = hrp(self.network);
+        // Unlike segwit addresses, version 0 uses bech32m as well.
+        let chars = self
+            .scan
+            .serialize()
+            .into_iter()
+            .chain(self.spend.serialize())
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp)
+            .with_witness_version(Fe32::Q)
+            .chars();
+        for c in chars {
+            fmt::Write::write_char(f, c)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = SilentPaymentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let checked = CheckedHrpstring::new::<Bech32m>(s)?;
+        let network = match checked.hrp() {
+            hrp if hrp == HRP_MAINNET => Network::Bitcoin,
+            hrp if hrp == HRP_TESTNET => Network::Testnet,
+            hrp if hrp == HRP_REGTEST => Network::Regtest,
+            hrp => return Err(SilentPaymentError::UnknownHrp(hrp.to_lowercase())),
+        };
+        let mut data = checked.fe32_iter::<core::iter::Empty<u8>>();
+        let version = data
+            .next()
+            .ok_or(SilentPaymentError::InvalidLength(0))?
+            .to_u8();
+        let payload: Vec<u8> = data.fes_to_bytes().collect();
+        // Future versions must stay compatible with version 0 and may only append data.
+        match version {
+            0 if payload.len() != PAYLOAD_LEN => {
+                return Err(SilentPaymentError::InvalidLength(payload.len()))
+            }
+            31 => return Err(SilentPaymentError::InvalidVersion(version)),
+            _ if payload.len() < PAYLOAD_LEN => {
+                return Err(SilentPaymentError::InvalidLength(payload.len()))
+            }
+            _ => {}
+        }
+        Ok(Self {
+            scan: PublicKey::from_slice(&payload[..33])?,
+            spend: PublicKey::from_slice(&payload[33..PAYLOAD_LEN])?,
+            network,
+        })
+    }
+}
+
+impl Serialize for SilentPaymentAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SilentPaymentAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Errors related to silent payments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SilentPaymentError {
+    /// The address isn't a valid bech32m string
+    Encoding(CheckedHrpstringError),
+    /// The human-readable part of the address is unknown
+    UnknownHrp(String),
+    /// The version of the address is invalid
+    InvalidVersion(u8),
+    /// The payload of the address has an invalid length
+    InvalidLength(usize),
+    /// The address contains an invalid public key
+    InvalidKey(secp256k1::Error),
+    /// The address isn't valid on the network of the wallet
+    NetworkMismatch(Network),
+    /// The input can't be used to pay a silent payment address, either because of its type or
+    /// because the wallet doesn't have its private key
+    IneligibleInput(OutPoint),
+    /// The private keys of the inputs can't be used to derive the outputs, e.g. because they sum
+    /// up to zero
+    InvalidInputKeys,
+}
+
+impl fmt::Display for SilentPaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encoding(err) => write!(f, "Invalid encoding: {err}"),
+            Self::UnknownHrp(hrp) => write!(f, "Unknown human-readable part: {hrp}"),
+            Self::InvalidVersion(version) => write!(f, "Invalid version: {version}"),
+            Self::InvalidLength(len) => write!(f, "Invalid payload length: {len}"),
+            Self::InvalidKey(err) => write!(f, "Invalid public key: {err}"),
+            Self::NetworkMismatch(network) => {
+                write!(f, "The address is not valid on {network}")
+            }
+            Self::IneligibleInput(outpoint) => {
+                write!(f, "Input {outpoint} can't be used for silent payments")
+            }
+            Self::InvalidInputKeys => {
+                write!(f, "The input keys can't be used to derive the outputs")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SilentPaymentError {}
+
+impl From<CheckedHrpstringError> for SilentPaymentError {
+    fn from(err: CheckedHrpstringError) -> Self {
+        Self::Encoding(err)
+    }
+}
+
+impl From<secp256k1::Error> for SilentPaymentError {
+    fn from(err: secp256k1::Error) -> Self {
+        Self::InvalidKey(err)
+    }
+}
+
+/// Private key of an input, and whether it spends a taproot output.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InputKey {
+    pub(crate) outpoint: OutPoint,
+    pub(crate) secret: SecretKey,
+    pub(crate) is_taproot: bool,
+}
+
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Derive the taproot outputs paying `recipients`, in the same order, from the keys of all the
+/// inputs of the transaction.
+pub(crate) fn derive_output_scripts(
+    secp: &SecpCtx,
+    inputs: &[InputKey],
+    recipients: &[SilentPaymentAddress],
+) -> Result<Vec<ScriptBuf>, SilentPaymentError> {
+    // Sum the input keys, taproot keys are used with an even y coordinate.
+    let mut sum: Option<SecretKey> = None;
+    for input in inputs {
+        let secret = match input.secret.x_only_public_key(secp) {
+            (_, Parity::Odd) if input.is_taproot => input.secret.negate(),
+            _ => input.secret,
+        };
+        sum = Some(match sum {
+            None => secret,
+            Some(sum) => sum
+                .add_tweak(&Scalar::from(secret))
+                .map_err(|_| SilentPaymentError::InvalidInputKeys)?,
+        });
+    }
+    let sum = sum.ok_or(SilentPaymentError::InvalidInputKeys)?;
+
+    let smallest_outpoint = inputs
+        .iter()
+        .map(|input| input.outpoint)
+        .min_by_key(bitcoin::consensus::serialize)
+        .expect("at least one input");
+    let tweaked_sum = input_hash(&smallest_outpoint, &sum.public_key(secp))
+        .and_then(|input_hash| sum.mul_tweak(&input_hash).ok())
+        .ok_or(SilentPaymentError::InvalidInputKeys)?;
+
+    // Outputs to the same scan key are told apart by a counter.
+    let mut counters: Vec<(PublicKey, u32)> = Vec::new();
+    recipients
+        .iter()
+        .map(|recipient| {
+            let k = match counters
+                .iter_mut()
+                .find(|(scan, _)| *scan == recipient.scan)
+            {
+                Some((_, k)) => {
+                    *k += 1;
+                    *k
+                }
+                None => {
+                    counters.push((recipient.scan, 0));
+                    0
+                }
+            };
+            let output = recipient
+                .scan
+                .mul_tweak(secp, &Scalar::from(tweaked_sum))
+                .ok()
+                .and_then(|shared_secret| output_tweak(&shared_secret, k))
+                .and_then(|tweak| {
+                    recipient
+                        .spend
+                        .add_exp_tweak(secp, &Scalar::from(tweak))
+                        .ok()
+                })
+                .ok_or(SilentPaymentError::InvalidInputKeys)?;
+            let (key, _) = output.x_only_public_key();
+            Ok(ScriptBuf::new_p2tr_tweaked(key.dangerous_assume_tweaked()))
+        })
+        .collect()
+}
+
+/// Hash committing to the inputs of a transaction, from its smallest outpoint and the sum of its
+/// input keys.
+fn input_hash(smallest_outpoint: &OutPoint, input_keys: &PublicKey) -> Option<Scalar> {
+    let hash = tagged_hash(
+        INPUTS_TAG,
+        &[
+            &bitcoin::consensus::serialize(smallest_outpoint),
+            &input_keys.serialize(),
+        ],
+    );
+    Scalar::from_be_bytes(hash).ok()
+}
+
+/// Tweak of the spend key for the `k`-th output sharing `shared_secret`.
+fn output_tweak(shared_secret: &PublicKey, k: u32) -> Option<SecretKey> {
+    let hash = tagged_hash(
+        SHARED_SECRET_TAG,
+        &[&shared_secret.serialize(), &k.to_be_bytes()],
+    );
+    SecretKey::from_slice(&hash).ok()
+}
+
+/// Returns the key of `descriptor` used to derive silent payment outputs and whether the
+/// descriptor is a taproot one, or `None` if the outputs of `descriptor` aren't eligible.
+fn input_public_key(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+) -> Option<(&DescriptorPublicKey, bool)> {
+    let (key, is_taproot) = match descriptor {
+        Descriptor::Pkh(pkh) => (pkh.as_inner(), false),
+        Descriptor::Wpkh(wpkh) => (wpkh.as_inner(), false),
+        Descriptor::Sh(sh) => match sh.as_inner() {
+            ShInner::Wpkh(wpkh) => (wpkh.as_inner(), false),
+            _ => return None,
+        },
+        Descriptor::Tr(tr) => (tr.internal_key(), true),
+        _ => return None,
+    };
+    match key {
+        // Outputs locked to uncompressed keys are skipped by the receiver.
+        DescriptorPublicKey::Single(single) => match single.key {
+            SinglePubKey::FullKey(pk) if !pk.compressed => None,
+            _ => Some((key, is_taproot)),
+        },
+        _ => Some((key, is_taproot)),
+    }
+}
+
+impl Wallet {
+    /// Returns the private key used to derive silent payment outputs when spending `utxo`, or
+    /// `None` if it isn't an eligible input.
+    pub(crate) fn silent_payment_input_key(&self, utxo: &LocalOutput) -> Option<InputKey> {
+        let keychain = utxo.keychain;
+        let descriptor = self.public_descriptor(keychain);
+        let (public, is_taproot) = input_public_key(descriptor)?;
+        let signers = match keychain {
+            KeychainKind::External => &self.signers,
+            KeychainKind::Internal => &self.change_signers,
+        };
+        let index = utxo.derivation_index;
+        let secret = match signers.as_key_map(&self.secp).get(public)? {
+            DescriptorSecretKey::Single(single) => single.key.inner,
+            DescriptorSecretKey::XPrv(xprv) => {
+                let path = match xprv.wildcard {
+                    Wildcard::None => xprv.derivation_path.clone(),
+                    Wildcard::Unhardened => xprv
+                        .derivation_path
+                        .child(ChildNumber::from_normal_idx(index).ok()?),
+                    Wildcard::Hardened => xprv
+                        .derivation_path
+                        .child(ChildNumber::from_hardened_idx(index).ok()?),
+                };
+                xprv.xkey.derive_priv(&self.secp, &path).ok()?.private_key
+            }
+            DescriptorSecretKey::MultiXPrv(_) => return None,
+        };
+
+        // Make sure we got the key of the output being spent.
+        let secret = if is_taproot {
+            let merkle_root = match descriptor.at_derivation_index(index).ok()? {
+                Descriptor::Tr(tr) => tr.spend_info().merkle_root(),
+                _ => unreachable!("taproot descriptor"),
+            };
+            let keypair = Keypair::from_secret_key(&self.secp, &secret)
+                .tap_tweak(&self.secp, merkle_root)
+                .to_keypair();
+            let output_key = keypair.x_only_public_key().0.dangerous_assume_tweaked();
+            if utxo.txout.script_pubkey != ScriptBuf::new_p2tr_tweaked(output_key) {
+                return None;
+            }
+            SecretKey::from_keypair(&keypair)
+        } else {
+            let expected = public
+                .clone()
+                .at_derivation_index(index)
+                .ok()?
+                .derive_public_key(&self.secp)
+                .ok()?;
+            if expected.inner != secret.public_key(&self.secp) {
+                return None;
+            }
+            secret
+        };
+
+        Some(InputKey {
+            outpoint: utxo.outpoint,
+            secret,
+            is_taproot,
+        })
+    }
+}
+
+/// PSBT input field holding the tweak of the spend key unlocking a silent payment output, as
+/// defined by BIP376.
+const PSBT_IN_SP_TWEAK: u8 = 0x1f;
+
+/// Internal key of the taproot outputs without a key path, which aren't eligible inputs.
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Maximum weight of the witness of a taproot key path spend, i.e. a signature with its sighash
+/// type and the length prefix, like `tr()` descriptors without script paths.
+const KEY_SPEND_SATISFACTION_WEIGHT: Weight = Weight::from_wu(1 + 65);
+
+/// Compute the tweak data of `tx`, i.e. the sum of the public keys of its eligible inputs
+/// multiplied by the hash of its inputs.
+///
+/// Together with its scan key, this is all a receiver needs to find the outputs of `tx` paying
+/// its address. `prevout` must return the outputs spent by `tx`. Returns `None` if a prevout is
+/// missing or if `tx` can't pay a silent payment address, e.g. because none of its inputs is
+/// eligible.
+pub fn input_tweak_data<C: Verification>(
+    secp: &Secp256k1<C>,
+    tx: &Transaction,
+    prevout: impl Fn(&OutPoint) -> Option<TxOut>,
+) -> Option<PublicKey> {
+    if tx.is_coinbase() {
+        return None;
+    }
+    let mut keys = Vec::new();
+    for txin in &tx.input {
+        let prevout = prevout(&txin.previous_output)?;
+        // Spending an output of a future segwit version makes the whole transaction ineligible.
+        if prevout
+            .script_pubkey
+            .witness_version()
+            .is_some_and(|version| version.to_num() > 1)
+        {
+            return None;
+        }
+        keys.extend(spent_public_key(txin, &prevout.script_pubkey));
+    }
+    let sum = PublicKey::combine_keys(&keys.iter().collect::<Vec<_>>()).ok()?;
+    let smallest_outpoint = tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .min_by_key(bitcoin::consensus::serialize)?;
+    sum.mul_tweak(secp, &input_hash(&smallest_outpoint, &sum)?)
+        .ok()
+}
+
+/// Returns the public key `txin` spends `script_pubkey` with, if it's an eligible input.
+fn spent_public_key(txin: &TxIn, script_pubkey: &Script) -> Option<PublicKey> {
+    let compressed_key = |bytes: &[u8]| match bytes.len() {
+        33 => PublicKey::from_slice(bytes).ok(),
+        _ => None,
+    };
+    if script_pubkey.is_p2tr() {
+        let mut witness: Vec<&[u8]> = txin.witness.iter().collect();
+        if witness.len() > 1 && witness.last().and_then(|annex| annex.first()) == Some(&0x50) {
+            witness.pop();
+        }
+        // A script path spend is only eligible if the output has a key path.
+        if witness.len() > 1 && witness.last()?.get(1..33) == Some(&NUMS_H[..]) {
+            return None;
+        }
+        let key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..]).ok()?;
+        Some(key.public_key(Parity::Even))
+    } else if script_pubkey.is_p2wpkh() {
+        compressed_key(txin.witness.last()?)
+    } else if script_pubkey.is_p2sh() {
+        // Only P2SH-P2WPKH outputs are eligible.
+        let redeem_script = match txin.script_sig.instructions().last()?.ok()? {
+            Instruction::PushBytes(bytes) => Script::from_bytes(bytes.as_bytes()),
+            Instruction::Op(_) => return None,
+        };
+        if !redeem_script.is_p2wpkh() {
+            return None;
+        }
+        compressed_key(txin.witness.last()?)
+    } else if script_pubkey.is_p2pkh() {
+        let pubkey_hash = &script_pubkey.as_bytes()[3..23];
+        let pushes: Vec<&[u8]> = txin
+            .script_sig
+            .instructions()
+            .filter_map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes()),
+                _ => None,
+            })
+            .collect();
+        pushes
+            .into_iter()
+            .rev()
+            .find(|bytes| hash160::Hash::hash(bytes).as_byte_array() == pubkey_hash)
+            .and_then(compressed_key)
+    } else {
+        None
+    }
+}
+
+/// Look for the outputs of `tx` paying the address with `scan_key` and `spend_key`, returning
+/// their index and the tweak of the spend key unlocking them.
+fn scan_tx(
+    secp: &SecpCtx,
+    scan_key: &SecretKey,
+    spend_key: &PublicKey,
+    tx: &Transaction,
+    tweak_data: &PublicKey,
+) -> Vec<(u32, SecretKey)> {
+    let Ok(shared_secret) = tweak_data.mul_tweak(secp, &Scalar::from(*scan_key)) else {
+        return Vec::new();
+    };
+    let mut outputs: Vec<(u32, XOnlyPublicKey)> = (0..)
+        .zip(&tx.output)
+        .filter(|(_, txout)| txout.script_pubkey.is_p2tr())
+        .filter_map(|(vout, txout)| {
+            let key = XOnlyPublicKey::from_slice(&txout.script_pubkey.as_bytes()[2..]).ok()?;
+            Some((vout, key))
+        })
+        .collect();
+
+    // The outputs paying the same address are derived with an increasing counter, stop at the
+    // first one missing.
+    let mut found = Vec::new();
+    for k in 0.. {
+        let Some(tweak) = output_tweak(&shared_secret, k) else {
+            break;
+        };
+        let Ok(output) = spend_key.add_exp_tweak(secp, &Scalar::from(tweak)) else {
+            break;
+        };
+        let (key, _) = output.x_only_public_key();
+        match outputs
+            .iter()
+            .position(|(_, output_key)| *output_key == key)
+        {
+            Some(pos) => {
+                let (vout, _) = outputs.remove(pos);
+                found.push((vout, tweak));
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+/// Returns the silent payment tweak stored in `psbt_input`, if any.
+fn psbt_input_tweak(psbt_input: &psbt::Input) -> Option<SecretKey> {
+    let key = raw::Key {
+        type_value: PSBT_IN_SP_TWEAK,
+        key: Vec::new(),
+    };
+    SecretKey::from_slice(psbt_input.unknown.get(&key)?).ok()
+}
+
+/// Whether `psbt_input` spends a silent payment output and can be finalized with its key path
+/// signature.
+pub(crate) fn finalize_input(psbt_input: &mut psbt::Input) -> bool {
+    let Some(signature) = psbt_input.tap_key_sig else {
+        return false;
+    };
+    if psbt_input_tweak(psbt_input).is_none() {
+        return false;
+    }
+    let original = core::mem::take(psbt_input);
+    psbt_input.non_witness_utxo = original.non_witness_utxo;
+    psbt_input.witness_utxo = original.witness_utxo;
+    psbt_input.final_script_witness = Some(Witness::p2tr_key_spend(&signature));
+    true
+}
+
+/// Changes to the silent payment outputs of the wallet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// Address the wallet receives silent payments to
+    pub address: Option<SilentPaymentAddress>,
+    /// Outputs paying the address, with the tweak of the spend key unlocking them
+    pub outputs: BTreeMap<OutPoint, SecretKey>,
+    /// Script pubkeys derived for the silent payment addresses the wallet sent to
+    pub sent: BTreeMap<ScriptBuf, SilentPaymentAddress>,
+}
+
+impl Merge for ChangeSet {
+    fn merge(&mut self, other: Self) {
+        if other.address.is_some() {
+            debug_assert!(
+                self.address.is_none() || self.address == other.address,
+                "silent payment address must never change"
+            );
+            self.address = other.address;
+        }
+        self.outputs.extend(other.outputs);
+        self.sent.extend(other.sent);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.address.is_none() && self.outputs.is_empty() && self.sent.is_empty()
+    }
+}
+
+/// Silent payment outputs tracked by the wallet, next to the ones of its descriptors.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReceiverState {
+    /// Scan key of the address, needed to find new outputs.
+    pub(crate) scan_key: Option<SecretKey>,
+    pub(crate) address: Option<SilentPaymentAddress>,
+    pub(crate) outputs: BTreeMap<OutPoint, SecretKey>,
+    /// Silent payment addresses paid by the wallet, by the script pubkey derived for them. A
+    /// replacement spending other inputs must derive them again.
+    pub(crate) sent: BTreeMap<ScriptBuf, SilentPaymentAddress>,
+}
+
+/// An output paying the silent payment address of the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SilentPaymentOutput {
+    /// Reference to the output
+    pub outpoint: OutPoint,
+    /// The output itself
+    pub txout: TxOut,
+    /// Tweak to add to the spend key to spend the output
+    pub tweak: SecretKey,
+    /// The position of the output in the chain
+    pub chain_position: ChainPosition<ConfirmationBlockTime>,
+}
+
+/// Signer for the silent payment outputs of a wallet
+///
+/// It signs the inputs spending the outputs found by the wallet with the spend key of the
+/// wallet's address plus the tweak stored in the PSBT. Add it to the wallet with
+/// [`Wallet::add_signer`].
+#[derive(Debug, Clone)]
+pub struct SilentPaymentSigner {
+    spend_key: SecretKey,
+}
+
+impl SilentPaymentSigner {
+    /// Create a signer from the secret spend key of the address.
+    pub fn new(spend_key: SecretKey) -> Self {
+        Self { spend_key }
+    }
+}
+
+impl SignerCommon for SilentPaymentSigner {
+    fn id(&self, secp: &SecpCtx) -> SignerId {
+        let pubkey = bitcoin::PublicKey::new(self.spend_key.public_key(secp));
+        SignerId::from(pubkey.pubkey_hash().to_raw_hash())
+    }
+}
+
+impl InputSigner for SilentPaymentSigner {
+    fn sign_input(
+        &self,
+        psbt: &mut Psbt,
+        input_index: usize,
+        _sign_options: &SignOptions,
+        secp: &SecpCtx,
+    ) -> Result<(), SignerError> {
+        let psbt_input = psbt
+            .inputs
+            .get(input_index)
+            .ok_or(IndexOutOfBoundsError::new(input_index, psbt.inputs.len()))?;
+        if psbt_input.final_script_sig.is_some()
+            || psbt_input.final_script_witness.is_some()
+            || psbt_input.tap_key_sig.is_some()
+        {
+            return Ok(());
+        }
+        let Some(tweak) = psbt_input_tweak(psbt_input) else {
+            return Ok(());
+        };
+        let secret = self
+            .spend_key
+            .add_tweak(&Scalar::from(tweak))
+            .map_err(|_| SignerError::InvalidKey)?;
+        let keypair = Keypair::from_secret_key(secp, &secret);
+        let (key, _) = keypair.x_only_public_key();
+        // The tweak may be meant for another spend key.
+        match psbt.get_utxo_for(input_index) {
+            Some(txout)
+                if txout.script_pubkey
+                    == ScriptBuf::new_p2tr_tweaked(key.dangerous_assume_tweaked()) => {}
+            _ => return Ok(()),
+        }
+
+        let (sighash, sighash_type) = compute_tap_sighash(psbt, input_index, None)?;
+        let signature = secp.sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair);
+        psbt.inputs[input_index].tap_key_sig = Some(taproot::Signature {
+            signature,
+            sighash_type,
+        });
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Returns the silent payment address of the wallet, if it has one.
+    ///
+    /// See [`CreateParams::silent_payments`](crate::CreateParams::silent_payments).
+    pub fn silent_payment_address(&self) -> Option<SilentPaymentAddress> {
+        self.silent_payments.address
+    }
+
+    /// Iterate over the unspent outputs paying the silent payment address of the wallet.
+    ///
+    /// These outputs aren't returned by [`Wallet::list_unspent`] since they aren't derived from
+    /// the descriptors of the wallet, but they count towards its [`Wallet::balance`] and are
+    /// selected when building transactions.
+    pub fn list_silent_payment_unspent(&self) -> impl Iterator<Item = SilentPaymentOutput> + '_ {
+        self.indexed_graph
+            .graph()
+            .filter_chain_unspents(
+                &self.chain,
+                self.chain.tip().block_id(),
+                CanonicalizationParams::default(),
+                self.silent_payments
+                    .outputs
+                    .iter()
+                    .map(|(&outpoint, &tweak)| (tweak, outpoint)),
+            )
+            .map(|(tweak, txo)| SilentPaymentOutput {
+                outpoint: txo.outpoint,
+                txout: txo.txout,
+                tweak,
+                chain_position: txo.chain_position,
+            })
+    }
+
+    /// Returns the silent payment outputs that may be selected for a transaction built with
+    /// `params`.
+    pub(crate) fn silent_payment_utxos(
+        &self,
+        params: &TxParams,
+        current_height: u32,
+    ) -> Vec<WeightedUtxo> {
+        if self.silent_payments.outputs.is_empty()
+            || params.change_policy == ChangeSpendPolicy::OnlyChange
+        {
+            return Vec::new();
+        }
+        let graph = self.indexed_graph.graph();
+        graph
+            .filter_chain_unspents(
+                &self.chain,
+                self.chain.tip().block_id(),
+                CanonicalizationParams::default(),
+                self.silent_payments
+                    .outputs
+                    .iter()
+                    .map(|(&outpoint, &tweak)| (tweak, outpoint)),
+            )
+            .filter(|(_, txo)| {
+                txo.is_mature(current_height)
+                    && !self.is_outpoint_locked(txo.outpoint)
+                    && !self.is_reserved_by_other_draft(txo.outpoint, params)
+                    && !params.unspendable.contains(&txo.outpoint)
+                    && !params
+                        .utxos
+                        .iter()
+                        .any(|wutxo| wutxo.utxo.outpoint() == txo.outpoint)
+                    && (params.bumping_fee.is_none() || txo.chain_position.is_confirmed())
+            })
+            .map(|(tweak, txo)| {
+                let mut psbt_input = psbt::Input {
+                    witness_utxo: Some(txo.txout),
+                    non_witness_utxo: graph
+                        .get_tx(txo.outpoint.txid)
+                        .map(|tx| tx.as_ref().clone()),
+                    ..Default::default()
+                };
+                psbt_input.unknown.insert(
+                    raw::Key {
+                        type_value: PSBT_IN_SP_TWEAK,
+                        key: Vec::new(),
+                    },
+                    tweak.secret_bytes().to_vec(),
+                );
+                WeightedUtxo {
+                    satisfaction_weight: KEY_SPEND_SATISFACTION_WEIGHT,
+                    utxo: Utxo::Foreign {
+                        outpoint: txo.outpoint,
+                        sequence: params.sequence.unwrap_or(Sequence::ENABLE_RBF_NO_LOCKTIME),
+                        psbt_input: Box::new(psbt_input),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Scan `block` for outputs paying the silent payment address of the wallet and for
+    /// transactions spending them, and insert them into the wallet.
+    ///
+    /// The tweak data of a transaction is taken from `tweaks`, or computed from the outputs it
+    /// spends if they are known to the wallet.
+    pub(crate) fn scan_block(
+        &mut self,
+        block: &Block,
+        height: u32,
+        tweaks: &BTreeMap<Txid, PublicKey>,
+    ) -> super::ChangeSet {
+        let mut changeset = super::ChangeSet::default();
+        let state = &self.silent_payments;
+        if state.scan_key.is_none() && state.outputs.is_empty() {
+            return changeset;
+        }
+
+        let block_txouts: HashMap<OutPoint, &TxOut> = block
+            .txdata
+            .iter()
+            .flat_map(|tx| {
+                let txid = tx.compute_txid();
+                (0..)
+                    .zip(&tx.output)
+                    .map(move |(vout, txout)| (OutPoint::new(txid, vout), txout))
+            })
+            .collect();
+        let prevout = |outpoint: &OutPoint| {
+            block_txouts
+                .get(outpoint)
+                .map(|&txout| txout.clone())
+                .or_else(|| self.indexed_graph.graph().get_txout(*outpoint).cloned())
+        };
+
+        let mut found = BTreeMap::new();
+        let mut txs = Vec::new();
+        for tx in &block.txdata {
+            let txid = tx.compute_txid();
+            let mut is_relevant = tx.input.iter().any(|txin| {
+                state.outputs.contains_key(&txin.previous_output)
+                    || found.contains_key(&txin.previous_output)
+            });
+            if let (Some(scan_key), Some(address)) = (state.scan_key, state.address) {
+                let tweak_data = tweaks
+                    .get(&txid)
+                    .copied()
+                    .or_else(|| input_tweak_data(&self.secp, tx, prevout));
+                if let Some(tweak_data) = tweak_data {
+                    for (vout, tweak) in
+                        scan_tx(&self.secp, &scan_key, &address.spend, tx, &tweak_data)
+                    {
+                        found.insert(OutPoint::new(txid, vout), tweak);
+                        is_relevant = true;
+                    }
+                }
+            }
+            if is_relevant {
+                txs.push((txid, tx.clone()));
+            }
+        }
+
+        let anchor = ConfirmationBlockTime {
+            block_id: BlockId {
+                height,
+                hash: block.block_hash(),
+            },
+            confirmation_time: block.header.time as u64,
+        };
+        for (txid, tx) in txs {
+            changeset.merge(self.indexed_graph.insert_tx(tx).into());
+            changeset.merge(self.indexed_graph.insert_anchor(txid, anchor).into());
+        }
+        self.silent_payments.outputs.extend(found.clone());
+        changeset.merge(
+            ChangeSet {
+                outputs: found,
+                ..Default::default()
+            }
+            .into(),
+        );
+        changeset
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::XOnlyPublicKey;
+
+    const ADDRESS: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+
+    #[test]
+    fn test_address() {
+        let address: SilentPaymentAddress = ADDRESS.parse().unwrap();
+        assert_eq!(address.network, Network::Bitcoin);
+        assert_eq!(address.to_string(), ADDRESS);
+        assert_eq!(
+            ADDRESS.to_uppercase().parse::<SilentPaymentAddress>(),
+            Ok(address)
+        );
+        assert!(!address.is_valid_for_network(Network::Signet));
+
+        let testnet = SilentPaymentAddress::new(address.scan, address.spend, Network::Signet);
+        assert!(testnet.to_string().starts_with("tsp1q"));
+        assert!(testnet.is_valid_for_network(Network::Testnet4));
+        assert_eq!(
+            testnet.to_string().parse::<SilentPaymentAddress>(),
+            Ok(SilentPaymentAddress {
+                network: Network::Testnet,
+                ..testnet
+            })
+        );
+
+        // Version 0 must use bech32m and carry exactly two keys, later versions may add data.
+        let encode = |version: Fe32, payload: &[u8], hrp: &Hrp| -> String {
+            payload
+                .iter()
+                .copied()
+                .bytes_to_fes()
+                .with_checksum::<Bech32m>(hrp)
+                .with_witness_version(version)
+                .chars()
+                .collect()
+        };
+        let mut payload = [address.scan.serialize(), address.spend.serialize()].concat();
+        assert_eq!(encode(Fe32::Q, &payload, &HRP_MAINNET), ADDRESS);
+        payload.push(0);
+        assert_eq!(
+            encode(Fe32::Q, &payload, &HRP_MAINNET).parse::<SilentPaymentAddress>(),
+            Err(SilentPaymentError::InvalidLength(67))
+        );
+        assert_eq!(
+            encode(Fe32::P, &payload, &HRP_MAINNET).parse::<SilentPaymentAddress>(),
+            Ok(address)
+        );
+        assert_eq!(
+            encode(Fe32::L, &payload, &HRP_MAINNET).parse::<SilentPaymentAddress>(),
+            Err(SilentPaymentError::InvalidVersion(31))
+        );
+        assert_eq!(
+            encode(Fe32::Q, &payload[..66], &Hrp::parse_unchecked("bc"))
+                .parse::<SilentPaymentAddress>(),
+            Err(SilentPaymentError::UnknownHrp("bc".into()))
+        );
+        assert!(matches!(
+            "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".parse::<SilentPaymentAddress>(),
+            Err(SilentPaymentError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_derive_output_scripts() {
+        // "Simple send: two inputs" test vector of BIP352.
+        let secp = SecpCtx::new();
+        let input = |txid: &str, secret: &str| InputKey {
+            outpoint: OutPoint::new(txid.parse().unwrap(), 0),
+            secret: SecretKey::from_slice(&<[u8; 32]>::from_hex(secret).unwrap()).unwrap(),
+            is_taproot: false,
+        };
+        let inputs = [
+            input(
+                "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+                "eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1",
+            ),
+            input(
+                "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d",
+                "93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16",
+            ),
+        ];
+        let address: SilentPaymentAddress = ADDRESS.parse().unwrap();
+        let expected = ScriptBuf::new_p2tr_tweaked(
+            XOnlyPublicKey::from_str(
+                "3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1",
+            )
+            .unwrap()
+            .dangerous_assume_tweaked(),
+        );
+        assert_eq!(
+            derive_output_scripts(&secp, &inputs, &[address]).unwrap(),
+            vec![expected.clone()]
+        );
+
+        // The order of the inputs doesn't matter, but outputs to the same address differ.
+        let reversed = [inputs[1], inputs[0]];
+        let scripts = derive_output_scripts(&secp, &reversed, &[address, address]).unwrap();
+        assert_eq!(scripts[0], expected);
+        assert_ne!(scripts[1], expected);
+
+        // Keys summing up to zero can't be used.
+        let negated = InputKey {
+            secret: inputs[0].secret.negate(),
+            ..inputs[1]
+        };
+        assert_eq!(
+            derive_output_scripts(&secp, &[inputs[0], negated], &[address]),
+            Err(SilentPaymentError::InvalidInputKeys)
+        );
+    }
+
+    #[test]
+    fn test_scan_tx() {
+        let secp = SecpCtx::new();
+        let secret =
+            |hex: &str| SecretKey::from_slice(&<[u8; 32]>::from_hex(hex).unwrap()).unwrap();
+        let scan_key = secret("0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c");
+        let spend_key = secret("9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3");
+        let address: SilentPaymentAddress = ADDRESS.parse().unwrap();
+        assert_eq!(address.scan, scan_key.public_key(&secp));
+        assert_eq!(address.spend, spend_key.public_key(&secp));
+
+        // Spend a P2WPKH and a P2TR output.
+        let wpkh_secret =
+            secret("eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1");
+        let tr_secret = secret("93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16");
+        let wpkh_key = bitcoin::CompressedPublicKey(wpkh_secret.public_key(&secp));
+        let (tr_key, _) = tr_secret.x_only_public_key(&secp);
+        let prevouts = [
+            TxOut {
+                value: bitcoin::Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&wpkh_key.wpubkey_hash()),
+            },
+            TxOut {
+                value: bitcoin::Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(tr_key.dangerous_assume_tweaked()),
+            },
+        ];
+        let inputs = [
+            InputKey {
+                outpoint: OutPoint::new(
+                    "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+                        .parse()
+                        .unwrap(),
+                    0,
+                ),
+                secret: wpkh_secret,
+                is_taproot: false,
+            },
+            InputKey {
+                outpoint: OutPoint::new(
+                    "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d"
+                        .parse()
+                        .unwrap(),
+                    0,
+                ),
+                secret: tr_secret,
+                is_taproot: true,
+            },
+        ];
+        let scripts = derive_output_scripts(&secp, &inputs, &[address, address]).unwrap();
+        let mut tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![
+                TxIn {
+                    previous_output: inputs[0].outpoint,
+                    witness: Witness::from_slice(&[vec![0; 71], wpkh_key.to_bytes().to_vec()]),
+                    ..Default::default()
+                },
+                TxIn {
+                    previous_output: inputs[1].outpoint,
+                    witness: Witness::from_slice(&[vec![0; 64]]),
+                    ..Default::default()
+                },
+            ],
+            output: vec![
+                TxOut {
+                    value: bitcoin::Amount::from_sat(5_000),
+                    script_pubkey: prevouts[0].script_pubkey.clone(),
+                },
+                TxOut {
+                    value: bitcoin::Amount::from_sat(5_000),
+                    script_pubkey: scripts[1].clone(),
+                },
+                TxOut {
+                    value: bitcoin::Amount::from_sat(5_000),
+                    script_pubkey: scripts[0].clone(),
+                },
+            ],
+        };
+        let prevout = |outpoint: &OutPoint| {
+            inputs
+                .iter()
+                .position(|input| input.outpoint == *outpoint)
+                .map(|i| prevouts[i].clone())
+        };
+        let tweak_data = input_tweak_data(&secp, &tx, prevout).unwrap();
+
+        let found = scan_tx(&secp, &scan_key, &address.spend, &tx, &tweak_data);
+        assert_eq!(
+            found.iter().map(|(vout, _)| *vout).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        for (vout, tweak) in found {
+            let secret = spend_key.add_tweak(&Scalar::from(tweak)).unwrap();
+            let (key, _) = secret.x_only_public_key(&secp);
+            assert_eq!(
+                tx.output[vout as usize].script_pubkey,
+                ScriptBuf::new_p2tr_tweaked(key.dangerous_assume_tweaked())
+            );
+        }
+
+        // Another scan key finds nothing.
+        assert!(scan_tx(&secp, &spend_key, &address.spend, &tx, &tweak_data).is_empty());
+
+        // Outputs are found in order, the second one can't be found without the first.
+        tx.output.pop();
+        assert!(scan_tx(&secp, &scan_key, &address.spend, &tx, &tweak_data).is_empty());
+
+        // Spending an output of an unknown segwit version makes the transaction ineligible.
+        let prevout = |_: &OutPoint| {
+            Some(TxOut {
+                value: bitcoin::Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::from_hex("52020000").unwrap(),
+            })
+        };
+        assert_eq!(input_tweak_data(&secp, &tx, prevout), None);
+    }
+}
@@ -48,13 +48,30 @@ use bitcoin::{
     TxIn, TxOut, Txid, Weight,
 };
 use rand_core::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use super::coin_selection::CoinSelectionAlgorithm;
+use super::bip21::{Bip21Error, PaymentUri};
+use super::coin_selection::{CoinSelectionAlgorithm, CoinSelectionContext};
+use super::reservations::Reservation;
+use super::silent_payments::{SilentPaymentAddress, SilentPaymentError};
 use super::utils::shuffle_slice;
 use super::{CreateTxError, Wallet};
 use crate::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "std")]
+use crate::psbt::PsbtV2;
 use crate::{KeychainKind, LocalOutput, Utxo, WeightedUtxo};
 
+/// Version of TRUC (Topologically Restricted Until Confirmation) transactions, see [BIP431].
+///
+/// [BIP431]: https://github.com/bitcoin/bips/blob/master/bip-0431.mediawiki
+pub const TRUC_VERSION: Version = Version(3);
+
+/// Maximum virtual size of a TRUC transaction.
+pub const TRUC_MAX_VSIZE: u64 = 10_000;
+
+/// Maximum virtual size of a TRUC transaction spending an unconfirmed TRUC transaction.
+pub const TRUC_CHILD_MAX_VSIZE: u64 = 1_000;
+
 /// A transaction builder
 ///
 /// A `TxBuilder` is created by calling [`build_tx`] or [`build_fee_bump`] on a wallet. After
@@ -117,10 +134,40 @@ pub struct TxBuilder<'a, Cs> {
 }
 
 /// The parameters for transaction creation sans coin selection algorithm.
-//TODO: TxParams should eventually be exposed publicly.
-#[derive(Default, Debug, Clone)]
-pub(crate) struct TxParams {
+///
+/// `TxParams` is a reusable transaction template: it can be built without access to a [`Wallet`],
+/// serialized, stored or sent elsewhere and later turned into a [`TxBuilder`] with
+/// [`Wallet::build_tx_from_params`]. The setters mirror the ones on [`TxBuilder`] that don't need
+/// to query the wallet.
+///
+/// ```
+/// # use bdk_wallet::*;
+/// # use bdk_wallet::tx_builder::*;
+/// # use bitcoin::*;
+/// # use core::str::FromStr;
+/// # let mut wallet = doctest_wallet!();
+/// # let addr = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt").unwrap().assume_checked();
+/// let mut params = TxParams::new();
+/// params
+///     .add_recipient(addr.script_pubkey(), Amount::from_sat(50_000))
+///     .fee_rate(FeeRate::from_sat_per_vb(5).expect("valid feerate"));
+///
+/// // The template can be moved around as JSON (or any other serde format).
+/// let json = serde_json::to_string(&params)?;
+/// let params: TxParams = serde_json::from_str(&json)?;
+///
+/// let psbt = wallet.build_tx_from_params(params).finish()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// Note that [`TxOrdering::Custom`] holds closures and therefore can't be serialized. Trying to
+/// serialize a template with a custom ordering returns an error.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TxParams {
     pub(crate) recipients: Vec<(ScriptBuf, Amount)>,
+    pub(crate) silent_payment_recipients: Vec<(SilentPaymentAddress, Amount)>,
+    pub(crate) subtract_fee_from: Vec<usize>,
     pub(crate) drain_wallet: bool,
     pub(crate) drain_to: Option<ScriptBuf>,
     pub(crate) fee_policy: Option<FeePolicy>,
@@ -128,30 +175,54 @@ pub(crate) struct TxParams {
     pub(crate) external_policy_path: Option<BTreeMap<String, Vec<usize>>>,
     pub(crate) utxos: Vec<WeightedUtxo>,
     pub(crate) unspendable: HashSet<OutPoint>,
+    pub(crate) min_confirmations: Option<u32>,
     pub(crate) manually_selected_only: bool,
     pub(crate) sighash: Option<psbt::PsbtSighashType>,
     pub(crate) ordering: TxOrdering,
     pub(crate) locktime: Option<absolute::LockTime>,
     pub(crate) sequence: Option<Sequence>,
+    pub(crate) anti_fee_sniping: bool,
     pub(crate) version: Option<Version>,
     pub(crate) change_policy: ChangeSpendPolicy,
     pub(crate) only_witness_utxo: bool,
     pub(crate) add_global_xpubs: bool,
     pub(crate) include_output_redeem_witness_script: bool,
     pub(crate) bumping_fee: Option<PreviousFee>,
+    pub(crate) package_fee: Option<PackageFee>,
+    pub(crate) reservation: Option<Reservation>,
     pub(crate) current_height: Option<absolute::LockTime>,
     pub(crate) allow_dust: bool,
+    pub(crate) allow_non_standard: bool,
+    pub(crate) data_carrier_policy: DataCarrierPolicy,
+    pub(crate) avoid_partial_spends: Option<Amount>,
+    pub(crate) avoid_mixed_script_types: bool,
+    pub(crate) coin_selection_context: CoinSelectionContext,
 }
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) struct PreviousFee {
+/// The fee paid by the transaction being replaced when bumping fees.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviousFee {
+    /// Absolute fee of the original transaction
     pub absolute: Amount,
+    /// Feerate of the original transaction
     pub rate: FeeRate,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum FeePolicy {
+/// The fee and weight of the unconfirmed ancestors a child transaction pays for (CPFP).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageFee {
+    /// Total absolute fee paid by the ancestors
+    pub absolute: Amount,
+    /// Total weight of the ancestors
+    pub weight: Weight,
+}
+
+/// The policy used to determine the fee of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeePolicy {
+    /// Pay a fee proportional to the size of the transaction
     FeeRate(FeeRate),
+    /// Pay an absolute fee
     FeeAmount(Amount),
 }
 
@@ -161,6 +232,340 @@ impl Default for FeePolicy {
     }
 }
 
+impl TxParams {
+    /// Create an empty set of parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the recipients of the transaction.
+    pub fn recipients(&self) -> &[(ScriptBuf, Amount)] {
+        &self.recipients
+    }
+
+    /// Returns the fee policy, if one was set.
+    pub fn fee_policy(&self) -> Option<FeePolicy> {
+        self.fee_policy
+    }
+
+    /// Returns the UTXOs that **must** be spent.
+    pub fn utxos(&self) -> &[WeightedUtxo] {
+        &self.utxos
+    }
+
+    /// See [`TxBuilder::fee_rate`].
+    pub fn fee_rate(&mut self, fee_rate: FeeRate) -> &mut Self {
+        self.fee_policy = Some(FeePolicy::FeeRate(fee_rate));
+        self
+    }
+
+    /// See [`TxBuilder::fee_absolute`].
+    pub fn fee_absolute(&mut self, fee_amount: Amount) -> &mut Self {
+        self.fee_policy = Some(FeePolicy::FeeAmount(fee_amount));
+        self
+    }
+
+    /// See [`TxBuilder::policy_path`].
+    pub fn policy_path(
+        &mut self,
+        policy_path: BTreeMap<String, Vec<usize>>,
+        keychain: KeychainKind,
+    ) -> &mut Self {
+        let to_update = match keychain {
+            KeychainKind::Internal => &mut self.internal_policy_path,
+            KeychainKind::External => &mut self.external_policy_path,
+        };
+
+        *to_update = Some(policy_path);
+        self
+    }
+
+    /// See [`TxBuilder::add_foreign_utxo`].
+    pub fn add_foreign_utxo(
+        &mut self,
+        outpoint: OutPoint,
+        psbt_input: psbt::Input,
+        satisfaction_weight: Weight,
+    ) -> Result<&mut Self, AddForeignUtxoError> {
+        self.add_foreign_utxo_with_sequence(
+            outpoint,
+            psbt_input,
+            satisfaction_weight,
+            Sequence::MAX,
+        )
+    }
+
+    /// See [`TxBuilder::add_foreign_utxo_with_sequence`].
+    pub fn add_foreign_utxo_with_sequence(
+        &mut self,
+        outpoint: OutPoint,
+        psbt_input: psbt::Input,
+        satisfaction_weight: Weight,
+        sequence: Sequence,
+    ) -> Result<&mut Self, AddForeignUtxoError> {
+        if psbt_input.witness_utxo.is_none() {
+            match psbt_input.non_witness_utxo.as_ref() {
+                Some(tx) => {
+                    if tx.compute_txid() != outpoint.txid {
+                        return Err(AddForeignUtxoError::InvalidTxid {
+                            input_txid: tx.compute_txid(),
+                            foreign_utxo: outpoint,
+                        });
+                    }
+                    if tx.output.len() <= outpoint.vout as usize {
+                        return Err(AddForeignUtxoError::InvalidOutpoint(outpoint));
+                    }
+                }
+                None => {
+                    return Err(AddForeignUtxoError::MissingUtxo);
+                }
+            }
+        }
+
+        let mut existing_index: Option<usize> = None;
+
+        for (idx, wutxo) in self.utxos.iter().enumerate() {
+            if wutxo.utxo.outpoint() == outpoint {
+                match wutxo.utxo {
+                    Utxo::Local(..) => return Ok(self),
+                    Utxo::Foreign { .. } => {
+                        existing_index = Some(idx);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(idx) = existing_index {
+            self.utxos.remove(idx);
+        }
+
+        self.utxos.push(WeightedUtxo {
+            satisfaction_weight,
+            utxo: Utxo::Foreign {
+                outpoint,
+                sequence,
+                psbt_input: Box::new(psbt_input),
+            },
+        });
+
+        Ok(self)
+    }
+
+    /// See [`TxBuilder::manually_selected_only`].
+    pub fn manually_selected_only(&mut self) -> &mut Self {
+        self.manually_selected_only = true;
+        self
+    }
+
+    /// See [`TxBuilder::unspendable`].
+    pub fn unspendable(&mut self, unspendable: Vec<OutPoint>) -> &mut Self {
+        self.unspendable = unspendable.into_iter().collect();
+        self
+    }
+
+    /// See [`TxBuilder::add_unspendable`].
+    pub fn add_unspendable(&mut self, unspendable: OutPoint) -> &mut Self {
+        self.unspendable.insert(unspendable);
+        self
+    }
+
+    /// See [`TxBuilder::sighash`].
+    pub fn sighash(&mut self, sighash: psbt::PsbtSighashType) -> &mut Self {
+        self.sighash = Some(sighash);
+        self
+    }
+
+    /// See [`TxBuilder::ordering`].
+    pub fn ordering(&mut self, ordering: TxOrdering) -> &mut Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// See [`TxBuilder::nlocktime`].
+    pub fn nlocktime(&mut self, locktime: absolute::LockTime) -> &mut Self {
+        self.locktime = Some(locktime);
+        self
+    }
+
+    /// See [`TxBuilder::anti_fee_sniping`].
+    pub fn anti_fee_sniping(&mut self) -> &mut Self {
+        self.anti_fee_sniping = true;
+        self
+    }
+
+    /// See [`TxBuilder::version`].
+    pub fn version(&mut self, version: i32) -> &mut Self {
+        self.version = Some(Version(version));
+        self
+    }
+
+    /// See [`TxBuilder::truc`].
+    pub fn truc(&mut self) -> &mut Self {
+        self.version = Some(TRUC_VERSION);
+        self
+    }
+
+    /// See [`TxBuilder::do_not_spend_change`].
+    pub fn do_not_spend_change(&mut self) -> &mut Self {
+        self.change_policy = ChangeSpendPolicy::ChangeForbidden;
+        self
+    }
+
+    /// See [`TxBuilder::only_spend_change`].
+    pub fn only_spend_change(&mut self) -> &mut Self {
+        self.change_policy = ChangeSpendPolicy::OnlyChange;
+        self
+    }
+
+    /// See [`TxBuilder::change_policy`].
+    pub fn change_policy(&mut self, change_policy: ChangeSpendPolicy) -> &mut Self {
+        self.change_policy = change_policy;
+        self
+    }
+
+    /// See [`TxBuilder::only_witness_utxo`].
+    pub fn only_witness_utxo(&mut self) -> &mut Self {
+        self.only_witness_utxo = true;
+        self
+    }
+
+    /// See [`TxBuilder::include_output_redeem_witness_script`].
+    pub fn include_output_redeem_witness_script(&mut self) -> &mut Self {
+        self.include_output_redeem_witness_script = true;
+        self
+    }
+
+    /// See [`TxBuilder::add_global_xpubs`].
+    pub fn add_global_xpubs(&mut self) -> &mut Self {
+        self.add_global_xpubs = true;
+        self
+    }
+
+    /// See [`TxBuilder::drain_wallet`].
+    pub fn drain_wallet(&mut self) -> &mut Self {
+        self.drain_wallet = true;
+        self
+    }
+
+    /// See [`TxBuilder::set_exact_sequence`].
+    pub fn set_exact_sequence(&mut self, n_sequence: Sequence) -> &mut Self {
+        self.sequence = Some(n_sequence);
+        self
+    }
+
+    /// See [`TxBuilder::current_height`].
+    pub fn current_height(&mut self, height: u32) -> &mut Self {
+        self.current_height =
+            Some(absolute::LockTime::from_height(height).expect("Invalid height"));
+        self
+    }
+
+    /// See [`TxBuilder::avoid_partial_spends`].
+    pub fn avoid_partial_spends(&mut self, max_extra_fee: Amount) -> &mut Self {
+        self.avoid_partial_spends = Some(max_extra_fee);
+        self
+    }
+
+    /// See [`TxBuilder::avoid_mixed_script_types`].
+    pub fn avoid_mixed_script_types(&mut self) -> &mut Self {
+        self.avoid_mixed_script_types = true;
+        self
+    }
+
+    /// See [`TxBuilder::coin_selection_context`].
+    pub fn coin_selection_context(&mut self, context: CoinSelectionContext) -> &mut Self {
+        self.coin_selection_context = context;
+        self
+    }
+
+    /// See [`TxBuilder::diagnostics`].
+    pub fn diagnostics(&mut self) -> &mut Self {
+        self.coin_selection_context.diagnostics = true;
+        self
+    }
+
+    /// See [`TxBuilder::allow_dust`].
+    pub fn allow_dust(&mut self, allow_dust: bool) -> &mut Self {
+        self.allow_dust = allow_dust;
+        self
+    }
+
+    /// See [`TxBuilder::allow_non_standard`].
+    pub fn allow_non_standard(&mut self, allow_non_standard: bool) -> &mut Self {
+        self.allow_non_standard = allow_non_standard;
+        self
+    }
+
+    /// See [`TxBuilder::data_carrier_policy`].
+    pub fn data_carrier_policy(&mut self, policy: DataCarrierPolicy) -> &mut Self {
+        self.data_carrier_policy = policy;
+        self
+    }
+
+    /// See [`TxBuilder::set_recipients`].
+    pub fn set_recipients(&mut self, recipients: Vec<(ScriptBuf, Amount)>) -> &mut Self {
+        self.recipients = recipients;
+        self
+    }
+
+    /// See [`TxBuilder::add_recipient`].
+    pub fn add_recipient(
+        &mut self,
+        script_pubkey: impl Into<ScriptBuf>,
+        amount: Amount,
+    ) -> &mut Self {
+        self.recipients.push((script_pubkey.into(), amount));
+        self
+    }
+
+    /// See [`TxBuilder::add_silent_payment_recipient`].
+    pub fn add_silent_payment_recipient(
+        &mut self,
+        address: SilentPaymentAddress,
+        amount: Amount,
+    ) -> &mut Self {
+        self.silent_payment_recipients.push((address, amount));
+        self
+    }
+
+    /// See [`TxBuilder::add_data`].
+    pub fn add_data<T: AsRef<PushBytes>>(&mut self, data: &T) -> &mut Self {
+        let script = ScriptBuf::new_op_return(data);
+        self.add_recipient(script, Amount::ZERO)
+    }
+
+    /// See [`TxBuilder::add_anchor`].
+    pub fn add_anchor(&mut self, amount: Amount) -> &mut Self {
+        self.add_recipient(ScriptBuf::new_p2a(), amount)
+    }
+
+    /// See [`TxBuilder::drain_to`].
+    pub fn drain_to(&mut self, script_pubkey: ScriptBuf) -> &mut Self {
+        self.drain_to = Some(script_pubkey);
+        self
+    }
+
+    /// See [`TxBuilder::subtract_fee_from`].
+    pub fn subtract_fee_from(&mut self, recipient_indexes: &[usize]) -> &mut Self {
+        self.subtract_fee_from = recipient_indexes.to_vec();
+        self
+    }
+
+    /// See [`TxBuilder::reserve_utxos`].
+    pub fn reserve_utxos(
+        &mut self,
+        draft_id: impl Into<String>,
+        expiry_height: Option<u32>,
+    ) -> &mut Self {
+        self.reservation = Some(Reservation {
+            draft_id: draft_id.into(),
+            expiry_height,
+        });
+        self
+    }
+}
+
 // Methods supported for any CoinSelectionAlgorithm.
 impl<'a, Cs> TxBuilder<'a, Cs> {
     /// Set a custom fee rate.
@@ -173,8 +578,12 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// Note that this is really a minimum feerate -- it's possible to
     /// overshoot it slightly since adding a change output to drain the remaining
     /// excess might not be viable.
+    ///
+    /// When spending unconfirmed outputs whose ancestors pay less than `fee_rate`, the
+    /// transaction also pays for the difference so that the whole package reaches `fee_rate`.
+    /// See [`TxPreview::package_fee_rate`](crate::TxPreview::package_fee_rate).
     pub fn fee_rate(&mut self, fee_rate: FeeRate) -> &mut Self {
-        self.params.fee_policy = Some(FeePolicy::FeeRate(fee_rate));
+        self.params.fee_rate(fee_rate);
         self
     }
 
@@ -188,7 +597,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// overshoot it slightly since adding a change output to drain the remaining
     /// excess might not be viable.
     pub fn fee_absolute(&mut self, fee_amount: Amount) -> &mut Self {
-        self.params.fee_policy = Some(FeePolicy::FeeAmount(fee_amount));
+        self.params.fee_absolute(fee_amount);
         self
     }
 
@@ -260,12 +669,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         policy_path: BTreeMap<String, Vec<usize>>,
         keychain: KeychainKind,
     ) -> &mut Self {
-        let to_update = match keychain {
-            KeychainKind::Internal => &mut self.params.internal_policy_path,
-            KeychainKind::External => &mut self.params.external_policy_path,
-        };
-
-        *to_update = Some(policy_path);
+        self.params.policy_path(policy_path, keychain);
         self
     }
 
@@ -278,6 +682,9 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// the "UTXOs" and the "unspendable" list, it will be spent.
     ///
     /// If a UTXO is inserted multiple times, only the final insertion will take effect.
+    ///
+    /// Unspent pay-to-anchor (P2A) outputs of the wallet's transactions can be added too, see
+    /// [`Wallet::list_unspent_anchors`].
     pub fn add_utxos(&mut self, outpoints: &[OutPoint]) -> Result<&mut Self, AddUtxoError> {
         // Canonicalize once, instead of once for every call to `get_utxo`.
         let unspent: HashMap<OutPoint, LocalOutput> = self
@@ -285,6 +692,15 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
             .list_unspent()
             .map(|output| (output.outpoint, output))
             .collect();
+        let anchors: HashMap<OutPoint, WeightedUtxo> =
+            if outpoints.iter().all(|op| unspent.contains_key(op)) {
+                HashMap::new()
+            } else {
+                self.wallet
+                    .list_unspent_anchors()
+                    .map(|anchor| (anchor.utxo.outpoint(), anchor))
+                    .collect()
+            };
 
         // Ensure that only unique outpoints are added, but keep insertion order.
         let mut visited = <HashSet<OutPoint>>::new();
@@ -293,6 +709,9 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
             .iter()
             .filter(|&&op| visited.insert(op))
             .map(|&op| -> Result<_, AddUtxoError> {
+                if let Some(anchor) = anchors.get(&op) {
+                    return Ok(anchor.clone());
+                }
                 let output = unspent
                     .get(&op)
                     .cloned()
@@ -408,52 +827,12 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         satisfaction_weight: Weight,
         sequence: Sequence,
     ) -> Result<&mut Self, AddForeignUtxoError> {
-        if psbt_input.witness_utxo.is_none() {
-            match psbt_input.non_witness_utxo.as_ref() {
-                Some(tx) => {
-                    if tx.compute_txid() != outpoint.txid {
-                        return Err(AddForeignUtxoError::InvalidTxid {
-                            input_txid: tx.compute_txid(),
-                            foreign_utxo: outpoint,
-                        });
-                    }
-                    if tx.output.len() <= outpoint.vout as usize {
-                        return Err(AddForeignUtxoError::InvalidOutpoint(outpoint));
-                    }
-                }
-                None => {
-                    return Err(AddForeignUtxoError::MissingUtxo);
-                }
-            }
-        }
-
-        let mut existing_index: Option<usize> = None;
-
-        for (idx, wutxo) in self.params.utxos.iter().enumerate() {
-            if wutxo.utxo.outpoint() == outpoint {
-                match wutxo.utxo {
-                    Utxo::Local(..) => return Ok(self),
-                    Utxo::Foreign { .. } => {
-                        existing_index = Some(idx);
-                        break;
-                    }
-                }
-            }
-        }
-
-        if let Some(idx) = existing_index {
-            self.params.utxos.remove(idx);
-        }
-
-        self.params.utxos.push(WeightedUtxo {
+        self.params.add_foreign_utxo_with_sequence(
+            outpoint,
+            psbt_input,
             satisfaction_weight,
-            utxo: Utxo::Foreign {
-                outpoint,
-                sequence,
-                psbt_input: Box::new(psbt_input),
-            },
-        });
-
+            sequence,
+        )?;
         Ok(self)
     }
 
@@ -464,7 +843,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// [`add_utxo`]: Self::add_utxo
     pub fn manually_selected_only(&mut self) -> &mut Self {
-        self.params.manually_selected_only = true;
+        self.params.manually_selected_only();
         self
     }
 
@@ -473,7 +852,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// It's important to note that the "must-be-spent" utxos added with [`TxBuilder::add_utxo`]
     /// have priority over these. See the docs of the two linked methods for more details.
     pub fn unspendable(&mut self, unspendable: Vec<OutPoint>) -> &mut Self {
-        self.params.unspendable = unspendable.into_iter().collect();
+        self.params.unspendable(unspendable);
         self
     }
 
@@ -482,7 +861,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// It's important to note that the "must-be-spent" utxos added with [`TxBuilder::add_utxo`]
     /// have priority over this. See the docs of the two linked methods for more details.
     pub fn add_unspendable(&mut self, unspendable: OutPoint) -> &mut Self {
-        self.params.unspendable.insert(unspendable);
+        self.params.add_unspendable(unspendable);
         self
     }
 
@@ -513,6 +892,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         for op in to_exclude {
             self.params.unspendable.insert(op);
         }
+        self.params.min_confirmations = self.params.min_confirmations.max(Some(min_confirms));
         self
     }
 
@@ -529,7 +909,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// **Use this option very carefully**
     pub fn sighash(&mut self, sighash: psbt::PsbtSighashType) -> &mut Self {
-        self.params.sighash = Some(sighash);
+        self.params.sighash(sighash);
         self
     }
 
@@ -540,7 +920,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// output and input vectors respectively. If algorithmically selected UTXOs are included, they
     /// will be placed after all the manually selected ones in the transaction's input vector.
     pub fn ordering(&mut self, ordering: TxOrdering) -> &mut Self {
-        self.params.ordering = ordering;
+        self.params.ordering(ordering);
         self
     }
 
@@ -548,7 +928,26 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// This can cause conflicts if the wallet's descriptors contain an "after" (OP_CLTV) operator.
     pub fn nlocktime(&mut self, locktime: absolute::LockTime) -> &mut Self {
-        self.params.locktime = Some(locktime);
+        self.params.nlocktime(locktime);
+        self
+    }
+
+    /// Discourage fee sniping the same way Bitcoin Core does
+    ///
+    /// By default the nLockTime is set to the current height. With this option the nLockTime is
+    /// sometimes set up to 99 blocks further back, and when every input is a confirmed taproot
+    /// output of this wallet, half of the time the nLockTime is left at `0` and one input instead
+    /// gets a relative timelock matching its confirmation depth, as described in [BIP326].
+    ///
+    /// This has no effect if a nLockTime or nSequence is set with [`TxBuilder::nlocktime`] or
+    /// [`TxBuilder::set_exact_sequence`], or if the wallet's descriptors require an "after"
+    /// (OP_CLTV) timelock. A relative timelock is never used when they require an "older"
+    /// (OP_CSV) timelock, when a version lower than `2` is requested, or when an input is
+    /// unconfirmed or buried deeper than `65535` blocks.
+    ///
+    /// [BIP326]: https://github.com/bitcoin/bips/blob/master/bip-0326.mediawiki
+    pub fn anti_fee_sniping(&mut self) -> &mut Self {
+        self.params.anti_fee_sniping();
         self
     }
 
@@ -556,8 +955,32 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// The `version` should always be greater than `0` and greater than `1` if the wallet's
     /// descriptors contain an "older" (OP_CSV) operator.
+    ///
+    /// Version `3` transactions are TRUC transactions, see [`TxBuilder::truc`].
     pub fn version(&mut self, version: i32) -> &mut Self {
-        self.params.version = Some(Version(version));
+        self.params.version(version);
+        self
+    }
+
+    /// Build a TRUC (Topologically Restricted Until Confirmation) transaction
+    ///
+    /// This sets the version to [`TRUC_VERSION`] and makes the builder follow the [BIP431]
+    /// topology rules:
+    ///
+    /// - The transaction can't be larger than [`TRUC_MAX_VSIZE`], or [`TRUC_CHILD_MAX_VSIZE`] if
+    ///   it spends an unconfirmed transaction.
+    /// - It can only spend the outputs of a single unconfirmed transaction, which must be a TRUC
+    ///   transaction without unconfirmed ancestors and without other unconfirmed descendants.
+    ///
+    /// Coin selection only picks unconfirmed outputs of the unconfirmed parent of a manually
+    /// selected UTXO, if any. Manually selected UTXOs breaking the rules make [`finish`] fail with
+    /// [`CreateTxError::Truc`]. Conversely, non-TRUC transactions never spend unconfirmed outputs
+    /// of TRUC transactions.
+    ///
+    /// [BIP431]: https://github.com/bitcoin/bips/blob/master/bip-0431.mediawiki
+    /// [`finish`]: Self::finish
+    pub fn truc(&mut self) -> &mut Self {
+        self.params.truc();
         self
     }
 
@@ -567,7 +990,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// [`TxBuilder::unspendable`]. This method assumes the presence of an internal
     /// keychain, otherwise it has no effect.
     pub fn do_not_spend_change(&mut self) -> &mut Self {
-        self.params.change_policy = ChangeSpendPolicy::ChangeForbidden;
+        self.params.do_not_spend_change();
         self
     }
 
@@ -577,7 +1000,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// [`TxBuilder::unspendable`]. This method assumes the presence of an internal
     /// keychain, otherwise it has no effect.
     pub fn only_spend_change(&mut self) -> &mut Self {
-        self.params.change_policy = ChangeSpendPolicy::OnlyChange;
+        self.params.only_spend_change();
         self
     }
 
@@ -585,7 +1008,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// [`TxBuilder::only_spend_change`] for some shortcuts. This method assumes the presence
     /// of an internal keychain, otherwise it has no effect.
     pub fn change_policy(&mut self, change_policy: ChangeSpendPolicy) -> &mut Self {
-        self.params.change_policy = change_policy;
+        self.params.change_policy(change_policy);
         self
     }
 
@@ -595,7 +1018,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// This reduces the size of the PSBT, but some signers might reject them due to the lack of
     /// the `non_witness_utxo`.
     pub fn only_witness_utxo(&mut self) -> &mut Self {
-        self.params.only_witness_utxo = true;
+        self.params.only_witness_utxo();
         self
     }
 
@@ -604,7 +1027,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// This is useful for signers which always require it, like ColdCard hardware wallets.
     pub fn include_output_redeem_witness_script(&mut self) -> &mut Self {
-        self.params.include_output_redeem_witness_script = true;
+        self.params.include_output_redeem_witness_script();
         self
     }
 
@@ -614,14 +1037,14 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// This is useful for offline signers that take part to a multisig. Some hardware wallets like
     /// BitBox and ColdCard are known to require this.
     pub fn add_global_xpubs(&mut self) -> &mut Self {
-        self.params.add_global_xpubs = true;
+        self.params.add_global_xpubs();
         self
     }
 
     /// Spend all the available inputs. This respects filters like [`TxBuilder::unspendable`] and
     /// the change policy.
     pub fn drain_wallet(&mut self) -> &mut Self {
-        self.params.drain_wallet = true;
+        self.params.drain_wallet();
         self
     }
 
@@ -644,7 +1067,7 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// This can cause conflicts if the wallet's descriptors contain an
     /// "older" (OP_CSV) operator and the given `nsequence` is lower than the CSV value.
     pub fn set_exact_sequence(&mut self, n_sequence: Sequence) -> &mut Self {
-        self.params.sequence = Some(n_sequence);
+        self.params.set_exact_sequence(n_sequence);
         self
     }
 
@@ -660,8 +1083,49 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     ///
     /// In both cases, if you don't provide a current height, we use the last sync height.
     pub fn current_height(&mut self, height: u32) -> &mut Self {
-        self.params.current_height =
-            Some(absolute::LockTime::from_height(height).expect("Invalid height"));
+        self.params.current_height(height);
+        self
+    }
+
+    /// Avoid partial spends of UTXOs sent to the same address.
+    ///
+    /// Spending only some of the UTXOs received by an address links the others to the transaction
+    /// anyway. With this option the coin selection spends all the UTXOs of an address or none of
+    /// them, as long as it doesn't cost more than `max_extra_fee` of additional fee. See
+    /// [`AvoidPartialSpends`](super::coin_selection::AvoidPartialSpends).
+    pub fn avoid_partial_spends(&mut self, max_extra_fee: Amount) -> &mut Self {
+        self.params.avoid_partial_spends(max_extra_fee);
+        self
+    }
+
+    /// Avoid telling which outputs are ours through their script types.
+    ///
+    /// The coin selection prefers inputs of a single script type, see
+    /// [`AvoidMixedScriptTypes`](super::coin_selection::AvoidMixedScriptTypes). When the recipients
+    /// all have the same script type, the change goes to the internal keychain if its descriptor
+    /// has that type, else to a new address of the external keychain if it has.
+    pub fn avoid_mixed_script_types(&mut self) -> &mut Self {
+        self.params.avoid_mixed_script_types();
+        self
+    }
+
+    /// Set the long-term fee expectations and change requirements used by the coin selection.
+    ///
+    /// See [`CoinSelectionContext`] and
+    /// [`CoinSelectionAlgorithm::coin_select_with_context`].
+    pub fn coin_selection_context(&mut self, context: CoinSelectionContext) -> &mut Self {
+        self.params.coin_selection_context(context);
+        self
+    }
+
+    /// Report how the coins are selected.
+    ///
+    /// The [`SelectionDiagnostics`](super::coin_selection::SelectionDiagnostics) list the UTXOs
+    /// coin selection could choose from, the ones it could not and why, and the algorithms run.
+    /// They are returned with [`InsufficientFunds`](super::coin_selection::InsufficientFunds)
+    /// when the selection fails, and by [`Wallet::preview_tx`] otherwise.
+    pub fn diagnostics(&mut self) -> &mut Self {
+        self.params.diagnostics();
         self
     }
 
@@ -670,13 +1134,36 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// **Note**: by avoiding a dust limit check you may end up with a transaction that is
     /// non-standard.
     pub fn allow_dust(&mut self, allow_dust: bool) -> &mut Self {
-        self.params.allow_dust = allow_dust;
+        self.params.allow_dust(allow_dust);
+        self
+    }
+
+    /// Set whether or not the transaction is checked against the default relay policy of
+    /// Bitcoin Core.
+    ///
+    /// By default [`finish`] fails with [`CreateTxError::NonStandard`] if the signed transaction
+    /// would be too heavy or too small, break the [`DataCarrierPolicy`] (see
+    /// [`TxBuilder::data_carrier_policy`]), create a bare multisig output, exceed the sigops limits or spend oversized redeem or
+    /// witness scripts. Set this if the transaction is meant to be mined without being relayed.
+    ///
+    /// [`finish`]: Self::finish
+    pub fn allow_non_standard(&mut self, allow_non_standard: bool) -> &mut Self {
+        self.params.allow_non_standard(allow_non_standard);
+        self
+    }
+
+    /// Set the limits on the `OP_RETURN` outputs checked along the relay policy.
+    ///
+    /// By default a single `OP_RETURN` output of up to 83 bytes is allowed, like Bitcoin Core
+    /// before version 30 does. See [`DataCarrierPolicy`] for the policy of newer versions.
+    pub fn data_carrier_policy(&mut self, policy: DataCarrierPolicy) -> &mut Self {
+        self.params.data_carrier_policy(policy);
         self
     }
 
     /// Replace the recipients already added with a new list
     pub fn set_recipients(&mut self, recipients: Vec<(ScriptBuf, Amount)>) -> &mut Self {
-        self.params.recipients = recipients;
+        self.params.set_recipients(recipients);
         self
     }
 
@@ -686,14 +1173,71 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
         script_pubkey: impl Into<ScriptBuf>,
         amount: Amount,
     ) -> &mut Self {
-        self.params.recipients.push((script_pubkey.into(), amount));
+        self.params.add_recipient(script_pubkey, amount);
         self
     }
 
     /// Add data as an output, using OP_RETURN
     pub fn add_data<T: AsRef<PushBytes>>(&mut self, data: &T) -> &mut Self {
-        let script = ScriptBuf::new_op_return(data);
-        self.add_recipient(script, Amount::ZERO);
+        self.params.add_data(data);
+        self
+    }
+
+    /// Add a recipient from a BIP21 payment URI
+    ///
+    /// Only the address and the amount of the URI are used. Fails if the address isn't valid on
+    /// the network of the wallet or if the URI doesn't specify an amount.
+    pub fn add_payment_uri(&mut self, uri: &PaymentUri) -> Result<&mut Self, Bip21Error> {
+        let address = uri.address_for(self.wallet.network())?;
+        let amount = uri.amount.ok_or(Bip21Error::MissingAmount)?;
+        self.params.add_recipient(address.script_pubkey(), amount);
+        Ok(self)
+    }
+
+    /// Add a BIP352 silent payment recipient
+    ///
+    /// The taproot output paying `address` is derived from the private keys of the inputs once
+    /// coin selection is done, so only inputs of the wallet spending P2PKH, P2WPKH, P2SH-P2WPKH or
+    /// P2TR outputs and whose private keys are known to the wallet are selected. [`finish`] fails
+    /// with [`SilentPaymentError::IneligibleInput`] if another kind of input must be spent, e.g.
+    /// one added with [`add_foreign_utxo`].
+    ///
+    /// Silent payment outputs are added after the other recipients, so their index in
+    /// [`subtract_fee_from`] comes after the ones of the recipients added with
+    /// [`add_recipient`]. Since the outputs depend on the inputs, they must not be replaced by a
+    /// fee bump that adds inputs to the transaction.
+    ///
+    /// Fails if the address isn't valid on the network of the wallet.
+    ///
+    /// [`finish`]: Self::finish
+    /// [`add_foreign_utxo`]: Self::add_foreign_utxo
+    /// [`subtract_fee_from`]: Self::subtract_fee_from
+    /// [`add_recipient`]: Self::add_recipient
+    pub fn add_silent_payment_recipient(
+        &mut self,
+        address: SilentPaymentAddress,
+        amount: Amount,
+    ) -> Result<&mut Self, SilentPaymentError> {
+        let network = self.wallet.network();
+        if !address.is_valid_for_network(network) {
+            return Err(SilentPaymentError::NetworkMismatch(network));
+        }
+        self.params.add_silent_payment_recipient(address, amount);
+        Ok(self)
+    }
+
+    /// Add a pay-to-anchor (P2A) output, i.e. `OP_1 <0x4e73>`
+    ///
+    /// Anyone can spend a P2A output without a signature, which makes it a convenient hook to
+    /// bump the fee of the transaction with a child later on, see [`Wallet::build_cpfp`].
+    ///
+    /// The `amount` must be above the dust limit, unless the transaction is a TRUC transaction
+    /// (see [`TxBuilder::truc`]), in which case a zero-value *ephemeral* anchor can be used.
+    /// Note that relay policy requires a transaction with an ephemeral anchor to pay no fee, which
+    /// can be done with [`TxBuilder::fee_absolute`], and the anchor to be spent by a child in the
+    /// same package.
+    pub fn add_anchor(&mut self, amount: Amount) -> &mut Self {
+        self.params.add_anchor(amount);
         self
     }
 
@@ -742,11 +1286,84 @@ impl<'a, Cs> TxBuilder<'a, Cs> {
     /// [`add_utxos`]: Self::add_utxos
     /// [`drain_wallet`]: Self::drain_wallet
     pub fn drain_to(&mut self, script_pubkey: ScriptBuf) -> &mut Self {
-        self.params.drain_to = Some(script_pubkey);
+        self.params.drain_to(script_pubkey);
+        self
+    }
+
+    /// Pay the fee out of the recipients at `recipient_indexes`
+    ///
+    /// The indexes refer to the order in which the recipients were added. Instead of selecting
+    /// coins for the recipients' amounts plus the fee, coin selection only targets the
+    /// recipients' amounts and the fee is then deducted from the given recipients, split evenly
+    /// between them. Whatever can't be split evenly is deducted from the first one.
+    ///
+    /// This is useful to send an exact balance "minus the network fee" to a recipient, along
+    /// with other recipients receiving fixed amounts. The dust limit is checked on the amounts
+    /// left after deducting the fee, unless [`allow_dust`] is set.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use bitcoin::*;
+    /// # use bdk_wallet::*;
+    /// # let to_address = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+    /// #    .unwrap()
+    /// #    .assume_checked();
+    /// # let mut wallet = doctest_wallet!();
+    /// let mut tx_builder = wallet.build_tx();
+    /// tx_builder
+    ///     .add_recipient(to_address.script_pubkey(), Amount::from_sat(50_000))
+    ///     .subtract_fee_from(&[0])
+    ///     .fee_rate(FeeRate::from_sat_per_vb(5).expect("valid feerate"));
+    /// let psbt = tx_builder.finish()?;
+    /// let output = psbt
+    ///     .unsigned_tx
+    ///     .output
+    ///     .iter()
+    ///     .find(|txout| txout.script_pubkey == to_address.script_pubkey())
+    ///     .unwrap();
+    /// assert!(output.value < Amount::from_sat(50_000));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    /// [`allow_dust`]: Self::allow_dust
+    pub fn subtract_fee_from(&mut self, recipient_indexes: &[usize]) -> &mut Self {
+        self.params.subtract_fee_from(recipient_indexes);
+        self
+    }
+
+    /// Reserve the UTXOs spent by the transaction under `draft_id`.
+    ///
+    /// When the transaction is successfully created, the wallet outputs it spends are reserved
+    /// and won't be selected again by coin selection, so that several drafts can be built back to
+    /// back without spending the same coins. Manually added UTXOs are always spent, even if
+    /// reserved by another draft.
+    ///
+    /// The reservation lasts until it is released with [`Wallet::release_reservation`] or
+    /// [`Wallet::cancel_tx`], or until the chain reaches `expiry_height`, if given.
+    ///
+    /// **You must persist the staged change for the reservation to be persistent**.
+    pub fn reserve_utxos(
+        &mut self,
+        draft_id: impl Into<String>,
+        expiry_height: Option<u32>,
+    ) -> &mut Self {
+        self.params.reserve_utxos(draft_id, expiry_height);
         self
     }
 }
 
+impl<Cs> TxBuilder<'_, Cs> {
+    /// Returns the parameters set so far.
+    ///
+    /// The returned [`TxParams`] can be cloned and stored as a template to be reused later with
+    /// [`Wallet::build_tx_from_params`].
+    pub fn params(&self) -> &TxParams {
+        &self.params
+    }
+}
+
 impl<Cs: CoinSelectionAlgorithm> TxBuilder<'_, Cs> {
     /// Finish building the transaction.
     ///
@@ -776,6 +1393,93 @@ impl<Cs: CoinSelectionAlgorithm> TxBuilder<'_, Cs> {
     pub fn finish_with_aux_rand(self, rng: &mut impl RngCore) -> Result<Psbt, CreateTxError> {
         self.wallet.create_tx(self.coin_selection, self.params, rng)
     }
+
+    /// Finish building the transaction into a PSBT version 2.
+    ///
+    /// Returns a new [`PsbtV2`] per [`BIP370`], to which other parties can still add inputs and
+    /// outputs until it is signed. The lock time of the transaction is carried as the fallback
+    /// lock time, and the wallet inputs whose descriptor has an absolute timelock (`after()`)
+    /// require it as their lock time. See [`Wallet::sign_v2`] to sign it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use bdk_wallet::psbt::{PsbtV2, PsbtV2Output};
+    /// # use bdk_wallet::test_utils::*;
+    /// # use bdk_wallet::SignOptions;
+    /// # use bitcoin::{psbt, Amount, ScriptBuf, TxOut};
+    /// # let (mut wallet, _) = get_funded_wallet_wpkh();
+    /// # let to_address = wallet.peek_address(bdk_wallet::KeychainKind::External, 0);
+    /// let mut builder = wallet.build_tx();
+    /// builder.add_recipient(to_address.script_pubkey(), Amount::from_sat(10_000));
+    /// let mut psbt: PsbtV2 = builder.finish_v2()?;
+    ///
+    /// // Another party adds an output before the transaction is signed.
+    /// let script_pubkey = ScriptBuf::new_op_return([1, 2, 3]);
+    /// let txout = TxOut { value: Amount::ZERO, script_pubkey };
+    /// psbt.add_output(PsbtV2Output::new(txout, psbt::Output::default()))?;
+    ///
+    /// let finalized = wallet.sign_v2(&mut psbt, SignOptions::default())?;
+    /// assert!(finalized);
+    /// assert!(!psbt.tx_modifiable.outputs);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// [`BIP370`]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+    ///
+    /// **WARNING**: To avoid change address reuse you must persist the changes resulting from one
+    /// or more calls to this method before closing the wallet. See [`Wallet::reveal_next_address`].
+    #[cfg(feature = "std")]
+    pub fn finish_v2(self) -> Result<PsbtV2, CreateTxError> {
+        let TxBuilder {
+            wallet,
+            params,
+            coin_selection,
+        } = self;
+        let policy_paths = [
+            (KeychainKind::External, params.external_policy_path.clone()),
+            (KeychainKind::Internal, params.internal_policy_path.clone()),
+        ];
+        let mut psbt = PsbtV2::from_v0(wallet.create_tx(
+            coin_selection,
+            params,
+            &mut bitcoin::key::rand::thread_rng(),
+        )?);
+        psbt.tx_modifiable.inputs = true;
+        psbt.tx_modifiable.outputs = true;
+
+        let mut lock_times = BTreeMap::new();
+        for (keychain, policy_path) in policy_paths {
+            lock_times.insert(
+                keychain,
+                wallet
+                    .spending_condition(keychain, policy_path.as_ref())?
+                    .timelock,
+            );
+        }
+        for input in &mut psbt.inputs {
+            let psbt_input = &input.psbt_input;
+            let txout = psbt_input.witness_utxo.clone().or_else(|| {
+                let tx = psbt_input.non_witness_utxo.as_ref()?;
+                tx.output.get(input.previous_output.vout as usize).cloned()
+            });
+            let Some((keychain, _)) =
+                txout.and_then(|txout| wallet.derivation_of_spk(txout.script_pubkey))
+            else {
+                continue;
+            };
+            match lock_times.get(&keychain).copied().flatten() {
+                Some(absolute::LockTime::Blocks(height)) => {
+                    input.required_height_lock_time = Some(height)
+                }
+                Some(absolute::LockTime::Seconds(time)) => {
+                    input.required_time_lock_time = Some(time)
+                }
+                None => {}
+            }
+        }
+        Ok(psbt)
+    }
 }
 
 #[derive(Debug)]
@@ -843,6 +1547,8 @@ impl std::error::Error for AddForeignUtxoError {}
 type TxSort<T> = dyn (Fn(&T, &T) -> core::cmp::Ordering) + Send + Sync;
 
 /// Ordering of the transaction's inputs and outputs
+///
+/// Only [`TxOrdering::Shuffle`] and [`TxOrdering::Untouched`] can be serialized.
 #[derive(Clone, Default)]
 pub enum TxOrdering {
     /// Randomized (default)
@@ -875,6 +1581,37 @@ impl core::fmt::Debug for TxOrdering {
     }
 }
 
+/// Serializable subset of [`TxOrdering`].
+#[derive(Serialize, Deserialize)]
+enum SerdeTxOrdering {
+    Shuffle,
+    Untouched,
+}
+
+impl Serialize for TxOrdering {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ordering = match self {
+            TxOrdering::Shuffle => SerdeTxOrdering::Shuffle,
+            TxOrdering::Untouched => SerdeTxOrdering::Untouched,
+            TxOrdering::Custom { .. } => {
+                return Err(serde::ser::Error::custom(
+                    "custom tx ordering cannot be serialized",
+                ))
+            }
+        };
+        ordering.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TxOrdering {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match SerdeTxOrdering::deserialize(deserializer)? {
+            SerdeTxOrdering::Shuffle => TxOrdering::Shuffle,
+            SerdeTxOrdering::Untouched => TxOrdering::Untouched,
+        })
+    }
+}
+
 impl TxOrdering {
     /// Sort transaction inputs and outputs by [`TxOrdering`] variant.
     ///
@@ -905,8 +1642,40 @@ impl TxOrdering {
     }
 }
 
+/// Relay policy limits on the `OP_RETURN` (data carrier) outputs of a transaction
+///
+/// The default is the policy of Bitcoin Core before version 30: a single `OP_RETURN` output of up
+/// to 83 bytes. Version 30 relays any number of them by default, see
+/// [`DataCarrierPolicy::CORE_V30`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DataCarrierPolicy {
+    /// Maximum total size of the `OP_RETURN` output scripts in bytes, `None` for no limit
+    pub max_size: Option<usize>,
+    /// Whether the transaction may have more than one `OP_RETURN` output
+    pub allow_multiple: bool,
+}
+
+impl DataCarrierPolicy {
+    /// The default policy of Bitcoin Core 30 and later
+    pub const CORE_V30: Self = Self {
+        max_size: Some(100_000),
+        allow_multiple: true,
+    };
+}
+
+impl Default for DataCarrierPolicy {
+    fn default() -> Self {
+        Self {
+            max_size: Some(83),
+            allow_multiple: false,
+        }
+    }
+}
+
 /// Policy regarding the use of change outputs when creating a transaction
-#[derive(Default, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(
+    Default, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Clone, Copy, Serialize, Deserialize,
+)]
 pub enum ChangeSpendPolicy {
     /// Use both change and non-change outputs (default)
     #[default]
@@ -1443,4 +2212,51 @@ mod test {
             matches!(&builder.params.utxos[0].utxo, Utxo::Local(output) if output.outpoint == outpoint)
         );
     }
+
+    #[test]
+    fn test_tx_params_serde_roundtrip() {
+        use crate::test_utils::*;
+
+        let (mut wallet, _) = get_funded_wallet_wpkh();
+        let addr = wallet.next_unused_address(KeychainKind::External);
+        let outpoint = wallet.list_unspent().next().unwrap().outpoint;
+
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+            .add_data(&[0xaa; 4])
+            .fee_absolute(Amount::from_sat(1_000))
+            .ordering(TxOrdering::Untouched)
+            .nlocktime(absolute::LockTime::from_height(100).unwrap())
+            .only_spend_change()
+            .add_unspendable(OutPoint::null())
+            .add_utxo(outpoint)
+            .unwrap();
+        let params = builder.params().clone();
+
+        let json = serde_json::to_string(&params).unwrap();
+        let decoded: TxParams = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded.recipients, params.recipients);
+        assert_eq!(decoded.fee_policy, params.fee_policy);
+        assert_eq!(decoded.utxos, params.utxos);
+        assert_eq!(decoded.unspendable, params.unspendable);
+        assert_eq!(decoded.locktime, params.locktime);
+        assert_eq!(decoded.change_policy, ChangeSpendPolicy::OnlyChange);
+        assert!(matches!(decoded.ordering, TxOrdering::Untouched));
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    }
+
+    #[test]
+    fn test_tx_params_custom_ordering_not_serializable() {
+        let mut params = TxParams::new();
+        params.ordering(TxOrdering::Custom {
+            input_sort: Arc::new(|a, b| a.previous_output.cmp(&b.previous_output)),
+            output_sort: Arc::new(|a, b| a.value.cmp(&b.value)),
+        });
+        assert!(serde_json::to_string(&params).is_err());
+
+        params.ordering(TxOrdering::Shuffle);
+        assert!(serde_json::to_string(&params).is_ok());
+    }
 }
@@ -9,16 +9,26 @@
 // You may not use this file except in accordance with one or both of these
 // licenses.
 
-use alloc::sync::Arc;
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use bitcoin::constants::{MAX_SCRIPT_ELEMENT_SIZE, WITNESS_SCALE_FACTOR};
 use bitcoin::secp256k1::{All, Secp256k1};
 use bitcoin::{
-    absolute, relative, Amount, FeeRate, Script, Sequence, SignedAmount, Transaction, Txid,
+    absolute, relative, Amount, FeeRate, Psbt, Script, ScriptBuf, Sequence, SignedAmount,
+    Transaction, Txid, Weight,
 };
 use chain::{ChainPosition, ConfirmationBlockTime};
+use miniscript::descriptor::DescriptorType;
 use miniscript::{MiniscriptKey, Satisfier, ToPublicKey};
 
 use rand_core::RngCore;
 
+use crate::descriptor::ExtendedDescriptor;
+use crate::psbt::PsbtUtils;
+use crate::types::Utxo;
+use crate::wallet::coin_selection::SelectionDiagnostics;
+use crate::wallet::error::NonStandardError;
+use crate::wallet::tx_builder::DataCarrierPolicy;
+
 /// Trait to check if a value is below the dust limit.
 /// We are performing dust value calculation for a given script public key using rust-bitcoin to
 /// keep it compatible with network dust rate
@@ -134,6 +144,222 @@ pub(crate) fn shuffle_slice<T>(list: &mut [T], rng: &mut impl RngCore) {
     }
 }
 
+/// Discourage fee sniping as described in [BIP326].
+///
+/// When `input_depths` is given, i.e. every input can carry a relative timelock equal to its
+/// confirmation depth, half of the time the nLockTime is set to `0` and one random input gets its
+/// nSequence set to its depth. Otherwise the nLockTime is set to `current_height`. In both cases,
+/// with a 10% chance, the timelock is moved up to 99 blocks further back.
+///
+/// [BIP326]: https://github.com/bitcoin/bips/blob/master/bip-0326.mediawiki
+pub(crate) fn discourage_fee_sniping(
+    tx: &mut Transaction,
+    current_height: absolute::Height,
+    input_depths: Option<&[u16]>,
+    rng: &mut impl RngCore,
+) {
+    fn further_back(rng: &mut impl RngCore) -> u32 {
+        if rng.next_u32() % 10 == 0 {
+            rng.next_u32() % 100
+        } else {
+            0
+        }
+    }
+
+    match input_depths {
+        Some(depths) if !depths.is_empty() && rng.next_u32() % 2 == 0 => {
+            let index = rng.next_u32() as usize % depths.len();
+            let depth = depths[index]
+                .saturating_sub(further_back(rng) as u16)
+                .max(1);
+            tx.lock_time = absolute::LockTime::ZERO;
+            tx.input[index].sequence = Sequence::from_height(depth);
+        }
+        _ => {
+            let height = current_height
+                .to_consensus_u32()
+                .saturating_sub(further_back(rng));
+            tx.lock_time = absolute::LockTime::from_height(height).expect("valid height");
+        }
+    }
+}
+
+/// Coarse type of an output script, which is also the type of the input spending it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Other,
+}
+
+impl ScriptType {
+    pub(crate) fn of(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_p2tr() {
+            ScriptType::P2tr
+        } else {
+            ScriptType::Other
+        }
+    }
+
+    /// The type of the scripts derived from `descriptor`.
+    pub(crate) fn of_descriptor(descriptor: &ExtendedDescriptor) -> Self {
+        match descriptor.desc_type() {
+            DescriptorType::Pkh => ScriptType::P2pkh,
+            DescriptorType::Sh
+            | DescriptorType::ShWsh
+            | DescriptorType::ShWpkh
+            | DescriptorType::ShSortedMulti
+            | DescriptorType::ShWshSortedMulti => ScriptType::P2sh,
+            DescriptorType::Wpkh => ScriptType::P2wpkh,
+            DescriptorType::Wsh | DescriptorType::WshSortedMulti => ScriptType::P2wsh,
+            DescriptorType::Tr => ScriptType::P2tr,
+            DescriptorType::Bare => ScriptType::Other,
+        }
+    }
+}
+
+/// Whether `script` is a pay-to-anchor (P2A) output script, i.e. `OP_1 <0x4e73>`.
+pub(crate) fn is_p2a(script: &Script) -> bool {
+    script == ScriptBuf::new_p2a().as_script()
+}
+
+// Bitcoin Core's default relay policy, see `policy/policy.h`.
+const MAX_STANDARD_TX_WEIGHT: Weight = Weight::from_wu(400_000);
+const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+const MAX_STANDARD_TX_SIGOPS_COST: usize = 16_000;
+const MAX_P2SH_SIGOPS: usize = 15;
+const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3_600;
+
+/// Check that `psbt` would be relayed by nodes running the default standardness policy, with
+/// the `OP_RETURN` limits of `data_carrier_policy`, once signed. `estimated_weight` is the
+/// weight of the signed transaction.
+pub(crate) fn check_standardness(
+    psbt: &Psbt,
+    estimated_weight: Weight,
+    data_carrier_policy: &DataCarrierPolicy,
+) -> Result<(), NonStandardError> {
+    let tx = &psbt.unsigned_tx;
+
+    if estimated_weight > MAX_STANDARD_TX_WEIGHT {
+        return Err(NonStandardError::TxWeight {
+            weight: estimated_weight,
+            max: MAX_STANDARD_TX_WEIGHT,
+        });
+    }
+
+    if let Some(index) = tx
+        .output
+        .iter()
+        .position(|txout| txout.script_pubkey.is_multisig())
+    {
+        return Err(NonStandardError::BareMultisig(index));
+    }
+    let data_carriers = tx
+        .output
+        .iter()
+        .enumerate()
+        .filter(|(_, txout)| txout.script_pubkey.is_op_return());
+    if data_carriers.clone().count() > 1 && !data_carrier_policy.allow_multiple {
+        return Err(NonStandardError::MultipleDataCarriers);
+    }
+    if let Some(max) = data_carrier_policy.max_size {
+        let mut size = 0;
+        for (index, txout) in data_carriers {
+            size += txout.script_pubkey.len();
+            if size > max {
+                return Err(NonStandardError::DataCarrierSize { index, size, max });
+            }
+        }
+    }
+
+    // Signature operations in output scripts are counted at creation, those in the previous
+    // output scripts are already paid for and the script sigs only push data.
+    let mut sigops_cost = tx
+        .output
+        .iter()
+        .map(|txout| txout.script_pubkey.count_sigops_legacy())
+        .sum::<usize>()
+        * WITNESS_SCALE_FACTOR;
+    let mut only_witness_programs = true;
+    for (index, psbt_input) in psbt.inputs.iter().enumerate() {
+        let Some(prev_txout) = psbt.get_utxo_for(index) else {
+            only_witness_programs = false;
+            continue;
+        };
+        let mut program = prev_txout.script_pubkey;
+        if program.is_p2sh() {
+            only_witness_programs = false;
+            let Some(redeem_script) = &psbt_input.redeem_script else {
+                continue;
+            };
+            if redeem_script.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(NonStandardError::RedeemScriptSize {
+                    input: index,
+                    size: redeem_script.len(),
+                    max: MAX_SCRIPT_ELEMENT_SIZE,
+                });
+            }
+            if !redeem_script.is_witness_program() {
+                let sigops = redeem_script.count_sigops();
+                if sigops > MAX_P2SH_SIGOPS {
+                    return Err(NonStandardError::P2shSigops {
+                        input: index,
+                        sigops,
+                        max: MAX_P2SH_SIGOPS,
+                    });
+                }
+                sigops_cost += sigops * WITNESS_SCALE_FACTOR;
+                continue;
+            }
+            program = redeem_script.clone();
+        } else if !program.is_witness_program() {
+            only_witness_programs = false;
+        }
+
+        if program.is_p2wpkh() {
+            sigops_cost += 1;
+        } else if program.is_p2wsh() {
+            if let Some(witness_script) = &psbt_input.witness_script {
+                if witness_script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+                    return Err(NonStandardError::WitnessScriptSize {
+                        input: index,
+                        size: witness_script.len(),
+                        max: MAX_STANDARD_P2WSH_SCRIPT_SIZE,
+                    });
+                }
+                sigops_cost += witness_script.count_sigops();
+            }
+        }
+    }
+    if sigops_cost > MAX_STANDARD_TX_SIGOPS_COST {
+        return Err(NonStandardError::SigopsCost {
+            cost: sigops_cost,
+            max: MAX_STANDARD_TX_SIGOPS_COST,
+        });
+    }
+
+    // Inputs that aren't native segwit have a script sig that makes the transaction large enough.
+    if only_witness_programs && tx.base_size() < MIN_STANDARD_TX_NONWITNESS_SIZE {
+        return Err(NonStandardError::TooSmall {
+            size: tx.base_size(),
+            min: MIN_STANDARD_TX_NONWITNESS_SIZE,
+        });
+    }
+
+    Ok(())
+}
+
 pub(crate) type SecpCtx = Secp256k1<All>;
 
 /// Details about a transaction affecting the wallet (relevant and canonical).
@@ -163,14 +389,46 @@ pub struct TxDetails {
     pub tx: Arc<Transaction>,
 }
 
+/// The projected outcome of building a transaction, see [`Wallet::preview_tx`].
+///
+/// [`Wallet::preview_tx`]: crate::Wallet::preview_tx
+#[derive(Debug, Clone)]
+pub struct TxPreview {
+    /// The fee the transaction would pay.
+    pub fee: Amount,
+    /// The fee rate of the transaction, based on its estimated size once signed.
+    pub fee_rate: FeeRate,
+    /// The part of `fee` paying for unconfirmed ancestors of the spent UTXOs whose fee rate is
+    /// below the target fee rate.
+    pub bump_fee: Amount,
+    /// The fee rate of the transaction together with the unconfirmed ancestors it pays for.
+    ///
+    /// Ancestors already paying the target fee rate are not part of the package, so this is
+    /// [`fee_rate`](Self::fee_rate) when there is nothing to pay for.
+    pub package_fee_rate: FeeRate,
+    /// The UTXOs the transaction would spend.
+    pub selected: Vec<Utxo>,
+    /// The value of the change output, or of the output set with
+    /// [`TxBuilder::drain_to`](crate::TxBuilder::drain_to), if any.
+    pub change: Option<Amount>,
+    /// The estimated virtual size of the transaction once signed, in vbytes.
+    pub vsize: u64,
+    /// How the coins were selected, if requested with
+    /// [`TxBuilder::diagnostics`](crate::TxBuilder::diagnostics).
+    pub diagnostics: Option<Box<SelectionDiagnostics>>,
+}
+
 #[cfg(test)]
 mod test {
     // When nSequence is lower than this flag the timelock is interpreted as block-height-based,
     // otherwise it's time-based
     pub(crate) const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
 
-    use super::{check_nsequence_rbf, shuffle_slice, IsDust};
-    use crate::bitcoin::{Address, Network, Sequence};
+    use super::{
+        check_nsequence_rbf, check_standardness, discourage_fee_sniping, shuffle_slice,
+        DataCarrierPolicy, IsDust,
+    };
+    use crate::bitcoin::{absolute, transaction, Address, Network, Sequence, Transaction, TxIn};
     use alloc::vec::Vec;
     use core::str::FromStr;
     use rand::{rngs::StdRng, thread_rng, SeedableRng};
@@ -277,4 +535,143 @@ mod test {
         shuffle_slice(&mut test, &mut rng);
         assert_eq!(test, &[0, 4, 1, 2, 5]);
     }
+
+    fn dummy_tx(inputs: usize) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![
+                TxIn {
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    ..Default::default()
+                };
+                inputs
+            ],
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn test_discourage_fee_sniping_locktime() {
+        let height = absolute::Height::from_consensus(800_000).unwrap();
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let mut further_back = false;
+        for _ in 0..200 {
+            let mut tx = dummy_tx(2);
+            discourage_fee_sniping(&mut tx, height, None, &mut rng);
+            let lock_time = tx.lock_time.to_consensus_u32();
+            assert!((800_000 - 99..=800_000).contains(&lock_time));
+            assert!(tx
+                .input
+                .iter()
+                .all(|txin| txin.sequence == Sequence::ENABLE_RBF_NO_LOCKTIME));
+            further_back |= lock_time < 800_000;
+        }
+        assert!(further_back);
+    }
+
+    #[test]
+    fn test_discourage_fee_sniping_sequence() {
+        let height = absolute::Height::from_consensus(800_000).unwrap();
+        let depths = [1, 150];
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let (mut locktime_mode, mut sequence_mode) = (0, 0);
+        for _ in 0..200 {
+            let mut tx = dummy_tx(2);
+            discourage_fee_sniping(&mut tx, height, Some(&depths), &mut rng);
+            if tx.lock_time == absolute::LockTime::ZERO {
+                sequence_mode += 1;
+                let (index, txin) = tx
+                    .input
+                    .iter()
+                    .enumerate()
+                    .find(|(_, txin)| txin.sequence != Sequence::ENABLE_RBF_NO_LOCKTIME)
+                    .expect("one input must carry the relative timelock");
+                let depth = txin.sequence.0;
+                assert!(txin.sequence.is_height_locked());
+                assert!(depth >= 1 && depth <= depths[index] as u32);
+                assert!(depth + 99 >= depths[index] as u32);
+            } else {
+                locktime_mode += 1;
+            }
+        }
+        assert!(locktime_mode > 50 && sequence_mode > 50);
+    }
+
+    #[test]
+    fn test_check_standardness_sigops_and_scripts() {
+        use crate::bitcoin::opcodes::all::OP_CHECKSIG;
+        use crate::bitcoin::{Amount, Psbt, ScriptBuf, TxOut, Weight};
+        use crate::wallet::error::NonStandardError;
+
+        let checksigs = |n: usize| ScriptBuf::from_bytes(vec![OP_CHECKSIG.to_u8(); n]);
+        let check = |output: ScriptBuf, prevout: ScriptBuf, redeem: Option<ScriptBuf>, witness| {
+            let mut tx = dummy_tx(1);
+            tx.output.push(TxOut {
+                value: Amount::from_sat(1_000),
+                script_pubkey: output,
+            });
+            let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+            psbt.inputs[0].witness_utxo = Some(TxOut {
+                value: Amount::from_sat(2_000),
+                script_pubkey: prevout,
+            });
+            psbt.inputs[0].redeem_script = redeem;
+            psbt.inputs[0].witness_script = witness;
+            check_standardness(&psbt, Weight::from_wu(1_000), &DataCarrierPolicy::default())
+        };
+        let p2sh = |script: &ScriptBuf| ScriptBuf::new_p2sh(&script.script_hash());
+        let p2wsh = |script: &ScriptBuf| ScriptBuf::new_p2wsh(&script.wscript_hash());
+        let output = p2wsh(&checksigs(1));
+
+        let redeem = checksigs(15);
+        assert_eq!(
+            check(output.clone(), p2sh(&redeem), Some(redeem), None),
+            Ok(())
+        );
+        let redeem = checksigs(16);
+        assert_eq!(
+            check(output.clone(), p2sh(&redeem), Some(redeem), None),
+            Err(NonStandardError::P2shSigops {
+                input: 0,
+                sigops: 16,
+                max: 15
+            })
+        );
+        let redeem = checksigs(521);
+        assert_eq!(
+            check(output.clone(), p2sh(&redeem), Some(redeem), None),
+            Err(NonStandardError::RedeemScriptSize {
+                input: 0,
+                size: 521,
+                max: 520
+            })
+        );
+
+        let witness = checksigs(3_600);
+        assert_eq!(
+            check(output.clone(), p2wsh(&witness), None, Some(witness)),
+            Ok(())
+        );
+        let witness = checksigs(3_601);
+        assert_eq!(
+            check(output.clone(), p2wsh(&witness), None, Some(witness)),
+            Err(NonStandardError::WitnessScriptSize {
+                input: 0,
+                size: 3_601,
+                max: 3_600
+            })
+        );
+
+        // Output scripts are counted with the legacy rules and a witness scale factor, on top of
+        // the witness script sigops.
+        let witness = checksigs(1);
+        assert_eq!(
+            check(checksigs(4_001), p2wsh(&witness), None, Some(witness)),
+            Err(NonStandardError::SigopsCost {
+                cost: 16_005,
+                max: 16_000
+            })
+        );
+    }
 }
@@ -0,0 +1,308 @@
+use std::str::FromStr;
+
+use assert_matches::assert_matches;
+use bdk_chain::ConfirmationBlockTime;
+use bdk_wallet::error::BuildCpfpError;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, SignOptions, TxParams, Wallet};
+use bitcoin::{hashes::Hash, Address, Amount, FeeRate, Transaction, Txid};
+
+/// Create, sign and broadcast a low feerate transaction paying `amount` to an external address.
+fn send_low_fee_tx(wallet: &mut Wallet, amount: Amount) -> Transaction {
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), amount)
+        .fee_rate(FeeRate::BROADCAST_MIN);
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    insert_tx(wallet, tx.clone());
+    tx
+}
+
+#[test]
+fn test_cpfp_reaches_target_package_feerate() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent = send_low_fee_tx(&mut wallet, Amount::from_sat(25_000));
+    let parent_txid = parent.compute_txid();
+    let change = parent
+        .output
+        .iter()
+        .position(|txout| wallet.is_mine(txout.script_pubkey.clone()))
+        .unwrap();
+
+    let target = FeeRate::from_sat_per_vb(10).unwrap();
+    let mut psbt = wallet
+        .build_cpfp(&[parent_txid], target)
+        .unwrap()
+        .finish()
+        .unwrap();
+
+    // The child spends the parent's change and sends everything back to us.
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .any(|txin| txin.previous_output.txid == parent_txid
+            && txin.previous_output.vout == change as u32));
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert!(wallet.is_mine(psbt.unsigned_tx.output[0].script_pubkey.clone()));
+
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+
+    // The child alone pays more than the target, but the package is just above it.
+    assert!(wallet.calculate_fee_rate(&child).unwrap() > target);
+    let package_feerate = wallet.calculate_package_fee_rate(&child).unwrap();
+    assert!(package_feerate >= target, "{package_feerate} < {target}");
+    assert!(package_feerate < FeeRate::from_sat_per_vb(11).unwrap());
+}
+
+#[test]
+fn test_cpfp_includes_unconfirmed_ancestors() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let grandparent = send_low_fee_tx(&mut wallet, Amount::from_sat(10_000));
+    let parent = send_low_fee_tx(&mut wallet, Amount::from_sat(10_000));
+    assert!(parent
+        .input
+        .iter()
+        .any(|txin| txin.previous_output.txid == grandparent.compute_txid()));
+
+    let target = FeeRate::from_sat_per_vb(5).unwrap();
+    let mut psbt = wallet
+        .build_cpfp(&[parent.compute_txid()], target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+
+    let fees = wallet.calculate_fee(&grandparent).unwrap()
+        + wallet.calculate_fee(&parent).unwrap()
+        + wallet.calculate_fee(&child).unwrap();
+    let weight = grandparent.weight() + parent.weight() + child.weight();
+    assert_eq!(
+        wallet.calculate_package_fee_rate(&child).unwrap(),
+        fees / weight
+    );
+    assert!(fees / weight >= target);
+}
+
+#[test]
+fn test_cpfp_errors() {
+    let (mut wallet, funding_txid) = get_funded_wallet_wpkh();
+
+    // Unknown transaction.
+    let unknown = Txid::all_zeros();
+    assert_matches!(
+        wallet.build_cpfp(&[unknown], FeeRate::BROADCAST_MIN),
+        Err(BuildCpfpError::TransactionNotFound(txid)) if txid == unknown
+    );
+
+    // Already confirmed transaction.
+    assert_matches!(
+        wallet.build_cpfp(&[funding_txid], FeeRate::BROADCAST_MIN),
+        Err(BuildCpfpError::TransactionConfirmed(txid)) if txid == funding_txid
+    );
+
+    // Once the parent confirms there's nothing to pay for anymore.
+    let parent = send_low_fee_tx(&mut wallet, Amount::from_sat(1_000));
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 42_000,
+    };
+    insert_anchor(&mut wallet, parent.compute_txid(), anchor);
+    assert_matches!(
+        wallet.build_cpfp(&[parent.compute_txid()], FeeRate::BROADCAST_MIN),
+        Err(BuildCpfpError::TransactionConfirmed(_))
+    );
+
+    // Transaction without any output of ours.
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder.drain_wallet().drain_to(addr.script_pubkey());
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+    assert_matches!(
+        wallet.build_cpfp(&[txid], FeeRate::BROADCAST_MIN),
+        Err(BuildCpfpError::NoSpendableOutputs(t)) if t == txid
+    );
+}
+
+#[test]
+fn test_cpfp_truc_parent() {
+    use bdk_wallet::TRUC_VERSION;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::BROADCAST_MIN)
+        .truc();
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let parent = psbt.extract_tx().expect("failed to extract tx");
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent);
+
+    // The child of a TRUC transaction is a TRUC transaction.
+    let target = FeeRate::from_sat_per_vb(10).unwrap();
+    let mut psbt = wallet
+        .build_cpfp(&[parent_txid], target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+    assert!(wallet.calculate_package_fee_rate(&child).unwrap() >= target);
+}
+
+#[test]
+fn test_cpfp_through_ephemeral_anchor() {
+    use bdk_wallet::TRUC_VERSION;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    // A zero-fee parent sending everything away, except for an ephemeral anchor.
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_wallet()
+        .drain_to(addr.script_pubkey())
+        .add_anchor(Amount::ZERO)
+        .fee_absolute(Amount::ZERO)
+        .truc();
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let parent = psbt.extract_tx().expect("failed to extract tx");
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent.clone());
+    let funding = receive_output_in_latest_block(&mut wallet, Amount::from_sat(20_000));
+
+    let anchors: Vec<_> = wallet.list_unspent_anchors().collect();
+    assert_eq!(anchors.len(), 1);
+    let anchor = anchors[0].utxo.outpoint();
+    assert_eq!(anchor.txid, parent_txid);
+    assert!(parent.output[anchor.vout as usize]
+        .script_pubkey
+        .is_witness_program());
+    assert_eq!(anchors[0].satisfaction_weight, bitcoin::Weight::ZERO);
+
+    // The child spends the anchor and pays for the whole package with a wallet UTXO.
+    let target = FeeRate::from_sat_per_vb(5).unwrap();
+    let mut psbt = wallet
+        .build_cpfp(&[parent_txid], target)
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+    let spent: Vec<_> = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect();
+    assert!(spent.contains(&anchor));
+    assert!(spent.contains(&funding));
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+
+    let fee = wallet.calculate_fee(&child).unwrap();
+    let weight = parent.weight() + child.weight();
+    assert!(fee / weight >= target);
+    assert_eq!(
+        wallet.calculate_package_fee_rate(&child).unwrap(),
+        fee / weight
+    );
+
+    insert_tx(&mut wallet, child);
+    assert_eq!(wallet.list_unspent_anchors().count(), 0);
+}
+
+#[test]
+fn test_create_tx_pays_for_low_fee_ancestors() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent = send_low_fee_tx(&mut wallet, Amount::from_sat(25_000));
+    let addr = wallet.peek_address(KeychainKind::External, 42);
+    let target = FeeRate::from_sat_per_vb(10).unwrap();
+
+    // The only UTXO left is the unconfirmed change of the parent.
+    let mut params = TxParams::new();
+    params
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(target);
+    let preview = wallet.preview_tx(params.clone()).unwrap();
+    let parent_fee = wallet.calculate_fee(&parent).unwrap();
+    assert_eq!(preview.bump_fee, target * parent.weight() - parent_fee);
+    assert!(preview.fee_rate > target);
+    assert!(preview.package_fee_rate >= target);
+
+    let mut psbt = wallet.build_tx_from_params(params).finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .all(|txin| txin.previous_output.txid == parent.compute_txid()));
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+    let package_feerate = wallet.calculate_package_fee_rate(&child).unwrap();
+    assert!(package_feerate >= target, "{package_feerate} < {target}");
+    assert!(package_feerate < FeeRate::from_sat_per_vb(11).unwrap());
+}
+
+#[test]
+fn test_create_tx_ignores_ancestors_paying_target() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    send_low_fee_tx(&mut wallet, Amount::from_sat(25_000));
+    let addr = wallet.peek_address(KeychainKind::External, 42);
+
+    let mut params = TxParams::new();
+    params
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::BROADCAST_MIN);
+    let preview = wallet.preview_tx(params).unwrap();
+    assert_eq!(preview.bump_fee, Amount::ZERO);
+    assert_eq!(preview.package_fee_rate, preview.fee_rate);
+}
+
+#[test]
+fn test_cpfp_preview_package_feerate() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let parent = send_low_fee_tx(&mut wallet, Amount::from_sat(25_000));
+
+    let target = FeeRate::from_sat_per_vb(10).unwrap();
+    let params = wallet
+        .build_cpfp(&[parent.compute_txid()], target)
+        .unwrap()
+        .params()
+        .clone();
+    let preview = wallet.preview_tx(params.clone()).unwrap();
+    assert!(preview.fee_rate > target);
+    assert!(
+        preview.package_fee_rate >= target,
+        "{} < {target}",
+        preview.package_fee_rate
+    );
+    assert!(preview.package_fee_rate < FeeRate::from_sat_per_vb(11).unwrap());
+
+    let mut psbt = wallet.build_tx_from_params(params).finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+    assert_eq!(wallet.calculate_fee(&child).unwrap(), preview.fee);
+    // The preview is based on the size of the child once signed, which is at most the estimate.
+    let package_feerate = wallet.calculate_package_fee_rate(&child).unwrap();
+    assert!(package_feerate >= preview.package_fee_rate);
+    assert!(package_feerate < FeeRate::from_sat_per_vb(11).unwrap());
+}
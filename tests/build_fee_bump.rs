@@ -3,13 +3,13 @@ use std::str::FromStr;
 use assert_matches::assert_matches;
 use bdk_chain::{ChainPosition, ConfirmationBlockTime};
 use bdk_wallet::coin_selection::LargestFirstCoinSelection;
-use bdk_wallet::error::CreateTxError;
+use bdk_wallet::error::{BuildFeeBumpError, CreateTxError};
 use bdk_wallet::psbt::PsbtUtils;
 use bdk_wallet::test_utils::*;
-use bdk_wallet::KeychainKind;
+use bdk_wallet::{KeychainKind, SignOptions, TRUC_VERSION};
 use bitcoin::{
     absolute, hashes::Hash, psbt, transaction, Address, Amount, FeeRate, OutPoint, ScriptBuf,
-    Sequence, Transaction, TxOut, Weight,
+    Sequence, Transaction, TxOut, Txid, Weight,
 };
 
 mod common;
@@ -992,3 +992,492 @@ fn test_bump_fee_pay_to_anchor_foreign_utxo() {
     let tx = &psbt.unsigned_tx;
     assert!(tx.input.iter().any(|txin| txin.previous_output == outpoint));
 }
+
+#[test]
+fn test_bump_fee_many_merges_chained_payments() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr1 = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let addr2 = Address::from_str("2N4eQYCbKUHCCTUjBJeHcJp9ok6J2GZsTDt")
+        .unwrap()
+        .assume_checked();
+
+    // The second payment spends the change of the first one.
+    let mut txs = vec![];
+    for addr in [&addr1, &addr2] {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+            .fee_rate(FeeRate::BROADCAST_MIN);
+        let mut psbt = builder.finish().unwrap();
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let tx = psbt.extract_tx().expect("failed to extract tx");
+        insert_tx(&mut wallet, tx.clone());
+        txs.push(tx);
+    }
+    let txids: Vec<_> = txs.iter().map(|tx| tx.compute_txid()).collect();
+    assert!(txs[1]
+        .input
+        .iter()
+        .any(|txin| txin.previous_output.txid == txids[0]));
+    let original_fee =
+        wallet.calculate_fee(&txs[0]).unwrap() + wallet.calculate_fee(&txs[1]).unwrap();
+
+    // Just above the required feerate of ~2 sat/vb, which alone wouldn't pay for the fees of
+    // both replaced txs.
+    let mut builder = wallet.build_fee_bump_many(&txids).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_kwu(505));
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+
+    // Only the original confirmed input is spent.
+    assert_eq!(tx.input.len(), 1);
+    assert_eq!(tx.input[0].previous_output, txs[0].input[0].previous_output);
+
+    // Both recipients are kept and there's a single change output.
+    assert_eq!(tx.output.len(), 3);
+    for addr in [&addr1, &addr2] {
+        assert!(tx
+            .output
+            .iter()
+            .any(|txout| txout.script_pubkey == addr.script_pubkey()
+                && txout.value == Amount::from_sat(10_000)));
+    }
+    let change = tx
+        .output
+        .iter()
+        .filter(|txout| wallet.is_mine(txout.script_pubkey.clone()))
+        .count();
+    assert_eq!(change, 1);
+
+    // BIP125 rules 3 and 4.
+    let fee = wallet.calculate_fee(&tx).unwrap();
+    assert!(fee >= original_fee + FeeRate::BROADCAST_MIN * tx.weight());
+    assert!(fee / tx.weight() > FeeRate::from_sat_per_kwu(505));
+}
+
+#[test]
+fn test_bump_fee_many_independent_payments() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let second_utxo = receive_output_in_latest_block(&mut wallet, Amount::from_sat(30_000));
+    let first_utxo = wallet
+        .list_unspent()
+        .find(|utxo| utxo.outpoint != second_utxo)
+        .unwrap()
+        .outpoint;
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    let mut txs = vec![];
+    for (utxo, amount) in [(first_utxo, 20_000), (second_utxo, 15_000)] {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_utxo(utxo)
+            .unwrap()
+            .manually_selected_only()
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(amount))
+            .fee_rate(FeeRate::from_sat_per_vb(3).unwrap());
+        let mut psbt = builder.finish().unwrap();
+        assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let tx = psbt.extract_tx().expect("failed to extract tx");
+        insert_tx(&mut wallet, tx.clone());
+        txs.push(tx);
+    }
+    let txids: Vec<_> = txs.iter().map(|tx| tx.compute_txid()).collect();
+    let original_fee =
+        wallet.calculate_fee(&txs[0]).unwrap() + wallet.calculate_fee(&txs[1]).unwrap();
+
+    // The feerate must be higher than the one of every replaced tx.
+    let mut builder = wallet.build_fee_bump_many(&txids).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(3).unwrap());
+    assert_matches!(builder.finish(), Err(CreateTxError::FeeRateTooLow { .. }));
+
+    // The merged tx pays for both replaced txs.
+    let mut builder = wallet.build_fee_bump_many(&txids).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(5).unwrap());
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+
+    assert_eq!(tx.input.len(), 2);
+    assert_eq!(
+        tx.output
+            .iter()
+            .filter(|txout| txout.script_pubkey == addr.script_pubkey())
+            .map(|txout| txout.value)
+            .sum::<Amount>(),
+        Amount::from_sat(35_000)
+    );
+    let fee = wallet.calculate_fee(&tx).unwrap();
+    assert!(fee >= original_fee + FeeRate::BROADCAST_MIN * tx.weight());
+    assert!(fee / tx.weight() >= FeeRate::from_sat_per_vb(5).unwrap());
+}
+
+#[test]
+fn test_bump_fee_many_errors() {
+    let (mut wallet, funding_txid) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(25_000));
+    let tx = builder.finish().unwrap().unsigned_tx;
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+
+    assert_matches!(
+        wallet.build_fee_bump_many(&[txid, funding_txid]),
+        Err(BuildFeeBumpError::TransactionConfirmed(t)) if t == funding_txid
+    );
+    assert_matches!(
+        wallet.build_fee_bump_many(&[txid, Txid::all_zeros()]),
+        Err(BuildFeeBumpError::TransactionNotFound(_))
+    );
+    assert_matches!(
+        wallet.build_fee_bump_many(&[]),
+        Err(BuildFeeBumpError::NoTransactions)
+    );
+    assert_matches!(
+        wallet.build_fee_bump_many(&[txid, txid]),
+        Err(BuildFeeBumpError::DuplicateTransaction(t)) if t == txid
+    );
+
+    // TRUC and non-TRUC transactions can't be merged.
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(30_000));
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(5_000))
+        .truc();
+    let truc_tx = builder.finish().unwrap().unsigned_tx;
+    let truc_txid = truc_tx.compute_txid();
+    insert_tx(&mut wallet, truc_tx);
+    assert_matches!(
+        wallet.build_fee_bump_many(&[txid, truc_txid]),
+        Err(BuildFeeBumpError::MixedTrucVersions)
+    );
+}
+
+#[test]
+fn test_bump_fee_many_single_output_parent() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    // The parent sweeps the wallet to one of our receive addresses, the child spends that output.
+    let sweep_addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_wallet()
+        .drain_to(sweep_addr.script_pubkey())
+        .fee_rate(FeeRate::BROADCAST_MIN);
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let parent = psbt.extract_tx().expect("failed to extract tx");
+    assert_eq!(parent.output.len(), 1);
+    insert_tx(&mut wallet, parent.clone());
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+        .fee_rate(FeeRate::BROADCAST_MIN);
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+    insert_tx(&mut wallet, child.clone());
+
+    let txids = [parent.compute_txid(), child.compute_txid()];
+    let mut builder = wallet.build_fee_bump_many(&txids).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(5).unwrap());
+    let psbt = builder.finish().unwrap();
+    let tx = &psbt.unsigned_tx;
+
+    // The swept output isn't paid again, only the child's recipient and our change are left.
+    assert_eq!(tx.input.len(), 1);
+    assert_eq!(tx.input[0].previous_output, parent.input[0].previous_output);
+    assert!(!tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == sweep_addr.script_pubkey()));
+    assert!(tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == addr.script_pubkey()
+            && txout.value == Amount::from_sat(30_000)));
+    assert_eq!(tx.output.len(), 2);
+}
+
+#[test]
+fn test_build_cancel_tx() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb(2).unwrap());
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let original_tx = psbt.extract_tx().expect("failed to extract tx");
+    let original_fee = wallet.calculate_fee(&original_tx).unwrap();
+    let txid = original_tx.compute_txid();
+    insert_tx(&mut wallet, original_tx.clone());
+
+    // No address is revealed until the builder is finished.
+    let internal_index = wallet.derivation_index(KeychainKind::Internal);
+    let _ = wallet.build_cancel_tx(txid).unwrap();
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        internal_index
+    );
+
+    let mut psbt = wallet.build_cancel_tx(txid).unwrap().finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+
+    // The cancellation double-spends the original input...
+    assert!(tx
+        .input
+        .iter()
+        .any(|txin| txin.previous_output == original_tx.input[0].previous_output));
+    // ...and sends everything back to a fresh internal address.
+    assert_eq!(tx.output.len(), 1);
+    let (keychain, _) = wallet
+        .derivation_of_spk(tx.output[0].script_pubkey.clone())
+        .unwrap();
+    assert_eq!(keychain, KeychainKind::Internal);
+    assert!(original_tx
+        .output
+        .iter()
+        .all(|txout| txout.script_pubkey != tx.output[0].script_pubkey));
+
+    // BIP125 rules 3, 4 and 6.
+    let fee = wallet.calculate_fee(&tx).unwrap();
+    assert!(fee >= original_fee + FeeRate::BROADCAST_MIN * tx.weight());
+    assert!(fee / tx.weight() > original_fee / original_tx.weight());
+
+    // Once the cancellation is seen, the funds are ours again.
+    wallet.apply_unconfirmed_txs([(tx, u64::MAX)]);
+    assert_eq!(wallet.balance().total(), Amount::from_sat(50_000) - fee);
+}
+
+#[test]
+fn test_build_cancel_tx_errors() {
+    let (mut wallet, funding_txid) = get_funded_wallet_wpkh();
+    assert_matches!(
+        wallet.build_cancel_tx(funding_txid),
+        Err(BuildFeeBumpError::TransactionConfirmed(_))
+    );
+
+    // A tx paying us, but not spending any of our coins, can't be cancelled by us.
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let foreign_outpoint = OutPoint::new(Hash::hash(b"foreign"), 0);
+    wallet.insert_txout(
+        foreign_outpoint,
+        TxOut {
+            value: Amount::from_sat(20_000),
+            script_pubkey: ScriptBuf::new_p2a(),
+        },
+    );
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: foreign_outpoint,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(19_000),
+            script_pubkey: addr.script_pubkey(),
+        }],
+    };
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+    assert_matches!(
+        wallet.build_cancel_tx(txid),
+        Err(BuildFeeBumpError::NoLocalInputs(t)) if t == txid
+    );
+}
+
+#[test]
+fn test_bump_fee_truc() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+
+    // TRUC transactions are replaceable even without signaling it.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::BROADCAST_MIN)
+        .set_exact_sequence(Sequence(0xFFFFFFFE))
+        .truc();
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let parent = psbt.extract_tx().expect("failed to extract tx");
+    let parent_txid = parent.compute_txid();
+    insert_tx(&mut wallet, parent.clone());
+
+    let mut builder = wallet.build_fee_bump(parent_txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(3).unwrap());
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+
+    // The child of an unconfirmed TRUC transaction can be replaced too, it isn't counted as a
+    // second descendant of its parent.
+    let change = parent
+        .output
+        .iter()
+        .position(|txout| wallet.is_mine(txout.script_pubkey.clone()))
+        .unwrap();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(OutPoint::new(parent_txid, change as u32))
+        .unwrap()
+        .fee_rate(FeeRate::BROADCAST_MIN)
+        .truc();
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let child = psbt.extract_tx().expect("failed to extract tx");
+    let child_txid = child.compute_txid();
+    insert_tx(&mut wallet, child);
+
+    let mut builder = wallet.build_fee_bump(child_txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(3).unwrap());
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .all(|txin| txin.previous_output.txid == parent_txid));
+}
+
+#[test]
+fn test_bump_fee_non_standard() {
+    use bdk_wallet::error::NonStandardError;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_data(&[0x2a])
+        .add_data(&[0x2b])
+        .fee_rate(FeeRate::BROADCAST_MIN)
+        .allow_non_standard(true);
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    let txid = tx.compute_txid();
+    insert_tx(&mut wallet, tx);
+
+    // The replacement is checked as well, unless told otherwise.
+    let mut builder = wallet.build_fee_bump(txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(5).unwrap());
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(
+            NonStandardError::MultipleDataCarriers
+        ))
+    );
+
+    let mut builder = wallet.build_fee_bump(txid).unwrap();
+    builder
+        .fee_rate(FeeRate::from_sat_per_vb(5).unwrap())
+        .allow_non_standard(true);
+    assert!(builder.finish().is_ok());
+}
+
+#[test]
+fn test_bump_fee_silent_payment() {
+    use bdk_wallet::silent_payments::input_tweak_data;
+    use bdk_wallet::Wallet;
+    use bitcoin::block::{Header, Version};
+    use bitcoin::key::Secp256k1;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{Block, CompactTarget, Network, TxMerkleNode};
+
+    let secp = Secp256k1::new();
+    let scan = SecretKey::from_slice(&[1; 32]).unwrap();
+    let spend = SecretKey::from_slice(&[2; 32]).unwrap();
+    let mut receiver = Wallet::create_single(get_test_tr_single_sig())
+        .network(Network::Regtest)
+        .silent_payments(scan, spend.public_key(&secp))
+        .create_wallet_no_persist()
+        .unwrap();
+    let address = receiver.silent_payment_address().unwrap();
+
+    let (mut sender, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+    let utxo = sender.list_unspent().next().unwrap().outpoint;
+    receive_output_in_latest_block(&mut sender, Amount::from_sat(25_000));
+    let mut builder = sender.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(49_000))
+        .unwrap()
+        .add_utxo(utxo)
+        .unwrap()
+        .manually_selected_only()
+        .fee_rate(FeeRate::BROADCAST_MIN);
+    let mut psbt = builder.finish().unwrap();
+    assert!(sender.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    let original_spk = tx
+        .output
+        .iter()
+        .find(|txout| txout.value == Amount::from_sat(49_000))
+        .unwrap()
+        .script_pubkey
+        .clone();
+    let txid = tx.compute_txid();
+    insert_tx(&mut sender, tx);
+
+    // The replacement needs another input, so the silent payment output is derived again.
+    let mut builder = sender.build_fee_bump(txid).unwrap();
+    builder.fee_rate(FeeRate::from_sat_per_vb(50).unwrap());
+    let mut psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+    assert!(sender.sign(&mut psbt, SignOptions::default()).unwrap());
+    let replacement = psbt.extract_tx().expect("failed to extract tx");
+    let vout = replacement
+        .output
+        .iter()
+        .position(|txout| txout.value == Amount::from_sat(49_000))
+        .unwrap();
+    assert_ne!(replacement.output[vout].script_pubkey, original_spk);
+
+    // The receiver finds the output of the replacement.
+    let tweak_data = input_tweak_data(&secp, &replacement, |outpoint| {
+        sender.tx_graph().get_txout(*outpoint).cloned()
+    })
+    .unwrap();
+    let replacement_txid = replacement.compute_txid();
+    let block = Block {
+        header: Header {
+            version: Version::ONE,
+            prev_blockhash: receiver.latest_checkpoint().hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_000,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        },
+        txdata: vec![replacement],
+    };
+    receiver
+        .apply_block_with_tweaks(&block, 1, &[(replacement_txid, tweak_data)].into())
+        .unwrap();
+    let outputs: Vec<_> = receiver.list_silent_payment_unspent().collect();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(
+        outputs[0].outpoint,
+        OutPoint::new(replacement_txid, vout as u32)
+    );
+}
@@ -0,0 +1,348 @@
+use std::str::FromStr;
+
+use assert_matches::assert_matches;
+use bdk_wallet::payjoin::{Params, PayjoinError, Receiver, Sender, Transport};
+use bdk_wallet::psbt::PsbtUtils;
+use bdk_wallet::test_utils::*;
+use bdk_wallet::{KeychainKind, SignOptions, Wallet};
+use bitcoin::{absolute, Address, Amount, FeeRate, Psbt};
+
+/// A receiver running in the same process as the sender.
+struct InProcess<'a> {
+    receiver: &'a Wallet,
+    requests: usize,
+}
+
+impl Transport for InProcess<'_> {
+    type Error = PayjoinError;
+
+    fn post(&mut self, url: &str, body: &str) -> Result<String, Self::Error> {
+        self.requests += 1;
+        let (_, query) = url.split_once('?').unwrap_or((url, ""));
+        let proposal =
+            Receiver::from_request(query, body)?.propose(self.receiver, SignOptions::default())?;
+        Ok(proposal.to_string())
+    }
+}
+
+/// Create and sign a transaction from `sender` paying 10_000 sats to `receiver`, returning the
+/// PSBT and the index of the change output.
+fn original_psbt(sender: &mut Wallet, receiver: &mut Wallet) -> (Psbt, usize) {
+    let address = receiver.reveal_next_address(KeychainKind::External);
+    let mut builder = sender.build_tx();
+    builder
+        .add_recipient(address.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::from_sat_per_vb(2).unwrap());
+    let mut psbt = builder.finish().unwrap();
+    assert!(sender.sign(&mut psbt, SignOptions::default()).unwrap());
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .position(|txout| sender.is_mine(txout.script_pubkey.clone()))
+        .unwrap();
+    (psbt, change)
+}
+
+#[test]
+fn test_payjoin() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let (mut receiver, _) = get_funded_wallet_single(get_test_wpkh());
+    let (original, change) = original_psbt(&mut sender, &mut receiver);
+    let original_tx = original.clone().extract_tx().unwrap();
+    let original_fee_rate = sender.calculate_fee_rate(&original_tx).unwrap();
+
+    let params = Params {
+        additional_fee_output_index: Some(change),
+        max_additional_fee_contribution: Amount::from_sat(1_000),
+        min_fee_rate: FeeRate::from_sat_per_vb(2).unwrap(),
+        ..Default::default()
+    };
+    let payjoin = Sender::new(original, "https://example.com/pj?foo=bar", params).unwrap();
+    let (url, _) = payjoin.request();
+    assert_eq!(
+        url,
+        format!(
+            "https://example.com/pj?foo=bar&v=1&additionalfeeoutputindex={change}&maxadditionalfeecontribution=1000&minfeerate=2"
+        )
+    );
+
+    let mut transport = InProcess {
+        receiver: &receiver,
+        requests: 0,
+    };
+    let mut proposal = payjoin.send(&sender, &mut transport).unwrap();
+    assert_eq!(transport.requests, 1);
+    assert!(sender.sign(&mut proposal, SignOptions::default()).unwrap());
+    let fee = proposal.fee_amount().unwrap();
+    let tx = proposal.extract_tx().unwrap();
+
+    // The receiver added its 50_000 sats UTXO, and we paid part of the fee of its input.
+    assert_eq!(tx.input.len(), 2);
+    assert!(tx
+        .input
+        .iter()
+        .all(|txin| txin.sequence == original_tx.input[0].sequence));
+    let paid = tx
+        .output
+        .iter()
+        .find(|txout| receiver.is_mine(txout.script_pubkey.clone()))
+        .unwrap()
+        .value;
+    assert!(paid > Amount::from_sat(59_000) && paid <= Amount::from_sat(60_000));
+    let contribution = original_tx.output[change].value
+        - tx.output
+            .iter()
+            .find(|txout| sender.is_mine(txout.script_pubkey.clone()))
+            .unwrap()
+            .value;
+    assert!(contribution > Amount::ZERO && contribution <= Amount::from_sat(1_000));
+
+    // The payjoin pays about the same fee rate as the original transaction.
+    let fee_rate = fee / tx.weight();
+    assert!(fee_rate >= FeeRate::from_sat_per_kwu(original_fee_rate.to_sat_per_kwu() - 10));
+}
+
+#[test]
+fn test_payjoin_receiver_checks() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let (mut receiver, _) = get_funded_wallet_single(get_test_wpkh());
+    let (original, _) = original_psbt(&mut sender, &mut receiver);
+    let query = Params::default().to_query();
+    let propose = |wallet: &Wallet, query: &str, psbt: &Psbt| {
+        Receiver::from_request(query, &psbt.to_string())?.propose(wallet, SignOptions::default())
+    };
+
+    // Unsupported version and garbage.
+    assert_matches!(
+        propose(&receiver, "v=2", &original),
+        Err(PayjoinError::InvalidParam(key)) if key == "v"
+    );
+    assert_matches!(
+        Receiver::from_request(&query, "not a psbt"),
+        Err(PayjoinError::Psbt(_))
+    );
+
+    // The original transaction must be signed.
+    let mut unsigned = original.clone();
+    unsigned.inputs[0].final_script_witness = None;
+    assert_matches!(
+        propose(&receiver, &query, &unsigned),
+        Err(PayjoinError::NotFinalized)
+    );
+
+    // It must pay us, and not with our own coins.
+    let (other, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    assert_matches!(
+        propose(&other, &query, &original),
+        Err(PayjoinError::OriginalDoesNotPayUs)
+    );
+    let (self_payment, _) = original_psbt(&mut receiver, &mut sender);
+    assert_matches!(
+        propose(&receiver, &query, &self_payment),
+        Err(PayjoinError::OriginalSpendsOurOutput(_))
+    );
+
+    // Its fee rate must satisfy the sender's own minimum.
+    let params = Params {
+        min_fee_rate: FeeRate::from_sat_per_vb(5).unwrap(),
+        ..Default::default()
+    };
+    assert_matches!(
+        propose(&receiver, &params.to_query(), &original),
+        Err(PayjoinError::FeeRateTooLow { .. })
+    );
+
+    // We need a UTXO of the same type as the sender's.
+    let (mut taproot, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let (original, _) = original_psbt(&mut sender, &mut taproot);
+    assert_matches!(
+        propose(&taproot, &query, &original),
+        Err(PayjoinError::NoMatchingUtxo)
+    );
+}
+
+#[test]
+fn test_payjoin_sender_checks() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let (mut receiver, _) = get_funded_wallet_single(get_test_wpkh());
+    let (original, change) = original_psbt(&mut sender, &mut receiver);
+    let params = Params {
+        additional_fee_output_index: Some(change),
+        max_additional_fee_contribution: Amount::from_sat(10_000),
+        ..Default::default()
+    };
+    let proposal = Receiver::from_request(&params.to_query(), &original.to_string())
+        .unwrap()
+        .propose(&receiver, SignOptions::default())
+        .unwrap();
+    let payjoin = Sender::new(original.clone(), "https://example.com/pj", params).unwrap();
+    assert!(payjoin.process_proposal(&sender, proposal.clone()).is_ok());
+    let ours = |psbt: &Psbt| {
+        psbt.unsigned_tx
+            .input
+            .iter()
+            .position(|txin| txin.previous_output == original.unsigned_tx.input[0].previous_output)
+            .unwrap()
+    };
+    let our_change = |psbt: &Psbt| {
+        psbt.unsigned_tx
+            .output
+            .iter()
+            .position(|txout| sender.is_mine(txout.script_pubkey.clone()))
+            .unwrap()
+    };
+
+    // The original must be signed.
+    let mut unsigned = original.clone();
+    unsigned.inputs[0].final_script_witness = None;
+    assert_matches!(
+        Sender::new(unsigned, "https://example.com/pj", Params::default()),
+        Err(PayjoinError::NotFinalized)
+    );
+
+    let mut tampered = proposal.clone();
+    tampered.unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+    assert_matches!(
+        payjoin.process_proposal(&sender, tampered),
+        Err(PayjoinError::TxModified)
+    );
+
+    let mut tampered = proposal.clone();
+    let index = ours(&tampered);
+    tampered.unsigned_tx.input.remove(index);
+    tampered.inputs.remove(index);
+    assert_matches!(
+        payjoin.process_proposal(&sender, tampered),
+        Err(PayjoinError::MissingInput(_))
+    );
+
+    let mut tampered = proposal.clone();
+    let index = 1 - ours(&tampered);
+    tampered.inputs[index].final_script_witness = None;
+    assert_matches!(
+        payjoin.process_proposal(&sender, tampered),
+        Err(PayjoinError::InvalidInput(_))
+    );
+
+    // Our change may only pay for the receiver's input, at the original fee rate.
+    let mut tampered = proposal.clone();
+    let index = our_change(&tampered);
+    tampered.unsigned_tx.output[index].value -= Amount::from_sat(5_000);
+    assert_matches!(
+        payjoin.process_proposal(&sender, tampered),
+        Err(PayjoinError::FeeContributionTooHigh { .. })
+    );
+    let strict = Sender::new(
+        original.clone(),
+        "https://example.com/pj",
+        Params {
+            additional_fee_output_index: Some(change),
+            max_additional_fee_contribution: Amount::from_sat(10),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_matches!(
+        strict.process_proposal(&sender, proposal.clone()),
+        Err(PayjoinError::InvalidOutput(_))
+    );
+
+    // The receiver can't keep our contribution for itself.
+    let mut tampered = proposal.clone();
+    let checked = payjoin.process_proposal(&sender, proposal.clone()).unwrap();
+    let additional_fee = checked.fee_amount().unwrap() - original.fee_amount().unwrap();
+    let index = 1 - our_change(&tampered);
+    tampered.unsigned_tx.output[index].value += additional_fee;
+    assert_matches!(
+        payjoin.process_proposal(&sender, tampered),
+        Err(PayjoinError::FeeContributionNotPaid { contribution, additional_fee })
+            if contribution > Amount::ZERO && additional_fee == Amount::ZERO
+    );
+
+    // The payee output may be changed, unless output substitution is disabled.
+    let mut substituted = proposal.clone();
+    let index = 1 - our_change(&substituted);
+    substituted.unsigned_tx.output[index].script_pubkey =
+        Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+    assert!(payjoin
+        .process_proposal(&sender, substituted.clone())
+        .is_ok());
+    let strict = Sender::new(
+        original.clone(),
+        "https://example.com/pj",
+        Params {
+            additional_fee_output_index: Some(change),
+            max_additional_fee_contribution: Amount::from_sat(10_000),
+            disable_output_substitution: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_matches!(
+        strict.process_proposal(&sender, substituted),
+        Err(PayjoinError::InvalidOutput(_))
+    );
+
+    // The proposal must pay at least the minimum fee rate.
+    let proposal = Receiver::from_request(&Params::default().to_query(), &original.to_string())
+        .unwrap()
+        .propose(&receiver, SignOptions::default())
+        .unwrap();
+    let strict = Sender::new(
+        original,
+        "https://example.com/pj",
+        Params {
+            min_fee_rate: FeeRate::from_sat_per_vb(3).unwrap(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_matches!(
+        strict.process_proposal(&sender, proposal),
+        Err(PayjoinError::FeeRateTooLow { .. })
+    );
+}
+
+#[test]
+fn test_payjoin_sender_checks_duplicate_outputs() {
+    let (mut sender, _) = get_funded_wallet_wpkh();
+    let (mut receiver, _) = get_funded_wallet_single(get_test_wpkh());
+    let address = receiver.reveal_next_address(KeychainKind::External);
+    let mut builder = sender.build_tx();
+    builder
+        .add_recipient(address.script_pubkey(), Amount::from_sat(10_000))
+        .add_recipient(address.script_pubkey(), Amount::from_sat(10_000))
+        .fee_rate(FeeRate::from_sat_per_vb(2).unwrap());
+    let mut original = builder.finish().unwrap();
+    assert!(sender.sign(&mut original, SignOptions::default()).unwrap());
+
+    let params = Params {
+        disable_output_substitution: true,
+        ..Default::default()
+    };
+    let proposal = Receiver::from_request(&params.to_query(), &original.to_string())
+        .unwrap()
+        .propose(&receiver, SignOptions::default())
+        .unwrap();
+    let payjoin = Sender::new(original, "https://example.com/pj", params).unwrap();
+    assert!(payjoin.process_proposal(&sender, proposal.clone()).is_ok());
+
+    // Both payments must still be there, one output can't account for the two of them.
+    let mut tampered = proposal;
+    let index = tampered
+        .unsigned_tx
+        .output
+        .iter()
+        .rposition(|txout| txout.script_pubkey == address.script_pubkey())
+        .unwrap();
+    tampered.unsigned_tx.output.remove(index);
+    tampered.outputs.remove(index);
+    assert_matches!(
+        payjoin.process_proposal(&sender, tampered),
+        Err(PayjoinError::InvalidOutput(_))
+    );
+}
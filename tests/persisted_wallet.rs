@@ -550,3 +550,69 @@ fn test_lock_outpoint_persist() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_reserve_outpoint_persist() -> anyhow::Result<()> {
+    use bdk_chain::rusqlite;
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+
+    let (desc, change_desc) = get_test_tr_single_sig_xprv_and_change_desc();
+    let mut wallet = Wallet::create(desc, change_desc)
+        .network(Network::Signet)
+        .create_wallet(&mut conn)?;
+
+    // Receive coins.
+    for i in 0..2 {
+        receive_output(&mut wallet, Amount::from_sat(10_000), ReceiveTo::Mempool(i));
+    }
+
+    // Test: build a draft reserving its inputs
+    let addr = wallet.next_unused_address(KeychainKind::External).address;
+    let mut tx_builder = wallet.build_tx();
+    tx_builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(5_000))
+        .reserve_utxos("draft", Some(100));
+    let psbt = tx_builder.finish()?;
+    let reserved: BTreeSet<_> = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|txin| txin.previous_output)
+        .collect();
+    assert_eq!(
+        wallet
+            .list_reserved_outpoints()
+            .map(|(op, _)| op)
+            .collect::<BTreeSet<_>>(),
+        reserved
+    );
+    wallet.persist(&mut conn)?;
+
+    // Test: The reservations are persistent
+    {
+        wallet = Wallet::load()
+            .load_wallet(&mut conn)?
+            .expect("wallet is persisted");
+
+        for (op, reservation) in wallet.list_reserved_outpoints() {
+            assert!(reserved.contains(&op));
+            assert_eq!(reservation.draft_id, "draft");
+            assert_eq!(reservation.expiry_height, Some(100));
+        }
+        assert_eq!(wallet.list_reserved_outpoints().count(), reserved.len());
+
+        wallet.release_reservation("draft");
+        assert!(wallet.list_reserved_outpoints().next().is_none());
+        wallet.persist(&mut conn)?;
+    }
+
+    // Test: The release is persistent
+    {
+        wallet = Wallet::load()
+            .load_wallet(&mut conn)?
+            .expect("wallet is persisted");
+        assert!(wallet.list_reserved_outpoints().next().is_none());
+    }
+
+    Ok(())
+}
@@ -221,3 +221,502 @@ fn test_psbt_multiple_internalkey_signers() {
     let verify_res = secp.verify_schnorr(&signature, &message, &xonlykey);
     assert!(verify_res.is_ok(), "The wrong internal key was used");
 }
+
+#[test]
+fn test_psbt_v2_conversion() {
+    use psbt::{PsbtV2, PsbtV2Error};
+
+    // Fields of version 0 survive the conversion.
+    let psbt_bip = Psbt::from_str(PSBT_STR).unwrap();
+    let psbt_v2 = PsbtV2::from_v0(psbt_bip.clone());
+    assert_eq!(psbt_v2.to_v0().unwrap(), psbt_bip);
+    assert_eq!(PsbtV2::deserialize(&psbt_v2.serialize()).unwrap(), psbt_v2);
+    assert_eq!(
+        PsbtV2::from_str(&psbt_v2.to_string()).unwrap(),
+        psbt_v2.clone()
+    );
+
+    // Unknown keys are kept, whatever their type.
+    let mut psbt_unknown = psbt_bip.clone();
+    for type_value in [0x42, 0xfd, 0xff] {
+        let key = bdk_wallet::bitcoin::psbt::raw::Key {
+            type_value,
+            key: vec![0x01],
+        };
+        psbt_unknown.unknown.insert(key.clone(), vec![0x02]);
+        psbt_unknown.inputs[0]
+            .unknown
+            .insert(key.clone(), vec![0x03]);
+        psbt_unknown.outputs[0].unknown.insert(key, vec![0x04]);
+    }
+    let psbt_v2_unknown = PsbtV2::from_v0(psbt_unknown.clone());
+    assert_eq!(
+        PsbtV2::deserialize(&psbt_v2_unknown.serialize()).unwrap(),
+        psbt_v2_unknown
+    );
+    assert_eq!(
+        PsbtV2::from_str(&psbt_v2_unknown.to_string())
+            .unwrap()
+            .to_v0()
+            .unwrap(),
+        psbt_unknown
+    );
+
+    // Even those repeating the key of a known field.
+    let mut psbt_repeated = psbt_bip.clone();
+    psbt_repeated.inputs[0].unknown.insert(
+        bdk_wallet::bitcoin::psbt::raw::Key {
+            type_value: 0x00,
+            key: vec![],
+        },
+        vec![0x01],
+    );
+    assert!(!PsbtV2::from_v0(psbt_repeated).serialize().is_empty());
+
+    // The serialization doesn't include the unsigned transaction.
+    assert!(Psbt::deserialize(&psbt_v2.serialize()).is_err());
+    assert!(matches!(
+        PsbtV2::deserialize(&psbt_bip.serialize()),
+        Err(PsbtV2Error::InvalidVersion(0))
+    ));
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish_v2().unwrap();
+    assert!(psbt.tx_modifiable.inputs && psbt.tx_modifiable.outputs);
+    assert_eq!(PsbtV2::deserialize(&psbt.serialize()).unwrap(), psbt);
+    let tx = psbt.unsigned_tx().unwrap();
+    assert_eq!(psbt.fallback_lock_time, Some(tx.lock_time));
+    assert_eq!(PsbtV2::from_v0(psbt.to_v0().unwrap()).inputs, psbt.inputs);
+}
+
+#[test]
+fn test_psbt_v2_sign() {
+    use bdk_wallet::bitcoin::{psbt::Input, psbt::Output, OutPoint, ScriptBuf, TxOut};
+    use psbt::{PsbtV2, PsbtV2Error, PsbtV2Input, PsbtV2Output};
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let mut psbt = builder.finish_v2().unwrap();
+
+    // Outputs can be added before signing.
+    let txout = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: ScriptBuf::new_op_return([1, 2, 3]),
+    };
+    psbt.add_output(PsbtV2Output::new(txout.clone(), Output::default()))
+        .unwrap();
+    assert_eq!(psbt.outputs.len(), 3);
+
+    let finalized = wallet.sign_v2(&mut psbt, SignOptions::default()).unwrap();
+    assert!(finalized);
+    assert!(!psbt.tx_modifiable.inputs);
+    assert!(!psbt.tx_modifiable.outputs);
+    assert!(matches!(
+        psbt.add_output(PsbtV2Output::new(txout, Output::default())),
+        Err(PsbtV2Error::OutputsNotModifiable)
+    ));
+    assert!(matches!(
+        psbt.add_input(PsbtV2Input::new(OutPoint::null(), Input::default())),
+        Err(PsbtV2Error::InputsNotModifiable)
+    ));
+
+    // The flags and the finalized inputs are serialized.
+    let psbt = PsbtV2::deserialize(&psbt.serialize()).unwrap();
+    assert!(!psbt.tx_modifiable.outputs);
+    let tx = psbt.to_v0().unwrap().extract_tx().unwrap();
+    assert_eq!(tx.output.len(), 3);
+    assert!(!tx.input[0].witness.is_empty());
+}
+
+#[test]
+fn test_psbt_v2_sign_anyonecanpay_single() {
+    use bdk_wallet::bitcoin::{psbt::Input, sighash::EcdsaSighashType, OutPoint};
+    use psbt::{PsbtV2Input, TxModifiable};
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000))
+        .sighash(EcdsaSighashType::SinglePlusAnyoneCanPay.into());
+    let mut psbt = builder.finish_v2().unwrap();
+    let options = SignOptions {
+        allow_all_sighashes: true,
+        try_finalize: false,
+        ..Default::default()
+    };
+    assert!(!wallet.sign_v2(&mut psbt, options).unwrap());
+    assert_eq!(
+        psbt.tx_modifiable,
+        TxModifiable {
+            inputs: true,
+            outputs: true,
+            sighash_single: true,
+        }
+    );
+
+    // The signature commits to its input only, so more can be added.
+    psbt.add_input(PsbtV2Input::new(OutPoint::null(), Input::default()))
+        .unwrap();
+    assert_eq!(psbt.inputs.len(), 2);
+}
+
+#[test]
+fn test_psbt_v2_required_lock_time() {
+    use bdk_wallet::bitcoin::absolute::{Height, LockTime, Time};
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish_v2().unwrap();
+    assert!(psbt.inputs.iter().all(|input| {
+        input.required_height_lock_time == Some(Height::from_consensus(100_000).unwrap())
+            && input.required_time_lock_time.is_none()
+    }));
+    assert_eq!(
+        psbt.lock_time().unwrap(),
+        LockTime::from_height(100_000).unwrap()
+    );
+
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv_timestamp());
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish_v2().unwrap();
+    assert!(psbt.inputs.iter().all(|input| {
+        input.required_time_lock_time == Some(Time::from_consensus(1_734_230_218).unwrap())
+            && input.required_height_lock_time.is_none()
+    }));
+
+    // Inputs without timelock don't require any lock time.
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish_v2().unwrap();
+    assert!(psbt.inputs.iter().all(|input| {
+        input.required_height_lock_time.is_none() && input.required_time_lock_time.is_none()
+    }));
+}
+
+#[test]
+fn test_psbt_v2_lock_time() {
+    use bdk_wallet::bitcoin::absolute::{Height, LockTime, Time};
+    use bdk_wallet::bitcoin::{psbt::Input, OutPoint};
+    use psbt::{PsbtV2Error, PsbtV2Input};
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let send_to = wallet.peek_address(KeychainKind::External, 0);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(send_to.script_pubkey(), Amount::from_sat(10_000))
+        .nlocktime(LockTime::from_height(1_000).unwrap());
+    let mut psbt = builder.finish_v2().unwrap();
+    assert_eq!(
+        psbt.lock_time().unwrap(),
+        LockTime::from_height(1_000).unwrap()
+    );
+
+    let input = |height: Option<u32>, time: Option<u32>| PsbtV2Input {
+        required_height_lock_time: height.map(|h| Height::from_consensus(h).unwrap()),
+        required_time_lock_time: time.map(|t| Time::from_consensus(t).unwrap()),
+        ..PsbtV2Input::new(OutPoint::null(), Input::default())
+    };
+
+    // Inputs requiring a lock time override the fallback one. A height is used if they all
+    // accept one, a time otherwise.
+    psbt.add_input(input(None, Some(500_000_010))).unwrap();
+    assert_eq!(
+        psbt.lock_time().unwrap(),
+        LockTime::from_time(500_000_010).unwrap()
+    );
+    psbt.add_input(input(Some(10), Some(500_000_020))).unwrap();
+    assert_eq!(
+        psbt.lock_time().unwrap(),
+        LockTime::from_time(500_000_020).unwrap()
+    );
+    assert!(matches!(
+        psbt.add_input(input(Some(20), None)),
+        Err(PsbtV2Error::LockTimeConflict)
+    ));
+    assert_eq!(psbt.inputs.len(), 3);
+
+    psbt.inputs.remove(1);
+    psbt.add_input(input(Some(20), None)).unwrap();
+    assert_eq!(
+        psbt.lock_time().unwrap(),
+        LockTime::from_height(20).unwrap()
+    );
+    let psbt_v2 = psbt::PsbtV2::deserialize(&psbt.serialize()).unwrap();
+    assert_eq!(psbt_v2, psbt);
+}
+
+#[test]
+fn test_psbt_contribution_and_combine() {
+    use bdk_wallet::bitcoin::{absolute, transaction, Transaction, TxOut, Weight};
+    use bdk_wallet::contribution::{combine_psbts, CombineError, Contribution};
+    use bdk_wallet::error::CreateTxError;
+
+    let (mut alice, _) = get_funded_wallet_wpkh();
+    let (mut bob, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let shared = TxOut {
+        value: Amount::from_sat(60_000),
+        script_pubkey: alice
+            .peek_address(KeychainKind::External, 42)
+            .script_pubkey(),
+    };
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![shared.clone()],
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+    let half = Amount::from_sat(30_000);
+    let contribution = Contribution::new(vec![], fee_rate).amount(half);
+    alice
+        .contribute_to_psbt(&mut psbt, contribution.clone())
+        .unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    // Alice pays for the weight of her input and change only.
+    let alice_change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| **txout != shared)
+        .unwrap();
+    assert_eq!(
+        alice.derivation_of_spk(alice_change.script_pubkey.clone()),
+        Some((KeychainKind::Internal, 0))
+    );
+    assert_eq!(alice.derivation_index(KeychainKind::Internal), Some(0));
+    let alice_fee = Amount::from_sat(50_000) - half - alice_change.value;
+    let alice_weight = psbt.unsigned_tx.input[0].segwit_weight()
+        + Weight::from_wu(107)
+        + Weight::from_vb_unchecked(alice_change.size() as u64);
+    assert!(alice_fee >= fee_rate * alice_weight);
+    assert!(alice_fee < fee_rate * (alice_weight + Weight::from_vb_unchecked(10)));
+    let alice_outpoint = psbt.unsigned_tx.input[0].previous_output;
+
+    // Bob adds an output of his own and can't spend Alice's coins.
+    let bob_output = TxOut {
+        value: Amount::from_sat(5_000),
+        script_pubkey: bob.peek_address(KeychainKind::External, 7).script_pubkey(),
+    };
+    bob.contribute_to_psbt(
+        &mut psbt,
+        Contribution::new(vec![bob_output.clone()], fee_rate).amount(half),
+    )
+    .unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+    assert_eq!(psbt.unsigned_tx.output.len(), 4);
+    assert!(psbt.unsigned_tx.output.contains(&bob_output));
+    let alice_input = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .position(|txin| txin.previous_output == alice_outpoint)
+        .unwrap();
+    let bob_input = 1 - alice_input;
+    assert!(psbt.inputs[bob_input].tap_internal_key.is_some());
+    assert!(psbt.inputs[alice_input].tap_internal_key.is_none());
+    // A failed contribution doesn't reveal a change address.
+    let bob_index = bob.derivation_index(KeychainKind::External);
+    assert!(matches!(
+        bob.contribute_to_psbt(&mut psbt.clone(), contribution),
+        Err(CreateTxError::CoinSelection(_))
+    ));
+    assert_eq!(bob.derivation_index(KeychainKind::External), bob_index);
+
+    let options = SignOptions {
+        try_finalize: false,
+        ..Default::default()
+    };
+    let mut alice_psbt = psbt.clone();
+    alice.sign(&mut alice_psbt, options.clone()).unwrap();
+    let mut bob_psbt = psbt.clone();
+    bob.sign(&mut bob_psbt, options).unwrap();
+    assert!(!alice_psbt.inputs[alice_input].partial_sigs.is_empty());
+    assert!(bob_psbt.inputs[bob_input].tap_key_sig.is_some());
+
+    let mut combined = combine_psbts([alice_psbt, bob_psbt.clone()]).unwrap();
+    // Each party only finalizes its own inputs.
+    assert!(!alice
+        .finalize_psbt(&mut combined, SignOptions::default())
+        .unwrap());
+    assert!(bob
+        .finalize_psbt(&mut combined, SignOptions::default())
+        .unwrap());
+    let tx = combined.extract_tx().unwrap();
+    assert!(tx.input.iter().all(|txin| !txin.witness.is_empty()));
+
+    assert!(matches!(
+        combine_psbts(Vec::<Psbt>::new()),
+        Err(CombineError::NoPsbt)
+    ));
+    let other = Psbt::from_str(PSBT_STR).unwrap();
+    assert!(matches!(
+        combine_psbts([bob_psbt, other]),
+        Err(CombineError::Psbt(_))
+    ));
+}
+
+#[test]
+fn test_psbt_contribution_reserve_utxos() {
+    use bdk_wallet::bitcoin::{absolute, transaction, Transaction, TxOut};
+    use bdk_wallet::contribution::Contribution;
+    use bdk_wallet::error::CreateTxError;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: wallet
+                .peek_address(KeychainKind::External, 42)
+                .script_pubkey(),
+        }],
+    };
+    let amount = Amount::from_sat(10_000);
+    let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+    wallet
+        .contribute_to_psbt(
+            &mut psbt,
+            Contribution::new(vec![], fee_rate)
+                .amount(amount)
+                .reserve_utxos("payjoin", None),
+        )
+        .unwrap();
+    let outpoint = psbt.unsigned_tx.input[0].previous_output;
+    assert!(wallet.is_outpoint_reserved(outpoint));
+
+    // Another draft can't select the reserved coin.
+    let mut other = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+    assert!(matches!(
+        wallet.contribute_to_psbt(
+            &mut other,
+            Contribution::new(vec![], fee_rate).amount(amount)
+        ),
+        Err(CreateTxError::CoinSelection(_))
+    ));
+    let recipient = wallet
+        .peek_address(KeychainKind::External, 43)
+        .script_pubkey();
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(recipient, amount);
+    assert!(matches!(
+        builder.finish(),
+        Err(CreateTxError::CoinSelection(_))
+    ));
+
+    // The same draft can contribute it again.
+    let mut again = Psbt::from_unsigned_tx(tx).unwrap();
+    wallet
+        .contribute_to_psbt(
+            &mut again,
+            Contribution::new(vec![], fee_rate)
+                .amount(amount)
+                .reserve_utxos("payjoin", None),
+        )
+        .unwrap();
+    assert_eq!(again.unsigned_tx.input[0].previous_output, outpoint);
+}
+
+#[test]
+fn test_psbt_contribution_random_positions() {
+    use bdk_wallet::bitcoin::hashes::Hash;
+    use bdk_wallet::bitcoin::key::rand::{rngs::StdRng, SeedableRng};
+    use bdk_wallet::bitcoin::{
+        absolute, psbt::PsbtSighashType, transaction, EcdsaSighashType, OutPoint, Transaction,
+        TxOut, Txid,
+    };
+    use bdk_wallet::contribution::Contribution;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let other_inputs = (0..3)
+        .map(|vout| TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), vout),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let other_outputs = (0..3)
+        .map(|i| TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: wallet
+                .peek_address(KeychainKind::External, 42 + i)
+                .script_pubkey(),
+        })
+        .collect::<Vec<_>>();
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: other_inputs.clone(),
+        output: other_outputs.clone(),
+    };
+    let ours = |psbt: &Psbt| {
+        let input = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .position(|txin| !other_inputs.contains(txin))
+            .unwrap();
+        let output = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .position(|txout| !other_outputs.contains(txout))
+            .unwrap();
+        (input, output)
+    };
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut positions = Vec::new();
+    for _ in 0..16 {
+        let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        wallet
+            .contribute_to_psbt_with_aux_rand(
+                &mut psbt,
+                Contribution::new(vec![], fee_rate).amount(Amount::from_sat(10_000)),
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(psbt.inputs.len(), psbt.unsigned_tx.input.len());
+        assert_eq!(psbt.outputs.len(), psbt.unsigned_tx.output.len());
+        let (input, _) = ours(&psbt);
+        assert!(psbt.inputs[input].witness_utxo.is_some());
+        positions.push(ours(&psbt));
+    }
+    // Our input and change are not always appended.
+    assert!(positions.iter().any(|(input, _)| *input != 3));
+    assert!(positions.iter().any(|(_, output)| *output != 3));
+
+    // With SIGHASH_SINGLE the order of the existing inputs and outputs is kept.
+    let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+    psbt.inputs[0].sighash_type = Some(PsbtSighashType::from(
+        EcdsaSighashType::SinglePlusAnyoneCanPay,
+    ));
+    for _ in 0..4 {
+        let mut psbt = psbt.clone();
+        wallet
+            .contribute_to_psbt_with_aux_rand(
+                &mut psbt,
+                Contribution::new(vec![], fee_rate).amount(Amount::from_sat(10_000)),
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(ours(&psbt), (3, 3));
+    }
+}
@@ -10,7 +10,10 @@ use bdk_wallet::psbt::PsbtUtils;
 use bdk_wallet::signer::{SignOptions, SignerError};
 use bdk_wallet::test_utils::*;
 use bdk_wallet::KeychainKind;
-use bdk_wallet::{AddressInfo, Balance, PersistedWallet, Update, Wallet, WalletTx};
+use bdk_wallet::{
+    AddressInfo, Balance, PersistedWallet, TxBuilder, TxOrdering, TxParams, Update, Wallet,
+    WalletTx, TRUC_CHILD_MAX_VSIZE, TRUC_MAX_VSIZE, TRUC_VERSION,
+};
 use bitcoin::constants::COINBASE_MATURITY;
 use bitcoin::hashes::Hash;
 use bitcoin::script::PushBytesBuf;
@@ -308,6 +311,115 @@ fn test_create_tx_custom_locktime() {
     assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), 630_000);
 }
 
+#[test]
+fn test_create_tx_anti_fee_sniping_taproot() {
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    // The only UTXO is confirmed at height 2_000, so it's 101 blocks deep at height 2_100.
+    let (mut locktime_mode, mut sequence_mode) = (0, 0);
+    for seed in 0..100 {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+            .current_height(2_100)
+            .anti_fee_sniping();
+        let psbt = builder
+            .finish_with_aux_rand(&mut StdRng::seed_from_u64(seed))
+            .unwrap();
+        let tx = psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 1);
+
+        if tx.lock_time == absolute::LockTime::ZERO {
+            sequence_mode += 1;
+            let sequence = tx.input[0].sequence;
+            assert!(sequence.is_height_locked());
+            assert!((2..=101).contains(&sequence.0), "{sequence}");
+        } else {
+            locktime_mode += 1;
+            assert!((2_001..=2_100).contains(&tx.lock_time.to_consensus_u32()));
+            assert_eq!(tx.input[0].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        }
+    }
+    assert!(locktime_mode > 0 && sequence_mode > 0);
+}
+
+#[test]
+fn test_create_tx_anti_fee_sniping_uses_locktime() {
+    fn check_locktime_only(
+        wallet: &mut Wallet,
+        configure: impl Fn(&mut TxBuilder<'_, coin_selection::DefaultCoinSelectionAlgorithm>),
+    ) {
+        let addr = wallet.next_unused_address(KeychainKind::External);
+        for seed in 0..50 {
+            let mut builder = wallet.build_tx();
+            builder
+                .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+                .anti_fee_sniping();
+            configure(&mut builder);
+            let psbt = builder
+                .finish_with_aux_rand(&mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            let tx = psbt.unsigned_tx;
+            let lock_time = tx.lock_time.to_consensus_u32();
+            assert!((1_901..=2_000).contains(&lock_time), "{lock_time}");
+            assert!(tx
+                .input
+                .iter()
+                .all(|txin| txin.sequence == Sequence::ENABLE_RBF_NO_LOCKTIME));
+        }
+    }
+
+    // Non-taproot inputs.
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    check_locktime_only(&mut wallet, |_| {});
+
+    // Unconfirmed taproot inputs.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let outpoint = receive_output(&mut wallet, Amount::from_sat(10_000), ReceiveTo::Mempool(1));
+    check_locktime_only(&mut wallet, |builder| {
+        builder.add_utxo(outpoint).unwrap();
+    });
+
+    // Transaction version 1 can't use relative timelocks.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    check_locktime_only(&mut wallet, |builder| {
+        builder.version(1);
+    });
+}
+
+#[test]
+fn test_create_tx_anti_fee_sniping_respects_timelocks() {
+    // A CLTV requirement takes precedence.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .anti_fee_sniping();
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), 100_000);
+
+    // So does an explicit nLockTime.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig());
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    for seed in 0..20 {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+            .nlocktime(absolute::LockTime::from_height(1_500).unwrap())
+            .anti_fee_sniping();
+        let psbt = builder
+            .finish_with_aux_rand(&mut StdRng::seed_from_u64(seed))
+            .unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), 1_500);
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        );
+    }
+}
+
 #[test]
 fn test_create_tx_custom_locktime_compatible_with_cltv() {
     let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_cltv());
@@ -579,6 +691,32 @@ fn test_create_tx_absolute_fee() {
     );
 }
 
+#[test]
+fn test_create_tx_from_params() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    // The template is prepared without touching the wallet and sent over the wire.
+    let mut params = TxParams::new();
+    params
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb(5).unwrap())
+        .ordering(TxOrdering::Untouched);
+    let json = serde_json::to_string(&params).unwrap();
+    let params: TxParams = serde_json::from_str(&json).unwrap();
+
+    let psbt = wallet.build_tx_from_params(params).finish().unwrap();
+    let fee = check_fee!(wallet, psbt);
+
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    assert_eq!(
+        psbt.unsigned_tx.output[0].script_pubkey,
+        addr.script_pubkey()
+    );
+    assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(25_000));
+    assert_fee_rate!(psbt, fee, FeeRate::from_sat_per_vb(5).unwrap(), @add_signature);
+}
+
 #[test]
 fn test_legacy_create_tx_absolute_fee() {
     let (mut wallet, _) = get_funded_wallet_single(get_test_pkh());
@@ -970,6 +1108,234 @@ fn test_create_tx_both_non_witness_utxo_and_witness_utxo_default() {
     assert!(psbt.inputs[0].witness_utxo.is_some());
 }
 
+#[test]
+fn test_create_tx_avoid_partial_spends() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let reused = wallet.next_unused_address(KeychainKind::External).address;
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 0,
+    };
+    let first = receive_output_to_address(
+        &mut wallet,
+        reused.clone(),
+        Amount::from_sat(20_000),
+        anchor,
+    );
+    let second = receive_output_to_address(&mut wallet, reused, Amount::from_sat(25_000), anchor);
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let inputs = |psbt: &bitcoin::Psbt| {
+        psbt.unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>()
+    };
+
+    // Only one of the UTXOs of the reused address is needed.
+    let mut builder = wallet
+        .build_tx()
+        .coin_selection(coin_selection::LargestFirstCoinSelection);
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(55_000));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 2);
+    assert!(!inputs(&psbt).contains(&first) && inputs(&psbt).contains(&second));
+
+    let mut builder = wallet
+        .build_tx()
+        .coin_selection(coin_selection::LargestFirstCoinSelection);
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(55_000))
+        .avoid_partial_spends(Amount::from_sat(1_000));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 3);
+    assert!(inputs(&psbt).contains(&first) && inputs(&psbt).contains(&second));
+}
+
+#[test]
+fn test_create_tx_avoid_mixed_script_types() {
+    let (mut wallet, _) = get_funded_wallet(get_test_tr_single_sig_xprv(), get_test_wpkh());
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 0,
+    };
+    let change_addr = wallet.peek_address(KeychainKind::Internal, 0).address;
+    receive_output_to_address(&mut wallet, change_addr, Amount::from_sat(30_000), anchor);
+    let wpkh_recipient = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+    let tr_recipient = wallet.peek_address(KeychainKind::External, 42).address;
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(wpkh_recipient.clone(), Amount::from_sat(20_000))
+        .avoid_mixed_script_types();
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| txout.script_pubkey != wpkh_recipient.clone())
+        .unwrap();
+    assert!(change.script_pubkey.is_p2wpkh());
+
+    // The change is sent to a new address of the external keychain to match the taproot
+    // recipient.
+    let external_index = wallet.derivation_index(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(tr_recipient.script_pubkey(), Amount::from_sat(20_000))
+        .avoid_mixed_script_types();
+    let psbt = builder.finish().unwrap();
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| txout.script_pubkey != tr_recipient.script_pubkey())
+        .unwrap();
+    assert!(change.script_pubkey.is_p2tr());
+    let (keychain, index) = wallet
+        .derivation_of_spk(change.script_pubkey.clone())
+        .unwrap();
+    assert_eq!(keychain, KeychainKind::External);
+    assert_eq!(Some(index), external_index.map(|i| i + 1));
+    assert_eq!(wallet.derivation_index(KeychainKind::External), Some(index));
+
+    // Without the option the change goes to the internal keychain.
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(tr_recipient.script_pubkey(), Amount::from_sat(20_000));
+    let psbt = builder.finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey.is_p2wpkh()));
+}
+
+#[test]
+fn test_create_tx_coin_selection_context_min_change() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(45_000));
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+    // The change is below the minimum change value and is added to the fee instead.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(45_000))
+        .coin_selection_context(coin_selection::CoinSelectionContext {
+            min_change: Amount::from_sat(10_000),
+            ..Default::default()
+        });
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(45_000));
+    assert!(check_fee!(wallet, psbt) > Amount::from_sat(4_000));
+}
+
+#[test]
+fn test_create_tx_diagnostics() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let anchor = ConfirmationBlockTime {
+        block_id: wallet.latest_checkpoint().block_id(),
+        confirmation_time: 0,
+    };
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    receive_output_to_address(&mut wallet, addr.address, Amount::from_sat(30_000), anchor);
+    let mut utxos = wallet
+        .list_unspent()
+        .map(|u| u.outpoint)
+        .collect::<Vec<_>>();
+    utxos.sort();
+    wallet.lock_outpoint(utxos[0]);
+    let recipient = wallet
+        .peek_address(KeychainKind::External, 42)
+        .script_pubkey();
+
+    // The unlocked UTXO is not enough, the report tells why the other one wasn't spent.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(recipient.clone(), Amount::from_sat(60_000))
+        .diagnostics();
+    let err = match builder.finish() {
+        Err(CreateTxError::CoinSelection(err)) => err,
+        res => panic!("unexpected result: {res:?}"),
+    };
+    let diagnostics = err.diagnostics.expect("diagnostics");
+    assert_eq!(
+        diagnostics.excluded,
+        vec![(utxos[0], coin_selection::ExclusionReason::Locked)]
+    );
+    assert_eq!(diagnostics.candidates.len(), 1);
+    assert_eq!(diagnostics.candidates[0].outpoint, utxos[1]);
+    assert!(!diagnostics.attempts.is_empty());
+
+    // UTXOs with too few confirmations are told apart from the other unspendable ones.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(recipient.clone(), Amount::from_sat(10_000))
+        .exclude_below_confirmations(2)
+        .diagnostics();
+    let err = match builder.finish() {
+        Err(CreateTxError::CoinSelection(err)) => err,
+        res => panic!("unexpected result: {res:?}"),
+    };
+    assert_eq!(
+        err.diagnostics.expect("diagnostics").excluded,
+        vec![
+            (utxos[0], coin_selection::ExclusionReason::Locked),
+            (
+                utxos[1],
+                coin_selection::ExclusionReason::BelowMinConfirmations
+            ),
+        ]
+    );
+
+    // The preview of a successful transaction reports the selection as well.
+    let mut params = TxParams::new();
+    params
+        .add_recipient(recipient, Amount::from_sat(10_000))
+        .diagnostics();
+    let preview = wallet.preview_tx(params).unwrap();
+    let diagnostics = preview.diagnostics.expect("diagnostics");
+    assert_eq!(
+        diagnostics.excluded,
+        vec![(utxos[0], coin_selection::ExclusionReason::Locked)]
+    );
+    assert!(diagnostics.attempts.iter().any(|attempt| matches!(
+        attempt.outcome,
+        coin_selection::SelectionOutcome::Selected { .. }
+    )));
+
+    // The coins of a descriptor with a relative timelock that isn't met yet are reported.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_csv());
+    let utxo = wallet.list_unspent().next().unwrap().outpoint;
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut params = TxParams::new();
+    params
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .diagnostics();
+    let diagnostics = wallet
+        .preview_tx(params.clone())
+        .unwrap()
+        .diagnostics
+        .expect("diagnostics");
+    assert_eq!(diagnostics.timelock_not_met, vec![utxo]);
+    assert!(diagnostics.excluded.is_empty());
+    assert!(diagnostics.to_string().contains("(timelock not met)"));
+    params.current_height(2_005);
+    let diagnostics = wallet
+        .preview_tx(params)
+        .unwrap()
+        .diagnostics
+        .expect("diagnostics");
+    assert!(diagnostics.timelock_not_met.is_empty());
+}
+
 #[test]
 fn test_create_tx_add_utxo() {
     let (mut wallet, _) = get_funded_wallet_wpkh();
@@ -2537,7 +2903,8 @@ fn test_spend_coinbase() {
         Err(CreateTxError::CoinSelection(
             coin_selection::InsufficientFunds {
                 needed: _,
-                available: Amount::ZERO
+                available: Amount::ZERO,
+                ..
             }
         ))
     ));
@@ -2552,7 +2919,8 @@ fn test_spend_coinbase() {
         Err(CreateTxError::CoinSelection(
             coin_selection::InsufficientFunds {
                 needed: _,
-                available: Amount::ZERO
+                available: Amount::ZERO,
+                ..
             }
         ))
     );
@@ -3026,3 +3394,1057 @@ fn test_tx_ordering_untouched_preserves_insertion_ordering() {
     // Check vout is sorted by recipient insertion order
     assert!(txouts == vec![400, 300, 500]);
 }
+
+#[test]
+fn test_create_tx_reserve_utxos() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let second_utxo = receive_output_in_latest_block(&mut wallet, Amount::from_sat(50_000));
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let build_draft = |wallet: &mut Wallet, draft_id: &str| {
+        let mut builder = wallet.build_tx();
+        builder
+            .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+            .reserve_utxos(draft_id, None);
+        builder.finish()
+    };
+
+    // Two drafts built back to back don't spend the same coins.
+    let psbt1 = build_draft(&mut wallet, "first").unwrap();
+    let psbt2 = build_draft(&mut wallet, "second").unwrap();
+    let input1 = psbt1.unsigned_tx.input[0].previous_output;
+    let input2 = psbt2.unsigned_tx.input[0].previous_output;
+    assert_ne!(input1, input2);
+    assert!(wallet.is_outpoint_reserved(input1));
+    assert!(wallet.is_outpoint_reserved(input2));
+    assert!([input1, input2].contains(&second_utxo));
+
+    // Nothing left for a third one.
+    assert_matches!(
+        build_draft(&mut wallet, "third"),
+        Err(CreateTxError::CoinSelection(_))
+    );
+
+    // But a draft can be rebuilt with the coins it reserved.
+    let psbt = build_draft(&mut wallet, "first").unwrap();
+    assert_eq!(psbt.unsigned_tx.input[0].previous_output, input1);
+
+    // Reserved coins can still be spent when added manually.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+        .add_utxo(input1)
+        .unwrap();
+    assert!(builder.finish().is_ok());
+
+    // Releasing a draft frees its coins.
+    wallet.release_reservation("first");
+    assert!(!wallet.is_outpoint_reserved(input1));
+    assert!(wallet.is_outpoint_reserved(input2));
+    let psbt3 = build_draft(&mut wallet, "third").unwrap();
+    assert_eq!(psbt3.unsigned_tx.input[0].previous_output, input1);
+
+    // So does cancelling the tx.
+    wallet.cancel_tx(&psbt2.unsigned_tx);
+    assert!(!wallet.is_outpoint_reserved(input2));
+    assert!(wallet
+        .list_reserved_outpoints()
+        .all(|(op, reservation)| op == input1 && reservation.draft_id == "third"));
+}
+
+#[test]
+fn test_create_tx_reservation_expiry() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let tip = wallet.latest_checkpoint().height();
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+        .reserve_utxos("draft", Some(tip + 1));
+    let psbt = builder.finish().unwrap();
+    let outpoint = psbt.unsigned_tx.input[0].previous_output;
+    assert!(wallet.is_outpoint_reserved(outpoint));
+
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(30_000));
+    assert_matches!(builder.finish(), Err(CreateTxError::CoinSelection(_)));
+
+    // Expiry follows the chain tip, not the height the transaction is built for.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+        .current_height(tip + 1);
+    assert_matches!(builder.finish(), Err(CreateTxError::CoinSelection(_)));
+
+    // The reservation no longer applies once the chain reaches the expiry height.
+    insert_checkpoint(
+        &mut wallet,
+        BlockId {
+            height: tip + 1,
+            hash: BlockHash::all_zeros(),
+        },
+    );
+    assert!(!wallet.is_outpoint_reserved(outpoint));
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(30_000));
+    assert!(builder.finish().is_ok());
+}
+
+/// Spend `outpoints` in an unconfirmed transaction of `version` paying `amount` to ourselves in
+/// the first output, and the change in the second.
+fn insert_unconfirmed_self_payment(
+    wallet: &mut Wallet,
+    outpoints: &[OutPoint],
+    amount: Amount,
+    version: i32,
+) -> Transaction {
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), amount)
+        .add_utxos(outpoints)
+        .unwrap()
+        .manually_selected_only()
+        .ordering(TxOrdering::Untouched)
+        .version(version);
+    let tx = builder.finish().unwrap().unsigned_tx;
+    insert_tx(wallet, tx.clone());
+    tx
+}
+
+#[test]
+fn test_create_tx_truc_unconfirmed_parents() {
+    use bdk_wallet::error::TrucError;
+
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let confirmed = receive_output_in_latest_block(&mut wallet, Amount::from_sat(50_000));
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    // A TRUC transaction can't spend an unconfirmed non-TRUC parent.
+    let parent = insert_unconfirmed_self_payment(
+        &mut wallet,
+        &[OutPoint::new(txid, 0)],
+        Amount::from_sat(20_000),
+        2,
+    );
+    let parent_txid = parent.compute_txid();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(OutPoint::new(parent_txid, 0))
+        .unwrap()
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::Truc(TrucError::UnconfirmedNonTrucParent(t))) if t == parent_txid
+    );
+
+    // Coin selection only picks the confirmed output.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .truc();
+    let psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.version, TRUC_VERSION);
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert_eq!(psbt.unsigned_tx.input[0].previous_output, confirmed);
+
+    // Conversely, a non-TRUC transaction can't spend an unconfirmed TRUC parent.
+    let truc_parent =
+        insert_unconfirmed_self_payment(&mut wallet, &[confirmed], Amount::from_sat(20_000), 3);
+    let truc_parent_txid = truc_parent.compute_txid();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(OutPoint::new(truc_parent_txid, 0))
+        .unwrap();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::Truc(TrucError::UnconfirmedTrucParent(t))) if t == truc_parent_txid
+    );
+    let mut builder = wallet.build_tx();
+    builder.add_recipient(addr.script_pubkey(), Amount::from_sat(10_000));
+    let psbt = builder.finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .all(|txin| txin.previous_output.txid == parent_txid));
+
+    // Several outputs of the same unconfirmed TRUC parent can be spent together.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_utxos(&[
+            OutPoint::new(truc_parent_txid, 0),
+            OutPoint::new(truc_parent_txid, 1),
+        ])
+        .unwrap()
+        .truc();
+    assert!(builder.finish().is_ok());
+}
+
+#[test]
+fn test_create_tx_truc_topology_limits() {
+    use bdk_wallet::error::TrucError;
+
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let parent = insert_unconfirmed_self_payment(
+        &mut wallet,
+        &[OutPoint::new(txid, 0)],
+        Amount::from_sat(20_000),
+        3,
+    );
+    let parent_txid = parent.compute_txid();
+    assert_eq!(parent.output.len(), 2);
+
+    // The parent gets a first child.
+    let child = insert_unconfirmed_self_payment(
+        &mut wallet,
+        &[OutPoint::new(parent_txid, 0)],
+        Amount::from_sat(10_000),
+        3,
+    );
+
+    // A second child would be a second unconfirmed descendant of the parent.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(1_000))
+        .add_utxo(OutPoint::new(parent_txid, 1))
+        .unwrap()
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::Truc(TrucError::DescendantLimit(t))) if t == parent_txid
+    );
+
+    // A grandchild would have two unconfirmed ancestors.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(1_000))
+        .add_utxo(OutPoint::new(child.compute_txid(), 0))
+        .unwrap()
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::Truc(TrucError::AncestorLimit(t))) if t == parent_txid
+    );
+
+    // Coin selection can't find anything it's allowed to spend.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(1_000))
+        .truc();
+    assert_matches!(builder.finish(), Err(CreateTxError::CoinSelection(_)));
+}
+
+#[test]
+fn test_create_tx_truc_size_limits() {
+    use bdk_wallet::error::TrucError;
+
+    let (mut wallet, txid) = get_funded_wallet_wpkh();
+    receive_output_in_latest_block(&mut wallet, Amount::from_sat(500_000));
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    // About 31 vB per output.
+    let mut builder = wallet.build_tx();
+    builder
+        .set_recipients(vec![(addr.script_pubkey(), Amount::from_sat(1_000)); 330])
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::Truc(TrucError::TooLarge {
+            max: TRUC_MAX_VSIZE,
+            ..
+        }))
+    );
+    let mut builder = wallet.build_tx();
+    builder
+        .set_recipients(vec![(addr.script_pubkey(), Amount::from_sat(1_000)); 300])
+        .truc();
+    assert!(builder.finish().is_ok());
+
+    // Children of unconfirmed TRUC transactions are limited to 1 kvB.
+    let parent = insert_unconfirmed_self_payment(
+        &mut wallet,
+        &[OutPoint::new(txid, 0)],
+        Amount::from_sat(40_000),
+        3,
+    );
+    let mut builder = wallet.build_tx();
+    builder
+        .set_recipients(vec![(addr.script_pubkey(), Amount::from_sat(1_000)); 35])
+        .add_utxo(OutPoint::new(parent.compute_txid(), 0))
+        .unwrap()
+        .manually_selected_only()
+        .truc();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::Truc(TrucError::TooLarge {
+            max: TRUC_CHILD_MAX_VSIZE,
+            ..
+        }))
+    );
+}
+
+#[test]
+fn test_create_tx_anchor_output() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let p2a = ScriptBuf::new_p2a();
+
+    // Anchors above the dust limit can be added to any transaction.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_anchor(Amount::from_sat(240));
+    let psbt = builder.finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == p2a && txout.value == Amount::from_sat(240)));
+
+    // Ephemeral anchors are only allowed in TRUC transactions.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_anchor(Amount::ZERO)
+        .ordering(TxOrdering::Untouched);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::OutputBelowDustLimit(1))
+    );
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_anchor(Amount::ZERO)
+        .truc();
+    let psbt = builder.finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == p2a && txout.value == Amount::ZERO));
+}
+
+#[test]
+fn test_create_tx_spend_anchor() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_anchor(Amount::from_sat(1_000))
+        .ordering(TxOrdering::Untouched);
+    let mut psbt = builder.finish().unwrap();
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    let anchor = OutPoint::new(tx.compute_txid(), 1);
+    insert_tx(&mut wallet, tx);
+
+    // The anchor isn't ours, but can be spent like any of our UTXOs.
+    assert!(!wallet.list_unspent().any(|utxo| utxo.outpoint == anchor));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .add_utxo(anchor)
+        .unwrap();
+    let mut psbt = builder.finish().unwrap();
+    assert!(psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .any(|txin| txin.previous_output == anchor));
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    let txin = tx
+        .input
+        .iter()
+        .find(|txin| txin.previous_output == anchor)
+        .unwrap();
+    assert!(txin.witness.is_empty() && txin.script_sig.is_empty());
+}
+
+#[test]
+fn test_create_tx_subtract_fee_from_recipient() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let fee_rate = FeeRate::from_sat_per_vb(5).unwrap();
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(30_000))
+        .subtract_fee_from(&[0])
+        .ordering(TxOrdering::Untouched)
+        .fee_rate(fee_rate);
+    let mut psbt = builder.finish().unwrap();
+    let fee = psbt.fee().unwrap();
+
+    // The recipient pays for the fee and the change is untouched.
+    assert_eq!(psbt.unsigned_tx.output.len(), 2);
+    assert_eq!(
+        psbt.unsigned_tx.output[0].value,
+        Amount::from_sat(30_000) - fee
+    );
+    assert_eq!(psbt.unsigned_tx.output[1].value, Amount::from_sat(20_000));
+
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    assert!(wallet.calculate_fee_rate(&tx).unwrap() >= fee_rate);
+}
+
+#[test]
+fn test_create_tx_subtract_fee_from_whole_balance() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(50_000))
+        .subtract_fee_from(&[0]);
+    let psbt = builder.finish().unwrap();
+
+    assert_eq!(psbt.unsigned_tx.output.len(), 1);
+    assert_eq!(
+        psbt.unsigned_tx.output[0].value + psbt.fee().unwrap(),
+        Amount::from_sat(50_000)
+    );
+}
+
+#[test]
+fn test_create_tx_subtract_fee_from_several_recipients() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let amounts = [10_000, 5_000, 20_000].map(Amount::from_sat);
+    let mut builder = wallet.build_tx();
+    builder
+        .set_recipients(amounts.iter().map(|&a| (addr.script_pubkey(), a)).collect())
+        .subtract_fee_from(&[0, 2])
+        .ordering(TxOrdering::Untouched)
+        .fee_rate(FeeRate::from_sat_per_vb(3).unwrap());
+    let psbt = builder.finish().unwrap();
+    let fee = psbt.fee().unwrap();
+    let outputs = &psbt.unsigned_tx.output;
+
+    // The fee is split between the first and last recipient, the second one isn't affected.
+    let deducted_first = amounts[0] - outputs[0].value;
+    let deducted_last = amounts[2] - outputs[2].value;
+    assert_eq!(outputs[1].value, amounts[1]);
+    assert_eq!(deducted_first + deducted_last, fee);
+    assert!(deducted_first >= deducted_last);
+    assert!(deducted_first - deducted_last <= Amount::from_sat(1));
+}
+
+#[test]
+fn test_create_tx_subtract_fee_from_errors() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(10_000))
+        .subtract_fee_from(&[1]);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::InvalidSubtractFeeIndex(1))
+    );
+
+    // The dust limit applies to what's left after paying the fee.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(1_000))
+        .subtract_fee_from(&[0])
+        .fee_rate(FeeRate::from_sat_per_vb(6).unwrap());
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::OutputBelowDustLimit(0))
+    );
+
+    // Not enough to cover the fee at all.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(1_000))
+        .subtract_fee_from(&[0])
+        .fee_rate(FeeRate::from_sat_per_vb(50).unwrap());
+    assert_matches!(builder.finish(), Err(CreateTxError::CoinSelection(_)));
+}
+
+#[test]
+fn test_create_tx_standardness() {
+    use bdk_wallet::error::NonStandardError;
+    use bdk_wallet::DataCarrierPolicy;
+    use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1};
+    use bitcoin::script::Builder;
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let data = PushBytesBuf::try_from(vec![0; 80]).unwrap();
+
+    // More than one OP_RETURN output.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_data(&data)
+        .add_data(&[0x2a]);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(
+            NonStandardError::MultipleDataCarriers
+        ))
+    );
+
+    // An OP_RETURN output carrying more than 80 bytes.
+    let large_data = PushBytesBuf::try_from(vec![0; 81]).unwrap();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_data(&large_data);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(
+            NonStandardError::DataCarrierSize {
+                size: 84,
+                max: 83,
+                ..
+            }
+        ))
+    );
+
+    // Newer nodes relay several and larger OP_RETURN outputs, up to the total size allowed.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_data(&data)
+        .add_data(&large_data)
+        .data_carrier_policy(DataCarrierPolicy::CORE_V30);
+    assert!(builder.finish().is_ok());
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .add_data(&data)
+        .add_data(&large_data)
+        .data_carrier_policy(DataCarrierPolicy {
+            max_size: Some(160),
+            allow_multiple: true,
+        })
+        .ordering(TxOrdering::Untouched);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(
+            NonStandardError::DataCarrierSize {
+                index: 2,
+                size: 167,
+                max: 160,
+            }
+        ))
+    );
+
+    // A bare multisig output.
+    let pubkey = bitcoin::PublicKey::from_str(
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    )
+    .unwrap();
+    let multisig = Builder::new()
+        .push_opcode(OP_PUSHNUM_1)
+        .push_key(&pubkey)
+        .push_opcode(OP_PUSHNUM_1)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script();
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(multisig.clone(), Amount::from_sat(25_000))
+        .ordering(TxOrdering::Untouched);
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(NonStandardError::BareMultisig(
+            0
+        )))
+    );
+
+    // A transaction too small to be relayed.
+    let mut builder = wallet.build_tx();
+    builder
+        .drain_wallet()
+        .drain_to(ScriptBuf::new_op_return([0x2a]));
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(NonStandardError::TooSmall {
+            size: 63,
+            min: 65
+        }))
+    );
+
+    // A transaction too heavy to be relayed.
+    let mut builder = wallet.build_tx();
+    for _ in 0..3_300 {
+        builder.add_recipient(addr.script_pubkey(), Amount::ZERO);
+    }
+    builder
+        .allow_dust(true)
+        .fee_absolute(Amount::from_sat(1_000));
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::NonStandard(
+            NonStandardError::TxWeight { .. }
+        ))
+    );
+
+    // All of them can still be created on purpose.
+    let mut builder = wallet.build_tx();
+    builder
+        .add_recipient(multisig, Amount::from_sat(25_000))
+        .add_data(&data)
+        .add_data(&large_data)
+        .allow_non_standard(true);
+    let psbt = builder.finish().unwrap();
+    assert_eq!(
+        psbt.unsigned_tx
+            .output
+            .iter()
+            .filter(|txout| txout.script_pubkey.is_op_return())
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn test_create_tx_payment_uri() {
+    use bdk_wallet::bip21::{Bip21Error, PaymentUri};
+
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let uri: PaymentUri = "bitcoin:2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX?amount=0.00025&label=Lunch"
+        .parse()
+        .unwrap();
+    let mut builder = wallet.build_tx();
+    builder.add_payment_uri(&uri).unwrap();
+    let psbt = builder.finish().unwrap();
+    let address = uri.address.assume_checked();
+    assert!(psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == address.script_pubkey()
+            && txout.value == Amount::from_sat(25_000)));
+
+    // Addresses of another network and URIs without an amount can't be paid.
+    let uri: PaymentUri = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1"
+        .parse()
+        .unwrap();
+    let mut builder = wallet.build_tx();
+    assert_eq!(
+        builder.add_payment_uri(&uri).unwrap_err(),
+        Bip21Error::NetworkMismatch(Network::Regtest)
+    );
+    let uri: PaymentUri = "bitcoin:2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        builder.add_payment_uri(&uri).unwrap_err(),
+        Bip21Error::MissingAmount
+    );
+
+    // Request a payment to one of our addresses.
+    let address = wallet.reveal_next_address(KeychainKind::External);
+    let uri = address.payment_uri(Some(Amount::from_sat(1_500)), Some("Coffee & cake".into()));
+    assert_eq!(
+        uri.to_string(),
+        format!(
+            "bitcoin:{}?amount=0.000015&label=Coffee%20%26%20cake",
+            address.address
+        )
+    );
+    assert_eq!(uri.to_string().parse::<PaymentUri>().unwrap(), uri);
+}
+
+#[test]
+fn test_preview_tx() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = wallet.next_unused_address(KeychainKind::External);
+    let mut params = TxParams::new();
+    params
+        .add_recipient(addr.script_pubkey(), Amount::from_sat(25_000))
+        .fee_rate(FeeRate::from_sat_per_vb(5).unwrap())
+        .reserve_utxos("draft", None);
+
+    // Previewing leaves the wallet untouched.
+    let staged = wallet.staged().cloned();
+    let change_index = wallet.derivation_index(KeychainKind::Internal);
+    let preview = wallet
+        .preview_tx_with_aux_rand(params.clone(), &mut StdRng::seed_from_u64(7))
+        .unwrap();
+    assert!(wallet.preview_tx(params.clone()).is_ok());
+    assert_eq!(wallet.staged().cloned(), staged);
+    assert_eq!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+    assert_eq!(wallet.list_reserved_outpoints().count(), 0);
+
+    // And projects the transaction that is built eventually.
+    let mut psbt = wallet
+        .build_tx_from_params(params)
+        .finish_with_aux_rand(&mut StdRng::seed_from_u64(7))
+        .unwrap();
+    assert_eq!(preview.fee, psbt.fee().unwrap());
+    assert_eq!(
+        preview
+            .selected
+            .iter()
+            .map(|utxo| utxo.outpoint())
+            .collect::<Vec<_>>(),
+        psbt.unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>()
+    );
+    let change = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .find(|txout| {
+            matches!(
+                wallet.derivation_of_spk(txout.script_pubkey.clone()),
+                Some((KeychainKind::Internal, _))
+            )
+        })
+        .unwrap();
+    assert_eq!(preview.change, Some(change.value));
+    assert_ne!(
+        wallet.derivation_index(KeychainKind::Internal),
+        change_index
+    );
+
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().expect("failed to extract tx");
+    assert!(tx.vsize() as u64 <= preview.vsize);
+    assert!(preview.vsize - (tx.vsize() as u64) <= 1);
+    assert!(preview.fee_rate >= FeeRate::from_sat_per_vb(5).unwrap());
+}
+
+#[test]
+fn test_preview_tx_errors() {
+    let (wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let mut params = TxParams::new();
+    params.add_recipient(addr.script_pubkey(), Amount::from_sat(100_000));
+    assert_matches!(
+        wallet.preview_tx(params),
+        Err(CreateTxError::CoinSelection(_))
+    );
+}
+
+#[test]
+fn test_max_sendable() {
+    let (mut wallet, _) = get_funded_wallet_wpkh();
+    let addr = Address::from_str("2N1Ffz3WaNzbeLFBb51xyFMHYSEUXcbiSoX")
+        .unwrap()
+        .assume_checked();
+    let fee_rate = FeeRate::from_sat_per_vb(5).unwrap();
+    let confirmed = receive_output_in_latest_block(&mut wallet, Amount::from_sat(10_000));
+    let unconfirmed = receive_output(&mut wallet, Amount::from_sat(20_000), ReceiveTo::Mempool(1));
+    let locked = receive_output_in_latest_block(&mut wallet, Amount::from_sat(40_000));
+    wallet.lock_outpoint(locked);
+
+    let drained = |wallet: &mut Wallet, options: TxParams| {
+        let mut builder = wallet.build_tx_from_params(options);
+        builder
+            .drain_wallet()
+            .drain_to(addr.script_pubkey())
+            .fee_rate(fee_rate);
+        let psbt = builder.finish().unwrap();
+        let inputs: Vec<_> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        let value = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .find(|txout| txout.script_pubkey == addr.script_pubkey())
+            .unwrap()
+            .value;
+        (value, inputs)
+    };
+
+    // Everything but the locked output.
+    let options = TxParams::new();
+    let max = wallet
+        .max_sendable(addr.script_pubkey(), fee_rate, options.clone())
+        .unwrap();
+    let (value, inputs) = drained(&mut wallet, options);
+    assert_eq!(max, value);
+    assert_eq!(inputs.len(), 3);
+    assert!(!inputs.contains(&locked));
+    assert!(max > Amount::from_sat(78_000) && max < Amount::from_sat(80_000));
+
+    // Only confirmed outputs, and not this one.
+    let mut builder = wallet.build_tx();
+    builder.exclude_unconfirmed().add_unspendable(confirmed);
+    let options = builder.params().clone();
+    let max = wallet
+        .max_sendable(addr.script_pubkey(), fee_rate, options.clone())
+        .unwrap();
+    let (value, inputs) = drained(&mut wallet, options);
+    assert_eq!(max, value);
+    assert_eq!(inputs.len(), 1);
+    assert!(!inputs.contains(&unconfirmed) && !inputs.contains(&confirmed));
+
+    // Paying another recipient first.
+    let mut options = TxParams::new();
+    options.add_recipient(addr.script_pubkey(), Amount::from_sat(30_000));
+    let max_with_recipient = wallet
+        .max_sendable(addr.script_pubkey(), fee_rate, options)
+        .unwrap();
+    assert!(max_with_recipient < Amount::from_sat(50_000));
+
+    // Nothing left to send.
+    let mut options = TxParams::new();
+    options.change_policy(bdk_wallet::ChangeSpendPolicy::OnlyChange);
+    assert_matches!(
+        wallet.max_sendable(addr.script_pubkey(), fee_rate, options),
+        Err(CreateTxError::CoinSelection(_))
+    );
+}
+
+#[test]
+fn test_create_tx_silent_payment() {
+    use bdk_wallet::silent_payments::{SilentPaymentAddress, SilentPaymentError};
+    use bitcoin::hashes::{sha256, HashEngine};
+    use bitcoin::key::{Parity, Secp256k1, TapTweak};
+    use bitcoin::secp256k1::{PublicKey, Scalar, SecretKey};
+    use bitcoin::XOnlyPublicKey;
+
+    fn tagged_hash(tag: &str, data: &[&[u8]]) -> Scalar {
+        let tag = sha256::Hash::hash(tag.as_bytes());
+        let mut engine = sha256::Hash::engine();
+        engine.input(tag.as_ref());
+        engine.input(tag.as_ref());
+        for data in data {
+            engine.input(data);
+        }
+        Scalar::from_be_bytes(sha256::Hash::from_engine(engine).to_byte_array()).unwrap()
+    }
+
+    let secp = Secp256k1::new();
+    let scan = SecretKey::from_slice(&[1; 32]).unwrap();
+    let spend = SecretKey::from_slice(&[2; 32]).unwrap();
+    let address = SilentPaymentAddress::new(
+        scan.public_key(&secp),
+        spend.public_key(&secp),
+        Network::Regtest,
+    );
+
+    // Scan the transaction as the receiver would, from the taproot key of its single input.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(10_000))
+        .unwrap();
+    let mut psbt = builder.finish().unwrap();
+    let input = &psbt.unsigned_tx.input[0];
+    let prevout = wallet.get_utxo(input.previous_output).unwrap().txout;
+    let input_key = PublicKey::from_x_only_public_key(
+        XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..]).unwrap(),
+        Parity::Even,
+    );
+    let input_hash = tagged_hash(
+        "BIP0352/Inputs",
+        &[
+            &bitcoin::consensus::serialize(&input.previous_output),
+            &input_key.serialize(),
+        ],
+    );
+    let shared_secret = input_key
+        .mul_tweak(&secp, &input_hash)
+        .unwrap()
+        .mul_tweak(&secp, &Scalar::from(scan))
+        .unwrap();
+    let tweak = tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&shared_secret.serialize(), &0u32.to_be_bytes()],
+    );
+    let (output_key, _) = spend
+        .public_key(&secp)
+        .add_exp_tweak(&secp, &tweak)
+        .unwrap()
+        .x_only_public_key();
+    let expected = ScriptBuf::new_p2tr_tweaked(output_key.dangerous_assume_tweaked());
+    assert!(psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .any(|txout| txout.script_pubkey == expected && txout.value == Amount::from_sat(10_000)));
+    assert!(wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+
+    // Addresses of another network can't be paid.
+    let mainnet = SilentPaymentAddress {
+        network: Network::Bitcoin,
+        ..address
+    };
+    assert_eq!(
+        wallet
+            .build_tx()
+            .add_silent_payment_recipient(mainnet, Amount::from_sat(10_000))
+            .unwrap_err(),
+        SilentPaymentError::NetworkMismatch(Network::Regtest)
+    );
+
+    // Outputs of other types can't be spent.
+    let (mut wallet, _) = get_funded_wallet_single(get_test_single_sig_csv());
+    let utxo = wallet.list_unspent().next().unwrap().outpoint;
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(10_000))
+        .unwrap();
+    assert_matches!(builder.finish(), Err(CreateTxError::CoinSelection(_)));
+    let mut builder = wallet.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(10_000))
+        .unwrap()
+        .add_utxo(utxo)
+        .unwrap();
+    assert_matches!(
+        builder.finish(),
+        Err(CreateTxError::SilentPayment(SilentPaymentError::IneligibleInput(outpoint))) if outpoint == utxo
+    );
+}
+
+#[test]
+fn test_receive_silent_payment() {
+    use bdk_wallet::signer::SignerOrdering;
+    use bdk_wallet::silent_payments::{input_tweak_data, SilentPaymentSigner};
+    use bdk_wallet::{LoadError, LoadMismatch};
+    use bitcoin::block::{Header, Version};
+    use bitcoin::key::Secp256k1;
+    use bitcoin::secp256k1::{Message, SecretKey};
+    use bitcoin::sighash::{Prevouts, SighashCache};
+    use bitcoin::{Block, CompactTarget, TxMerkleNode, XOnlyPublicKey};
+
+    let secp = Secp256k1::new();
+    let scan = SecretKey::from_slice(&[1; 32]).unwrap();
+    let spend = SecretKey::from_slice(&[2; 32]).unwrap();
+    let mut receiver = Wallet::create_single(get_test_tr_single_sig())
+        .network(Network::Regtest)
+        .silent_payments(scan, spend.public_key(&secp))
+        .create_wallet_no_persist()
+        .unwrap();
+    let address = receiver.silent_payment_address().unwrap();
+    assert_eq!(address.scan, scan.public_key(&secp));
+
+    // Pay the address from another wallet.
+    let (mut sender, _) = get_funded_wallet_single(get_test_tr_single_sig_xprv());
+    let mut builder = sender.build_tx();
+    builder
+        .add_silent_payment_recipient(address, Amount::from_sat(10_000))
+        .unwrap();
+    let mut psbt = builder.finish().unwrap();
+    assert!(sender.sign(&mut psbt, SignOptions::default()).unwrap());
+    let tx = psbt.extract_tx().unwrap();
+    let txid = tx.compute_txid();
+    let vout = tx
+        .output
+        .iter()
+        .position(|txout| txout.value == Amount::from_sat(10_000))
+        .unwrap() as u32;
+
+    // The receiver doesn't know the outputs spent by the transaction, so it is given its tweak
+    // data along with the block.
+    let tweak_data = input_tweak_data(&secp, &tx, |outpoint| {
+        sender.get_utxo(*outpoint).map(|utxo| utxo.txout)
+    })
+    .unwrap();
+    let block = Block {
+        header: Header {
+            version: Version::ONE,
+            prev_blockhash: receiver.latest_checkpoint().hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_000,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        },
+        txdata: vec![tx.clone()],
+    };
+    receiver.apply_block(&block, 1).unwrap();
+    assert_eq!(receiver.list_silent_payment_unspent().count(), 0);
+    receiver
+        .apply_block_with_tweaks(&block, 1, &[(txid, tweak_data)].into())
+        .unwrap();
+
+    let outputs: Vec<_> = receiver.list_silent_payment_unspent().collect();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].outpoint, OutPoint::new(txid, vout));
+    assert!(outputs[0].chain_position.is_confirmed());
+    assert_eq!(receiver.balance().confirmed, Amount::from_sat(10_000));
+    assert_eq!(
+        receiver.staged().unwrap().silent_payments.outputs,
+        [(outputs[0].outpoint, outputs[0].tweak)].into()
+    );
+
+    // The scan key isn't persisted and must match the address when loading.
+    let changeset = receiver.staged().unwrap().clone();
+    let loaded = Wallet::load()
+        .load_wallet_no_persist(changeset.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.silent_payment_address(), Some(address));
+    assert_eq!(loaded.balance().confirmed, Amount::from_sat(10_000));
+    let err = Wallet::load()
+        .silent_payments(spend, spend.public_key(&secp))
+        .load_wallet_no_persist(changeset)
+        .unwrap_err();
+    assert_matches!(
+        err,
+        LoadError::Mismatch(LoadMismatch::SilentPaymentAddress { .. })
+    );
+
+    // Spend the output with the spend key.
+    receiver.add_signer(
+        KeychainKind::External,
+        SignerOrdering::default(),
+        Arc::new(SilentPaymentSigner::new(spend)),
+    );
+    let drain = receiver.peek_address(KeychainKind::External, 0);
+    let mut builder = receiver.build_tx();
+    builder.drain_wallet().drain_to(drain.script_pubkey());
+    let mut psbt = builder.finish().unwrap();
+    assert_eq!(psbt.unsigned_tx.input.len(), 1);
+    assert!(receiver.sign(&mut psbt, SignOptions::default()).unwrap());
+
+    let spending_tx = psbt.extract_tx().unwrap();
+    let witness = &spending_tx.input[0].witness;
+    assert_eq!(witness.len(), 1);
+    let signature = bitcoin::taproot::Signature::from_slice(&witness[0]).unwrap();
+    let prevouts = [tx.output[vout as usize].clone()];
+    let sighash = SighashCache::new(&spending_tx)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), signature.sighash_type)
+        .unwrap();
+    let output_key =
+        XOnlyPublicKey::from_slice(&prevouts[0].script_pubkey.as_bytes()[2..]).unwrap();
+    secp.verify_schnorr(&signature.signature, &Message::from(sighash), &output_key)
+        .unwrap();
+
+    // A spend of the output paying nothing back to the wallet is still relevant.
+    let external = sender.peek_address(KeychainKind::External, 0);
+    let mut builder = receiver.build_tx();
+    builder.drain_wallet().drain_to(external.script_pubkey());
+    let mut psbt = builder.finish().unwrap();
+    assert!(receiver.sign(&mut psbt, SignOptions::default()).unwrap());
+    let spending_tx = psbt.extract_tx().unwrap();
+    let spending_txid = spending_tx.compute_txid();
+    receiver.apply_unconfirmed_txs([(spending_tx, 2_000)]);
+    assert!(receiver.get_tx(spending_txid).is_some());
+    assert_eq!(receiver.list_silent_payment_unspent().count(), 0);
+    assert_eq!(receiver.balance().total(), Amount::ZERO);
+}